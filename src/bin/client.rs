@@ -1,24 +1,48 @@
 //! Rustis客户端
 
+#[path = "client/completion.rs"]
+mod completion;
+
 use bytes::Bytes;
 use clap::Parser;
+use completion::Completer;
 use crossterm::{
     event::{self, Event, KeyCode, KeyEvent, KeyModifiers},
     terminal::{disable_raw_mode, enable_raw_mode},
 };
-use rustis::{client::Client, DEFAULT_PORT};
-use tracing::{span, Level};
-use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt};
+use rustis::{
+    client::{Client, Frame},
+    DEFAULT_PORT,
+};
 use std::{
-    fs::File, io::{stdout, Write}, str, sync::{
+    fs::File,
+    io::{stdout, IsTerminal, Write},
+    process::ExitCode,
+    str,
+    sync::{
         atomic::{AtomicBool, Ordering},
         Arc,
     }
 };
 use tokio::{
+    io::{AsyncBufReadExt, BufReader},
     sync::{broadcast, mpsc, Mutex},
     time::Duration,
 };
+use tracing::{span, Level};
+use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
+
+/// 管道模式每一批发送的命令数量
+const PIPE_BATCH_SIZE: usize = 1000;
+
+/// 管道模式不接受的命令，它们会让连接进入订阅或监控模式，之后的回复无法和命令对应
+const PIPE_REJECTED_COMMANDS: &[&str] = &[
+    "subscribe",
+    "unsubscribe",
+    "psubscribe",
+    "punsubscribe",
+    "monitor",
+];
 
 #[derive(Parser, Debug)]
 #[command(name = "rustis-client", version, author, about = "rustis client")]
@@ -28,18 +52,44 @@ struct Cli {
 
     #[arg(long, default_value_t = DEFAULT_PORT)]
     port: u16,
+
+    /// 日志级别，使用RUST_LOG的语法（如debug或者warn,rustis::client=trace）
+    #[arg(long, default_value = "trace")]
+    log_level: String,
+
+    /// 将日志输出到标准错误而不是logs/client.log
+    #[arg(long)]
+    log_stderr: bool,
+
+    /// 从标准输入逐行读取命令，分批以pipeline发送，最后输出回复和错误的数量
+    #[arg(long, conflicts_with = "command")]
+    pipe: bool,
+
+    /// 要执行的命令，例如`set foo bar`。给出命令时只执行一次并输出结果，不进入交互模式
+    #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+    command: Vec<String>,
 }
 
 #[tokio::main]
-async fn main() -> rustis::Result<()> {
-    // 如果不存在logs文件夹，则创建
-    if !std::path::Path::new("logs").exists() {
-        std::fs::create_dir("logs").expect("无法创建logs文件夹");
-    }
-    // 输出到文件中
-    let file = File::create("logs/client.log").expect("无法创建日志文件");
-    let (non_blocking_appender, _guard) = tracing_appender::non_blocking(file);
-    let file_layer = fmt::layer()
+async fn main() -> rustis::Result<ExitCode> {
+    // 解析命令行参数
+    let cli = Cli::parse();
+
+    let filter = EnvFilter::try_new(&cli.log_level)
+        .map_err(|err| format!("无效的日志级别 '{}': {err}", cli.log_level))?;
+
+    // 输出到标准错误或者文件中
+    let (non_blocking_appender, _guard) = if cli.log_stderr {
+        tracing_appender::non_blocking(std::io::stderr())
+    } else {
+        // 如果不存在logs文件夹，则创建
+        if !std::path::Path::new("logs").exists() {
+            std::fs::create_dir("logs").expect("无法创建logs文件夹");
+        }
+        let file = File::create("logs/client.log").expect("无法创建日志文件");
+        tracing_appender::non_blocking(file)
+    };
+    let fmt_layer = fmt::layer()
         .with_timer(fmt::time::UtcTime::rfc_3339()) // 使用 RFC 3339 格式的 UTC 时间
         .with_target(true) // 显示日志目标
         .with_level(true) // 显示日志级别
@@ -49,19 +99,27 @@ async fn main() -> rustis::Result<()> {
         .compact();
 
     // 初始化全局subscriber
-    tracing_subscriber::registry().with(file_layer).init();
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(fmt_layer)
+        .init();
 
     // 创建一个root span
     let main_span = span!(Level::DEBUG, "client-main");
 
-    // 解析命令行参数
-    let cli = Cli::parse();
-
     // 获取远程连接地址以及端口
     let addr = format!("{}:{}", cli.host, cli.port);
 
     // 创建一个客户端
     let mut client = Client::connect(&addr).await?;
+
+    // 命令行中给出了命令时只执行这一个命令
+    if !cli.command.is_empty() {
+        return Ok(run_once(client, &cli.command).await);
+    }
+    if cli.pipe {
+        return run_pipe(client).await;
+    }
     // 客户端是否处于订阅模式
     let is_subscription_mode = Arc::new(AtomicBool::new(false));
     // 创建命令历史管理器
@@ -126,48 +184,6 @@ async fn main() -> rustis::Result<()> {
                     match parse_command(&input) {
                         Ok(command) => {
                             match command {
-                                Command::Get { key } => {
-                                    if let Some(value) = client.get(&key).await? {
-                                        if let Ok(string) = str::from_utf8(&value) {
-                                            println!("\r\"{}\"", string);
-                                        } else {
-                                            println!("\r{:?}", value);
-                                        }
-                                    } else {
-                                        // \r打断前面的>输出
-                                        println!("\r(nil)");
-                                    }
-                                }
-                                Command::Ping { msg } => {
-                                    let value = client.ping(msg).await?;
-                                    if let Ok(string) = str::from_utf8(&value) {
-                                        println!("\r\"{}\"", string);
-                                    } else {
-                                        println!("\r{:?}", value);
-                                    }
-                                }
-                                Command::Publish { channel, message } => {
-                                    client.publish(&channel, message).await?;
-                                    // \r打断前面的>输出
-                                    println!("\rPublish Ok");
-                                }
-                                Command::Set { key, value, expire } => {
-                                    match expire {
-                                        Some((ExpireMode::EX, seconds)) => {
-                                            let duration = Duration::from_secs(seconds);
-                                            client.set_with_expires(&key, value, duration).await?;
-                                        }
-                                        Some((ExpireMode::PX, milliseconds)) => {
-                                            let duration = Duration::from_millis(milliseconds);
-                                            client.set_with_expires(&key, value, duration).await?;
-                                        }
-                                        None => {
-                                            client.set(&key, value).await?;
-                                        }
-                                    }
-                                    // \r打断前面的>输出
-                                    println!("\rSet Ok");
-                                }
                                 Command::Subscribe { channels } => {
                                     if channels.is_empty() {
                                         return Err("\rchannel(s) must be provided".into());
@@ -176,6 +192,8 @@ async fn main() -> rustis::Result<()> {
 
                                     // 等待消息
                                     is_subscription_mode.store(true, Ordering::SeqCst);
+                                    // 收到中断信号时退出订阅模式，连接关闭或出错时直接取回Client
+                                    let mut exit_subscribe = false;
                                     loop {
                                         tokio::select! {
                                             msg = subscriber.next_message() => {
@@ -194,11 +212,8 @@ async fn main() -> rustis::Result<()> {
                                                 }
                                             }
                                             _ = shutdown_rx.recv() => {
-                                                // 取消所有订阅
-                                                subscriber.unsubscribe(&[]).await?;
-                                                // 发送退出订阅者模式的信号给服务器
-                                                subscriber.exit_subscribe().await?;
                                                 println!("\rReceived interrupt. Exiting subscription mode...");
+                                                exit_subscribe = true;
                                                 break;
                                             }
                                             input = rx.recv() => {
@@ -237,23 +252,22 @@ async fn main() -> rustis::Result<()> {
                                         }
                                     }
 
-                                    // 从subscriber中取回Client对象所有权
-                                    client = subscriber.into_client();
+                                    // 从subscriber中取回Client对象所有权，退出订阅模式时会先取消所有订阅
+                                    client = if exit_subscribe {
+                                        subscriber.exit_subscribe().await?
+                                    } else {
+                                        subscriber.into_client()
+                                    };
                                     is_subscription_mode.store(false, Ordering::SeqCst);
                                 }
                                 Command::Unsubscribe { channels } => {
                                     // 在客户端模式下，取消订阅是不支持的
                                     println!("\rUnsubscribe is unsupported in client mode. channels: {:?}", channels);
                                 }
-                                Command::Save {} => {
-                                    client.save().await?;
-                                    // \r打断前面的>输出
-                                    println!("\rSave Ok");
-                                }
-                                Command::Del { key } => {
-                                    client.del(&key).await?;
+                                command => {
+                                    let reply = execute(&mut client, command).await?;
                                     // \r打断前面的>输出
-                                    println!("\rDel Ok");
+                                    println!("\r{}", reply);
                                 }
                             };
                         }
@@ -272,7 +286,193 @@ async fn main() -> rustis::Result<()> {
     print!("\x1B[?25h");
     disable_raw_mode()?;
 
-    Ok(())
+    Ok(ExitCode::SUCCESS)
+}
+
+/// # execute() 函数
+///
+/// 执行一个订阅以外的命令，返回交互模式和单次执行共用的输出格式
+async fn execute(client: &mut Client, command: Command) -> rustis::Result<String> {
+    let reply = match command {
+        Command::Get { key } => match client.get(&key).await? {
+            Some(value) => format_value(&value),
+            None => "(nil)".to_string(),
+        },
+        Command::Ping { msg } => format_value(&client.ping(msg).await?),
+        Command::Publish { channel, message } => {
+            client.publish(&channel, message).await?;
+            "Publish Ok".to_string()
+        }
+        Command::Set { key, value, expire } => {
+            match expire {
+                Some((ExpireMode::EX, seconds)) => {
+                    let duration = Duration::from_secs(seconds);
+                    client.set_with_expires(&key, value, duration).await?;
+                }
+                Some((ExpireMode::PX, milliseconds)) => {
+                    let duration = Duration::from_millis(milliseconds);
+                    client.set_with_expires(&key, value, duration).await?;
+                }
+                None => {
+                    client.set(&key, value).await?;
+                }
+            }
+            "Set Ok".to_string()
+        }
+        Command::Save {} => {
+            client.save().await?;
+            "Save Ok".to_string()
+        }
+        Command::Del { key } => {
+            client.del(&key).await?;
+            "Del Ok".to_string()
+        }
+        Command::Subscribe { .. } | Command::Unsubscribe { .. } => {
+            return Err("subscribe and unsubscribe are handled by the caller".into());
+        }
+    };
+    Ok(reply)
+}
+
+/// # format_value() 函数
+///
+/// UTF-8的值加上引号输出，其他值输出字节
+fn format_value(value: &Bytes) -> String {
+    match str::from_utf8(value) {
+        Ok(string) => format!("\"{}\"", string),
+        Err(_) => format!("{:?}", value),
+    }
+}
+
+/// # run_once() 函数
+///
+/// 执行命令行参数中的一个命令并输出结果，返回进程的退出码。
+/// 每个参数原样作为命令的一部分，不会再按照空白拆分；命令无效或者服务器回复错误时退出码为1
+async fn run_once(mut client: Client, args: &[String]) -> ExitCode {
+    let parts: Vec<&str> = args.iter().map(String::as_str).collect();
+    let command = match parse_args(&parts) {
+        Ok(command) => command,
+        Err(err) => {
+            eprintln!("{}", err.trim_start_matches('\r'));
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let result = match command {
+        Command::Subscribe { channels } => subscribe_once(client, channels).await,
+        Command::Unsubscribe { .. } => {
+            Err("unsubscribe can only be used in subscription mode".into())
+        }
+        command => execute(&mut client, command)
+            .await
+            .map(|reply| println!("{}", reply)),
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("(error) {}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// # run_pipe() 函数
+///
+/// 管道模式：从标准输入逐行读取命令，每PIPE_BATCH_SIZE个命令作为一个pipeline发送，输出回复和错误的数量。
+/// 命令原样发送给服务器，只有订阅和监控命令输出行号后跳过，有错误回复或者跳过了行时退出码为1
+async fn run_pipe(mut client: Client) -> rustis::Result<ExitCode> {
+    if std::io::stdin().is_terminal() {
+        eprintln!("--pipe reads commands from stdin, which must not be a terminal");
+        return Ok(ExitCode::FAILURE);
+    }
+
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    let mut pipeline = client.pipeline();
+    let mut counts = PipeCounts::default();
+    let mut line_number = 0;
+
+    while let Some(line) = lines.next_line().await? {
+        line_number += 1;
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        if parts.is_empty() {
+            continue;
+        }
+
+        // 其它命令原样发送，参数错误和未知命令由服务器回复错误；
+        // 会让连接进入订阅或监控模式的命令不能在pipeline中执行
+        let name = parts[0].to_lowercase();
+        if PIPE_REJECTED_COMMANDS.contains(&name.as_str()) {
+            eprintln!(
+                "line {}: '{}' can't be used in pipe mode",
+                line_number, name
+            );
+            counts.skipped += 1;
+            continue;
+        }
+
+        let args: Vec<&[u8]> = parts.iter().map(|part| part.as_bytes()).collect();
+        pipeline.command(&args);
+        if pipeline.len() >= PIPE_BATCH_SIZE {
+            counts.add(std::mem::take(&mut pipeline).execute(&mut client).await?);
+        }
+    }
+
+    println!("All data transferred. Waiting for the last reply...");
+    counts.add(pipeline.execute(&mut client).await?);
+    println!("Last reply received from server.");
+    println!("errors: {}, replies: {}", counts.errors, counts.replies);
+
+    if counts.errors == 0 && counts.skipped == 0 {
+        Ok(ExitCode::SUCCESS)
+    } else {
+        Ok(ExitCode::FAILURE)
+    }
+}
+
+/// # PipeCounts 结构体
+///
+/// 管道模式的统计
+#[derive(Debug, Default)]
+struct PipeCounts {
+    /// 收到的回复数量，包括错误回复
+    replies: u64,
+    /// 错误回复的数量
+    errors: u64,
+    /// 因为无效而跳过的行数
+    skipped: u64,
+}
+
+impl PipeCounts {
+    /// 统计一批回复，错误回复输出到标准错误
+    fn add(&mut self, replies: Vec<Frame>) {
+        for reply in replies {
+            self.replies += 1;
+            if let Frame::Error(msg) = reply {
+                eprintln!("(error) {}", msg);
+                self.errors += 1;
+            }
+        }
+    }
+}
+
+/// # subscribe_once() 函数
+///
+/// 订阅channels并输出收到的消息，直到连接关闭或者收到Ctrl+C
+async fn subscribe_once(client: Client, channels: Vec<String>) -> rustis::Result<()> {
+    let mut subscriber = client.subscribe(channels).await?;
+    loop {
+        tokio::select! {
+            msg = subscriber.next_message() => match msg? {
+                Some(msg) => println!(
+                    "got message from the channel: {}; message = {:?}",
+                    msg.channel, msg.content
+                ),
+                None => return Ok(()),
+            },
+            _ = tokio::signal::ctrl_c() => return Ok(()),
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -311,95 +511,184 @@ enum ExpireMode {
     PX, // 毫秒
 }
 
+/// # parse_command() 函数
+///
+/// 解析交互模式中输入的一行命令，参数之间用空白分隔
 fn parse_command(input: &str) -> Result<Command, String> {
     let parts: Vec<&str> = input.split_whitespace().collect();
+    parse_args(&parts)
+}
 
-    if parts.is_empty() {
-        return Err("\rEmpty command".to_string());
+/// # CommandSpec 结构体
+///
+/// 交互模式支持的一个命令，命令解析、参数提示和Tab补全都使用COMMANDS这一张表
+pub(crate) struct CommandSpec {
+    /// 命令名称（小写）
+    pub(crate) name: &'static str,
+    /// 参数的说明，用于参数提示和用法说明
+    pub(crate) args: &'static [&'static str],
+    /// 参数个数（包括命令名称），负数表示至少需要-arity个参数，与服务器命令表的arity含义相同
+    arity: i32,
+    /// 解析参数个数已经检查过的命令，parts包括命令名称
+    parse: fn(&[&str]) -> Result<Command, String>,
+}
+
+impl CommandSpec {
+    /// # usage() 函数
+    ///
+    /// 返回命令的用法说明，例如`get <key>`
+    fn usage(&self) -> String {
+        std::iter::once(self.name)
+            .chain(self.args.iter().copied())
+            .collect::<Vec<_>>()
+            .join(" ")
     }
 
-    match parts[0].to_lowercase().as_str() {
-        "get" => {
-            if parts.len() != 2 {
-                // \r打断前面的>输出
-                return Err("\r'get' command usage: get <key>".to_string());
-            }
+    /// # check_arity() 函数
+    ///
+    /// 检查参数个数（包括命令名称）是否符合arity
+    fn check_arity(&self, argc: usize) -> bool {
+        let arity = self.arity.unsigned_abs() as usize;
+        if self.arity < 0 {
+            argc >= arity
+        } else {
+            argc == arity
+        }
+    }
+}
+
+/// 交互模式支持的命令，按照命令名称排序，Tab补全按照这个顺序列出候选
+pub(crate) const COMMANDS: &[CommandSpec] = &[
+    CommandSpec {
+        name: "del",
+        args: &["<key>"],
+        arity: 2,
+        parse: |parts| {
+            Ok(Command::Del {
+                key: parts[1].to_string(),
+            })
+        },
+    },
+    CommandSpec {
+        name: "get",
+        args: &["<key>"],
+        arity: 2,
+        parse: |parts| {
             Ok(Command::Get {
                 key: parts[1].to_string(),
             })
-        }
-        "ping" => {
+        },
+    },
+    CommandSpec {
+        name: "ping",
+        args: &["[message]"],
+        arity: -1,
+        parse: |parts| {
             let msg = if parts.len() > 1 {
                 Some(Bytes::from(parts[1..].join(" ").into_bytes()))
             } else {
                 None
             };
             Ok(Command::Ping { msg })
-        }
-        "publish" => {
-            if parts.len() < 3 {
-                return Err("\r'publish' command usage: publish <channel> <message>".to_string());
-            }
+        },
+    },
+    CommandSpec {
+        name: "publish",
+        args: &["<channel>", "<message>"],
+        arity: -3,
+        parse: |parts| {
             Ok(Command::Publish {
                 channel: parts[1].to_string(),
                 message: Bytes::from(parts[2..].join(" ").into_bytes()),
             })
-        }
-        "set" => {
-            if parts.len() < 3 {
-                return Err("\r'set' command usage: set <key> <value>".to_string());
-            }
-
-            let key = parts[1].to_string();
-            let value = Bytes::from(parts[2].as_bytes().to_vec());
-
-            // 处理可选的过期参数
-            let expire = if parts.len() > 3 {
-                match parts[3].to_lowercase().as_str() {
-                    "ex" if parts.len() == 5 => parts[4]
-                        .parse::<u64>()
-                        .map(|seconds| Some((ExpireMode::EX, seconds)))
-                        .map_err(|_| "Invalid expire time for EX".to_string()),
-                    "px" if parts.len() == 5 => parts[4]
-                        .parse::<u64>()
-                        .map(|milliseconds| Some((ExpireMode::PX, milliseconds)))
-                        .map_err(|_| "Invalid expire time for PX".to_string()),
-                    _ => Ok(None),
-                }
-            } else {
-                Ok(None)
-            }?;
-
-            Ok(Command::Set { key, value, expire })
-        }
-        "subscribe" => {
-            if parts.len() < 2 {
-                return Err("\r'subscribe' command usage: subscribe <channel(s)>".to_string());
-            }
+        },
+    },
+    CommandSpec {
+        name: "save",
+        args: &[],
+        arity: -1,
+        parse: |_| Ok(Command::Save {}),
+    },
+    CommandSpec {
+        name: "set",
+        args: &["<key>", "<value>", "[EX seconds|PX milliseconds]"],
+        arity: -3,
+        parse: parse_set,
+    },
+    CommandSpec {
+        name: "subscribe",
+        args: &["<channel>", "[channel ...]"],
+        arity: -2,
+        parse: |parts| {
             Ok(Command::Subscribe {
                 channels: parts[1..].iter().map(|&s| s.to_string()).collect(),
             })
-        }
-        "unsubscribe" => {
-            if parts.len() < 2 {
-                return Err("\r'unsubscribe' command usage: unsubscribe <channel(s)>".to_string());
-            }
+        },
+    },
+    CommandSpec {
+        name: "unsubscribe",
+        args: &["<channel>", "[channel ...]"],
+        arity: -2,
+        parse: |parts| {
             Ok(Command::Unsubscribe {
                 channels: parts[1..].iter().map(|&s| s.to_string()).collect(),
             })
-        }
-        "save" => Ok(Command::Save {}),
-        "del" => {
-            if parts.len() != 2 {
-                // \r打断前面的>输出
-                return Err("\r'del' command usage: del <key>".to_string());
-            }
-            Ok(Command::Del {
-                key: parts[1].to_string(),
-            })
-        }
-        _ => Err(format!("\rUnknown command: {}", parts[0])),
+        },
+    },
+];
+
+/// # lookup_command() 函数
+///
+/// 按照名称（大小写不敏感）在COMMANDS中查找命令
+pub(crate) fn lookup_command(name: &str) -> Option<&'static CommandSpec> {
+    COMMANDS
+        .iter()
+        .find(|spec| spec.name.eq_ignore_ascii_case(name))
+}
+
+/// # parse_args() 函数
+///
+/// 解析已经拆分好的命令参数，参数中的空白和引号原样保留
+fn parse_args(parts: &[&str]) -> Result<Command, String> {
+    if parts.is_empty() {
+        return Err("\rEmpty command".to_string());
+    }
+
+    let Some(spec) = lookup_command(parts[0]) else {
+        return Err(format!("\rUnknown command: {}", parts[0]));
+    };
+    if !spec.check_arity(parts.len()) {
+        // \r打断前面的>输出
+        return Err(format!("\r'{}' command usage: {}", spec.name, spec.usage()));
     }
+    (spec.parse)(parts)
+}
+
+/// # parse_set() 函数
+///
+/// 解析set命令，支持可选的EX或PX过期参数
+fn parse_set(parts: &[&str]) -> Result<Command, String> {
+    let key = parts[1].to_string();
+    let value = Bytes::from(parts[2].as_bytes().to_vec());
+
+    // 处理可选的过期参数
+    let expire = if parts.len() > 3 {
+        match parts[3].to_lowercase().as_str() {
+            "ex" if parts.len() == 5 => parts[4]
+                .parse::<u64>()
+                .map(|seconds| Some((ExpireMode::EX, seconds)))
+                .map_err(|_| "Invalid expire time for EX".to_string()),
+            "px" if parts.len() == 5 => parts[4]
+                .parse::<u64>()
+                .map(|milliseconds| Some((ExpireMode::PX, milliseconds)))
+                .map_err(|_| "Invalid expire time for PX".to_string()),
+            _ => Ok(None),
+        }
+    } else {
+        Ok(None)
+    }?;
+
+    Ok(Command::Set { key, value, expire })
 }
 
 // 命令历史管理器
@@ -414,7 +703,7 @@ impl CommandHistory {
     async fn add(&self, command: &str) {
         let mut history = self.history.lock().await;
         // 避免重复添加最近的命令
-        if history.last().map_or(true, |last| last != command) {
+        if history.last().is_none_or(|last| last != command) {
             history.push(command.to_string());
         }
         *self.current_index.lock().await = None;
@@ -466,6 +755,7 @@ async fn read_line(
 
     let mut input = String::new();
     let mut cursor_pos = 0;
+    let mut completer = Completer::default();
 
     // 隐藏光标
     print!("\x1B[?25l");
@@ -480,9 +770,16 @@ async fn read_line(
                 print!("{}", c);
             }
         }
-        // 如果光标在输入的末尾，显示一个高亮的空格
+        // 如果光标在输入的末尾，显示一个高亮的空格，命令名称完整时在后面用灰色显示参数提示
         if cursor_pos == input.len() {
-            print!("\x1B[7m \x1B[0m");
+            match completion::hint(&input) {
+                Some(hint) => {
+                    let mut chars = hint.chars();
+                    let first = chars.next().unwrap_or(' ');
+                    print!("\x1B[7m{}\x1B[0m\x1B[90m{}\x1B[0m", first, chars.as_str());
+                }
+                None => print!("\x1B[7m \x1B[0m"),
+            }
         }
         // 清除光标后的内容
         print!("\x1B[K");
@@ -491,7 +788,20 @@ async fn read_line(
         // 这里一开始用的阻塞，因为每次输出响应后都会阻塞直到下一次输入才会运行上面的代码，所以这里改成非阻塞
         if event::poll(Duration::from_millis(100))? {
             if let Event::Key(key_event) = event::read()? {
+                // 除了Tab之外的按键都会结束这一轮补全
+                if key_event.code != KeyCode::Tab {
+                    completer.reset();
+                }
+
                 match key_event {
+                    KeyEvent {
+                        code: KeyCode::Tab, ..
+                    } => {
+                        if let Some(completed) = completer.complete(&input) {
+                            input = completed;
+                            cursor_pos = input.len();
+                        }
+                    }
                     KeyEvent {
                         code: KeyCode::Char(c),
                         modifiers: KeyModifiers::NONE,
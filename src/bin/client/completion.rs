@@ -0,0 +1,160 @@
+//! 交互模式下的命令补全和参数提示，只处理输入的文本，不涉及终端的读写
+
+use crate::{lookup_command, COMMANDS};
+
+/// # Completer 结构体
+///
+/// Tab补全的状态。第一次按Tab时按照输入的前缀找出候选的命令名称，
+/// 连续按Tab时在候选之间循环，按下其它键时应该调用reset()结束这一轮补全
+#[derive(Debug, Default)]
+pub(crate) struct Completer {
+    /// 这一轮补全的候选命令名称
+    candidates: Vec<&'static str>,
+    /// 当前使用的候选
+    index: usize,
+}
+
+impl Completer {
+    /// # complete() 函数
+    ///
+    /// 处理一次Tab，返回补全后的输入，没有可以补全的命令时返回None。
+    /// 只补全命令名称，输入中已经有参数时不补全
+    pub(crate) fn complete(&mut self, input: &str) -> Option<String> {
+        if !self.candidates.is_empty() {
+            self.index = (self.index + 1) % self.candidates.len();
+            return Some(self.candidates[self.index].to_string());
+        }
+
+        let prefix = input.trim_start().to_lowercase();
+        if prefix.contains(char::is_whitespace) {
+            return None;
+        }
+
+        self.candidates = COMMANDS
+            .iter()
+            .map(|spec| spec.name)
+            .filter(|name| name.starts_with(&prefix))
+            .collect();
+        self.index = 0;
+
+        self.candidates.first().map(|name| name.to_string())
+    }
+
+    /// # reset() 函数
+    ///
+    /// 结束这一轮补全，下一次Tab重新按照输入查找候选
+    pub(crate) fn reset(&mut self) {
+        self.candidates.clear();
+        self.index = 0;
+    }
+}
+
+/// # hint() 函数
+///
+/// 命令名称完整时，返回还没有输入的参数的提示，例如输入`set foo`时返回` <value> [EX seconds|PX milliseconds]`。
+/// 提示以空格开头，除非输入已经以空白结尾
+pub(crate) fn hint(input: &str) -> Option<String> {
+    let mut parts = input.split_whitespace();
+    let spec = lookup_command(parts.next()?)?;
+
+    let remaining = spec
+        .args
+        .get(parts.count()..)
+        .filter(|args| !args.is_empty())?;
+    let separator = if input.ends_with(char::is_whitespace) {
+        ""
+    } else {
+        " "
+    };
+
+    Some(format!("{}{}", separator, remaining.join(" ")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 测试按照前缀补全命令名称，大小写不敏感，前导空白被忽略
+    #[test]
+    fn test_complete_prefix() {
+        let mut completer = Completer::default();
+        assert_eq!(completer.complete("pu").as_deref(), Some("publish"));
+
+        completer.reset();
+        assert_eq!(completer.complete("  GE").as_deref(), Some("get"));
+
+        completer.reset();
+        assert_eq!(completer.complete("foo"), None);
+    }
+
+    /// 测试连续按Tab时在候选之间循环，reset()之后重新按照输入查找
+    #[test]
+    fn test_complete_cycles_candidates() {
+        let mut completer = Completer::default();
+        assert_eq!(completer.complete("s").as_deref(), Some("save"));
+        assert_eq!(completer.complete("save").as_deref(), Some("set"));
+        assert_eq!(completer.complete("set").as_deref(), Some("subscribe"));
+        assert_eq!(completer.complete("subscribe").as_deref(), Some("save"));
+
+        completer.reset();
+        assert_eq!(completer.complete("su").as_deref(), Some("subscribe"));
+        assert_eq!(
+            completer.complete("subscribe").as_deref(),
+            Some("subscribe")
+        );
+    }
+
+    /// 测试空输入时补全所有命令，已经输入参数时不补全
+    #[test]
+    fn test_complete_empty_and_arguments() {
+        let mut completer = Completer::default();
+        assert_eq!(completer.complete("").as_deref(), Some("del"));
+        assert_eq!(completer.complete("del").as_deref(), Some("get"));
+
+        completer.reset();
+        assert_eq!(completer.complete("get fo"), None);
+        assert_eq!(completer.complete("get "), None);
+    }
+
+    /// 测试命令表按照名称排序，解析命令和补全使用同一张表，参数个数不对时的用法说明与参数提示一致
+    #[test]
+    fn test_commands_table() {
+        assert!(COMMANDS.windows(2).all(|pair| pair[0].name < pair[1].name));
+
+        let mut completer = Completer::default();
+        for spec in COMMANDS {
+            completer.reset();
+            assert_eq!(completer.complete(spec.name).as_deref(), Some(spec.name));
+        }
+
+        let err = crate::parse_args(&["SET", "foo"]).unwrap_err();
+        assert_eq!(
+            err,
+            "\r'set' command usage: set <key> <value> [EX seconds|PX milliseconds]"
+        );
+        assert!(crate::parse_args(&["Get", "foo"]).is_ok());
+        assert!(crate::parse_args(&["expire", "foo", "10"]).is_err());
+    }
+
+    /// 测试参数提示跳过已经输入的参数
+    #[test]
+    fn test_hint() {
+        assert_eq!(
+            hint("set").as_deref(),
+            Some(" <key> <value> [EX seconds|PX milliseconds]")
+        );
+        assert_eq!(
+            hint("SET foo ").as_deref(),
+            Some("<value> [EX seconds|PX milliseconds]")
+        );
+        assert_eq!(
+            hint("set foo bar").as_deref(),
+            Some(" [EX seconds|PX milliseconds]")
+        );
+        assert_eq!(hint("subscribe a").as_deref(), Some(" [channel ...]"));
+        assert_eq!(hint("get foo"), None);
+        assert_eq!(hint("save"), None);
+        assert_eq!(hint("se"), None);
+        assert_eq!(hint(""), None);
+    }
+}
@@ -1,23 +1,48 @@
 //! 服务器运行命令的实现
 
-use std::fs::File;
+use std::{
+    fs::File,
+    net::{IpAddr, SocketAddr},
+    path::PathBuf,
+};
 
 use clap::Parser;
-use rustis::{server::run, DEFAULT_PORT};
+use rustis::server::{
+    config::{Cidr, ServerConfig},
+    run_with_listeners,
+};
 use tokio::{net::TcpListener, signal};
 use tracing::{event, span, Level};
-use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt};
+use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
 
 #[tokio::main]
 async fn main() -> rustis::Result<()> {
-    // 如果不存在logs文件夹，则创建
-    if !std::path::Path::new("logs").exists() {
-        std::fs::create_dir("logs").expect("无法创建logs文件夹");
-    }
-    // 输出到文件中
-    let file = File::create("logs/server.log").expect("无法创建日志文件");
-    let (non_blocking_appender, _guard) = tracing_appender::non_blocking(file);
-    let file_layer = fmt::layer()
+    let cli = Cli::parse();
+
+    // 读取配置文件（如果有），命令行参数会覆盖配置文件中的值
+    let mut config = match &cli.config {
+        Some(path) => ServerConfig::from_file(path)?,
+        None => ServerConfig::default(),
+    };
+    cli.apply_to(&mut config);
+
+    // redis的日志级别会被映射为tracing的级别，其他值使用RUST_LOG的语法，既可以是单个级别，
+    // 也可以是"warn,rustis::cmd=trace"这样的指令列表
+    let filter = EnvFilter::try_new(config.log_filter())
+        .map_err(|err| format!("无效的日志级别 '{}': {err}", config.loglevel))?;
+
+    // 输出到标准错误或者文件中，_guard在main返回之前不能被drop，否则缓冲的日志会丢失
+    let (non_blocking_appender, _guard) = if cli.log_stderr {
+        tracing_appender::non_blocking(std::io::stderr())
+    } else {
+        // 如果不存在logs文件夹，则创建
+        if !std::path::Path::new("logs").exists() {
+            std::fs::create_dir("logs").expect("无法创建logs文件夹");
+        }
+        let file = File::create("logs/server.log").expect("无法创建日志文件");
+        tracing_appender::non_blocking(file)
+    };
+    let fmt_layer = fmt::layer()
         .with_timer(fmt::time::UtcTime::rfc_3339()) // 使用 RFC 3339 格式的 UTC 时间
         .with_target(true) // 显示日志目标
         .with_level(true) // 显示日志级别
@@ -27,22 +52,44 @@ async fn main() -> rustis::Result<()> {
         .compact();
 
     // 初始化全局subscriber
-    tracing_subscriber::registry().with(file_layer).init();
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(fmt_layer)
+        .init();
 
     // 创建一个root span
     let main_span = span!(Level::DEBUG, "server-main");
 
-    let cli = Cli::parse();
-    let port = cli.port.unwrap_or(DEFAULT_PORT);
-
-    // 绑定到指定端口
-    let listener = TcpListener::bind(&format!("localhost:{port}")).await?;
+    // 绑定到配置的地址和端口
+    let port = config.port;
+    let listeners = bind_listeners(&config.bind, port).await?;
 
     event!(parent: &main_span, Level::DEBUG, "Rustis server has been started on port {port}");
+
     // 运行服务器
-    run(listener, signal::ctrl_c(), true).await;
+    run_with_listeners(listeners, signal::ctrl_c(), config).await
+}
+
+/// # bind_listeners() 函数
+///
+/// 在每个地址的指定端口上绑定一个监听器，任何一个地址无效或绑定失败都会返回错误
+async fn bind_listeners(addrs: &[String], port: u16) -> rustis::Result<Vec<TcpListener>> {
+    let mut listeners = Vec::with_capacity(addrs.len());
+
+    for addr in addrs {
+        // IP地址直接拼接端口（IPv6地址需要用[]包裹），否则当作主机名解析
+        let socket_addr = match addr.parse::<IpAddr>() {
+            Ok(ip) => SocketAddr::new(ip, port).to_string(),
+            Err(_) => format!("{addr}:{port}"),
+        };
+
+        let listener = TcpListener::bind(&socket_addr)
+            .await
+            .map_err(|err| format!("无法绑定地址 '{addr}': {err}"))?;
+        listeners.push(listener);
+    }
 
-    Ok(())
+    Ok(listeners)
 }
 
 #[derive(Parser, Debug)]
@@ -53,8 +100,76 @@ async fn main() -> rustis::Result<()> {
     about = "rust redis server"
 )]
 struct Cli {
+    /// 配置文件的路径，扩展名为.toml时按TOML解析，否则按redis风格的`参数名 值`解析
+    #[arg(long)]
+    config: Option<PathBuf>,
+
     // 使用了clap crate的#[arg]宏
     // 指定了long参数，long是指以两个连字符(--)开头的参数
     #[arg(long)]
     port: Option<u16>,
+
+    /// 绑定的地址，可以指定多个，默认为localhost
+    #[arg(long, num_args = 1..)]
+    bind: Vec<String>,
+
+    /// 允许连接的客户端地址段（如10.0.0.0/8），可以指定多个，默认允许所有地址
+    #[arg(long, num_args = 1..)]
+    allow: Vec<Cidr>,
+
+    /// 最大连接数，默认为10000
+    #[arg(long)]
+    maxclients: Option<usize>,
+
+    /// 达到最大连接数时，让新连接排队等待而不是直接拒绝
+    #[arg(long)]
+    maxclients_queue: bool,
+
+    /// RDB文件损坏时使用空数据库启动，而不是拒绝启动
+    #[arg(long)]
+    ignore_corrupt_rdb: bool,
+
+    /// 健康检查端口，开启后在第一个绑定地址的这个端口上回复HTTP 200，用于存活探针
+    #[arg(long)]
+    health_port: Option<u16>,
+
+    /// 日志级别，可以是redis的日志级别（如notice）或者RUST_LOG的语法（如warn,rustis::cmd=trace），覆盖配置文件中的loglevel
+    #[arg(long)]
+    log_level: Option<String>,
+
+    /// 将日志输出到标准错误而不是logs/server.log，用于在前台运行
+    #[arg(long)]
+    log_stderr: bool,
+}
+
+impl Cli {
+    /// # apply_to() 函数
+    ///
+    /// 用命令行中指定了的参数覆盖配置
+    fn apply_to(&self, config: &mut ServerConfig) {
+        if let Some(port) = self.port {
+            config.port = port;
+        }
+        if !self.bind.is_empty() {
+            config.bind = self.bind.clone();
+        }
+        if !self.allow.is_empty() {
+            config.allowlist = self.allow.clone();
+        }
+        if let Some(maxclients) = self.maxclients {
+            config.max_connections = maxclients;
+        }
+        if self.maxclients_queue {
+            config.reject_on_max_connections = false;
+        }
+        if self.ignore_corrupt_rdb {
+            config.ignore_corrupt_rdb = true;
+        }
+        if let Some(health_port) = self.health_port {
+            config.health_port = Some(health_port);
+        }
+        if let Some(log_level) = &self.log_level {
+            config.loglevel = log_level.clone();
+        }
+    }
 }
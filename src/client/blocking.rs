@@ -0,0 +1,150 @@
+//! BlockingClient结构体，同步代码使用的客户端
+
+use bytes::Bytes;
+use tokio::{
+    runtime::{Builder, Runtime},
+    time::Duration,
+};
+
+use super::{Client, IntoConnectAddr, Message, Subscriber};
+
+/// # BlockingClient 结构体
+///
+/// 同步代码使用的客户端，内部持有一个单线程的tokio运行时和一个Client，每个方法都阻塞到响应返回，
+/// 方法的名称和参数与Client相同
+///
+/// 不能在异步运行时中调用BlockingClient的方法，tokio会因为在运行时中阻塞而panic
+pub struct BlockingClient {
+    /// 异步的客户端，必须在运行时之前被drop
+    inner: Client,
+    /// 执行异步请求的运行时
+    rt: Runtime,
+}
+
+impl BlockingClient {
+    /// # connect() 函数
+    ///
+    /// 创建一个单线程运行时，并在其中与远程服务器建立连接
+    pub fn connect<T: IntoConnectAddr>(addr: T) -> crate::Result<BlockingClient> {
+        let rt = Builder::new_current_thread().enable_all().build()?;
+        let inner = rt.block_on(Client::connect(addr))?;
+
+        Ok(BlockingClient { inner, rt })
+    }
+
+    /// # ping() 函数
+    ///
+    /// 发送PING，没有提供参数时返回PONG
+    pub fn ping(&mut self, msg: Option<Bytes>) -> crate::Result<Bytes> {
+        self.rt.block_on(self.inner.ping(msg))
+    }
+
+    /// # get() 函数
+    ///
+    /// 获取key的值
+    pub fn get(&mut self, key: &str) -> crate::Result<Option<Bytes>> {
+        self.rt.block_on(self.inner.get(key))
+    }
+
+    /// # set() 函数
+    ///
+    /// 设置key的值
+    pub fn set(&mut self, key: &str, value: Bytes) -> crate::Result<()> {
+        self.rt.block_on(self.inner.set(key, value))
+    }
+
+    /// # set_with_expires() 函数
+    ///
+    /// 设置key的值，并设置过期时间
+    pub fn set_with_expires(
+        &mut self,
+        key: &str,
+        value: Bytes,
+        expires: Duration,
+    ) -> crate::Result<()> {
+        self.rt
+            .block_on(self.inner.set_with_expires(key, value, expires))
+    }
+
+    /// # del() 函数
+    ///
+    /// 删除key
+    pub fn del(&mut self, key: &str) -> crate::Result<()> {
+        self.rt.block_on(self.inner.del(key))
+    }
+
+    /// # publish() 函数
+    ///
+    /// 将消息发布到channel，返回收到消息的订阅者数量
+    pub fn publish(&mut self, channel: &str, message: Bytes) -> crate::Result<u64> {
+        self.rt.block_on(self.inner.publish(channel, message))
+    }
+
+    /// # subscribe() 函数
+    ///
+    /// 订阅channels，连接进入订阅模式，转换为BlockingSubscriber
+    pub fn subscribe(self, channels: Vec<String>) -> crate::Result<BlockingSubscriber> {
+        let BlockingClient { inner, rt } = self;
+        let inner = rt.block_on(inner.subscribe(channels))?;
+
+        Ok(BlockingSubscriber { inner, rt })
+    }
+}
+
+/// # BlockingSubscriber 结构体
+///
+/// 同步代码使用的订阅者，方法与Subscriber相同。也可以作为迭代器逐条读取消息，连接关闭时迭代结束
+pub struct BlockingSubscriber {
+    /// 异步的订阅者，必须在运行时之前被drop
+    inner: Subscriber,
+    /// 执行异步请求的运行时
+    rt: Runtime,
+}
+
+impl BlockingSubscriber {
+    /// # get_subscriber_channels() 函数
+    ///
+    /// 获取当前订阅的channels
+    pub fn get_subscriber_channels(&self) -> &[String] {
+        self.inner.get_subscriber_channels()
+    }
+
+    /// # next_message() 函数
+    ///
+    /// 阻塞到收到下一条消息，None表示连接已经关闭
+    pub fn next_message(&mut self) -> crate::Result<Option<Message>> {
+        self.rt.block_on(self.inner.next_message())
+    }
+
+    /// # subscribe() 函数
+    ///
+    /// 订阅更多的channels
+    pub fn subscribe(&mut self, channels: &[String]) -> crate::Result<()> {
+        self.rt.block_on(self.inner.subscribe(channels))
+    }
+
+    /// # unsubscribe() 函数
+    ///
+    /// 退订channels，channels为空时退订所有channel
+    pub fn unsubscribe(&mut self, channels: &[String]) -> crate::Result<()> {
+        self.rt.block_on(self.inner.unsubscribe(channels))
+    }
+
+    /// # exit_subscribe() 函数
+    ///
+    /// 取消所有订阅并退出订阅模式，返回可以继续执行普通命令的BlockingClient
+    pub fn exit_subscribe(self) -> crate::Result<BlockingClient> {
+        let BlockingSubscriber { inner, rt } = self;
+        let inner = rt.block_on(inner.exit_subscribe())?;
+
+        Ok(BlockingClient { inner, rt })
+    }
+}
+
+impl Iterator for BlockingSubscriber {
+    type Item = crate::Result<Message>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_message().transpose()
+    }
+}
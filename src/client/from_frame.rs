@@ -0,0 +1,293 @@
+//! FromFrame trait，把服务器的响应帧转换为Rust类型
+
+use bytes::Bytes;
+
+use crate::{networking::frame::Frame, RustisError};
+
+/// # FromFrame trait
+///
+/// 把服务器的响应帧转换为Rust类型，Client的方法用它解码响应，
+/// 也可以用来解码`Client::command`返回的原始帧，例如`Vec::<Bytes>::from_frame(frame)`
+///
+/// 错误帧转换为对应的RustisError，帧的类型与期望的类型不符时返回描述期望类型的协议错误
+pub trait FromFrame: Sized {
+    /// # from_frame() 函数
+    ///
+    /// 把帧转换为Self
+    fn from_frame(frame: Frame) -> crate::Result<Self>;
+}
+
+/// # unexpected() 函数
+///
+/// 帧不能转换为expected时的错误，错误帧转换为服务器返回的错误
+fn unexpected(frame: Frame, expected: &str) -> crate::Error {
+    match frame {
+        Frame::Error(msg) => RustisError::from_error_message(msg).into(),
+        frame => RustisError::Protocol(format!(
+            "unexpected frame, expected {}: {:?}",
+            expected, frame
+        ))
+        .into(),
+    }
+}
+
+/// 原样返回帧，错误帧转换为Err
+impl FromFrame for Frame {
+    fn from_frame(frame: Frame) -> crate::Result<Frame> {
+        match frame {
+            Frame::Error(msg) => Err(RustisError::from_error_message(msg).into()),
+            frame => Ok(frame),
+        }
+    }
+}
+
+/// Simple、Bulk和Verbatim帧的内容
+impl FromFrame for Bytes {
+    fn from_frame(frame: Frame) -> crate::Result<Bytes> {
+        match frame {
+            Frame::Simple(value) => Ok(value.into()),
+            Frame::Bulk(value) => Ok(value),
+            Frame::Verbatim { text, .. } => Ok(text),
+            frame => Err(unexpected(frame, "a string")),
+        }
+    }
+}
+
+/// Simple、Bulk和Verbatim帧的内容，必须是合法的UTF-8
+impl FromFrame for String {
+    fn from_frame(frame: Frame) -> crate::Result<String> {
+        let value = match frame {
+            Frame::Simple(value) => return Ok(value),
+            Frame::Bulk(value) | Frame::Verbatim { text: value, .. } => value,
+            frame => return Err(unexpected(frame, "a string")),
+        };
+        String::from_utf8(value.to_vec())
+            .map_err(|_| unexpected(Frame::Bulk(value), "a UTF-8 string"))
+    }
+}
+
+/// Integer帧
+impl FromFrame for i64 {
+    fn from_frame(frame: Frame) -> crate::Result<i64> {
+        match frame {
+            Frame::Integer(value) => Ok(value),
+            frame => Err(unexpected(frame, "an integer")),
+        }
+    }
+}
+
+/// 非负的Integer帧
+impl FromFrame for u64 {
+    fn from_frame(frame: Frame) -> crate::Result<u64> {
+        match frame {
+            Frame::Integer(value) if value >= 0 => Ok(value as u64),
+            frame => Err(unexpected(frame, "a non-negative integer")),
+        }
+    }
+}
+
+/// 状态回复OK
+impl FromFrame for () {
+    fn from_frame(frame: Frame) -> crate::Result<()> {
+        match frame {
+            Frame::Simple(response) if response.eq_ignore_ascii_case("OK") => Ok(()),
+            frame => Err(unexpected(frame, "OK")),
+        }
+    }
+}
+
+/// Null和NullArray转换为None，其他帧转换为T
+impl<T: FromFrame> FromFrame for Option<T> {
+    fn from_frame(frame: Frame) -> crate::Result<Option<T>> {
+        match frame {
+            Frame::Null | Frame::NullArray => Ok(None),
+            frame => T::from_frame(frame).map(Some),
+        }
+    }
+}
+
+/// Array和Set帧的每个元素，Map帧的每个键值对作为两个元素的数组转换
+impl<T: FromFrame> FromFrame for Vec<T> {
+    fn from_frame(frame: Frame) -> crate::Result<Vec<T>> {
+        match frame {
+            Frame::Array(frames) | Frame::Set(frames) => {
+                frames.into_iter().map(T::from_frame).collect()
+            }
+            Frame::Map(pairs) => pairs
+                .into_iter()
+                .map(|(key, value)| T::from_frame(Frame::Array(vec![key, value])))
+                .collect(),
+            frame => Err(unexpected(frame, "an array")),
+        }
+    }
+}
+
+/// 两个元素的Array帧
+impl<A: FromFrame, B: FromFrame> FromFrame for (A, B) {
+    fn from_frame(frame: Frame) -> crate::Result<(A, B)> {
+        match frame {
+            Frame::Array(frames) if frames.len() == 2 => {
+                let mut frames = frames.into_iter();
+                let a = A::from_frame(frames.next().unwrap())?;
+                let b = B::from_frame(frames.next().unwrap())?;
+                Ok((a, b))
+            }
+            frame => Err(unexpected(frame, "an array of two elements")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bulk(value: &str) -> Frame {
+        Frame::Bulk(Bytes::from(value.to_string()))
+    }
+
+    fn is_protocol_error(err: &crate::Error) -> bool {
+        matches!(
+            err.downcast_ref::<RustisError>(),
+            Some(RustisError::Protocol(_))
+        )
+    }
+
+    /// 测试字符串类型的转换
+    #[test]
+    fn test_strings() {
+        assert_eq!(Bytes::from_frame(bulk("a")).unwrap(), Bytes::from("a"));
+        assert_eq!(
+            Bytes::from_frame(Frame::Simple("OK".to_string())).unwrap(),
+            Bytes::from("OK")
+        );
+        let verbatim = Frame::Verbatim {
+            format: "txt".to_string(),
+            text: Bytes::from("v"),
+        };
+        assert_eq!(
+            Bytes::from_frame(verbatim.clone()).unwrap(),
+            Bytes::from("v")
+        );
+        assert!(is_protocol_error(
+            &Bytes::from_frame(Frame::Integer(1)).unwrap_err()
+        ));
+
+        assert_eq!(String::from_frame(bulk("a")).unwrap(), "a");
+        assert_eq!(String::from_frame(verbatim).unwrap(), "v");
+        let err = String::from_frame(Frame::Bulk(Bytes::from_static(b"\xff"))).unwrap_err();
+        assert!(is_protocol_error(&err));
+        assert!(err.to_string().contains("UTF-8"), "{}", err);
+        assert!(is_protocol_error(
+            &String::from_frame(Frame::Null).unwrap_err()
+        ));
+    }
+
+    /// 测试整数的转换，负数不能转换为u64
+    #[test]
+    fn test_integers() {
+        assert_eq!(i64::from_frame(Frame::Integer(-3)).unwrap(), -3);
+        assert_eq!(u64::from_frame(Frame::Integer(3)).unwrap(), 3);
+        assert!(is_protocol_error(
+            &u64::from_frame(Frame::Integer(-3)).unwrap_err()
+        ));
+        assert!(is_protocol_error(&i64::from_frame(bulk("3")).unwrap_err()));
+    }
+
+    /// 测试OK和Option的转换
+    #[test]
+    fn test_ok_and_option() {
+        <()>::from_frame(Frame::Simple("OK".to_string())).unwrap();
+        <()>::from_frame(Frame::Simple("ok".to_string())).unwrap();
+        assert!(is_protocol_error(
+            &<()>::from_frame(Frame::Simple("QUEUED".to_string())).unwrap_err()
+        ));
+
+        assert_eq!(Option::<Bytes>::from_frame(Frame::Null).unwrap(), None);
+        assert_eq!(Option::<Bytes>::from_frame(Frame::NullArray).unwrap(), None);
+        assert_eq!(
+            Option::<Bytes>::from_frame(bulk("a")).unwrap(),
+            Some(Bytes::from("a"))
+        );
+        assert!(is_protocol_error(
+            &Option::<Bytes>::from_frame(Frame::Integer(1)).unwrap_err()
+        ));
+    }
+
+    /// 测试数组和元组的转换，包括嵌套的数组和map
+    #[test]
+    fn test_vec_and_tuple() {
+        let frame = Frame::Array(vec![bulk("a"), Frame::Null, bulk("c")]);
+        assert_eq!(
+            Vec::<Option<String>>::from_frame(frame).unwrap(),
+            vec![Some("a".to_string()), None, Some("c".to_string())]
+        );
+
+        let nested = Frame::Array(vec![
+            Frame::Array(vec![Frame::Integer(1), Frame::Integer(2)]),
+            Frame::Array(vec![]),
+            Frame::Set(vec![Frame::Integer(3)]),
+        ]);
+        assert_eq!(
+            Vec::<Vec<i64>>::from_frame(nested).unwrap(),
+            vec![vec![1, 2], vec![], vec![3]]
+        );
+
+        let pairs = Frame::Array(vec![
+            Frame::Array(vec![bulk("a"), Frame::Integer(1)]),
+            Frame::Array(vec![bulk("b"), Frame::Integer(2)]),
+        ]);
+        assert_eq!(
+            Vec::<(String, u64)>::from_frame(pairs).unwrap(),
+            vec![("a".to_string(), 1), ("b".to_string(), 2)]
+        );
+        let map = Frame::Map(vec![(bulk("a"), Frame::Integer(1))]);
+        assert_eq!(
+            Vec::<(String, u64)>::from_frame(map).unwrap(),
+            vec![("a".to_string(), 1)]
+        );
+
+        // 元素个数不对、元素类型不对都是协议错误
+        let three = Frame::Array(vec![bulk("a"), bulk("b"), bulk("c")]);
+        assert!(is_protocol_error(
+            &<(String, String)>::from_frame(three).unwrap_err()
+        ));
+        let wrong = Frame::Array(vec![Frame::Array(vec![Frame::Integer(1), bulk("x")])]);
+        assert!(is_protocol_error(
+            &Vec::<Vec<i64>>::from_frame(wrong).unwrap_err()
+        ));
+        assert!(is_protocol_error(
+            &Vec::<Bytes>::from_frame(bulk("a")).unwrap_err()
+        ));
+    }
+
+    /// 测试错误帧转换为对应的错误类型，嵌套在数组中的错误帧也一样
+    #[test]
+    fn test_error_frames() {
+        let err = Bytes::from_frame(Frame::Error("ERR no such key".to_string())).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<RustisError>(),
+            Some(RustisError::NoSuchKey)
+        ));
+
+        let wrong_type =
+            "WRONGTYPE Operation against a key holding the wrong kind of value".to_string();
+        let err = <()>::from_frame(Frame::Error(wrong_type)).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<RustisError>(),
+            Some(RustisError::WrongType)
+        ));
+
+        let frame = Frame::Array(vec![bulk("a"), Frame::Error("ERR boom".to_string())]);
+        let err = Vec::<Bytes>::from_frame(frame).unwrap_err();
+        assert!(
+            matches!(err.downcast_ref::<RustisError>(), Some(RustisError::Command(msg)) if msg == "ERR boom")
+        );
+
+        let err = Frame::from_frame(Frame::Error("ERR boom".to_string())).unwrap_err();
+        assert_eq!(err.to_string(), "ERR boom");
+        assert_eq!(
+            Frame::from_frame(Frame::Integer(1)).unwrap(),
+            Frame::Integer(1)
+        );
+    }
+}
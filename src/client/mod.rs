@@ -2,27 +2,104 @@
 
 use async_stream::try_stream;
 use bytes::Bytes;
-use std::io::{Error, ErrorKind};
-use tokio::{
-    net::{TcpStream, ToSocketAddrs},
-    time::Duration,
+use std::{
+    collections::VecDeque,
+    io::{Error, ErrorKind},
+    net::SocketAddr,
 };
+use tokio::time::{self, Duration, Instant};
 use tokio_stream::Stream;
-use tracing::{debug, instrument};
+use tracing::{debug, instrument, warn};
 
 use crate::{
     cmd::{
-        del::Del, get::Get, ping::Ping, publish::Publish, save::Save, set::Set, subscribe::{ExitSubscribe, Subscribe, Unsubscribe}
+        auth::Auth,
+        client::Client as ClientCmd,
+        command::CommandCmd,
+        config::Config,
+        debug::Debug,
+        del::Del,
+        expire::{Expire, ExpireUnit},
+        expiretime::ExpireTime,
+        flush::{FlushAll, FlushDb},
+        get::Get,
+        getrange::GetRange,
+        hello::Hello,
+        info::Info,
+        latencystats::LatencyStats,
+        memory::Memory,
+        mget::MGet,
+        monitor::Monitor as MonitorCmd,
+        object::Object,
+        ping::Ping,
+        publish::Publish,
+        readonly::{ReadOnly, ReadWrite},
+        replicaof::ReplicaOf,
+        save::{BgSave, Save},
+        scan::Scan,
+        select::Select,
+        set::Set,
+        stats::Stats,
+        subscribe::{ExitSubscribe, Subscribe, Unsubscribe},
     },
-    networking::{connection::Connection, frame::Frame},
+    networking::{connection::Connection, socket::configure_socket},
+    RustisError,
 };
 
+mod addr;
+mod blocking;
+mod from_frame;
+mod options;
+mod pipeline;
+mod pool;
+mod shared;
+
+pub use crate::cmd::{
+    expire::ExpireCondition,
+    set::{SetCondition, SetOptions, SetResult},
+};
+pub use crate::networking::frame::Frame;
+pub use addr::{ConnectAddr, IntoConnectAddr};
+pub use blocking::{BlockingClient, BlockingSubscriber};
+pub use from_frame::FromFrame;
+pub use options::{ClientBuilder, ConnectOptions};
+pub use pipeline::Pipeline;
+pub use pool::{Pool, PoolStats, PooledClient};
+pub use shared::SharedClient;
+
+/// Client被drop时发送的QUIT命令，Drop中不能异步编码帧，所以直接使用编码好的字节
+const QUIT_REQUEST: &[u8] = b"*1\r\n$4\r\nQUIT\r\n";
+
+/// 自动重连时的最大尝试次数
+const RECONNECT_ATTEMPTS: u32 = 10;
+/// 自动重连的初始等待时间，每次失败后翻倍
+const RECONNECT_BACKOFF: Duration = Duration::from_millis(50);
+/// 自动重连的最长等待时间
+const MAX_RECONNECT_BACKOFF: Duration = Duration::from_secs(2);
+
 /// # Client 结构体
 ///
 /// Client结构体是一个客户端结构体，用于与服务器进行通信
 pub struct Client {
     /// Connection实例
     connection: Connection,
+    /// 服务器地址，重连时使用
+    addr: SocketAddr,
+    /// 建立连接时的选项，重连时使用
+    options: ConnectOptions,
+    /// 认证使用的密码，重连后会重新认证
+    password: Option<String>,
+    /// 每个请求从写入到读完响应的最长时间，None表示一直等待
+    request_timeout: Option<Duration>,
+    /// 当前请求的截止时间，由send_request()设置
+    deadline: Option<Instant>,
+    /// 有请求超时之后，连接上可能还有没有读取的旧响应，之后的请求直接失败，直到重新连接
+    poisoned: bool,
+    /// 已经开始发送但还没有读到响应的请求数量，请求的future在读到响应之前被取消时不为0，
+    /// 连接池不会复用这样的连接
+    pending_replies: usize,
+    /// 服务器是否已经因为不认识MGET而拒绝过，之后get_many()直接使用GET
+    mget_unsupported: bool,
 }
 
 impl Client {
@@ -32,32 +109,321 @@ impl Client {
     ///
     /// # 参数
     ///
-    /// - `addr`: 远程服务器的ip地址, 可以是任何可以异步转换为SocketAddr的类型，ToSocketAddrs trait是tokio版本不是std版本
-    pub async fn connect<T: ToSocketAddrs>(addr: T) -> crate::Result<Client> {
-        // 通过TcpStream::connect()函数与远程服务器建立连接, 返回一个socket
-        let socket = TcpStream::connect(addr).await?;
+    /// - `addr`: 远程服务器的地址，可以是SocketAddr、(主机, 端口)、`host:port`形式的字符串，
+    ///   或者`redis://[[username]:password@]host[:port][/database]`形式的URL，URL中的密码和数据库在建立连接后设置
+    pub async fn connect<T: IntoConnectAddr>(addr: T) -> crate::Result<Client> {
+        Client::connect_with_options(addr, ConnectOptions::default()).await
+    }
+
+    /// # builder() 函数
+    ///
+    /// 创建一个ClientBuilder，设置好连接选项之后通过build()建立连接
+    pub fn builder() -> ClientBuilder {
+        ClientBuilder::new()
+    }
+
+    /// # connect_with_options() 函数
+    ///
+    /// 使用指定的连接选项与远程服务器建立连接。选项中设置了密码、数据库或者连接名称时，
+    /// 建立连接后依次发送AUTH、SELECT和CLIENT SETNAME，任何一个失败都返回错误
+    pub async fn connect_with_options<T: IntoConnectAddr>(
+        addr: T,
+        options: ConnectOptions,
+    ) -> crate::Result<Client> {
+        // URL中的用户名、密码和数据库补充选项中没有设置的部分
+        let addr = addr.into_connect_addr()?;
+        let options = addr.apply_to(options);
+
+        // 与远程服务器建立连接, 返回一个socket
+        let socket = match options.connect_timeout {
+            Some(timeout) => time::timeout(timeout, addr.connect())
+                .await
+                .map_err(|_| RustisError::Io(Error::new(ErrorKind::TimedOut, "建立连接超时")))??,
+            None => addr.connect().await?,
+        };
+        // 按照选项设置TCP_NODELAY和keepalive
+        configure_socket(&socket, options.nodelay, options.keepalive)?;
+        // 记录实际连接的地址，重连时直接使用
+        let addr = socket.peer_addr()?;
 
         // 初始化Connection实例，将socket传入，会为其分配读写缓冲区来执行redis协议帧解析
         let connection = Connection::new(socket);
 
-        Ok(Client { connection })
+        let mut client = Client {
+            connection,
+            addr,
+            request_timeout: options.request_timeout,
+            options,
+            password: None,
+            deadline: None,
+            poisoned: false,
+            pending_replies: 0,
+            mget_unsupported: false,
+        };
+
+        if let Some(password) = client.options.password.clone() {
+            let username = client.options.username.clone();
+            client
+                .auth_cmd(Auth::new(username, &password), &password)
+                .await?;
+        }
+        if let Some(database) = client.options.database {
+            client.select(database).await?;
+        }
+        if let Some(name) = client.options.client_name.clone() {
+            client.client_setname(&name).await?;
+        }
+
+        Ok(client)
+    }
+
+    /// # with_request_timeout() 函数
+    ///
+    /// 设置每个请求从写入到读完响应的最长时间，None表示一直等待
+    ///
+    /// 请求超时后返回ErrorKind::TimedOut的IO错误，并且连接被标记为不可用：之后的请求直接返回TimedOut错误，
+    /// 不会读到超时请求的响应，调用reconnect()之后才能继续使用
+    pub fn with_request_timeout(mut self, timeout: Option<Duration>) -> Client {
+        self.request_timeout = timeout;
+        self
+    }
+
+    /// # connect_with_auth() 函数
+    ///
+    /// 与远程服务器建立连接后立即发送AUTH命令进行认证，认证失败时返回错误
+    pub async fn connect_with_auth<T: IntoConnectAddr>(
+        addr: T,
+        password: &str,
+    ) -> crate::Result<Client> {
+        let mut client = Client::connect(addr).await?;
+        client.auth(password).await?;
+
+        Ok(client)
+    }
+
+    /// # connect_resp3() 函数
+    ///
+    /// 与远程服务器建立连接后发送`HELLO 3`，服务器支持时切换到RESP3，之后的响应可能包含map、double、boolean等类型。
+    /// 服务器不支持HELLO命令或者不支持RESP3时，继续使用RESP2
+    pub async fn connect_resp3<T: IntoConnectAddr>(addr: T) -> crate::Result<Client> {
+        let mut client = Client::connect(addr).await?;
+        client.hello_resp3().await?;
+
+        Ok(client)
+    }
+
+    /// # pipeline() 函数
+    ///
+    /// 创建一个空的Pipeline，命令入队之后通过Pipeline::execute()一次性发送
+    pub fn pipeline(&self) -> Pipeline {
+        Pipeline::new()
+    }
+
+    /// # protocol() 函数
+    ///
+    /// 返回与服务器协商的协议版本，2或者3
+    pub fn protocol(&self) -> u8 {
+        self.connection.protocol()
+    }
+
+    /// # hello_resp3() 函数
+    ///
+    /// 发送`HELLO 3`尝试切换到RESP3，服务器回复错误时保持RESP2
+    async fn hello_resp3(&mut self) -> crate::Result<()> {
+        let frame = Hello::new(Some(3)).code_hello_into_frame();
+        debug!(request = ?frame);
+
+        self.send_request(&frame).await?;
+
+        match self.read_response().await {
+            Ok(Frame::Map(_)) => {
+                self.connection.set_protocol(3);
+                Ok(())
+            }
+            Ok(frame) => Err(frame.to_error()),
+            // 服务器不认识HELLO命令或者不支持RESP3
+            Err(err)
+                if matches!(
+                    err.downcast_ref::<RustisError>(),
+                    Some(RustisError::Command(_))
+                ) =>
+            {
+                debug!(cause = %err, "server does not support RESP3, using RESP2");
+                Ok(())
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    /// # reconnect() 函数
+    ///
+    /// 使用原来的地址和选项重新建立连接，失败时按指数退避重试，之前认证过的连接会重新认证
+    ///
+    /// 连接选项中的数据库和连接名称会重新设置，之后通过select()等方法修改的连接状态不会恢复
+    pub async fn reconnect(&mut self) -> crate::Result<()> {
+        let mut backoff = RECONNECT_BACKOFF;
+        let mut attempts = 0;
+
+        loop {
+            match self.try_reconnect().await {
+                Ok(()) => return Ok(()),
+                Err(err) => {
+                    attempts += 1;
+                    if attempts >= RECONNECT_ATTEMPTS {
+                        return Err(err);
+                    }
+                    debug!(cause = %err, attempts, "reconnect failed");
+                }
+            }
+
+            time::sleep(backoff).await;
+            backoff = (backoff * 2).min(MAX_RECONNECT_BACKOFF);
+        }
+    }
+
+    /// # try_reconnect() 函数
+    ///
+    /// 尝试重新建立一次连接
+    async fn try_reconnect(&mut self) -> crate::Result<()> {
+        let mut client = Client::connect_with_options(self.addr, self.options.clone())
+            .await?
+            .with_request_timeout(self.request_timeout);
+        // 连接选项中的密码已经在connect_with_options()中认证过，只有之后通过auth()换过的密码需要重新认证
+        if let Some(password) = &self.password {
+            if client.password.as_ref() != Some(password) {
+                client.auth(password).await?;
+            }
+        }
+        if self.protocol() == 3 {
+            client.hello_resp3().await?;
+        }
+
+        *self = client;
+        Ok(())
+    }
+
+    /// # send_request() 函数
+    ///
+    /// 将请求帧写入socket，设置了request_timeout时从这里开始计算请求的截止时间
+    async fn send_request(&mut self, frame: &Frame) -> crate::Result<()> {
+        self.check_poisoned()?;
+
+        // 在写入之前计数，写到一半被取消时请求也可能已经部分发送
+        self.pending_replies += 1;
+        self.deadline = self.request_timeout.map(|timeout| Instant::now() + timeout);
+        match self.deadline {
+            Some(deadline) => {
+                match time::timeout_at(deadline, self.connection.write_frame(frame)).await {
+                    Ok(res) => res?,
+                    Err(_) => return Err(self.poison()),
+                }
+            }
+            None => self.connection.write_frame(frame).await?,
+        }
+
+        Ok(())
+    }
+
+    /// # is_idle() 函数
+    ///
+    /// 连接上是否没有等待读取的响应，并且没有因为请求超时而不可用，只有这样的连接才能被连接池复用
+    fn is_idle(&self) -> bool {
+        self.pending_replies == 0 && !self.poisoned
+    }
+
+    /// # check_poisoned() 函数
+    ///
+    /// 连接因为请求超时而不可用时返回TimedOut错误
+    fn check_poisoned(&self) -> crate::Result<()> {
+        if self.poisoned {
+            let error = Error::new(ErrorKind::TimedOut, "连接上有超时的请求，需要重新连接");
+            return Err(RustisError::Io(error).into());
+        }
+
+        Ok(())
+    }
+
+    /// # poison() 函数
+    ///
+    /// 请求超时，将连接标记为不可用，返回TimedOut错误
+    fn poison(&mut self) -> crate::Error {
+        self.poisoned = true;
+        warn!(timeout = ?self.request_timeout, "request timed out, connection is unusable until reconnect");
+        RustisError::Io(Error::new(ErrorKind::TimedOut, "请求超时")).into()
+    }
+
+    /// # send_pipeline() 函数
+    ///
+    /// 将多个请求帧写入写缓冲区，最后只flush一次，截止时间从这里开始计算，覆盖所有请求的写入和响应的读取
+    async fn send_pipeline(&mut self, frames: &[Frame]) -> crate::Result<()> {
+        self.check_poisoned()?;
+
+        self.pending_replies += frames.len();
+        self.deadline = self.request_timeout.map(|timeout| Instant::now() + timeout);
+        let connection = &mut self.connection;
+        let write = async move {
+            for frame in frames {
+                connection.write_frame_nowait(frame).await?;
+            }
+            connection.flush().await
+        };
+        match self.deadline {
+            Some(deadline) => match time::timeout_at(deadline, write).await {
+                Ok(res) => res?,
+                Err(_) => return Err(self.poison()),
+            },
+            None => write.await?,
+        }
+
+        Ok(())
     }
 
     /// # read_response() 函数
     ///
-    /// 从socket中读取响应帧
+    /// 从socket中读取响应帧，错误帧转换为Err，超过当前请求的截止时间时返回TimedOut错误
     async fn read_response(&mut self) -> crate::Result<Frame> {
+        match self.read_reply().await? {
+            // 将错误帧转换为结构化的错误类型，方便调用者区分
+            Frame::Error(msg) => Err(RustisError::from_error_message(msg).into()),
+            frame => Ok(frame),
+        }
+    }
+
+    /// # read_as() 函数
+    ///
+    /// 读取一个响应帧并转换为T，错误帧转换为结构化的错误类型
+    async fn read_as<T: FromFrame>(&mut self) -> crate::Result<T> {
+        T::from_frame(self.read_reply().await?)
+    }
+
+    /// # read_reply() 函数
+    ///
+    /// 从socket中读取一个响应帧，错误帧原样返回，超过当前请求的截止时间时返回TimedOut错误
+    async fn read_reply(&mut self) -> crate::Result<Frame> {
+        self.check_poisoned()?;
+
         // 读取响应帧
-        let response = self.connection.read_frame().await?;
+        let response = match self.deadline {
+            Some(deadline) => {
+                match time::timeout_at(deadline, self.connection.read_frame()).await {
+                    Ok(res) => res?,
+                    Err(_) => return Err(self.poison()),
+                }
+            }
+            None => self.connection.read_frame().await?,
+        };
         debug!(?response);
 
         match response {
-            Some(Frame::Error(msg)) => Err(msg.into()),
-            Some(frame) => Ok(frame),
+            Some(frame) => {
+                // 订阅模式下一个请求会有多个确认，所以不会减到0以下
+                self.pending_replies = self.pending_replies.saturating_sub(1);
+                Ok(frame)
+            }
             None => {
                 // 响应为None表示服务器已经关闭这个客户端的连接
                 let error = Error::new(ErrorKind::ConnectionReset, "连接被服务器重置");
-                Err(error.into())
+                Err(RustisError::Io(error).into())
             }
         }
     }
@@ -71,14 +437,51 @@ impl Client {
         let frame = Ping::new(msg).code_ping_into_frame();
         debug!(request = ?frame);
         // 将帧写入connection中
-        self.connection.write_frame(&frame).await?;
+        self.send_request(&frame).await?;
 
         // 读取服务器的响应
-        match self.read_response().await? {
-            Frame::Simple(value) => Ok(value.into()),
-            Frame::Bulk(value) => Ok(value),
-            frame => Err(frame.to_error()),
+        self.read_as().await
+    }
+
+    /// # command() 函数
+    ///
+    /// 发送任意命令，args的第一个元素是命令名称，返回服务器回复的原始帧，用于执行Client没有封装的命令。
+    /// 服务器回复错误时返回Err，与其他方法一致
+    #[instrument(skip(self, args))]
+    pub async fn command(&mut self, args: &[&[u8]]) -> crate::Result<Frame> {
+        self.send_command(args.iter().map(|arg| Bytes::copy_from_slice(arg)))
+            .await
+    }
+
+    /// # send_command() 函数
+    ///
+    /// 与command()相同，参数是Bytes的迭代器，不需要复制参数。返回的帧可以用Frame::decode()转换为需要的类型：
+    ///
+    /// ```no_run
+    /// # async fn example() -> rustis::Result<()> {
+    /// use bytes::Bytes;
+    /// use rustis::client::Client;
+    ///
+    /// let mut client = Client::connect("127.0.0.1:6379").await?;
+    /// let args = ["expiretime", "key"].map(Bytes::from);
+    /// let deadline = client.send_command(args).await?.decode::<i64>()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[instrument(skip(self, args))]
+    pub async fn send_command<I: IntoIterator<Item = Bytes>>(
+        &mut self,
+        args: I,
+    ) -> crate::Result<Frame> {
+        let mut frame = Frame::array();
+        for arg in args {
+            frame.push_bulk(arg);
         }
+        debug!(request = ?frame);
+
+        self.send_request(&frame).await?;
+
+        self.read_response().await
     }
 
     /// # get() 函数
@@ -90,15 +493,73 @@ impl Client {
         let frame = Get::new(key).code_get_into_frame();
         debug!(request = ?frame);
         // 将帧写入到连接中
-        self.connection.write_frame(&frame).await?;
+        self.send_request(&frame).await?;
 
         // 读取服务器的响应
-        match self.read_response().await? {
-            Frame::Simple(value) => Ok(Some(value.into())),
-            Frame::Bulk(value) => Ok(Some(value)),
-            Frame::Null => Ok(None),
-            frame => Err(frame.to_error()),
+        self.read_as().await
+    }
+
+    /// # getrange() 函数
+    ///
+    /// 获取key的值中从start到end（包含两端）的子串，负数下标从末尾开始计数，键不存在时返回空的Bytes
+    #[instrument(skip(self))]
+    pub async fn getrange(&mut self, key: &str, start: i64, end: i64) -> crate::Result<Bytes> {
+        let frame = GetRange::new(key, start, end).code_getrange_into_frame();
+        debug!(request = ?frame);
+        self.send_request(&frame).await?;
+
+        self.read_as().await
+    }
+
+    /// # get_many() 函数
+    ///
+    /// 获取多个key的值，按照keys的顺序返回。优先使用MGET；服务器不认识MGET时改为在一次flush中发送多个GET，
+    /// 并记住服务器不支持MGET，之后直接使用GET
+    #[instrument(skip(self))]
+    pub async fn get_many(&mut self, keys: &[&str]) -> crate::Result<Vec<Option<Bytes>>> {
+        if keys.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        if !self.mget_unsupported {
+            let frame =
+                MGet::new(keys.iter().map(|key| key.to_string()).collect()).code_mget_into_frame();
+            debug!(request = ?frame);
+
+            self.send_request(&frame).await?;
+
+            match self.read_response().await {
+                Ok(Frame::Array(values)) if values.len() == keys.len() => {
+                    return values.into_iter().map(value_from_frame).collect();
+                }
+                Ok(frame) => return Err(frame.to_error()),
+                Err(err) if is_unknown_command(&err) => {
+                    debug!("server does not support MGET, falling back to pipelined GET");
+                    self.mget_unsupported = true;
+                }
+                Err(err) => return Err(err),
+            }
         }
+
+        let frames: Vec<Frame> = keys
+            .iter()
+            .map(|key| Get::new(key).code_get_into_frame())
+            .collect();
+        self.send_pipeline(&frames).await?;
+
+        // 先读完所有的响应再处理错误回复，连接上不会留下没有读取的响应
+        let mut replies = Vec::with_capacity(frames.len());
+        for _ in 0..frames.len() {
+            replies.push(self.read_reply().await?);
+        }
+
+        replies
+            .into_iter()
+            .map(|frame| match frame {
+                Frame::Error(msg) => Err(RustisError::from_error_message(msg).into()),
+                frame => value_from_frame(frame),
+            })
+            .collect()
     }
 
     /// # set() 函数
@@ -122,6 +583,74 @@ impl Client {
         self.set_cmd(Set::new(key, value, Some(expires))).await
     }
 
+    /// # set_idempotent() 函数
+    ///
+    /// 向服务器编码并发送带有幂等令牌的set命令，服务器最近见过这个令牌时不会重复写入，
+    /// 可以在重试时安全地再次发送
+    #[instrument(skip(self))]
+    pub async fn set_idempotent(
+        &mut self,
+        key: &str,
+        value: Bytes,
+        token: &str,
+    ) -> crate::Result<()> {
+        self.set_cmd(Set::new(key, value, None).idempotent(token))
+            .await
+    }
+
+    /// # set_with_condition() 函数
+    ///
+    /// 只在满足condition时设置key的值，可以同时设置过期时间，返回是否写入了。
+    /// 条件的检查和写入是原子的，例如SET key value NX EX可以用来获取一个带租期的锁
+    #[instrument(skip(self))]
+    pub async fn set_with_condition(
+        &mut self,
+        key: &str,
+        value: Bytes,
+        expires: Option<Duration>,
+        condition: SetCondition,
+    ) -> crate::Result<bool> {
+        let frame = Set::new(key, value, expires)
+            .condition(condition)
+            .code_set_into_frame();
+        debug!(request = ?frame);
+
+        self.send_request(&frame).await?;
+
+        match self.read_response().await? {
+            Frame::Simple(response) if response.to_uppercase() == "OK" => Ok(true),
+            Frame::Null => Ok(false),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// # set_opts() 函数
+    ///
+    /// 带有NX/XX、KEEPTTL和GET等选项的SET。使用GET时返回键原来的值，否则返回是否写入
+    #[instrument(skip(self, value))]
+    pub async fn set_opts(
+        &mut self,
+        key: &str,
+        value: Bytes,
+        options: SetOptions,
+    ) -> crate::Result<SetResult> {
+        let frame = Set::with_options(key, value, options).code_set_into_frame();
+        debug!(request = ?frame);
+
+        self.send_request(&frame).await?;
+
+        if options.get {
+            return self
+                .read_as::<Option<Bytes>>()
+                .await
+                .map(SetResult::Previous);
+        }
+        match self.read_as::<Option<()>>().await? {
+            Some(()) => Ok(SetResult::Set),
+            None => Ok(SetResult::NotSet),
+        }
+    }
+
     /// # set_cmd() 函数
     ///
     /// set命令的核心实现
@@ -132,15 +661,64 @@ impl Client {
         debug!(request = ?frame);
 
         // 将帧写入到流中
-        self.connection.write_frame(&frame).await?;
+        self.send_request(&frame).await?;
 
         // 读取服务器的响应
-        match self.read_response().await? {
-            Frame::Simple(response) if response.to_uppercase() == "OK" => Ok(()),
-            frame => Err(frame.to_error()),
+        self.read_as().await
+    }
+
+    /// # get_json() 函数
+    ///
+    /// 获取key的值并按照JSON反序列化为T，键不存在时返回None。
+    /// 值不是合法的T时返回`RustisError::Deserialize`，其中带有服务器返回的原始值
+    #[cfg(feature = "serde")]
+    #[instrument(skip(self))]
+    pub async fn get_json<T: serde::de::DeserializeOwned>(
+        &mut self,
+        key: &str,
+    ) -> crate::Result<Option<T>> {
+        let Some(value) = self.get(key).await? else {
+            return Ok(None);
+        };
+        match serde_json::from_slice(&value) {
+            Ok(value) => Ok(Some(value)),
+            Err(err) => Err(RustisError::Deserialize {
+                value,
+                reason: err.to_string(),
+            }
+            .into()),
         }
     }
 
+    /// # set_json() 函数
+    ///
+    /// 将value序列化为JSON后设置为key的值
+    #[cfg(feature = "serde")]
+    #[instrument(skip(self, value))]
+    pub async fn set_json<T: serde::Serialize + ?Sized>(
+        &mut self,
+        key: &str,
+        value: &T,
+    ) -> crate::Result<()> {
+        let value = Bytes::from(serde_json::to_vec(value)?);
+        self.set_cmd(Set::new(key, value, None)).await
+    }
+
+    /// # set_json_with_expires() 函数
+    ///
+    /// 将value序列化为JSON后设置为key的值，并设置过期时间
+    #[cfg(feature = "serde")]
+    #[instrument(skip(self, value))]
+    pub async fn set_json_with_expires<T: serde::Serialize + ?Sized>(
+        &mut self,
+        key: &str,
+        value: &T,
+        expires: Duration,
+    ) -> crate::Result<()> {
+        let value = Bytes::from(serde_json::to_vec(value)?);
+        self.set_cmd(Set::new(key, value, Some(expires))).await
+    }
+
     /// # publish() 函数
     ///
     /// 向服务器编码并发送publish命令，将消息发布到给定的channel
@@ -151,11 +729,26 @@ impl Client {
         debug!(request = ?frame);
 
         // 将帧写入到流中
-        self.connection.write_frame(&frame).await?;
+        self.send_request(&frame).await?;
+
+        // 读取服务器的响应，订阅者数量不会是负数，收到负数说明服务器的响应有问题
+        self.read_as().await
+    }
+
+    /// # monitor() 函数
+    ///
+    /// 将连接切换为监控模式，返回一个Monitor实例，之后只能接收服务器推送的命令
+    #[instrument(skip(self))]
+    pub async fn monitor(mut self) -> crate::Result<Monitor> {
+        let frame = MonitorCmd::new().code_monitor_into_frame();
+        debug!(request = ?frame);
+
+        self.send_request(&frame).await?;
 
-        // 读取服务器的响应
         match self.read_response().await? {
-            Frame::Integer(response) => Ok(response),
+            Frame::Simple(response) if response.to_uppercase() == "OK" => {
+                Ok(Monitor { client: self })
+            }
             frame => Err(frame.to_error()),
         }
     }
@@ -164,81 +757,744 @@ impl Client {
     ///
     /// 将客户端订阅到指定channel，返回一个Subscriber实例
     #[instrument(skip(self))]
-    pub async fn subscribe(mut self, channels: Vec<String>) -> crate::Result<Subscriber> {
-        self.subscribe_cmd(&channels).await?;
+    pub async fn subscribe(self, channels: Vec<String>) -> crate::Result<Subscriber> {
+        let mut subscriber = Subscriber::new(self, Vec::new());
+        subscriber.subscribe(&channels).await?;
 
-        Ok(Subscriber::new(self, channels))
+        Ok(subscriber)
     }
 
-    /// # subscribe_cmd() 函数
+    /// # save() 函数
     ///
-    /// subscribe命令的核心实现
-    async fn subscribe_cmd(&mut self, channels: &[String]) -> crate::Result<()> {
-        // 将subscribe命令编码为帧
-        let frame = Subscribe::new(channels.to_vec()).code_subscribe_into_frame();
+    /// 向服务器编码并发送save命令，进行一次RDB快照
+    #[instrument(skip(self))]
+    pub async fn save(&mut self) -> crate::Result<()> {
+        // 将save命令编码为数据库帧，方便传输到服务器
+        let frame = Save::new().code_save_into_frame();
         debug!(request = ?frame);
 
         // 将帧写入到连接中
-        self.connection.write_frame(&frame).await?;
+        self.send_request(&frame).await?;
 
         // 读取服务器的响应
-        for channel in channels {
-            let response = self.read_response().await?;
-
-            match response {
-                Frame::Array(ref frame) => match frame.as_slice() {
-                    [subscribe, cha, ..] if *subscribe == "subscribe" && *cha == channel => {}
-                    _ => return Err(response.to_error()),
-                },
-                frame => return Err(frame.to_error()),
-            };
+        match self.read_response().await? {
+            Frame::Simple(response) if response.to_uppercase() == "OK" => Ok(()),
+            frame => Err(frame.to_error()),
         }
+    }
 
-        Ok(())
+    /// # bgsave() 函数
+    ///
+    /// 让服务器在后台进行一次RDB快照，命令在快照完成之前返回
+    #[instrument(skip(self))]
+    pub async fn bgsave(&mut self) -> crate::Result<()> {
+        let frame = BgSave::new().code_bgsave_into_frame();
+        debug!(request = ?frame);
+
+        self.send_request(&frame).await?;
+
+        match self.read_response().await? {
+            Frame::Simple(_) => Ok(()),
+            frame => Err(frame.to_error()),
+        }
     }
 
-    /// # save() 函数
+    /// # info() 函数
     ///
-    /// 向服务器编码并发送save命令，进行一次RDB快照
+    /// 向服务器编码并发送info命令，获取服务器的信息和统计数据
     #[instrument(skip(self))]
-    pub async fn save(&mut self) -> crate::Result<()> {
-        // 将save命令编码为数据库帧，方便传输到服务器
-        let frame = Save::new().code_save_into_frame();
+    pub async fn info(&mut self, section: Option<&str>) -> crate::Result<String> {
+        let frame = Info::new(section.map(|section| section.to_string())).code_info_into_frame();
         debug!(request = ?frame);
 
-        // 将帧写入到连接中
-        self.connection.write_frame(&frame).await?;
+        self.send_request(&frame).await?;
 
-        // 读取服务器的响应
         match self.read_response().await? {
-            Frame::Simple(response) if response.to_uppercase() == "OK" => Ok(()),
+            Frame::Bulk(value) => Ok(String::from_utf8_lossy(&value).into_owned()),
             frame => Err(frame.to_error()),
         }
     }
 
-    pub async fn del(&mut self, key: &str) ->crate::Result<()> {
-        let frame = Del::new(key).code_del_into_frame();
+    /// # config_get() 函数
+    ///
+    /// 向服务器编码并发送config get命令，返回匹配glob模式的参数名称和值
+    #[instrument(skip(self))]
+    pub async fn config_get(&mut self, pattern: &str) -> crate::Result<Vec<(String, String)>> {
+        let frame = Config::Get(vec![pattern.to_string()]).code_config_into_frame();
+        debug!(request = ?frame);
+
+        self.send_request(&frame).await?;
+
+        match self.read_response().await? {
+            // RESP3
+            Frame::Map(pairs) => Ok(pairs
+                .into_iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect()),
+            // RESP2
+            Frame::Array(parts) => parts
+                .chunks(2)
+                .map(|pair| match pair {
+                    [name, value] => Ok((name.to_string(), value.to_string())),
+                    _ => Err(Frame::Array(pair.to_vec()).to_error()),
+                })
+                .collect(),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// # config_set() 函数
+    ///
+    /// 向服务器编码并发送config set命令，在运行时修改一个参数
+    #[instrument(skip(self))]
+    pub async fn config_set(&mut self, name: &str, value: &str) -> crate::Result<()> {
+        let frame = Config::Set(name.to_string(), value.to_string()).code_config_into_frame();
         debug!(request = ?frame);
 
-        self.connection.write_frame(&frame).await?;
+        self.send_request(&frame).await?;
 
         match self.read_response().await? {
             Frame::Simple(response) if response.to_uppercase() == "OK" => Ok(()),
             frame => Err(frame.to_error()),
         }
     }
-}
 
+    /// # config_rewrite() 函数
+    ///
+    /// 向服务器编码并发送config rewrite命令，把当前配置写回服务器启动时读取的配置文件
+    #[instrument(skip(self))]
+    pub async fn config_rewrite(&mut self) -> crate::Result<()> {
+        let frame = Config::Rewrite.code_config_into_frame();
+        debug!(request = ?frame);
+
+        self.send_request(&frame).await?;
+
+        match self.read_response().await? {
+            Frame::Simple(response) if response.to_uppercase() == "OK" => Ok(()),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// # readonly() 函数
+    ///
+    /// 向服务器编码并发送readonly命令，将当前连接切换为只读模式
+    #[instrument(skip(self))]
+    pub async fn readonly(&mut self) -> crate::Result<()> {
+        let frame = ReadOnly::new().code_readonly_into_frame();
+        debug!(request = ?frame);
+
+        self.send_request(&frame).await?;
+
+        match self.read_response().await? {
+            Frame::Simple(response) if response.to_uppercase() == "OK" => Ok(()),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// # readwrite() 函数
+    ///
+    /// 向服务器编码并发送readwrite命令，关闭当前连接的只读模式
+    #[instrument(skip(self))]
+    pub async fn readwrite(&mut self) -> crate::Result<()> {
+        let frame = ReadWrite::new().code_readwrite_into_frame();
+        debug!(request = ?frame);
+
+        self.send_request(&frame).await?;
+
+        match self.read_response().await? {
+            Frame::Simple(response) if response.to_uppercase() == "OK" => Ok(()),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// # auth() 函数
+    ///
+    /// 向服务器编码并发送auth命令，使用密码对当前连接进行认证
+    #[instrument(skip(self, password))]
+    pub async fn auth(&mut self, password: &str) -> crate::Result<()> {
+        self.auth_cmd(Auth::new(None, password), password).await
+    }
+
+    /// # auth_cmd() 函数
+    ///
+    /// 发送auth命令，认证成功后记住密码，重连时使用
+    async fn auth_cmd(&mut self, cmd: Auth, password: &str) -> crate::Result<()> {
+        let frame = cmd.code_auth_into_frame();
+
+        self.send_request(&frame).await?;
+
+        match self.read_response().await? {
+            Frame::Simple(response) if response.to_uppercase() == "OK" => {
+                self.password = Some(password.to_string());
+                Ok(())
+            }
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// # select() 函数
+    ///
+    /// 向服务器编码并发送select命令，切换当前连接使用的逻辑数据库
+    #[instrument(skip(self))]
+    pub async fn select(&mut self, index: u64) -> crate::Result<()> {
+        let frame = Select::new(index).code_select_into_frame();
+        debug!(request = ?frame);
+
+        self.send_request(&frame).await?;
+
+        match self.read_response().await? {
+            Frame::Simple(response) if response.to_uppercase() == "OK" => Ok(()),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// # flushdb() 函数
+    ///
+    /// 向服务器编码并发送flushdb命令，清空当前逻辑数据库，lazy为true时服务器在后台释放数据
+    #[instrument(skip(self))]
+    pub async fn flushdb(&mut self, lazy: bool) -> crate::Result<()> {
+        let frame = FlushDb::new(lazy).code_flushdb_into_frame();
+        debug!(request = ?frame);
+
+        self.send_request(&frame).await?;
+
+        match self.read_response().await? {
+            Frame::Simple(response) if response.to_uppercase() == "OK" => Ok(()),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// # flushall() 函数
+    ///
+    /// 向服务器编码并发送flushall命令，清空所有逻辑数据库，lazy为true时服务器在后台释放数据
+    #[instrument(skip(self))]
+    pub async fn flushall(&mut self, lazy: bool) -> crate::Result<()> {
+        let frame = FlushAll::new(lazy).code_flushall_into_frame();
+        debug!(request = ?frame);
+
+        self.send_request(&frame).await?;
+
+        match self.read_response().await? {
+            Frame::Simple(response) if response.to_uppercase() == "OK" => Ok(()),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// # expire() 函数
+    ///
+    /// 为键设置以秒为单位的过期时间，返回是否设置成功
+    #[instrument(skip(self))]
+    pub async fn expire(&mut self, key: &str, seconds: u64) -> crate::Result<bool> {
+        self.expire_cmd(Expire::new(ExpireUnit::Seconds, key, seconds, vec![]))
+            .await
+    }
+
+    /// # expire_with() 函数
+    ///
+    /// 为键设置以秒为单位的过期时间，conditions中的条件都满足时才会设置，返回是否设置成功
+    #[instrument(skip(self))]
+    pub async fn expire_with(
+        &mut self,
+        key: &str,
+        seconds: u64,
+        conditions: &[ExpireCondition],
+    ) -> crate::Result<bool> {
+        self.expire_cmd(Expire::new(
+            ExpireUnit::Seconds,
+            key,
+            seconds,
+            conditions.to_vec(),
+        ))
+        .await
+    }
+
+    /// # pexpire() 函数
+    ///
+    /// 为键设置以毫秒为单位的过期时间，返回是否设置成功
+    #[instrument(skip(self))]
+    pub async fn pexpire(&mut self, key: &str, milliseconds: u64) -> crate::Result<bool> {
+        self.expire_cmd(Expire::new(
+            ExpireUnit::Milliseconds,
+            key,
+            milliseconds,
+            vec![],
+        ))
+        .await
+    }
+
+    /// # expireat() 函数
+    ///
+    /// 将键的过期时间设置为unix时间戳（秒），返回是否设置成功
+    #[instrument(skip(self))]
+    pub async fn expireat(&mut self, key: &str, timestamp: u64) -> crate::Result<bool> {
+        self.expire_cmd(Expire::new(ExpireUnit::UnixSeconds, key, timestamp, vec![]))
+            .await
+    }
+
+    /// # expiretime() 函数
+    ///
+    /// 获取键过期的unix时间戳（秒），键没有过期时间时返回-1，键不存在时返回-2
+    #[instrument(skip(self))]
+    pub async fn expiretime(&mut self, key: &str) -> crate::Result<i64> {
+        self.expiretime_cmd(ExpireTime::new(key, false)).await
+    }
+
+    /// # pexpiretime() 函数
+    ///
+    /// 获取键过期的unix时间戳（毫秒），键没有过期时间时返回-1，键不存在时返回-2
+    #[instrument(skip(self))]
+    pub async fn pexpiretime(&mut self, key: &str) -> crate::Result<i64> {
+        self.expiretime_cmd(ExpireTime::new(key, true)).await
+    }
+
+    /// # object_idletime() 函数
+    ///
+    /// 获取键距离上次被访问的秒数，键不存在时返回None
+    #[instrument(skip(self))]
+    pub async fn object_idletime(&mut self, key: &str) -> crate::Result<Option<u64>> {
+        let frame = Object::IdleTime(key.to_string()).code_object_into_frame();
+        debug!(request = ?frame);
+
+        self.send_request(&frame).await?;
+
+        match self.read_response().await? {
+            frame @ Frame::Integer(idle) => {
+                u64::try_from(idle).map(Some).map_err(|_| frame.to_error())
+            }
+            Frame::Null => Ok(None),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// # object_version() 函数
+    ///
+    /// 获取键最后一次被修改时的版本号，键不存在时返回None。修改前后读到的版本号不同，说明键在这期间被修改过
+    #[instrument(skip(self))]
+    pub async fn object_version(&mut self, key: &str) -> crate::Result<Option<u64>> {
+        let frame = Object::Version(key.to_string()).code_object_into_frame();
+        debug!(request = ?frame);
+
+        self.send_request(&frame).await?;
+
+        match self.read_response().await? {
+            frame @ Frame::Integer(version) => u64::try_from(version)
+                .map(Some)
+                .map_err(|_| frame.to_error()),
+            Frame::Null => Ok(None),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// # object_freq() 函数
+    ///
+    /// 获取键的LFU访问频率计数器，键不存在时返回None。计数器对数增长，空闲时会衰减
+    #[instrument(skip(self))]
+    pub async fn object_freq(&mut self, key: &str) -> crate::Result<Option<u64>> {
+        let frame = Object::Freq(key.to_string()).code_object_into_frame();
+        debug!(request = ?frame);
+
+        self.send_request(&frame).await?;
+
+        self.read_as().await
+    }
+
+    /// # scan() 函数
+    ///
+    /// 执行一次SCAN，返回下一次调用使用的游标和这一次遍历到的键，游标为0时遍历结束。
+    /// pattern在服务器遍历之后过滤键，所以返回的键可能少于count个
+    #[instrument(skip(self))]
+    pub async fn scan(
+        &mut self,
+        cursor: u64,
+        pattern: Option<&str>,
+        count: Option<u64>,
+    ) -> crate::Result<(u64, Vec<String>)> {
+        let frame = Scan::new(cursor, pattern.map(str::to_string), count).code_scan_into_frame();
+        debug!(request = ?frame);
+
+        self.send_request(&frame).await?;
+
+        let (next, keys): (String, Vec<String>) = self.read_as().await?;
+        let next = next
+            .parse()
+            .map_err(|_| RustisError::Protocol(format!("invalid SCAN cursor: {}", next)))?;
+        Ok((next, keys))
+    }
+
+    /// # scan_iter() 函数
+    ///
+    /// 返回匹配pattern的所有键组成的Stream，内部用返回的游标连续执行SCAN，每次只缓存一页键，游标回到0时结束。
+    /// 遍历期间一直存在的键正好返回一次，遍历期间增加或删除的键可能返回也可能不返回
+    pub fn scan_iter(
+        &mut self,
+        pattern: &str,
+    ) -> impl Stream<Item = crate::Result<String>> + Unpin + '_ {
+        self.scan_match_count(Some(pattern), None)
+    }
+
+    /// # scan_match_count() 函数
+    ///
+    /// 与scan_iter()相同，可以不指定pattern，count是每次SCAN遍历的键数量，None时使用服务器的默认值
+    pub fn scan_match_count(
+        &mut self,
+        pattern: Option<&str>,
+        count: Option<u64>,
+    ) -> impl Stream<Item = crate::Result<String>> + Unpin + '_ {
+        let pattern = pattern.map(str::to_string);
+        Box::pin(try_stream! {
+            let mut cursor = 0;
+            loop {
+                let (next, keys) = self.scan(cursor, pattern.as_deref(), count).await?;
+                for key in keys {
+                    yield key;
+                }
+                if next == 0 {
+                    break;
+                }
+                cursor = next;
+            }
+        })
+    }
+
+    /// # command_getkeys() 函数
+    ///
+    /// 返回完整命令args（第一个元素是命令名称）中的键
+    #[instrument(skip(self, args))]
+    pub async fn command_getkeys(&mut self, args: &[&[u8]]) -> crate::Result<Vec<Bytes>> {
+        let args = args.iter().map(|arg| Bytes::copy_from_slice(arg)).collect();
+        let frame = CommandCmd::GetKeys(args).code_command_into_frame();
+        debug!(request = ?frame);
+
+        self.send_request(&frame).await?;
+
+        match self.read_response().await? {
+            Frame::Array(keys) => keys
+                .into_iter()
+                .map(|key| key.to_bytes().ok_or_else(|| key.to_error()))
+                .collect(),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// # memory_usage() 函数
+    ///
+    /// 返回键和值占用的字节数的估计值，键不存在时返回None
+    #[instrument(skip(self))]
+    pub async fn memory_usage(&mut self, key: &str) -> crate::Result<Option<u64>> {
+        let frame = Memory::Usage(key.to_string()).code_memory_into_frame();
+        debug!(request = ?frame);
+
+        self.send_request(&frame).await?;
+
+        match self.read_response().await? {
+            frame @ Frame::Integer(bytes) => {
+                u64::try_from(bytes).map(Some).map_err(|_| frame.to_error())
+            }
+            Frame::Null => Ok(None),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// # expiretime_cmd() 函数
+    ///
+    /// 发送expiretime或pexpiretime命令，返回服务器回复的整数
+    async fn expiretime_cmd(&mut self, cmd: ExpireTime) -> crate::Result<i64> {
+        let frame = cmd.code_expiretime_into_frame();
+        debug!(request = ?frame);
+
+        self.send_request(&frame).await?;
+
+        match self.read_response().await? {
+            Frame::Integer(time) => Ok(time),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// # expire_cmd() 函数
+    ///
+    /// 发送expire系列命令，返回是否设置成功
+    async fn expire_cmd(&mut self, cmd: Expire) -> crate::Result<bool> {
+        let frame = cmd.code_expire_into_frame();
+        debug!(request = ?frame);
+
+        self.send_request(&frame).await?;
+
+        match self.read_response().await? {
+            Frame::Integer(updated) => Ok(updated == 1),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// # replicaof() 函数
+    ///
+    /// 让服务器成为host:port的副本，服务器当前的数据会被主节点的数据替换
+    #[instrument(skip(self))]
+    pub async fn replicaof(&mut self, host: &str, port: u16) -> crate::Result<()> {
+        let frame = ReplicaOf::Master {
+            host: host.to_string(),
+            port: port as u64,
+        }
+        .code_replicaof_into_frame();
+        debug!(request = ?frame);
+
+        self.send_request(&frame).await?;
+
+        match self.read_response().await? {
+            Frame::Simple(response) if response.to_uppercase() == "OK" => Ok(()),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// # replicaof_no_one() 函数
+    ///
+    /// 让服务器停止复制，已经复制的数据会被保留
+    #[instrument(skip(self))]
+    pub async fn replicaof_no_one(&mut self) -> crate::Result<()> {
+        let frame = ReplicaOf::NoOne.code_replicaof_into_frame();
+        debug!(request = ?frame);
+
+        self.send_request(&frame).await?;
+
+        match self.read_response().await? {
+            Frame::Simple(response) if response.to_uppercase() == "OK" => Ok(()),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// # stats() 函数
+    ///
+    /// 获取服务器的统计数据，返回统计项名称和值
+    pub async fn stats(&mut self) -> crate::Result<Vec<(String, u64)>> {
+        let frame = Stats::new().code_stats_into_frame();
+        debug!(request = ?frame);
+
+        self.send_request(&frame).await?;
+
+        match self.read_response().await? {
+            Frame::Array(frames) => {
+                let mut stats = Vec::with_capacity(frames.len() / 2);
+                let mut frames = frames.into_iter();
+                while let (Some(name), Some(value)) = (frames.next(), frames.next()) {
+                    match (name, value) {
+                        (Frame::Bulk(name), Frame::Integer(value)) => {
+                            stats.push((String::from_utf8_lossy(&name).into_owned(), value as u64))
+                        }
+                        frame => {
+                            return Err(format!(
+                                "protocol error; unexpected stats entry {:?}",
+                                frame
+                            )
+                            .into())
+                        }
+                    }
+                }
+                Ok(stats)
+            }
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// # latency_stats() 函数
+    ///
+    /// 获取服务器上每个被调用过的命令的调用次数、耗时和延迟直方图
+    pub async fn latency_stats(&mut self) -> crate::Result<Vec<CommandLatency>> {
+        let frame = LatencyStats::new().code_latencystats_into_frame();
+        debug!(request = ?frame);
+
+        self.send_request(&frame).await?;
+
+        let frames = match self.read_response().await? {
+            Frame::Array(frames) => frames,
+            frame => return Err(frame.to_error()),
+        };
+
+        let mut stats = Vec::with_capacity(frames.len() / 2);
+        let mut frames = frames.into_iter();
+        while let (Some(name), Some(entry)) = (frames.next(), frames.next()) {
+            match (name, entry) {
+                (Frame::Bulk(name), Frame::Array(entry)) => {
+                    stats.push(CommandLatency::from_frames(&name, entry)?)
+                }
+                frame => {
+                    return Err(format!(
+                        "protocol error; unexpected latencystats entry {:?}",
+                        frame
+                    )
+                    .into())
+                }
+            }
+        }
+        Ok(stats)
+    }
+
+    /// # client_id() 函数
+    ///
+    /// 获取服务器为当前连接分配的唯一标识
+    pub async fn client_id(&mut self) -> crate::Result<u64> {
+        let frame = ClientCmd::Id.code_client_into_frame();
+        debug!(request = ?frame);
+
+        self.send_request(&frame).await?;
+
+        match self.read_response().await? {
+            frame @ Frame::Integer(id) => u64::try_from(id).map_err(|_| frame.to_error()),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// # client_setname() 函数
+    ///
+    /// 设置当前连接的名称，name为空字符串时清除名称
+    #[instrument(skip(self))]
+    pub async fn client_setname(&mut self, name: &str) -> crate::Result<()> {
+        let frame = ClientCmd::SetName(name.to_string()).code_client_into_frame();
+        debug!(request = ?frame);
+
+        self.send_request(&frame).await?;
+
+        match self.read_response().await? {
+            Frame::Simple(response) if response.to_uppercase() == "OK" => Ok(()),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// # client_getname() 函数
+    ///
+    /// 获取当前连接的名称，没有设置时返回None
+    pub async fn client_getname(&mut self) -> crate::Result<Option<String>> {
+        let frame = ClientCmd::GetName.code_client_into_frame();
+        debug!(request = ?frame);
+
+        self.send_request(&frame).await?;
+
+        match self.read_response().await? {
+            Frame::Bulk(name) => Ok(Some(String::from_utf8_lossy(&name).into_owned())),
+            Frame::Null => Ok(None),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// # debug_dumpall() 函数
+    ///
+    /// 获取当前数据库中所有未过期的键值对，服务器需要开启enable-debug-command
+    pub async fn debug_dumpall(&mut self) -> crate::Result<Vec<(String, Bytes)>> {
+        let frame = Debug::DumpAll.code_debug_into_frame();
+        debug!(request = ?frame);
+
+        self.send_request(&frame).await?;
+
+        match self.read_response().await? {
+            Frame::Array(frames) => {
+                let mut entries = Vec::with_capacity(frames.len() / 2);
+                let mut frames = frames.into_iter();
+                while let (Some(key), Some(value)) = (frames.next(), frames.next()) {
+                    match (key, value) {
+                        (Frame::Bulk(key), Frame::Bulk(value)) => {
+                            entries.push((String::from_utf8_lossy(&key).into_owned(), value))
+                        }
+                        frame => {
+                            return Err(format!(
+                                "protocol error; unexpected dumpall entry {:?}",
+                                frame
+                            )
+                            .into())
+                        }
+                    }
+                }
+                Ok(entries)
+            }
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// # debug_reload() 函数
+    ///
+    /// 让服务器把数据按照RDB格式序列化后立即重新加载，需要服务器开启enable-debug-command
+    #[instrument(skip(self))]
+    pub async fn debug_reload(&mut self) -> crate::Result<()> {
+        let frame = Debug::Reload.code_debug_into_frame();
+        debug!(request = ?frame);
+
+        self.send_request(&frame).await?;
+
+        match self.read_response().await? {
+            Frame::Simple(response) if response == "OK" => Ok(()),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// # debug_object() 函数
+    ///
+    /// 返回DEBUG OBJECT的一行内部信息，其中ttl是剩余的毫秒数，没有过期时间时为-1。键不存在时返回NoSuchKey错误
+    #[instrument(skip(self))]
+    pub async fn debug_object(&mut self, key: &str) -> crate::Result<String> {
+        let frame = Debug::Object(key.to_string()).code_debug_into_frame();
+        debug!(request = ?frame);
+
+        self.send_request(&frame).await?;
+
+        self.read_as().await
+    }
+
+    pub async fn del(&mut self, key: &str) -> crate::Result<()> {
+        let frame = Del::new(key).code_del_into_frame();
+        debug!(request = ?frame);
+
+        self.send_request(&frame).await?;
+
+        match self.read_response().await? {
+            Frame::Simple(response) if response.to_uppercase() == "OK" => Ok(()),
+            frame => Err(frame.to_error()),
+        }
+    }
+}
+
+/// Client被drop时尽力向服务器发送QUIT，让服务器把这次断开记录为正常断开，而不是连接被重置
+///
+/// Drop中不能执行异步操作，所以只会尝试一次非阻塞写入：socket暂时不可写或者还有没有写出的数据时直接放弃，
+/// 也不会等待服务器回复OK，因此不能保证服务器一定收到QUIT。处于订阅模式的Subscriber和监控模式的Monitor被drop时，
+/// 服务器会拒绝QUIT，连接仍然算作异常断开
+impl Drop for Client {
+    fn drop(&mut self) {
+        if let Err(err) = self.connection.try_write_now(QUIT_REQUEST) {
+            debug!(cause = ?err, "failed to send QUIT on drop");
+        }
+    }
+}
 
 /// # Subscriber 结构体
 ///
 /// 一旦客户端订阅了一个channel，它们只能执行pub/sub相关的命令。
 /// Client类型被转换为Subscriber类型，防止调用非pub/sub相关的命令。
+///
+/// 订阅之后可以随时增加或取消订阅，等待确认期间收到的消息不会丢失，之后由next_message()按顺序返回。
+/// exit_subscribe()退出订阅模式并取回Client：
+///
+/// ```no_run
+/// # async fn example() -> rustis::Result<()> {
+/// use rustis::client::Client;
+///
+/// let client = Client::connect("127.0.0.1:6379").await?;
+/// let mut subscriber = client.subscribe(vec!["news".to_string()]).await?;
+///
+/// subscriber.subscribe(&["sports".to_string()]).await?;
+/// if let Some(message) = subscriber.next_message().await? {
+///     println!("{}: {:?}", message.channel, message.content);
+/// }
+/// subscriber.unsubscribe(&["news".to_string()]).await?;
+///
+/// let mut client = subscriber.exit_subscribe().await?;
+/// client.ping(None).await?;
+/// # Ok(())
+/// # }
+/// ```
 pub struct Subscriber {
     /// Client实例
     client: Client,
     /// 当前订阅的channels
     subscriber_channels: Vec<String>,
+    /// 等待取消订阅的确认时收到的消息，下次读取消息时先返回
+    pending_events: VecDeque<PushEvent>,
 }
 
 impl Subscriber {
@@ -246,78 +1502,192 @@ impl Subscriber {
         Self {
             client,
             subscriber_channels,
+            pending_events: VecDeque::new(),
+        }
+    }
+
+    /// # get_subscriber() 函数
+    ///
+    /// 获取当前订阅的channels
+    pub fn get_subscriber_channels(&self) -> &[String] {
+        &self.subscriber_channels
+    }
+
+    /// # next_message() 函数
+    ///
+    /// 从订阅的channels中接收消息。
+    /// None表示channels已经被关闭
+    ///
+    /// 服务器通知有消息因为订阅者跟不上而被丢弃时，只记录一条警告，需要感知丢失的调用者应该使用next_event()
+    #[instrument(skip(self))]
+    pub async fn next_message(&mut self) -> crate::Result<Option<Message>> {
+        loop {
+            match self.read_event().await? {
+                Some(PushEvent::Message(message)) => return Ok(Some(message)),
+                Some(PushEvent::MessagesDropped { channel, count }) => {
+                    warn!(
+                        channel,
+                        count, "server dropped messages for this subscriber"
+                    );
+                }
+                None => return Ok(None),
+            }
+        }
+    }
+
+    /// # read_event() 函数
+    ///
+    /// 读取服务器推送的下一条消息或消息丢失通知
+    ///
+    /// 心跳被忽略；订阅和取消订阅的确认（比如subscribe()或unsubscribe()在等待确认时被取消，确认在之后才到达）
+    /// 只用来更新本地的订阅列表，不会被当作错误
+    async fn read_event(&mut self) -> crate::Result<Option<PushEvent>> {
+        if let Some(event) = self.pending_events.pop_front() {
+            return Ok(Some(event));
+        }
+
+        loop {
+            match self.client.connection.read_frame().await? {
+                Some(frame) => {
+                    debug!(?frame);
+
+                    if let Some(event) = self.handle_push(decode_push(frame)?) {
+                        return Ok(Some(event));
+                    }
+                }
+                None => return Ok(None),
+            }
         }
     }
 
-    /// # get_subscriber() 函数
+    /// # handle_push() 函数
     ///
-    /// 获取当前订阅的channels
-    pub fn get_subscriber_channels(&self) -> &[String] {
-        &self.subscriber_channels
+    /// 根据订阅和取消订阅的确认更新本地的订阅列表，消息和消息丢失通知原样返回
+    fn handle_push(&mut self, push: Push) -> Option<PushEvent> {
+        match push {
+            Push::Event(event) => return Some(event),
+            Push::Subscribed(channel) => {
+                if !self.subscriber_channels.contains(&channel) {
+                    self.subscriber_channels.push(channel);
+                }
+            }
+            Push::Unsubscribed(Some(channel), _) => {
+                self.subscriber_channels.retain(|c| *c != channel)
+            }
+            Push::Unsubscribed(None, _) | Push::Pong => {}
+        }
+        None
     }
 
-    /// # next_message() 函数
+    /// # next_event() 函数
     ///
-    /// 从订阅的channels中接收消息。
-    /// None表示channels已经被关闭
+    /// 与next_message()相同，但是连接断开时会自动重连并重新订阅所有channels，
+    /// 重连成功后返回SubscriberEvent::Reconnected，提醒调用者在断开期间可能丢失了消息
     #[instrument(skip(self))]
-    pub async fn next_message(&mut self) -> crate::Result<Option<Message>> {
-        match self.client.connection.read_frame().await? {
-            Some(frame) => {
-                debug!(?frame);
-
-                match frame {
-                    Frame::Array(ref f) => match f.as_slice() {
-                        [message, channel, content] if *message == "message" => Ok(Some(
-                            Message::new(channel.to_string(), Bytes::from(content.to_string())),
-                        )),
-                        _ => Err(frame.to_error()),
-                    },
-                    f => Err(f.to_error()),
-                }
+    pub async fn next_event(&mut self) -> crate::Result<SubscriberEvent> {
+        match self.read_event().await {
+            Ok(Some(event)) => return Ok(event.into()),
+            Ok(None) => debug!("connection closed by server, resubscribing"),
+            Err(err) if is_connection_error(&err) => {
+                debug!(cause = %err, "connection lost, resubscribing")
             }
-            None => Ok(None),
+            Err(err) => return Err(err),
         }
+
+        self.client.reconnect().await?;
+        if !self.subscriber_channels.is_empty() {
+            let channels = self.subscriber_channels.clone();
+            self.subscribe_cmd(&channels).await?;
+        }
+
+        Ok(SubscriberEvent::Reconnected)
     }
 
     /// # into_stream() 函数
     ///
     /// 通过async-stream crate将Subscriber转换为Stream。
     /// 将Subcriber转换为Stream，是为了简化异步消息处理
-    #[allow(dead_code)]
-    pub(crate) fn into_stream(mut self) -> impl Stream<Item = crate::Result<Message>> {
-        try_stream! {
+    ///
+    /// 返回的Stream实现了Unpin，可以直接在select循环中使用StreamExt::next()：
+    ///
+    /// ```no_run
+    /// # async fn example() -> rustis::Result<()> {
+    /// use rustis::client::Client;
+    /// use tokio_stream::StreamExt;
+    ///
+    /// let client = Client::connect("127.0.0.1:6379").await?;
+    /// let mut messages = client.subscribe(vec!["news".to_string()]).await?.into_stream();
+    ///
+    /// loop {
+    ///     tokio::select! {
+    ///         message = messages.next() => match message {
+    ///             Some(message) => println!("{:?}", message?.content),
+    ///             None => break,
+    ///         },
+    ///         _ = tokio::signal::ctrl_c() => break,
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn into_stream(mut self) -> impl Stream<Item = crate::Result<Message>> + Unpin {
+        Box::pin(try_stream! {
             while let Some(message) = self.next_message().await? {
                 yield message;
             }
-        }
+        })
     }
 
     /// # into_client() 函数
     ///
-    /// 从Subscriber对象中取回Client对象所有权
+    /// 从Subscriber对象中取回Client对象所有权，不会通知服务器，连接仍然处于订阅模式。
+    /// 需要继续在这个连接上执行普通命令时应该使用exit_subscribe()
     pub fn into_client(self) -> Client {
         self.client
     }
 
     /// # subscribe() 函数
     ///
-    /// 订阅channels
+    /// 订阅更多的channels，已经订阅的channel不会重复记录
     #[instrument(skip(self))]
     pub async fn subscribe(&mut self, channels: &[String]) -> crate::Result<()> {
-        // 向服务器发出subscribe命令并等待确认
-        self.client.subscribe_cmd(channels).await?;
+        // 向服务器发出subscribe命令并等待确认，确认到达时更新订阅的channels
+        self.subscribe_cmd(channels).await
+    }
+
+    /// # subscribe_cmd() 函数
+    ///
+    /// 发送subscribe命令并读取每个channel的确认，等待确认时收到的消息会留给next_message()返回
+    async fn subscribe_cmd(&mut self, channels: &[String]) -> crate::Result<()> {
+        // 将subscribe命令编码为帧
+        let frame = Subscribe::new(channels.to_vec()).code_subscribe_into_frame();
+        debug!(request = ?frame);
 
-        // 更新订阅的channels
-        self.subscriber_channels
-            .extend(channels.iter().map(Clone::clone));
+        // 将帧写入到连接中
+        self.client.send_request(&frame).await?;
+
+        // 服务器按照请求中的顺序确认每个channel，之前被取消的请求的确认也可能在这里到达
+        let mut confirmed = 0;
+        while confirmed < channels.len() {
+            let push = decode_push(self.client.read_response().await?)?;
+            if matches!(&push, Push::Subscribed(channel) if *channel == channels[confirmed]) {
+                confirmed += 1;
+            }
+            if let Some(event) = self.handle_push(push) {
+                self.pending_events.push_back(event);
+            }
+        }
 
         Ok(())
     }
 
     /// # unsubscribe() 函数
     ///
-    /// 取消订阅channels
+    /// 取消订阅channels，channels为空时取消订阅所有channels
+    ///
+    /// 服务器对每个取消订阅的channel回复一次确认，确认中带有剩余的订阅数量。指定了channels时按顺序等待
+    /// 每个channel的确认；取消所有订阅时不依赖本地记录的channel数量，一直读取到剩余数量为0。
+    /// 本地的订阅列表按照确认中的channel名称更新，等待确认时收到的消息会留给next_message()返回
     #[instrument(skip(self))]
     pub async fn unsubscribe(&mut self, channels: &[String]) -> crate::Result<()> {
         // 将unsubscribe命令编码为帧
@@ -325,54 +1695,282 @@ impl Subscriber {
         debug!(request = ?frame);
 
         // 将帧写入到连接中
-        self.client.connection.write_frame(&frame).await?;
+        self.client.send_request(&frame).await?;
+
+        let mut confirmed = 0;
+        loop {
+            let push = decode_push(self.client.read_response().await?)?;
+            let done = match &push {
+                // 取消所有订阅时不依赖本地记录的channel数量，一直读取到剩余数量为0
+                Push::Unsubscribed(_, remaining) if channels.is_empty() => *remaining == 0,
+                Push::Unsubscribed(Some(channel), _) if *channel == channels[confirmed] => {
+                    confirmed += 1;
+                    confirmed == channels.len()
+                }
+                _ => false,
+            };
+            if let Some(event) = self.handle_push(push) {
+                self.pending_events.push_back(event);
+            }
+            if done {
+                break;
+            }
+        }
 
-        // 如果channels为空，则取消订阅所有channels
-        let num = if channels.is_empty() {
-            self.subscriber_channels.len()
-        } else {
-            channels.len()
-        };
+        // 服务器已经确认没有任何订阅了
+        if channels.is_empty() {
+            self.subscriber_channels.clear();
+        }
 
-        // 读取服务器的响应
-        for _ in 0..num {
-            let response = self.client.read_response().await?;
+        Ok(())
+    }
+
+    /// # exit_subscribe() 函数
+    ///
+    /// 取消所有订阅并退出订阅模式，返回可以继续执行普通命令的Client。
+    /// 还没有通过next_message()读取的消息和心跳会被丢弃
+    #[instrument(skip(self))]
+    pub async fn exit_subscribe(mut self) -> crate::Result<Client> {
+        // 先取消所有订阅，读完服务器的确认，之后服务器不会再推送新的消息
+        self.unsubscribe(&[]).await?;
+
+        // 服务器收到exitsubscribe命令后回到普通模式，不会回复。紧接着发送一个PING，
+        // 读到普通模式下的PONG之前收到的都是订阅模式下推送的帧，全部丢弃，之后的响应不会和推送的帧混在一起
+        let frames = [
+            ExitSubscribe::new().code_exit_subscribe_into_frame(),
+            Ping::new(None).code_ping_into_frame(),
+        ];
+        debug!(request = ?frames);
+        self.client.send_pipeline(&frames).await?;
+
+        loop {
+            match self.client.read_response().await? {
+                Frame::Simple(pong) if pong == "PONG" => break,
+                frame => {
+                    debug!(?frame, "discarding push while leaving subscribe mode");
+                    decode_push(frame)?;
+                }
+            }
+        }
+        // exitsubscribe没有响应，所有的请求都已经完成
+        self.client.pending_replies = 0;
 
-            match response {
-                Frame::Array(ref frame) => match frame.as_slice() {
-                    [unsubscribe, channel, ..] if *unsubscribe == "unsubscribe" => {
-                        let len = self.subscriber_channels.len();
+        Ok(self.client)
+    }
+}
 
-                        if len == 0 {
-                            return Err(response.to_error());
-                        }
+/// # Push 枚举
+///
+/// 订阅模式下服务器推送的帧
+#[derive(Debug)]
+enum Push {
+    /// 消息或消息丢失通知，交给调用者
+    Event(PushEvent),
+    /// 订阅一个channel的确认
+    Subscribed(String),
+    /// 取消订阅一个channel的确认，以及剩余的订阅数量，没有任何订阅时channel为空
+    Unsubscribed(Option<String>, i64),
+    /// 服务器推送的心跳
+    Pong,
+}
+
+/// # PushEvent 枚举
+///
+/// 服务器推送的、需要交给调用者的事件。与SubscriberEvent相比没有Reconnected，重连只由next_event()产生
+#[derive(Debug)]
+enum PushEvent {
+    /// 收到一条消息
+    Message(Message),
+    /// 服务器丢弃了channel中的count条消息
+    MessagesDropped { channel: String, count: u64 },
+}
+
+impl From<PushEvent> for SubscriberEvent {
+    fn from(event: PushEvent) -> Self {
+        match event {
+            PushEvent::Message(message) => SubscriberEvent::Message(message),
+            PushEvent::MessagesDropped { channel, count } => {
+                SubscriberEvent::MessagesDropped { channel, count }
+            }
+        }
+    }
+}
+
+/// # decode_push() 函数
+///
+/// 把订阅模式下服务器推送的帧解码为消息、消息丢失通知、订阅确认或者心跳，其他形状的帧返回错误
+fn decode_push(frame: Frame) -> crate::Result<Push> {
+    let Frame::Array(ref parts) = frame else {
+        return Err(frame.to_error());
+    };
+
+    let push =
+        match parts.as_slice() {
+            [message, channel, content] if *message == "message" => {
+                match (channel.as_str(), content.to_bytes()) {
+                    (Some(channel), Some(content)) => Push::Event(PushEvent::Message(
+                        Message::new(channel.to_string(), content),
+                    )),
+                    _ => return Err(frame.to_error()),
+                }
+            }
+            // 模式订阅的消息，调用者只关心实际的channel
+            [pmessage, _pattern, channel, content] if *pmessage == "pmessage" => {
+                match (channel.as_str(), content.to_bytes()) {
+                    (Some(channel), Some(content)) => Push::Event(PushEvent::Message(
+                        Message::new(channel.to_string(), content),
+                    )),
+                    _ => return Err(frame.to_error()),
+                }
+            }
+            [dropped, channel, Frame::Integer(count)] if *dropped == "message-dropped" => {
+                Push::Event(PushEvent::MessagesDropped {
+                    channel: channel.as_str().unwrap_or_default().to_string(),
+                    count: *count as u64,
+                })
+            }
+            [subscribe, channel, Frame::Integer(_)] if *subscribe == "subscribe" => {
+                match channel.as_str() {
+                    Some(channel) => Push::Subscribed(channel.to_string()),
+                    None => return Err(frame.to_error()),
+                }
+            }
+            [unsubscribe, channel, Frame::Integer(remaining)] if *unsubscribe == "unsubscribe" => {
+                Push::Unsubscribed(channel.as_str().map(str::to_string), *remaining)
+            }
+            [pong, _] if *pong == "pong" => Push::Pong,
+            _ => return Err(frame.to_error()),
+        };
+
+    Ok(push)
+}
+
+/// # CommandLatency 结构体
+///
+/// Client::latency_stats()返回的单个命令的统计
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandLatency {
+    /// 命令名称
+    pub name: String,
+    /// 调用次数
+    pub calls: u64,
+    /// 执行失败的次数
+    pub failed_calls: u64,
+    /// 总耗时（微秒）
+    pub usec: u64,
+    /// 延迟直方图，每一项为(桶上界（微秒）, 耗时不超过上界的累计调用次数)
+    pub histogram: Vec<(u64, u64)>,
+}
 
-                        // 想要取消的channel应该在订阅的channels中
-                        self.subscriber_channels.retain(|c| *channel != &c[..]);
+impl CommandLatency {
+    /// # from_frames() 函数
+    ///
+    /// 从LATENCYSTATS回复中单个命令的统计数组解析出CommandLatency
+    fn from_frames(name: &[u8], entry: Vec<Frame>) -> crate::Result<CommandLatency> {
+        let mut latency = CommandLatency {
+            name: String::from_utf8_lossy(name).into_owned(),
+            calls: 0,
+            failed_calls: 0,
+            usec: 0,
+            histogram: Vec::new(),
+        };
 
-                        // 每次只取消一个subcription
-                        if self.subscriber_channels.len() != len - 1 {
-                            return Err(response.to_error());
+        let mut entry = entry.into_iter();
+        while let (Some(field), Some(value)) = (entry.next(), entry.next()) {
+            match (field, value) {
+                (field, Frame::Integer(value)) if field == "calls" => latency.calls = value as u64,
+                (field, Frame::Integer(value)) if field == "failed_calls" => {
+                    latency.failed_calls = value as u64
+                }
+                (field, Frame::Integer(value)) if field == "usec" => latency.usec = value as u64,
+                (field, Frame::Array(buckets)) if field == "histogram_usec" => {
+                    let mut buckets = buckets.into_iter();
+                    while let (Some(bound), Some(count)) = (buckets.next(), buckets.next()) {
+                        match (bound, count) {
+                            (Frame::Integer(bound), Frame::Integer(count)) => {
+                                latency.histogram.push((bound as u64, count as u64))
+                            }
+                            frame => {
+                                return Err(format!(
+                                    "protocol error; unexpected histogram bucket {:?}",
+                                    frame
+                                )
+                                .into())
+                            }
                         }
                     }
-                    _ => return Err(response.to_error()),
-                },
-                frame => return Err(frame.to_error()),
-            };
+                }
+                // 忽略不认识的字段，兼容以后新增的统计项
+                _ => {}
+            }
         }
 
-        Ok(())
+        Ok(latency)
     }
+}
 
-    pub async fn exit_subscribe(&mut self) -> crate::Result<()> {
-        // 将exit_subscribe命令编码为帧
-        let frame = ExitSubscribe::new().code_exit_subscribe_into_frame();
-        debug!(request = ?frame);
+/// # SubscriberEvent 枚举
+///
+/// Subscriber::next_event()返回的事件
+#[derive(Debug)]
+pub enum SubscriberEvent {
+    /// 收到一条消息
+    Message(Message),
+    /// 连接断开后重新连接并重新订阅成功，断开期间发布的消息已经丢失
+    Reconnected,
+    /// 订阅者跟不上发布的速度，服务器丢弃了channel中的count条消息
+    MessagesDropped { channel: String, count: u64 },
+}
 
-        // 将帧写入到连接中
-        self.client.connection.write_frame(&frame).await?;
+/// # value_from_frame() 函数
+///
+/// 把GET或MGET回复中的一个值转换为Option<Bytes>
+fn value_from_frame(frame: Frame) -> crate::Result<Option<Bytes>> {
+    match frame {
+        Frame::Simple(value) => Ok(Some(value.into())),
+        Frame::Bulk(value) => Ok(Some(value)),
+        Frame::Null => Ok(None),
+        frame => Err(frame.to_error()),
+    }
+}
 
-        Ok(())
+/// # is_unknown_command() 函数
+///
+/// 判断错误是否是服务器不认识命令
+fn is_unknown_command(err: &crate::Error) -> bool {
+    matches!(
+        err.downcast_ref::<RustisError>(),
+        Some(RustisError::Command(msg)) if msg.starts_with("ERR unknown command")
+    )
+}
+
+/// # is_connection_error() 函数
+///
+/// 判断错误是否由连接断开引起
+fn is_connection_error(err: &crate::Error) -> bool {
+    matches!(err.downcast_ref::<RustisError>(), Some(RustisError::Io(_)))
+        || err.downcast_ref::<Error>().is_some()
+}
+
+/// # Monitor 结构体
+///
+/// 处于监控模式的客户端，Client类型被转换为Monitor类型，防止调用其他命令
+pub struct Monitor {
+    /// Client实例
+    client: Client,
+}
+
+impl Monitor {
+    /// # next_command() 函数
+    ///
+    /// 接收服务器推送的下一个命令，格式为`时间戳 [数据库编号 客户端地址] "命令" "参数"...`。
+    /// None表示连接已经被关闭
+    pub async fn next_command(&mut self) -> crate::Result<Option<String>> {
+        match self.client.connection.read_frame().await? {
+            Some(Frame::Simple(line)) => Ok(Some(line)),
+            Some(frame) => Err(frame.to_error()),
+            None => Ok(None),
+        }
     }
 }
 
@@ -453,9 +2051,9 @@ mod tests {
                 match frame {
                     Frame::Array(parts) => {
                         // 检查是否是get命令
-                        if let Some(Frame::Bulk(cmd)) = parts.get(0) {
+                        if let Some(Frame::Bulk(cmd)) = parts.first() {
                             // 将Bytes转换为&str
-                            let cmd = std::str::from_utf8(&cmd).unwrap();
+                            let cmd = std::str::from_utf8(cmd).unwrap();
                             if cmd.to_lowercase() == "get" {
                                 // 服务器响应值
                                 connection
@@ -487,6 +2085,43 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_wrong_type_error() -> crate::Result<()> {
+        let listener = TcpListener::bind("localhost:0").await?;
+        let addr = listener.local_addr()?;
+
+        // 创建一个任务来模拟服务器
+        let server = tokio::spawn(async move {
+            let (server_stream, _) = listener.accept().await.unwrap();
+            let mut connection = Connection::new(server_stream);
+
+            // 读取客户端发送的帧，响应WRONGTYPE错误
+            if connection.read_frame().await.unwrap().is_some() {
+                connection
+                    .write_frame(&Frame::Error(
+                        "WRONGTYPE Operation against a key holding the wrong kind of value"
+                            .to_string(),
+                    ))
+                    .await
+                    .unwrap();
+            }
+        });
+
+        // 创建一个客户端来连接到服务器
+        let mut client = Client::connect(addr).await?;
+
+        // 发送get命令，应该得到一个WrongType错误
+        let err = client.get("test_key").await.unwrap_err();
+        match err.downcast_ref::<RustisError>() {
+            Some(RustisError::WrongType) => {}
+            _ => panic!("错误类型不是WrongType: {:?}", err),
+        }
+
+        // 等待服务器任务结束
+        server.await?;
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_set() -> crate::Result<()> {
         let listener = TcpListener::bind("localhost:0").await?;
@@ -502,8 +2137,8 @@ mod tests {
                 match frame {
                     Frame::Array(parts) => {
                         // 检查是否为set命令
-                        if let Some(Frame::Bulk(cmd)) = parts.get(0) {
-                            let cmd = std::str::from_utf8(&cmd).unwrap();
+                        if let Some(Frame::Bulk(cmd)) = parts.first() {
+                            let cmd = std::str::from_utf8(cmd).unwrap();
                             if cmd.to_lowercase() == "set" {
                                 // 服务器响应OK
                                 connection
@@ -533,6 +2168,29 @@ mod tests {
         Ok(())
     }
 
+    /// 测试服务器不支持HELLO时，connect_resp3继续使用RESP2
+    #[tokio::test]
+    async fn test_connect_resp3_fallback() -> crate::Result<()> {
+        let listener = TcpListener::bind("localhost:0").await?;
+        let addr = listener.local_addr()?;
+
+        let server = tokio::spawn(async move {
+            let (server_stream, _) = listener.accept().await.unwrap();
+            let mut connection = Connection::new(server_stream);
+
+            let frame = connection.read_frame().await.unwrap().unwrap();
+            assert_eq!(frame.as_array().unwrap()[0], "hello");
+            let response = Frame::Error("ERR unknown command 'hello'".to_string());
+            connection.write_frame(&response).await.unwrap();
+        });
+
+        let client = Client::connect_resp3(addr).await?;
+        assert_eq!(client.protocol(), 2);
+
+        server.await?;
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_publish() -> crate::Result<()> {
         let listener = TcpListener::bind("localhost:0").await?;
@@ -543,15 +2201,20 @@ mod tests {
             let (server_stream, _) = listener.accept().await.unwrap();
             let mut connection = Connection::new(server_stream);
 
-            // 读取客户端发送的帧
-            if let Some(frame) = connection.read_frame().await.unwrap() {
+            // 读取客户端发送的帧，第一次响应1，第二次响应一个无效的负数
+            for count in [1, -1] {
+                let Some(frame) = connection.read_frame().await.unwrap() else {
+                    break;
+                };
                 match frame {
                     Frame::Array(parts) => {
-                        if let Some(Frame::Bulk(cmd)) = parts.get(0) {
-                            let cmd = std::str::from_utf8(&cmd).unwrap();
+                        if let Some(Frame::Bulk(cmd)) = parts.first() {
+                            let cmd = std::str::from_utf8(cmd).unwrap();
                             if cmd.to_lowercase() == "publish" {
-                                // 服务器响应1
-                                connection.write_frame(&Frame::Integer(1)).await.unwrap();
+                                connection
+                                    .write_frame(&Frame::Integer(count))
+                                    .await
+                                    .unwrap();
                             } else {
                                 panic!("Unexpected command");
                             }
@@ -573,6 +2236,12 @@ mod tests {
         // 检查响应是否为1
         assert_eq!(result, 1);
 
+        // 负数不能转换为订阅者数量
+        assert!(client
+            .publish(channel, Bytes::from("test_message"))
+            .await
+            .is_err());
+
         // 等待服务器任务结束
         server.await?;
         Ok(())
@@ -592,7 +2261,7 @@ mod tests {
             if let Some(frame) = connection.read_frame().await.unwrap() {
                 match frame {
                     Frame::Array(parts) => {
-                        if let Some(Frame::Bulk(cmd)) = parts.get(0).cloned() {
+                        if let Some(Frame::Bulk(cmd)) = parts.first().cloned() {
                             let cmd_str = std::str::from_utf8(&cmd).unwrap();
                             if cmd_str.eq_ignore_ascii_case("subscribe") {
                                 // 响应订阅确认消息
@@ -634,4 +2303,283 @@ mod tests {
 
         Ok(())
     }
+
+    /// 测试取消所有订阅时按照服务器的确认更新订阅列表，确认之前收到的消息留给next_message()
+    #[tokio::test]
+    async fn test_unsubscribe_all() -> crate::Result<()> {
+        let listener = TcpListener::bind("localhost:0").await?;
+        let addr = listener.local_addr()?;
+
+        let server = tokio::spawn(async move {
+            let (server_stream, _) = listener.accept().await.unwrap();
+            let mut connection = Connection::new(server_stream);
+            let reply = |parts: &[&str], count: i64| {
+                let mut frame = Frame::array();
+                for part in parts {
+                    frame.push_bulk(Bytes::from(part.to_string()));
+                }
+                if count >= 0 {
+                    frame.push_int(count);
+                }
+                frame
+            };
+
+            connection.read_frame().await.unwrap().unwrap();
+            for (count, channel) in ["a", "b", "c"].into_iter().enumerate() {
+                let frame = reply(&["subscribe", channel], count as i64 + 1);
+                connection.write_frame(&frame).await.unwrap();
+            }
+
+            // 确认的顺序与客户端订阅的顺序不同，中间还夹着一条消息
+            connection.read_frame().await.unwrap().unwrap();
+            connection
+                .write_frame(&reply(&["message", "b", "hello"], -1))
+                .await
+                .unwrap();
+            for (count, channel) in [(2, "c"), (1, "a"), (0, "b")] {
+                connection
+                    .write_frame(&reply(&["unsubscribe", channel], count))
+                    .await
+                    .unwrap();
+            }
+        });
+
+        let client = Client::connect(addr).await?;
+        let channels = vec!["a".to_string(), "b".to_string(), "c".to_string()];
+        let mut subscriber = client.subscribe(channels).await?;
+
+        subscriber.unsubscribe(&[]).await?;
+        assert!(subscriber.get_subscriber_channels().is_empty());
+
+        let message = subscriber.next_message().await?.unwrap();
+        assert_eq!(message.channel, "b");
+        assert_eq!(&message.content[..], b"hello");
+
+        server.await?;
+        Ok(())
+    }
+
+    /// 测试等待确认时被取消的unsubscribe和subscribe，迟到的确认在next_message()中更新订阅列表，不会返回错误
+    #[tokio::test]
+    async fn test_cancelled_subscribe_confirmations() -> crate::Result<()> {
+        let listener = TcpListener::bind("localhost:0").await?;
+        let addr = listener.local_addr()?;
+
+        let (cancelled_tx, cancelled_rx) = tokio::sync::oneshot::channel();
+        let server = tokio::spawn(async move {
+            let (server_stream, _) = listener.accept().await.unwrap();
+            let mut connection = Connection::new(server_stream);
+            let reply = |parts: &[&str], count: i64| {
+                let mut frame = Frame::array();
+                for part in parts {
+                    frame.push_bulk(Bytes::from(part.to_string()));
+                }
+                if count >= 0 {
+                    frame.push_int(count);
+                }
+                frame
+            };
+
+            connection.read_frame().await.unwrap().unwrap();
+            connection
+                .write_frame(&reply(&["subscribe", "a"], 1))
+                .await
+                .unwrap();
+            connection
+                .write_frame(&reply(&["subscribe", "b"], 2))
+                .await
+                .unwrap();
+
+            // 两个请求的确认都在客户端放弃等待之后才发送
+            connection.read_frame().await.unwrap().unwrap();
+            connection.read_frame().await.unwrap().unwrap();
+            cancelled_rx.await.unwrap();
+            connection
+                .write_frame(&reply(&["unsubscribe", "b"], 1))
+                .await
+                .unwrap();
+            connection
+                .write_frame(&reply(&["subscribe", "c"], 2))
+                .await
+                .unwrap();
+            connection
+                .write_frame(&reply(&["pmessage", "c*", "c", "hello"], -1))
+                .await
+                .unwrap();
+        });
+
+        let client = Client::connect(addr).await?;
+        let mut subscriber = client
+            .subscribe(vec!["a".to_string(), "b".to_string()])
+            .await?;
+
+        let wait = Duration::from_millis(20);
+        assert!(
+            time::timeout(wait, subscriber.unsubscribe(&["b".to_string()]))
+                .await
+                .is_err()
+        );
+        assert!(
+            time::timeout(wait, subscriber.subscribe(&["c".to_string()]))
+                .await
+                .is_err()
+        );
+        cancelled_tx.send(()).unwrap();
+
+        let message = subscriber.next_message().await?.unwrap();
+        assert_eq!(message.channel, "c");
+        assert_eq!(&message.content[..], b"hello");
+        assert_eq!(
+            subscriber.get_subscriber_channels(),
+            ["a".to_string(), "c".to_string()]
+        );
+
+        server.await?;
+        Ok(())
+    }
+
+    /// # timed_out() 函数
+    ///
+    /// 错误是否为请求超时
+    fn timed_out(err: &crate::Error) -> bool {
+        matches!(err.downcast_ref::<RustisError>(), Some(RustisError::Io(err)) if err.kind() == ErrorKind::TimedOut)
+    }
+
+    /// 测试服务器不回复时请求超时，之后的请求立即失败而不会读到迟到的响应，重连之后恢复
+    #[tokio::test]
+    async fn test_request_timeout() -> crate::Result<()> {
+        let listener = TcpListener::bind("localhost:0").await?;
+        let addr = listener.local_addr()?;
+
+        let server = tokio::spawn(async move {
+            // 第一个连接只接收请求，超时之后才回复
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut connection = Connection::new(stream);
+            connection.read_frame().await.unwrap().unwrap();
+            time::sleep(Duration::from_millis(100)).await;
+            connection
+                .write_frame(&Frame::Simple("PONG".to_string()))
+                .await
+                .unwrap();
+
+            // 重连之后正常回复
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut connection = Connection::new(stream);
+            connection.read_frame().await.unwrap().unwrap();
+            connection
+                .write_frame(&Frame::Bulk("fresh".into()))
+                .await
+                .unwrap();
+            connection
+        });
+
+        let mut client = Client::connect(addr)
+            .await?
+            .with_request_timeout(Some(Duration::from_millis(50)));
+
+        let err = client.ping(None).await.unwrap_err();
+        assert!(timed_out(&err), "{}", err);
+
+        // 等到迟到的PONG到达之后，连接仍然不可用
+        time::sleep(Duration::from_millis(100)).await;
+        let start = Instant::now();
+        let err = client.get("key").await.unwrap_err();
+        assert!(timed_out(&err), "{}", err);
+        assert!(start.elapsed() < Duration::from_millis(50));
+
+        client.reconnect().await?;
+        assert_eq!(client.get("key").await?, Some("fresh".into()));
+
+        server.await?;
+        Ok(())
+    }
+
+    /// 测试pipeline中的100个命令只flush一次，中间的错误回复不会打乱之后的响应
+    #[tokio::test]
+    async fn test_pipeline_single_flush() -> crate::Result<()> {
+        let listener = TcpListener::bind("localhost:0").await?;
+        let addr = listener.local_addr()?;
+
+        // 模拟服务器：GET回复键名，其它命令回复错误
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut connection = Connection::new(stream);
+            for _ in 0..100 {
+                let frame = connection.read_frame().await.unwrap().unwrap();
+                let args = frame.as_array().unwrap().to_vec();
+                let reply = if args[0] == "get" {
+                    args[1].clone()
+                } else {
+                    Frame::Error("ERR unknown command".to_string())
+                };
+                connection.write_frame(&reply).await.unwrap();
+            }
+        });
+
+        let mut client = Client::connect(addr).await?;
+        let mut pipeline = client.pipeline();
+        for i in 0..100 {
+            if i % 10 == 5 {
+                pipeline.command(&[b"BAD"]);
+            } else {
+                pipeline.get(&format!("key{}", i));
+            }
+        }
+        assert_eq!(pipeline.len(), 100);
+
+        let replies = pipeline.execute(&mut client).await?;
+        assert_eq!(client.connection.take_net_bytes().2, 1);
+        assert_eq!(replies.len(), 100);
+        for (i, reply) in replies.iter().enumerate() {
+            if i % 10 == 5 {
+                assert_eq!(reply, &Frame::Error("ERR unknown command".to_string()));
+            } else {
+                assert_eq!(reply, &Frame::Bulk(format!("key{}", i).into()));
+            }
+        }
+
+        server.await?;
+        Ok(())
+    }
+
+    /// 测试服务器不认识MGET时，get_many()改为一次flush发送多个GET，之后不再尝试MGET
+    #[tokio::test]
+    async fn test_get_many_fallback() -> crate::Result<()> {
+        let listener = TcpListener::bind("localhost:0").await?;
+        let addr = listener.local_addr()?;
+
+        // 模拟不支持MGET的服务器：key1不存在，其它GET回复键名，记录收到的命令名称
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut connection = Connection::new(stream);
+            let mut commands = Vec::new();
+            while let Some(frame) = connection.read_frame().await.unwrap() {
+                let args = frame.as_array().unwrap().to_vec();
+                let reply = match args[0].as_str().unwrap() {
+                    "get" if args[1] == "key1" => Frame::Null,
+                    "get" => args[1].clone(),
+                    name => Frame::Error(format!("ERR unknown command '{}'", name)),
+                };
+                commands.push(args[0].as_string().unwrap());
+                connection.write_frame(&reply).await.unwrap();
+            }
+            commands
+        });
+
+        let mut client = Client::connect(addr).await?;
+        let expected = vec![Some(Bytes::from("key0")), None, Some(Bytes::from("key2"))];
+        assert_eq!(client.get_many(&["key0", "key1", "key2"]).await?, expected);
+        assert_eq!(client.connection.take_net_bytes().2, 2);
+        assert_eq!(client.get_many(&["key0", "key1", "key2"]).await?, expected);
+        assert_eq!(client.connection.take_net_bytes().2, 1);
+        assert!(client.get_many(&[]).await?.is_empty());
+        drop(client);
+
+        // 第二次调用直接使用GET，最后是drop时发送的QUIT
+        assert_eq!(
+            server.await?,
+            ["mget", "get", "get", "get", "get", "get", "get", "QUIT"]
+        );
+        Ok(())
+    }
 }
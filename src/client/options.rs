@@ -0,0 +1,141 @@
+//! ConnectOptions结构体，客户端建立连接时的选项
+
+use tokio::time::Duration;
+
+use super::{Client, IntoConnectAddr};
+
+/// # ConnectOptions 结构体
+///
+/// 客户端建立连接时的选项，通过`Client::connect_with_options`传入，也可以通过`Client::builder()`逐项设置
+///
+/// 默认选项与`Client::connect`的行为完全相同：不限制建立连接的时间，建立连接后不发送任何命令。
+/// 连接地址是redis://形式的URL时，URL中的用户名、密码和数据库补充这里没有设置的选项
+#[derive(Debug, Clone)]
+pub struct ConnectOptions {
+    /// 是否开启TCP_NODELAY（关闭Nagle算法），默认开启
+    pub nodelay: bool,
+    /// TCP keepalive的空闲时间，None表示不开启，默认不开启
+    pub keepalive: Option<Duration>,
+    /// 建立TCP连接的最长时间，超时返回ErrorKind::TimedOut的IO错误，None表示不限制
+    pub connect_timeout: Option<Duration>,
+    /// 建立连接后认证使用的用户名，None时只发送密码
+    pub username: Option<String>,
+    /// 建立连接后立即用AUTH认证的密码
+    pub password: Option<String>,
+    /// 建立连接后用SELECT切换到的数据库
+    pub database: Option<u64>,
+    /// 建立连接后用CLIENT SETNAME设置的连接名称
+    pub client_name: Option<String>,
+    /// 每个请求的超时时间，见`Client::with_request_timeout`
+    pub request_timeout: Option<Duration>,
+}
+
+impl Default for ConnectOptions {
+    fn default() -> Self {
+        Self {
+            nodelay: true,
+            keepalive: None,
+            connect_timeout: None,
+            username: None,
+            password: None,
+            database: None,
+            client_name: None,
+            request_timeout: None,
+        }
+    }
+}
+
+/// # ClientBuilder 结构体
+///
+/// 逐项设置ConnectOptions，最后通过build()建立连接，由`Client::builder()`创建
+#[derive(Debug, Clone, Default)]
+pub struct ClientBuilder {
+    options: ConnectOptions,
+}
+
+impl ClientBuilder {
+    /// # new() 函数
+    ///
+    /// 创建一个使用默认选项的ClientBuilder
+    pub fn new() -> ClientBuilder {
+        ClientBuilder::default()
+    }
+
+    /// # connect_timeout() 函数
+    ///
+    /// 设置建立TCP连接的最长时间
+    pub fn connect_timeout(mut self, timeout: Duration) -> ClientBuilder {
+        self.options.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// # nodelay() 函数
+    ///
+    /// 设置是否开启TCP_NODELAY
+    pub fn nodelay(mut self, nodelay: bool) -> ClientBuilder {
+        self.options.nodelay = nodelay;
+        self
+    }
+
+    /// # keepalive() 函数
+    ///
+    /// 设置TCP keepalive的空闲时间，None表示不开启
+    pub fn keepalive(mut self, keepalive: Option<Duration>) -> ClientBuilder {
+        self.options.keepalive = keepalive;
+        self
+    }
+
+    /// # password() 函数
+    ///
+    /// 设置建立连接后立即认证的密码，重连时同样会认证
+    pub fn password(mut self, password: &str) -> ClientBuilder {
+        self.options.password = Some(password.to_string());
+        self
+    }
+
+    /// # username() 函数
+    ///
+    /// 设置认证使用的用户名，与password()一起使用
+    pub fn username(mut self, username: &str) -> ClientBuilder {
+        self.options.username = Some(username.to_string());
+        self
+    }
+
+    /// # database() 函数
+    ///
+    /// 设置建立连接后切换到的数据库
+    pub fn database(mut self, index: u64) -> ClientBuilder {
+        self.options.database = Some(index);
+        self
+    }
+
+    /// # client_name() 函数
+    ///
+    /// 设置建立连接后通过CLIENT SETNAME设置的连接名称
+    pub fn client_name(mut self, name: &str) -> ClientBuilder {
+        self.options.client_name = Some(name.to_string());
+        self
+    }
+
+    /// # request_timeout() 函数
+    ///
+    /// 设置每个请求的超时时间，None表示一直等待
+    pub fn request_timeout(mut self, timeout: Option<Duration>) -> ClientBuilder {
+        self.options.request_timeout = timeout;
+        self
+    }
+
+    /// # options() 函数
+    ///
+    /// 返回设置好的连接选项，可以传给`Pool::with_options`
+    pub fn options(&self) -> &ConnectOptions {
+        &self.options
+    }
+
+    /// # build() 函数
+    ///
+    /// 使用设置好的选项与远程服务器建立连接，addr是redis://形式的URL时，URL中的选项补充没有设置的选项
+    pub async fn build<T: IntoConnectAddr>(self, addr: T) -> crate::Result<Client> {
+        Client::connect_with_options(addr, self.options).await
+    }
+}
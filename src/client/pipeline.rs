@@ -0,0 +1,143 @@
+//! Pipeline结构体，批量发送命令
+
+use bytes::Bytes;
+use tokio::time::Duration;
+use tracing::{debug, instrument};
+
+use super::{Client, Frame};
+use crate::cmd::{
+    del::Del,
+    expire::{Expire, ExpireUnit},
+    get::Get,
+    ping::Ping,
+    publish::Publish,
+    set::Set,
+};
+
+/// # Pipeline 结构体
+///
+/// 先把命令放入队列，再通过execute()一次性写入socket并只flush一次，最后按顺序读取所有响应
+///
+/// 服务器对某个命令回复错误时，对应位置是一个Frame::Error，不会影响之后命令的响应
+#[derive(Debug, Default)]
+pub struct Pipeline {
+    /// 已经编码好的请求帧
+    frames: Vec<Frame>,
+}
+
+impl Pipeline {
+    /// # new() 函数
+    ///
+    /// 创建一个空的Pipeline
+    pub fn new() -> Pipeline {
+        Pipeline::default()
+    }
+
+    /// # len() 函数
+    ///
+    /// 返回队列中的命令个数
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// # is_empty() 函数
+    ///
+    /// 队列中是否没有命令
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// # ping() 函数
+    ///
+    /// 将PING命令放入队列
+    pub fn ping(&mut self, msg: Option<Bytes>) -> &mut Pipeline {
+        self.push(Ping::new(msg).code_ping_into_frame())
+    }
+
+    /// # get() 函数
+    ///
+    /// 将GET命令放入队列
+    pub fn get(&mut self, key: &str) -> &mut Pipeline {
+        self.push(Get::new(key).code_get_into_frame())
+    }
+
+    /// # set() 函数
+    ///
+    /// 将SET命令放入队列
+    pub fn set(&mut self, key: &str, value: Bytes) -> &mut Pipeline {
+        self.push(Set::new(key, value, None).code_set_into_frame())
+    }
+
+    /// # set_with_expires() 函数
+    ///
+    /// 将带有过期时间的SET命令放入队列
+    pub fn set_with_expires(
+        &mut self,
+        key: &str,
+        value: Bytes,
+        expiration: Duration,
+    ) -> &mut Pipeline {
+        self.push(Set::new(key, value, Some(expiration)).code_set_into_frame())
+    }
+
+    /// # del() 函数
+    ///
+    /// 将DEL命令放入队列
+    pub fn del(&mut self, key: &str) -> &mut Pipeline {
+        self.push(Del::new(key).code_del_into_frame())
+    }
+
+    /// # expire() 函数
+    ///
+    /// 将以秒为单位的EXPIRE命令放入队列
+    pub fn expire(&mut self, key: &str, seconds: u64) -> &mut Pipeline {
+        self.push(Expire::new(ExpireUnit::Seconds, key, seconds, vec![]).code_expire_into_frame())
+    }
+
+    /// # publish() 函数
+    ///
+    /// 将PUBLISH命令放入队列
+    pub fn publish(&mut self, channel: &str, message: Bytes) -> &mut Pipeline {
+        self.push(Publish::new(channel, message).code_publish_into_frame())
+    }
+
+    /// # command() 函数
+    ///
+    /// 将任意命令放入队列，args的第一个元素是命令名称
+    pub fn command(&mut self, args: &[&[u8]]) -> &mut Pipeline {
+        let mut frame = Frame::array();
+        for arg in args {
+            frame.push_bulk(Bytes::copy_from_slice(arg));
+        }
+        self.push(frame)
+    }
+
+    /// # execute() 函数
+    ///
+    /// 在client上执行队列中的所有命令，按照入队的顺序返回每个命令的响应，错误回复以Frame::Error返回。
+    /// 只有写入或读取socket失败（包括请求超时）时才返回Err，此时连接上可能还有没有读取的响应
+    #[instrument(skip(self, client), fields(commands = self.frames.len()))]
+    pub async fn execute(self, client: &mut Client) -> crate::Result<Vec<Frame>> {
+        if self.frames.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        client.send_pipeline(&self.frames).await?;
+
+        let mut replies = Vec::with_capacity(self.frames.len());
+        for _ in 0..self.frames.len() {
+            replies.push(client.read_reply().await?);
+        }
+        debug!(?replies);
+
+        Ok(replies)
+    }
+
+    /// # push() 函数
+    ///
+    /// 将一个编码好的命令放入队列
+    fn push(&mut self, frame: Frame) -> &mut Pipeline {
+        self.frames.push(frame);
+        self
+    }
+}
@@ -0,0 +1,206 @@
+//! Pool结构体，客户端连接池
+
+use std::{
+    io::{Error, ErrorKind},
+    net::SocketAddr,
+    ops::{Deref, DerefMut},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+use tokio::{
+    sync::{OwnedSemaphorePermit, Semaphore},
+    time::{self, Duration},
+};
+use tracing::debug;
+
+use super::{Client, ConnectOptions};
+use crate::RustisError;
+
+/// 连接池耗尽时默认等待空闲连接的时间
+const DEFAULT_WAIT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// # Pool 结构体
+///
+/// 客户端连接池，最多同时打开max_size个连接，可以clone之后在多个任务之间共享
+///
+/// 通过get()借出的连接在PooledClient被drop时归还，再次借出之前用PING检查连接是否可用，不可用的连接会被丢弃。
+/// 请求的future在读到响应之前被取消时，连接上还有没有读取的响应，这样的连接在归还时直接关闭，不会回到连接池。
+/// 连接归还时不会重置SELECT、READONLY等连接状态，借用者修改了这些状态时应该在归还之前恢复
+#[derive(Clone)]
+pub struct Pool {
+    inner: Arc<PoolInner>,
+}
+
+/// # PoolInner 结构体
+///
+/// 所有Pool共享的状态
+struct PoolInner {
+    /// 服务器地址
+    addr: SocketAddr,
+    /// 建立连接时的选项
+    options: ConnectOptions,
+    /// 连接池的容量
+    max_size: usize,
+    /// 连接池耗尽时等待空闲连接的时间
+    wait_timeout: Duration,
+    /// 每个借出的连接持有一个许可，许可的数量限制了同时打开的连接数
+    permits: Arc<Semaphore>,
+    /// 空闲的连接
+    idle: Mutex<Vec<Client>>,
+    /// 连接池建立的连接总数
+    created: AtomicU64,
+}
+
+/// # PoolStats 结构体
+///
+/// 连接池的计数
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PoolStats {
+    /// 连接池建立的连接总数，包括已经因为不可用而被丢弃的连接
+    pub created: u64,
+    /// 空闲的连接数
+    pub idle: usize,
+    /// 已经借出的连接数
+    pub in_use: usize,
+}
+
+impl Pool {
+    /// # new() 函数
+    ///
+    /// 创建一个最多打开max_size个连接的连接池，连接在第一次需要时才建立
+    pub fn new(addr: SocketAddr, max_size: usize) -> Pool {
+        Pool::with_options(addr, max_size, ConnectOptions::default())
+    }
+
+    /// # with_options() 函数
+    ///
+    /// 创建一个使用指定连接选项的连接池
+    pub fn with_options(addr: SocketAddr, max_size: usize, options: ConnectOptions) -> Pool {
+        let max_size = max_size.max(1);
+
+        Pool {
+            inner: Arc::new(PoolInner {
+                addr,
+                options,
+                max_size,
+                wait_timeout: DEFAULT_WAIT_TIMEOUT,
+                permits: Arc::new(Semaphore::new(max_size)),
+                idle: Mutex::new(Vec::new()),
+                created: AtomicU64::new(0),
+            }),
+        }
+    }
+
+    /// # with_wait_timeout() 函数
+    ///
+    /// 设置连接池耗尽时等待空闲连接的时间，只能在clone之前调用
+    ///
+    /// # panic
+    ///
+    /// 连接池已经被clone时会panic
+    pub fn with_wait_timeout(mut self, timeout: Duration) -> Pool {
+        Arc::get_mut(&mut self.inner)
+            .expect("with_wait_timeout() must be called before the pool is shared")
+            .wait_timeout = timeout;
+        self
+    }
+
+    /// # get() 函数
+    ///
+    /// 借出一个连接：优先复用通过了PING检查的空闲连接，没有空闲连接时建立新的连接。
+    /// 所有连接都已借出时最多等待wait_timeout，超时返回ErrorKind::TimedOut的IO错误
+    pub async fn get(&self) -> crate::Result<PooledClient> {
+        let permit = time::timeout(
+            self.inner.wait_timeout,
+            self.inner.permits.clone().acquire_owned(),
+        )
+        .await
+        .map_err(|_| {
+            RustisError::Io(Error::new(
+                ErrorKind::TimedOut,
+                "timed out waiting for a pooled connection",
+            ))
+        })?
+        .expect("pool semaphore is never closed");
+
+        loop {
+            let client = self.inner.idle.lock().unwrap().pop();
+            let Some(mut client) = client else {
+                break;
+            };
+
+            match client.ping(None).await {
+                Ok(_) => return Ok(PooledClient::new(client, self.inner.clone(), permit)),
+                Err(err) => debug!(cause = %err, "discarding broken pooled connection"),
+            }
+        }
+
+        let client =
+            Client::connect_with_options(self.inner.addr, self.inner.options.clone()).await?;
+        self.inner.created.fetch_add(1, Ordering::SeqCst);
+
+        Ok(PooledClient::new(client, self.inner.clone(), permit))
+    }
+
+    /// # stats() 函数
+    ///
+    /// 返回连接池的计数
+    pub fn stats(&self) -> PoolStats {
+        PoolStats {
+            created: self.inner.created.load(Ordering::SeqCst),
+            idle: self.inner.idle.lock().unwrap().len(),
+            in_use: self.inner.max_size - self.inner.permits.available_permits(),
+        }
+    }
+}
+
+/// # PooledClient 结构体
+///
+/// 从连接池借出的连接，可以像Client一样使用，被drop时连接回到连接池，有请求被取消时连接被关闭
+pub struct PooledClient {
+    /// 借出的连接，只有drop时才会被取走
+    client: Option<Client>,
+    /// 连接归还的连接池
+    pool: Arc<PoolInner>,
+    /// 借出期间持有的许可，在连接回到空闲列表之后才释放
+    _permit: OwnedSemaphorePermit,
+}
+
+impl PooledClient {
+    fn new(client: Client, pool: Arc<PoolInner>, permit: OwnedSemaphorePermit) -> Self {
+        Self {
+            client: Some(client),
+            pool,
+            _permit: permit,
+        }
+    }
+}
+
+impl Deref for PooledClient {
+    type Target = Client;
+
+    fn deref(&self) -> &Client {
+        self.client.as_ref().unwrap()
+    }
+}
+
+impl DerefMut for PooledClient {
+    fn deref_mut(&mut self) -> &mut Client {
+        self.client.as_mut().unwrap()
+    }
+}
+
+impl Drop for PooledClient {
+    fn drop(&mut self) {
+        if let Some(client) = self.client.take() {
+            // 还有没有读取的响应时，下一个借用者会读到上一个请求的响应
+            if !client.is_idle() {
+                debug!("discarding pooled connection with an unread reply");
+                return;
+            }
+            self.pool.idle.lock().unwrap().push(client);
+        }
+    }
+}
@@ -0,0 +1,239 @@
+//! SharedClient结构体，可以在多个任务之间共享的客户端
+
+use std::{
+    collections::VecDeque,
+    io::{Error, ErrorKind},
+};
+
+use bytes::Bytes;
+use tokio::sync::{mpsc, oneshot};
+use tracing::{debug, instrument};
+
+use super::{Client, Frame, IntoConnectAddr};
+use crate::{
+    cmd::{del::Del, get::Get, ping::Ping, publish::Publish, set::Set},
+    RustisError,
+};
+
+/// 等待后台任务发送的请求数量，队列满时调用者等待，形成背压
+const SHARED_CLIENT_QUEUE: usize = 1024;
+
+/// 后台任务一次最多从队列中取出多少个请求写入写缓冲区再flush
+const MAX_WRITE_BATCH: usize = 128;
+
+/// 请求帧，以及用于返回响应的oneshot
+type Request = (Frame, oneshot::Sender<crate::Result<Frame>>);
+
+/// # SharedClient 结构体
+///
+/// 可以clone之后在多个任务之间共享的客户端，所有的clone共用一个连接
+///
+/// 连接由一个后台任务独占：请求通过有界的channel发给后台任务，后台任务把请求依次写入socket，
+/// 同时按照发送的顺序把响应交给对应的调用者，多个任务的请求在同一个连接上以pipeline的方式执行。
+/// 所有的SharedClient都被drop之后，后台任务等待已经发出的请求的响应，然后关闭连接
+///
+/// 订阅、MONITOR等会改变连接模式的命令不能通过SharedClient执行
+#[derive(Clone)]
+pub struct SharedClient {
+    /// 向后台任务发送请求
+    requests: mpsc::Sender<Request>,
+}
+
+impl SharedClient {
+    /// # connect() 函数
+    ///
+    /// 与远程服务器建立连接，并启动后台任务
+    pub async fn connect<T: IntoConnectAddr>(addr: T) -> crate::Result<SharedClient> {
+        Ok(SharedClient::new(Client::connect(addr).await?))
+    }
+
+    /// # new() 函数
+    ///
+    /// 将一个已经建立的连接交给后台任务，之前认证和协商的协议版本保持不变
+    pub fn new(client: Client) -> SharedClient {
+        let (requests, rx) = mpsc::channel(SHARED_CLIENT_QUEUE);
+        tokio::spawn(run(client, rx));
+
+        SharedClient { requests }
+    }
+
+    /// # request() 函数
+    ///
+    /// 发送一个请求帧并等待响应，错误回复转换为Err
+    async fn request(&self, frame: Frame) -> crate::Result<Frame> {
+        debug!(request = ?frame);
+
+        let (tx, rx) = oneshot::channel();
+        self.requests
+            .send((frame, tx))
+            .await
+            .map_err(|_| closed())?;
+
+        match rx.await.map_err(|_| closed())?? {
+            Frame::Error(msg) => Err(RustisError::from_error_message(msg).into()),
+            frame => Ok(frame),
+        }
+    }
+
+    /// # ping() 函数
+    ///
+    /// 发送PING，没有提供参数时返回PONG
+    #[instrument(skip(self))]
+    pub async fn ping(&self, msg: Option<Bytes>) -> crate::Result<Bytes> {
+        match self.request(Ping::new(msg).code_ping_into_frame()).await? {
+            Frame::Simple(value) => Ok(value.into()),
+            Frame::Bulk(value) => Ok(value),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// # get() 函数
+    ///
+    /// 获取key的值
+    #[instrument(skip(self))]
+    pub async fn get(&self, key: &str) -> crate::Result<Option<Bytes>> {
+        match self.request(Get::new(key).code_get_into_frame()).await? {
+            Frame::Simple(value) => Ok(Some(value.into())),
+            Frame::Bulk(value) => Ok(Some(value)),
+            Frame::Null => Ok(None),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// # set() 函数
+    ///
+    /// 设置key的值
+    #[instrument(skip(self))]
+    pub async fn set(&self, key: &str, value: Bytes) -> crate::Result<()> {
+        match self
+            .request(Set::new(key, value, None).code_set_into_frame())
+            .await?
+        {
+            Frame::Simple(response) if response == "OK" => Ok(()),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// # del() 函数
+    ///
+    /// 删除key
+    #[instrument(skip(self))]
+    pub async fn del(&self, key: &str) -> crate::Result<()> {
+        match self.request(Del::new(key).code_del_into_frame()).await? {
+            Frame::Simple(response) if response.to_uppercase() == "OK" => Ok(()),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// # publish() 函数
+    ///
+    /// 将消息发布到channel，返回收到消息的订阅者数量
+    #[instrument(skip(self))]
+    pub async fn publish(&self, channel: &str, message: Bytes) -> crate::Result<u64> {
+        match self
+            .request(Publish::new(channel, message).code_publish_into_frame())
+            .await?
+        {
+            frame @ Frame::Integer(n) => u64::try_from(n).map_err(|_| frame.to_error()),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// # command() 函数
+    ///
+    /// 发送任意命令，args的第一个元素是命令名称，返回服务器回复的原始帧
+    #[instrument(skip(self, args))]
+    pub async fn command(&self, args: &[&[u8]]) -> crate::Result<Frame> {
+        let mut frame = Frame::array();
+        for arg in args {
+            frame.push_bulk(Bytes::copy_from_slice(arg));
+        }
+
+        self.request(frame).await
+    }
+}
+
+/// # closed() 函数
+///
+/// 后台任务已经退出（连接断开）时返回给调用者的错误
+fn closed() -> crate::Error {
+    RustisError::Io(Error::new(ErrorKind::BrokenPipe, "共享连接已经关闭")).into()
+}
+
+/// # run() 函数
+///
+/// 后台任务：把收到的请求写入socket，按顺序把响应交给等待的调用者。
+/// 所有的SharedClient都被drop并且已经发出的请求都收到响应之后退出，连接出错时让所有等待的调用者失败并退出
+async fn run(mut client: Client, mut requests: mpsc::Receiver<Request>) {
+    // 已经写入socket、等待响应的调用者，服务器按照请求的顺序回复
+    let mut pending: VecDeque<oneshot::Sender<crate::Result<Frame>>> = VecDeque::new();
+    let mut closed = false;
+
+    while !(closed && pending.is_empty()) {
+        tokio::select! {
+            request = requests.recv(), if !closed => {
+                let Some(request) = request else {
+                    closed = true;
+                    continue;
+                };
+
+                // 把已经在队列中的请求一起写入，只flush一次
+                let mut batch = vec![request];
+                while batch.len() < MAX_WRITE_BATCH {
+                    match requests.try_recv() {
+                        Ok(request) => batch.push(request),
+                        Err(_) => break,
+                    }
+                }
+
+                let mut result = Ok(());
+                for (frame, _) in &batch {
+                    result = client.connection.write_frame_nowait(frame).await;
+                    if result.is_err() {
+                        break;
+                    }
+                }
+                if result.is_ok() {
+                    result = client.connection.flush().await;
+                }
+
+                pending.extend(batch.into_iter().map(|(_, reply)| reply));
+                if let Err(err) = result {
+                    debug!(cause = %err, "shared connection write failed");
+                    fail_all(&mut pending, &err.to_string());
+                    return;
+                }
+            }
+            response = client.connection.read_frame(), if !pending.is_empty() => {
+                match response {
+                    Ok(Some(frame)) => {
+                        debug!(response = ?frame);
+                        if let Some(reply) = pending.pop_front() {
+                            // 调用者可能已经放弃等待，忽略发送失败
+                            let _ = reply.send(Ok(frame));
+                        }
+                    }
+                    Ok(None) => {
+                        fail_all(&mut pending, "连接被服务器重置");
+                        return;
+                    }
+                    Err(err) => {
+                        debug!(cause = %err, "shared connection read failed");
+                        fail_all(&mut pending, &err.to_string());
+                        return;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// # fail_all() 函数
+///
+/// 连接出错，让所有等待响应的调用者返回错误
+fn fail_all(pending: &mut VecDeque<oneshot::Sender<crate::Result<Frame>>>, msg: &str) {
+    for reply in pending.drain(..) {
+        let error = Error::new(ErrorKind::ConnectionReset, msg.to_string());
+        let _ = reply.send(Err(RustisError::Io(error).into()));
+    }
+}
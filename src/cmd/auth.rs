@@ -0,0 +1,118 @@
+//! auth命令的实现
+
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+use crate::{
+    networking::{
+        connection::Connection,
+        frame::Frame,
+        parse::{Parse, ParseError},
+    },
+    persistence::database::Database,
+    server::session::Session,
+};
+
+/// # Auth 结构体
+///
+/// 使用密码对当前连接进行认证，服务器配置了requirepass时，认证前只能执行AUTH命令
+///
+/// # 语法
+///
+/// - AUTH password
+/// - AUTH username password（目前只支持default用户）
+#[derive(Debug)]
+pub struct Auth {
+    /// 用户名
+    username: Option<String>,
+    /// 密码
+    password: String,
+}
+
+impl Auth {
+    /// # new() 函数
+    ///
+    /// 创建一个新的Auth命令
+    pub fn new(username: Option<String>, password: impl ToString) -> Auth {
+        Auth {
+            username,
+            password: password.to_string(),
+        }
+    }
+
+    /// # decode_auth_from_frame() 函数
+    ///
+    /// 将帧解码为auth命令
+    pub(crate) fn decode_auth_from_frame(parse: &mut Parse) -> crate::Result<Auth> {
+        let first = parse.next_string()?;
+
+        match parse.next_string() {
+            Ok(password) => Ok(Auth::new(Some(first), password)),
+            Err(ParseError::EndOfStream) => Ok(Auth::new(None, first)),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// # code_auth_into_frame() 函数
+    ///
+    /// 将auth命令编码为帧
+    pub(crate) fn code_auth_into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("auth".as_bytes()));
+        if let Some(username) = self.username {
+            frame.push_bulk(Bytes::from(username.into_bytes()));
+        }
+        frame.push_bulk(Bytes::from(self.password.into_bytes()));
+        frame
+    }
+
+    /// # apply() 函数
+    ///
+    /// 应用auth命令，并将响应写入到Connection实例
+    #[instrument(skip(self, db, connection, session))]
+    pub(crate) async fn apply(
+        self,
+        db: &Database,
+        connection: &mut Connection,
+        session: &mut Session,
+    ) -> crate::Result<()> {
+        let response = match db.requirepass() {
+            None => Frame::Error(
+                "ERR AUTH <password> called without any password configured for the default user. Are you sure your configuration is correct?"
+                    .to_string(),
+            ),
+            Some(requirepass) => {
+                match check_password(&requirepass, self.username.as_deref(), &self.password) {
+                    Ok(()) => {
+                        session.authenticated = true;
+                        Frame::Simple("OK".to_string())
+                    }
+                    Err(response) => response,
+                }
+            }
+        };
+
+        debug!(?response);
+
+        connection.write_frame_nowait(&response).await?;
+
+        Ok(())
+    }
+}
+
+/// # check_password() 函数
+///
+/// 检查用户名和密码是否与requirepass匹配，目前只支持default用户，不匹配时返回回复给客户端的错误帧
+pub(crate) fn check_password(
+    requirepass: &str,
+    username: Option<&str>,
+    password: &str,
+) -> Result<(), Frame> {
+    if username.is_none_or(|name| name == "default") && password == requirepass {
+        Ok(())
+    } else {
+        Err(Frame::Error(
+            "WRONGPASS invalid username-password pair or user is disabled.".to_string(),
+        ))
+    }
+}
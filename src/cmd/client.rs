@@ -0,0 +1,87 @@
+//! client命令的实现
+
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+use crate::networking::{connection::Connection, frame::Frame, parse::Parse};
+
+/// # Client 枚举
+///
+/// 查看或管理客户端连接
+///
+/// # 语法
+///
+/// - CLIENT ID：返回当前连接的唯一标识
+/// - CLIENT SETNAME name：设置当前连接的名称，name为空字符串时清除名称
+/// - CLIENT GETNAME：返回当前连接的名称，没有设置时返回nil
+#[derive(Debug)]
+pub enum Client {
+    /// 返回当前连接的唯一标识
+    Id,
+    /// 设置当前连接的名称
+    SetName(String),
+    /// 返回当前连接的名称
+    GetName,
+}
+
+impl Client {
+    /// # decode_client_from_frame() 函数
+    ///
+    /// 将帧解码为client命令
+    pub(crate) fn decode_client_from_frame(parse: &mut Parse) -> crate::Result<Client> {
+        let subcommand = parse.next_string()?.to_lowercase();
+
+        match &subcommand[..] {
+            "id" => Ok(Client::Id),
+            "setname" => Ok(Client::SetName(parse.next_string()?)),
+            "getname" => Ok(Client::GetName),
+            _ => Err(format!("ERR unknown subcommand '{}'", subcommand).into()),
+        }
+    }
+
+    /// # code_client_into_frame() 函数
+    ///
+    /// 将client命令编码为帧
+    pub(crate) fn code_client_into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("client".as_bytes()));
+        match self {
+            Client::Id => frame.push_bulk(Bytes::from("id".as_bytes())),
+            Client::SetName(name) => {
+                frame.push_bulk(Bytes::from("setname".as_bytes()));
+                frame.push_bulk(Bytes::from(name));
+            }
+            Client::GetName => frame.push_bulk(Bytes::from("getname".as_bytes())),
+        }
+        frame
+    }
+
+    /// # apply() 函数
+    ///
+    /// 应用client命令，并将响应写入到Connection实例
+    #[instrument(skip(self, connection))]
+    pub(crate) async fn apply(self, connection: &mut Connection) -> crate::Result<()> {
+        let response = match self {
+            Client::Id => Frame::Integer(connection.id() as i64),
+            // 与redis一样，名称中不能包含空格、换行等特殊字符，否则CLIENT LIST的输出无法解析
+            Client::SetName(name) if name.chars().any(|c| !c.is_ascii_graphic()) => Frame::Error(
+                "ERR Client names cannot contain spaces, newlines or special characters."
+                    .to_string(),
+            ),
+            Client::SetName(name) => {
+                connection.set_name((!name.is_empty()).then_some(name));
+                Frame::Simple("OK".to_string())
+            }
+            Client::GetName => match connection.name() {
+                Some(name) => Frame::Bulk(Bytes::from(name.to_string())),
+                None => Frame::Null,
+            },
+        };
+
+        debug!(?response);
+
+        connection.write_frame_nowait(&response).await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,169 @@
+//! command命令的实现
+
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+use crate::{
+    cmd::table,
+    networking::{
+        connection::Connection,
+        frame::Frame,
+        parse::{Parse, ParseError},
+    },
+};
+
+/// # CommandCmd 枚举
+///
+/// 查看命令的元信息，代理和集群工具用它来确定请求中哪些参数是键
+///
+/// # 语法
+///
+/// - COMMAND：返回所有命令的元信息
+/// - COMMAND INFO [command ...]：返回指定命令的元信息，未知的命令为nil
+/// - COMMAND COUNT：返回命令的个数
+/// - COMMAND GETKEYS command [arg ...]：按照命令表中键的位置返回完整命令中的键
+#[derive(Debug)]
+pub enum CommandCmd {
+    /// COMMAND INFO [command ...]，没有指定命令时返回所有命令
+    Info(Vec<String>),
+    /// COMMAND COUNT
+    Count,
+    /// COMMAND GETKEYS command [arg ...]，保存完整的命令，包括命令名称
+    GetKeys(Vec<Bytes>),
+}
+
+impl CommandCmd {
+    /// # decode_command_from_frame() 函数
+    ///
+    /// 将帧解码为command命令
+    pub(crate) fn decode_command_from_frame(parse: &mut Parse) -> crate::Result<CommandCmd> {
+        let subcommand = match parse.next_string() {
+            Ok(subcommand) => subcommand.to_lowercase(),
+            Err(ParseError::EndOfStream) => return Ok(CommandCmd::Info(Vec::new())),
+            Err(err) => return Err(err.into()),
+        };
+        match subcommand.as_str() {
+            "info" => {
+                let mut names = Vec::new();
+                loop {
+                    match parse.next_string() {
+                        Ok(name) => names.push(name),
+                        Err(ParseError::EndOfStream) => break,
+                        Err(err) => return Err(err.into()),
+                    }
+                }
+                Ok(CommandCmd::Info(names))
+            }
+            "count" => Ok(CommandCmd::Count),
+            "getkeys" => {
+                let mut args = vec![parse.next_bytes()?];
+                loop {
+                    match parse.next_bytes() {
+                        Ok(arg) => args.push(arg),
+                        Err(ParseError::EndOfStream) => break,
+                        Err(err) => return Err(err.into()),
+                    }
+                }
+                Ok(CommandCmd::GetKeys(args))
+            }
+            _ => Err(format!("ERR unknown subcommand '{}'", subcommand).into()),
+        }
+    }
+
+    /// # code_command_into_frame() 函数
+    ///
+    /// 将command命令编码为帧
+    pub(crate) fn code_command_into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("command".as_bytes()));
+        match self {
+            CommandCmd::Info(names) => {
+                frame.push_bulk(Bytes::from("info".as_bytes()));
+                for name in names {
+                    frame.push_bulk(Bytes::from(name.into_bytes()));
+                }
+            }
+            CommandCmd::Count => frame.push_bulk(Bytes::from("count".as_bytes())),
+            CommandCmd::GetKeys(args) => {
+                frame.push_bulk(Bytes::from("getkeys".as_bytes()));
+                for arg in args {
+                    frame.push_bulk(arg);
+                }
+            }
+        }
+        frame
+    }
+
+    /// # apply() 函数
+    ///
+    /// 应用command命令，并将响应写入到Connection实例
+    #[instrument(skip(self, connection))]
+    pub(crate) async fn apply(self, connection: &mut Connection) -> crate::Result<()> {
+        let response = match self {
+            CommandCmd::Info(names) if names.is_empty() => {
+                Frame::Array(table::commands().map(command_info).collect())
+            }
+            CommandCmd::Info(names) => Frame::Array(
+                names
+                    .iter()
+                    .map(|name| match table::lookup(&name.to_lowercase()) {
+                        Some(spec) => command_info(spec),
+                        None => Frame::Null,
+                    })
+                    .collect(),
+            ),
+            CommandCmd::Count => Frame::Integer(table::commands().count() as i64),
+            CommandCmd::GetKeys(args) => get_keys(&args),
+        };
+        debug!(?response);
+
+        connection.write_frame_nowait(&response).await?;
+
+        Ok(())
+    }
+}
+
+/// # command_info() 函数
+///
+/// 按照redis的COMMAND INFO格式返回命令的元信息：名称、arity、标志、第一个键、最后一个键和键的步长
+fn command_info(spec: &table::CommandSpec) -> Frame {
+    let flags = spec
+        .flags
+        .iter()
+        .map(|flag| Frame::Simple(flag.to_string()))
+        .collect();
+
+    Frame::Array(vec![
+        Frame::Bulk(Bytes::from_static(spec.name.as_bytes())),
+        Frame::Integer(spec.arity as i64),
+        Frame::Array(flags),
+        Frame::Integer(spec.first_key as i64),
+        Frame::Integer(spec.last_key as i64),
+        Frame::Integer(spec.key_step as i64),
+    ])
+}
+
+/// # get_keys() 函数
+///
+/// 返回完整命令中的键，命令未知、参数个数不对或者命令没有键时返回错误帧
+fn get_keys(args: &[Bytes]) -> Frame {
+    let name = String::from_utf8_lossy(&args[0]).to_lowercase();
+    let Some(spec) = table::lookup(&name) else {
+        return Frame::Error("ERR Invalid command specified".to_string());
+    };
+
+    if !spec.check_arity(args.len()) {
+        return Frame::Error("ERR Invalid number of arguments specified for command".to_string());
+    }
+
+    let keys = spec.key_indexes(args.len());
+    if keys.is_empty() {
+        return Frame::Error("ERR The command has no key arguments".to_string());
+    }
+
+    Frame::Array(
+        keys.into_iter()
+            .map(|index| Frame::Bulk(args[index].clone()))
+            .collect(),
+    )
+}
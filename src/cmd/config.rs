@@ -0,0 +1,139 @@
+//! config命令的实现
+
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+use crate::{
+    networking::{
+        connection::Connection,
+        frame::Frame,
+        parse::{Parse, ParseError},
+    },
+    persistence::database::Database,
+};
+
+use super::util::glob_match;
+
+/// # Config 枚举
+///
+/// 在运行时获取或修改服务器配置
+///
+/// # 语法
+///
+/// - CONFIG GET parameter [parameter ...]
+/// - CONFIG SET parameter value
+/// - CONFIG REWRITE：把当前配置写回启动时读取的配置文件
+#[derive(Debug)]
+pub enum Config {
+    /// 获取匹配glob模式的参数
+    Get(Vec<String>),
+    /// 修改一个参数
+    Set(String, String),
+    /// 把当前配置写回配置文件
+    Rewrite,
+}
+
+impl Config {
+    /// # decode_config_from_frame() 函数
+    ///
+    /// 将帧解码为config命令
+    pub(crate) fn decode_config_from_frame(parse: &mut Parse) -> crate::Result<Config> {
+        let subcommand = parse.next_string()?.to_lowercase();
+
+        match &subcommand[..] {
+            "get" => {
+                let mut patterns = vec![parse.next_string()?];
+                loop {
+                    match parse.next_string() {
+                        Ok(pattern) => patterns.push(pattern),
+                        Err(ParseError::EndOfStream) => break,
+                        Err(err) => return Err(err.into()),
+                    }
+                }
+                Ok(Config::Get(patterns))
+            }
+            "set" => {
+                let name = parse.next_string()?.to_lowercase();
+                let value = parse.next_string()?;
+                Ok(Config::Set(name, value))
+            }
+            "rewrite" => Ok(Config::Rewrite),
+            _ => Err(format!("ERR unknown subcommand '{}'", subcommand).into()),
+        }
+    }
+
+    /// # code_config_into_frame() 函数
+    ///
+    /// 将config命令编码为帧
+    pub(crate) fn code_config_into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("config".as_bytes()));
+        match self {
+            Config::Get(patterns) => {
+                frame.push_bulk(Bytes::from("get".as_bytes()));
+                for pattern in patterns {
+                    frame.push_bulk(Bytes::from(pattern.into_bytes()));
+                }
+            }
+            Config::Set(name, value) => {
+                frame.push_bulk(Bytes::from("set".as_bytes()));
+                frame.push_bulk(Bytes::from(name.into_bytes()));
+                frame.push_bulk(Bytes::from(value.into_bytes()));
+            }
+            Config::Rewrite => frame.push_bulk(Bytes::from("rewrite".as_bytes())),
+        }
+        frame
+    }
+
+    /// # apply() 函数
+    ///
+    /// 应用config命令，并将响应写入到Connection实例
+    #[instrument(skip(self, db, connection))]
+    pub(crate) async fn apply(
+        self,
+        db: &Database,
+        connection: &mut Connection,
+    ) -> crate::Result<()> {
+        let response = match self {
+            // RESP3下回复map，RESP2下降级为名称和值交替的数组
+            Config::Get(patterns) => Frame::Map(
+                db.config()
+                    .parameters()
+                    .into_iter()
+                    .filter(|(name, _)| {
+                        patterns.iter().any(|pattern| {
+                            glob_match(pattern.to_lowercase().as_bytes(), name.as_bytes())
+                        })
+                    })
+                    .map(|(name, value)| {
+                        (
+                            Frame::Bulk(Bytes::from(name)),
+                            Frame::Bulk(Bytes::from(value)),
+                        )
+                    })
+                    .collect(),
+            ),
+            Config::Set(name, value) => {
+                match db.update_config(|config| config.set_parameter(&name, &value)) {
+                    Ok(()) => Frame::Simple("OK".to_string()),
+                    Err(msg) => Frame::Error(msg),
+                }
+            }
+            // 在锁外写文件，写入期间的CONFIG SET不会被阻塞；文件IO是阻塞的，放到专门的线程池中执行
+            Config::Rewrite => {
+                let config = db.config();
+                match tokio::task::spawn_blocking(move || config.rewrite_config_file()).await {
+                    Ok(Ok(())) => Frame::Simple("OK".to_string()),
+                    Ok(Err(msg)) => Frame::Error(msg),
+                    Err(err) => Frame::Error(format!("ERR Rewriting config file: {}", err)),
+                }
+            }
+        };
+
+        debug!(?response);
+
+        connection.write_frame_nowait(&response).await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,114 @@
+//! debug命令的实现
+
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+use crate::{
+    networking::{connection::Connection, frame::Frame, parse::Parse},
+    persistence::database::Database,
+};
+
+/// DEBUG DUMPALL最多返回的键值对数量，避免一次回复过大
+pub(crate) const DUMPALL_MAX_ENTRIES: usize = 10000;
+
+/// # Debug 枚举
+///
+/// 调试用的非标准命令，需要在配置中开启enable-debug-command才能执行
+///
+/// # 语法
+///
+/// - DEBUG DUMPALL：以[key, value, key, value, ...]的数组返回当前数据库中所有未过期的键值对，
+///   按键排序，最多返回DUMPALL_MAX_ENTRIES个
+/// - DEBUG RELOAD：把数据按照RDB格式序列化到内存中再重新加载，不会读写RDB文件，用于检查数据经过持久化之后是否保持不变
+/// - DEBUG OBJECT key：以一行`字段:值`的形式返回键的内部信息，ttl是剩余的毫秒数，没有过期时间时为-1
+#[derive(Debug)]
+pub enum Debug {
+    /// 返回当前数据库中的所有键值对
+    DumpAll,
+    /// 序列化并重新加载数据
+    Reload,
+    /// 返回键的内部信息
+    Object(String),
+}
+
+impl Debug {
+    /// # decode_debug_from_frame() 函数
+    ///
+    /// 将帧解码为debug命令
+    pub(crate) fn decode_debug_from_frame(parse: &mut Parse) -> crate::Result<Debug> {
+        let subcommand = parse.next_string()?.to_lowercase();
+
+        match &subcommand[..] {
+            "dumpall" => Ok(Debug::DumpAll),
+            "reload" => Ok(Debug::Reload),
+            "object" => Ok(Debug::Object(parse.next_string()?)),
+            _ => Err(format!("ERR unknown subcommand '{}'", subcommand).into()),
+        }
+    }
+
+    /// # code_debug_into_frame() 函数
+    ///
+    /// 将debug命令编码为帧
+    pub(crate) fn code_debug_into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("debug".as_bytes()));
+        match self {
+            Debug::DumpAll => frame.push_bulk(Bytes::from("dumpall".as_bytes())),
+            Debug::Reload => frame.push_bulk(Bytes::from("reload".as_bytes())),
+            Debug::Object(key) => {
+                frame.push_bulk(Bytes::from("object".as_bytes()));
+                frame.push_bulk(Bytes::from(key.into_bytes()));
+            }
+        }
+        frame
+    }
+
+    /// # apply() 函数
+    ///
+    /// 应用debug命令，并将响应写入到Connection实例
+    #[instrument(skip(self, db, connection))]
+    pub(crate) async fn apply(
+        self,
+        db: &Database,
+        connection: &mut Connection,
+    ) -> crate::Result<()> {
+        let response = if !db.config().enable_debug_command {
+            Frame::Error(
+                "ERR DEBUG command not allowed. Set enable-debug-command in the configuration file and restart the server."
+                    .to_string(),
+            )
+        } else {
+            match self {
+                Debug::DumpAll => {
+                    let mut response = Frame::array();
+                    for (key, value) in db.dump_all(DUMPALL_MAX_ENTRIES) {
+                        response.push_bulk(Bytes::from(key));
+                        response.push_bulk(value);
+                    }
+                    response
+                }
+                Debug::Reload => match db.debug_reload() {
+                    Ok(()) => Frame::Simple("OK".to_string()),
+                    Err(err) => Frame::Error(format!("ERR Error trying to reload the RDB: {}", err)),
+                },
+                Debug::Object(key) => match db.debug_object(&key) {
+                    Some(info) => Frame::Simple(format!(
+                        "encoding:raw serializedlength:{} lru_seconds_idle:{} freq:{} version:{} ttl:{}",
+                        info.serialized_length,
+                        info.idle.as_secs(),
+                        info.freq,
+                        info.version,
+                        info.ttl.map_or(-1, |ttl| ttl.as_millis() as i64),
+                    )),
+                    None => Frame::Error("ERR no such key".to_string()),
+                },
+            }
+        };
+
+        debug!(?response);
+
+        connection.write_frame_nowait(&response).await?;
+
+        Ok(())
+    }
+}
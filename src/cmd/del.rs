@@ -55,7 +55,7 @@ impl Del {
         debug!(?response);
 
         // 往连接中写入响应
-        connection.write_frame(&response).await?;
+        connection.write_frame_nowait(&response).await?;
 
         Ok(())
     }
@@ -0,0 +1,249 @@
+//! expire、pexpire、expireat和pexpireat命令的实现
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use bytes::Bytes;
+use tokio::time::{Duration, Instant};
+use tracing::{debug, instrument};
+
+use crate::{
+    networking::{
+        connection::Connection,
+        frame::Frame,
+        parse::{Parse, ParseError},
+    },
+    persistence::database::{deadline_after, Database},
+};
+
+use super::util::expire_millis;
+
+/// # ExpireCondition 枚举
+///
+/// 设置过期时间的条件，没有过期时间的键在比较时视为永不过期
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExpireCondition {
+    /// 只在键没有过期时间时设置
+    Nx,
+    /// 只在键已经有过期时间时设置
+    Xx,
+    /// 只在新的过期时间比当前的晚时设置
+    Gt,
+    /// 只在新的过期时间比当前的早时设置
+    Lt,
+}
+
+impl ExpireCondition {
+    /// # name() 函数
+    ///
+    /// 返回条件在命令中的名称
+    fn name(self) -> &'static str {
+        match self {
+            ExpireCondition::Nx => "nx",
+            ExpireCondition::Xx => "xx",
+            ExpireCondition::Gt => "gt",
+            ExpireCondition::Lt => "lt",
+        }
+    }
+
+    /// # is_met() 函数
+    ///
+    /// 根据键当前的过期时间判断是否满足条件
+    pub(crate) fn is_met(self, current: Option<Instant>, when: Instant) -> bool {
+        match self {
+            ExpireCondition::Nx => current.is_none(),
+            ExpireCondition::Xx => current.is_some(),
+            ExpireCondition::Gt => current.is_some_and(|current| when > current),
+            ExpireCondition::Lt => current.is_none_or(|current| when < current),
+        }
+    }
+}
+
+/// # ExpireUnit 枚举
+///
+/// 时间参数的含义，区分EXPIRE系列的四个命令
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ExpireUnit {
+    /// EXPIRE：从现在开始的秒数
+    Seconds,
+    /// PEXPIRE：从现在开始的毫秒数
+    Milliseconds,
+    /// EXPIREAT：unix时间戳，以秒为单位
+    UnixSeconds,
+    /// PEXPIREAT：unix时间戳，以毫秒为单位
+    UnixMilliseconds,
+}
+
+/// # Expire 结构体
+///
+/// 为键设置过期时间，设置成功返回1，键不存在或者不满足条件时返回0
+///
+/// # 语法
+///
+/// - EXPIRE key seconds [NX | XX | GT | LT]
+/// - PEXPIRE key milliseconds [NX | XX | GT | LT]
+/// - EXPIREAT key unix-time-seconds [NX | XX | GT | LT]
+/// - PEXPIREAT key unix-time-milliseconds [NX | XX | GT | LT]
+#[derive(Debug)]
+pub struct Expire {
+    /// 时间参数的含义
+    unit: ExpireUnit,
+    /// 键
+    key: String,
+    /// 时间参数
+    time: u64,
+    /// 设置过期时间的条件，所有条件都满足时才会设置
+    conditions: Vec<ExpireCondition>,
+}
+
+impl Expire {
+    pub(crate) fn new(
+        unit: ExpireUnit,
+        key: impl ToString,
+        time: u64,
+        conditions: Vec<ExpireCondition>,
+    ) -> Expire {
+        Expire {
+            unit,
+            key: key.to_string(),
+            time,
+            conditions,
+        }
+    }
+
+    /// # get_name() 函数
+    ///
+    /// 返回命令的名称
+    pub(crate) fn get_name(&self) -> &'static str {
+        match self.unit {
+            ExpireUnit::Seconds => "expire",
+            ExpireUnit::Milliseconds => "pexpire",
+            ExpireUnit::UnixSeconds => "expireat",
+            ExpireUnit::UnixMilliseconds => "pexpireat",
+        }
+    }
+
+    /// # decode_expire_from_frame() 函数
+    ///
+    /// 将帧解码为expire系列命令
+    pub(crate) fn decode_expire_from_frame(
+        unit: ExpireUnit,
+        parse: &mut Parse,
+    ) -> crate::Result<Expire> {
+        let key = parse.next_string()?;
+        let time = parse.next_int()?;
+        // 换算成毫秒后溢出的时间是无效的
+        let millis_per_unit = match unit {
+            ExpireUnit::Seconds | ExpireUnit::UnixSeconds => 1000,
+            ExpireUnit::Milliseconds | ExpireUnit::UnixMilliseconds => 1,
+        };
+        expire_millis(time, millis_per_unit)?;
+
+        let mut conditions = Vec::new();
+        loop {
+            let condition = match parse.next_string() {
+                Ok(option) => match &option.to_lowercase()[..] {
+                    "nx" => ExpireCondition::Nx,
+                    "xx" => ExpireCondition::Xx,
+                    "gt" => ExpireCondition::Gt,
+                    "lt" => ExpireCondition::Lt,
+                    _ => return Err(format!("ERR Unsupported option {}", option).into()),
+                },
+                Err(ParseError::EndOfStream) => break,
+                Err(err) => return Err(err.into()),
+            };
+            if !conditions.contains(&condition) {
+                conditions.push(condition);
+            }
+        }
+
+        // XX可以和GT或LT同时使用，其他组合互相矛盾
+        let has = |condition| conditions.contains(&condition);
+        if has(ExpireCondition::Nx) && conditions.len() > 1 {
+            return Err(
+                "ERR NX and XX, GT or LT options at the same time are not compatible".into(),
+            );
+        }
+        if has(ExpireCondition::Gt) && has(ExpireCondition::Lt) {
+            return Err("ERR GT and LT options at the same time are not compatible".into());
+        }
+
+        Ok(Expire::new(unit, key, time, conditions))
+    }
+
+    /// # code_expire_into_frame() 函数
+    ///
+    /// 将expire系列命令编码为帧
+    pub(crate) fn code_expire_into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from(self.get_name().as_bytes()));
+        frame.push_bulk(Bytes::from(self.key.into_bytes()));
+        frame.push_bulk(Bytes::from(self.time.to_string()));
+        for condition in self.conditions {
+            frame.push_bulk(Bytes::from(condition.name().as_bytes()));
+        }
+        frame
+    }
+
+    /// # deadline() 函数
+    ///
+    /// 将时间参数转换为过期的时刻，已经过去的时间点转换为现在
+    fn deadline(&self) -> Instant {
+        let now = Instant::now();
+        let unix = |time: Duration| {
+            let remaining = (UNIX_EPOCH + time)
+                .duration_since(SystemTime::now())
+                .unwrap_or_default();
+            deadline_after(now, remaining)
+        };
+
+        match self.unit {
+            ExpireUnit::Seconds => deadline_after(now, Duration::from_secs(self.time)),
+            ExpireUnit::Milliseconds => deadline_after(now, Duration::from_millis(self.time)),
+            ExpireUnit::UnixSeconds => unix(Duration::from_secs(self.time)),
+            ExpireUnit::UnixMilliseconds => unix(Duration::from_millis(self.time)),
+        }
+    }
+
+    /// # apply() 函数
+    ///
+    /// 应用expire系列命令，并将响应写入到Connection实例
+    #[instrument(skip(self, db, connection))]
+    pub(crate) async fn apply(
+        self,
+        db: &Database,
+        connection: &mut Connection,
+    ) -> crate::Result<()> {
+        let updated = db.expire(&self.key, self.deadline(), &self.conditions);
+
+        let response = Frame::Integer(updated as i64);
+        debug!(?response);
+
+        connection.write_frame_nowait(&response).await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expire_condition() {
+        let now = Instant::now();
+        let later = now + Duration::from_secs(100);
+
+        assert!(ExpireCondition::Nx.is_met(None, now));
+        assert!(!ExpireCondition::Nx.is_met(Some(now), later));
+        assert!(ExpireCondition::Xx.is_met(Some(now), later));
+        assert!(!ExpireCondition::Xx.is_met(None, later));
+
+        // 没有过期时间的键视为永不过期
+        assert!(ExpireCondition::Gt.is_met(Some(now), later));
+        assert!(!ExpireCondition::Gt.is_met(Some(later), now));
+        assert!(!ExpireCondition::Gt.is_met(None, later));
+        assert!(ExpireCondition::Lt.is_met(Some(later), now));
+        assert!(!ExpireCondition::Lt.is_met(Some(now), later));
+        assert!(ExpireCondition::Lt.is_met(None, later));
+    }
+}
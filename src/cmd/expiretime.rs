@@ -0,0 +1,89 @@
+//! expiretime和pexpiretime命令的实现
+
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+use crate::{
+    networking::{connection::Connection, frame::Frame, parse::Parse},
+    persistence::database::Database,
+};
+
+/// # ExpireTime 结构体
+///
+/// 返回键过期的unix时间戳，键没有过期时间时返回-1，键不存在时返回-2
+///
+/// # 语法
+///
+/// - EXPIRETIME key：以秒为单位
+/// - PEXPIRETIME key：以毫秒为单位
+#[derive(Debug)]
+pub struct ExpireTime {
+    /// 键
+    key: String,
+    /// 是否以毫秒为单位
+    millis: bool,
+}
+
+impl ExpireTime {
+    pub(crate) fn new(key: impl ToString, millis: bool) -> ExpireTime {
+        ExpireTime {
+            key: key.to_string(),
+            millis,
+        }
+    }
+
+    /// # get_name() 函数
+    ///
+    /// 返回命令的名称
+    pub(crate) fn get_name(&self) -> &'static str {
+        if self.millis {
+            "pexpiretime"
+        } else {
+            "expiretime"
+        }
+    }
+
+    /// # decode_expiretime_from_frame() 函数
+    ///
+    /// 将帧解码为expiretime或pexpiretime命令
+    pub(crate) fn decode_expiretime_from_frame(
+        parse: &mut Parse,
+        millis: bool,
+    ) -> crate::Result<ExpireTime> {
+        let key = parse.next_string()?;
+
+        Ok(ExpireTime::new(key, millis))
+    }
+
+    /// # code_expiretime_into_frame() 函数
+    ///
+    /// 将expiretime或pexpiretime命令编码为帧
+    pub(crate) fn code_expiretime_into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from(self.get_name().as_bytes()));
+        frame.push_bulk(Bytes::from(self.key.into_bytes()));
+        frame
+    }
+
+    /// # apply() 函数
+    ///
+    /// 应用expiretime或pexpiretime命令，并将响应写入到Connection实例
+    #[instrument(skip(self, db, connection))]
+    pub(crate) async fn apply(
+        self,
+        db: &Database,
+        connection: &mut Connection,
+    ) -> crate::Result<()> {
+        let response = match db.expire_time(&self.key) {
+            Some(Some(millis)) if self.millis => Frame::Integer(millis as i64),
+            Some(Some(millis)) => Frame::Integer((millis / 1000) as i64),
+            Some(None) => Frame::Integer(-1),
+            None => Frame::Integer(-2),
+        };
+        debug!(?response);
+
+        connection.write_frame_nowait(&response).await?;
+
+        Ok(())
+    }
+}
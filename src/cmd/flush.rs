@@ -0,0 +1,150 @@
+//! flushdb和flushall命令的实现
+
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+use crate::{
+    networking::{
+        connection::Connection,
+        frame::Frame,
+        parse::{Parse, ParseError},
+    },
+    persistence::database::Database,
+};
+
+/// # decode_flush_mode() 函数
+///
+/// 解析可选的ASYNC/SYNC参数，返回是否在后台释放数据
+fn decode_flush_mode(parse: &mut Parse) -> crate::Result<bool> {
+    match parse.next_string() {
+        Ok(mode) => match &mode.to_lowercase()[..] {
+            "async" => Ok(true),
+            "sync" => Ok(false),
+            _ => Err("ERR syntax error".into()),
+        },
+        Err(ParseError::EndOfStream) => Ok(false),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// # code_flush_into_frame() 函数
+///
+/// 将flush类命令编码为帧
+fn code_flush_into_frame(name: &str, lazy: bool) -> Frame {
+    let mut frame = Frame::array();
+    frame.push_bulk(Bytes::from(name.to_string()));
+    if lazy {
+        frame.push_bulk(Bytes::from("async".as_bytes()));
+    }
+    frame
+}
+
+/// # FlushDb 结构体
+///
+/// 清空当前连接使用的逻辑数据库
+///
+/// # 语法
+///
+/// FLUSHDB [ASYNC | SYNC]
+#[derive(Debug, Default)]
+pub struct FlushDb {
+    /// 是否在后台释放数据
+    lazy: bool,
+}
+
+impl FlushDb {
+    /// # new() 函数
+    ///
+    /// 创建一个新的FlushDb命令
+    pub fn new(lazy: bool) -> FlushDb {
+        FlushDb { lazy }
+    }
+
+    /// # decode_flushdb_from_frame() 函数
+    ///
+    /// 将帧解码为flushdb命令
+    pub(crate) fn decode_flushdb_from_frame(parse: &mut Parse) -> crate::Result<FlushDb> {
+        Ok(FlushDb::new(decode_flush_mode(parse)?))
+    }
+
+    /// # code_flushdb_into_frame() 函数
+    ///
+    /// 将flushdb命令编码为帧
+    pub(crate) fn code_flushdb_into_frame(self) -> Frame {
+        code_flush_into_frame("flushdb", self.lazy)
+    }
+
+    /// # apply() 函数
+    ///
+    /// 应用flushdb命令，并将响应写入到Connection实例
+    #[instrument(skip(self, db, connection))]
+    pub(crate) async fn apply(
+        self,
+        db: &Database,
+        connection: &mut Connection,
+    ) -> crate::Result<()> {
+        db.flush_db(self.lazy);
+
+        let response = Frame::Simple("OK".to_string());
+        debug!(?response);
+
+        connection.write_frame_nowait(&response).await?;
+
+        Ok(())
+    }
+}
+
+/// # FlushAll 结构体
+///
+/// 清空所有逻辑数据库，发布/订阅的状态不受影响
+///
+/// # 语法
+///
+/// FLUSHALL [ASYNC | SYNC]
+#[derive(Debug, Default)]
+pub struct FlushAll {
+    /// 是否在后台释放数据
+    lazy: bool,
+}
+
+impl FlushAll {
+    /// # new() 函数
+    ///
+    /// 创建一个新的FlushAll命令
+    pub fn new(lazy: bool) -> FlushAll {
+        FlushAll { lazy }
+    }
+
+    /// # decode_flushall_from_frame() 函数
+    ///
+    /// 将帧解码为flushall命令
+    pub(crate) fn decode_flushall_from_frame(parse: &mut Parse) -> crate::Result<FlushAll> {
+        Ok(FlushAll::new(decode_flush_mode(parse)?))
+    }
+
+    /// # code_flushall_into_frame() 函数
+    ///
+    /// 将flushall命令编码为帧
+    pub(crate) fn code_flushall_into_frame(self) -> Frame {
+        code_flush_into_frame("flushall", self.lazy)
+    }
+
+    /// # apply() 函数
+    ///
+    /// 应用flushall命令，并将响应写入到Connection实例
+    #[instrument(skip(self, db, connection))]
+    pub(crate) async fn apply(
+        self,
+        db: &Database,
+        connection: &mut Connection,
+    ) -> crate::Result<()> {
+        db.flush_all(self.lazy);
+
+        let response = Frame::Simple("OK".to_string());
+        debug!(?response);
+
+        connection.write_frame_nowait(&response).await?;
+
+        Ok(())
+    }
+}
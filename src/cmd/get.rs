@@ -78,7 +78,7 @@ impl Get {
         debug!(?response);
 
         // 将响应写入到connection实例
-        connection.write_frame(&response).await?;
+        connection.write_frame_nowait(&response).await?;
 
         Ok(())
     }
@@ -0,0 +1,91 @@
+//! getrange命令实现
+
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+use crate::{
+    networking::{connection::Connection, frame::Frame, parse::Parse},
+    persistence::database::Database,
+};
+
+use super::util::normalize_range;
+
+/// # GetRange 结构体
+///
+/// 获取key的值中从start到end（包含两端）的子串，负数下标从末尾开始计数，键不存在时返回空字符串
+///
+/// # 语法
+///
+/// GETRANGE key start end
+#[derive(Debug)]
+pub struct GetRange {
+    /// 键
+    key: String,
+    /// 起始下标
+    start: i64,
+    /// 结束下标
+    end: i64,
+}
+
+impl GetRange {
+    /// # new() 函数
+    ///
+    /// 创建一个新的GetRange命令
+    pub(crate) fn new(key: impl ToString, start: i64, end: i64) -> GetRange {
+        GetRange {
+            key: key.to_string(),
+            start,
+            end,
+        }
+    }
+
+    /// # decode_getrange_from_frame() 函数
+    ///
+    /// 将帧解码为getrange命令
+    pub(crate) fn decode_getrange_from_frame(parse: &mut Parse) -> crate::Result<GetRange> {
+        let key = parse.next_string()?;
+        let start = parse.next_string()?;
+        let end = parse.next_string()?;
+
+        let (Ok(start), Ok(end)) = (start.parse(), end.parse()) else {
+            return Err("value is not an integer or out of range".into());
+        };
+
+        Ok(GetRange::new(key, start, end))
+    }
+
+    /// # code_getrange_into_frame() 函数
+    ///
+    /// 将getrange命令编码为帧
+    pub(crate) fn code_getrange_into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("getrange".as_bytes()));
+        frame.push_bulk(Bytes::from(self.key.into_bytes()));
+        frame.push_bulk(Bytes::from(self.start.to_string()));
+        frame.push_bulk(Bytes::from(self.end.to_string()));
+        frame
+    }
+
+    /// # apply() 函数
+    ///
+    /// 应用GetRange命令，并将响应写入到Connection实例
+    #[instrument(skip(self, db, connection))]
+    pub(crate) async fn apply(
+        self,
+        db: &Database,
+        connection: &mut Connection,
+    ) -> crate::Result<()> {
+        let value = db.get(&self.key).unwrap_or_default();
+
+        // 子串与原来的值共享同一块内存
+        let response = match normalize_range(self.start, self.end, value.len()) {
+            Some((start, end)) => Frame::Bulk(value.slice(start..=end)),
+            None => Frame::Bulk(Bytes::new()),
+        };
+        debug!(?response);
+
+        connection.write_frame_nowait(&response).await?;
+
+        Ok(())
+    }
+}
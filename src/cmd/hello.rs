@@ -0,0 +1,168 @@
+//! hello命令的实现
+
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+use crate::{
+    networking::{
+        connection::Connection,
+        frame::Frame,
+        parse::{Parse, ParseError},
+    },
+    persistence::database::Database,
+    server::{replication::Role, session::Session},
+};
+
+use super::auth::check_password;
+
+/// # Hello 结构体
+///
+/// 与服务器握手，切换连接使用的协议版本，并返回服务器的信息。
+/// 切换到3之后，服务器按照RESP3编码响应，例如CONFIG GET返回map
+///
+/// # 语法
+///
+/// HELLO [protover [AUTH username password]]
+#[derive(Debug, Default)]
+pub struct Hello {
+    /// 协议版本，不指定时不切换
+    protover: Option<u8>,
+    /// 同时进行认证的用户名和密码
+    auth: Option<(String, String)>,
+}
+
+impl Hello {
+    /// # new() 函数
+    ///
+    /// 创建一个新的Hello命令
+    pub fn new(protover: Option<u8>) -> Hello {
+        Hello {
+            protover,
+            auth: None,
+        }
+    }
+
+    /// # decode_hello_from_frame() 函数
+    ///
+    /// 将帧解码为hello命令
+    pub(crate) fn decode_hello_from_frame(parse: &mut Parse) -> crate::Result<Hello> {
+        let protover = match parse.next_int() {
+            Ok(protover) => protover,
+            Err(ParseError::EndOfStream) => return Ok(Hello::default()),
+            Err(_) => return Err("ERR Protocol version is not an integer or out of range".into()),
+        };
+        // 不支持的版本在apply中回复NOPROTO，这里只需要保证不会溢出
+        let protover = u8::try_from(protover).unwrap_or(u8::MAX);
+
+        let mut hello = Hello::new(Some(protover));
+        loop {
+            let option = match parse.next_string() {
+                Ok(option) => option,
+                Err(ParseError::EndOfStream) => return Ok(hello),
+                Err(err) => return Err(err.into()),
+            };
+
+            match option.to_lowercase().as_str() {
+                "auth" => {
+                    let username = parse.next_string()?;
+                    let password = parse.next_string()?;
+                    hello.auth = Some((username, password));
+                }
+                _ => return Err(format!("ERR Syntax error in HELLO option '{}'", option).into()),
+            }
+        }
+    }
+
+    /// # code_hello_into_frame() 函数
+    ///
+    /// 将hello命令编码为帧
+    pub(crate) fn code_hello_into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("hello".as_bytes()));
+        if let Some(protover) = self.protover {
+            frame.push_bulk(Bytes::from(protover.to_string()));
+        }
+        if let Some((username, password)) = self.auth {
+            frame.push_bulk(Bytes::from("auth".as_bytes()));
+            frame.push_bulk(Bytes::from(username.into_bytes()));
+            frame.push_bulk(Bytes::from(password.into_bytes()));
+        }
+        frame
+    }
+
+    /// # apply() 函数
+    ///
+    /// 应用hello命令，切换协议版本后按照新的版本回复服务器的信息
+    #[instrument(skip(self, db, connection, session))]
+    pub(crate) async fn apply(
+        self,
+        db: &Database,
+        connection: &mut Connection,
+        session: &mut Session,
+    ) -> crate::Result<()> {
+        let response = match self.check(db, session) {
+            Ok(()) => {
+                if let Some(protover) = self.protover {
+                    connection.set_protocol(protover);
+                }
+                server_info(db, connection)
+            }
+            Err(response) => response,
+        };
+        debug!(?response);
+
+        connection.write_frame_nowait(&response).await?;
+
+        Ok(())
+    }
+
+    /// # check() 函数
+    ///
+    /// 检查协议版本和认证，带有AUTH选项时同时完成认证，失败时返回错误帧
+    fn check(&self, db: &Database, session: &mut Session) -> Result<(), Frame> {
+        if let Some(protover) = self.protover {
+            if !(2..=3).contains(&protover) {
+                return Err(Frame::Error(
+                    "NOPROTO unsupported protocol version".to_string(),
+                ));
+            }
+        }
+
+        let Some(requirepass) = db.requirepass() else {
+            return Ok(());
+        };
+        match &self.auth {
+            Some((username, password)) => {
+                check_password(&requirepass, Some(username), password)?;
+                session.authenticated = true;
+                Ok(())
+            }
+            None if session.authenticated => Ok(()),
+            None => Err(Frame::Error(
+                "NOAUTH HELLO must be called with the client already authenticated, otherwise the HELLO <proto> AUTH <user> <pass> option can be used to authenticate the client and select the RESP protocol version at the same time"
+                    .to_string(),
+            )),
+        }
+    }
+}
+
+/// # server_info() 函数
+///
+/// 返回HELLO回复的服务器信息，RESP2下会被降级为键值交替的数组
+fn server_info(db: &Database, connection: &Connection) -> Frame {
+    let bulk = |value: &str| Frame::Bulk(Bytes::from(value.to_string()));
+    let role = match db.role() {
+        Role::Master => "master",
+        Role::Replica { .. } => "replica",
+    };
+
+    Frame::Map(vec![
+        (bulk("server"), bulk("rustis")),
+        (bulk("version"), bulk(env!("CARGO_PKG_VERSION"))),
+        (bulk("proto"), Frame::Integer(connection.protocol() as i64)),
+        (bulk("id"), Frame::Integer(connection.id() as i64)),
+        (bulk("mode"), bulk("standalone")),
+        (bulk("role"), bulk(role)),
+        (bulk("modules"), Frame::Array(Vec::new())),
+    ])
+}
@@ -0,0 +1,179 @@
+//! info命令的实现
+
+use std::time::UNIX_EPOCH;
+
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+use crate::{
+    networking::{
+        connection::Connection,
+        frame::Frame,
+        parse::{Parse, ParseError},
+    },
+    persistence::database::Database,
+    server::replication::Role,
+};
+
+/// # Info 结构体
+///
+/// 返回服务器的信息和统计数据
+///
+/// # 语法
+///
+/// INFO [section]
+#[derive(Debug, Default)]
+pub struct Info {
+    /// 需要返回的section，为None时返回所有section
+    section: Option<String>,
+}
+
+impl Info {
+    /// # new() 函数
+    ///
+    /// 创建一个新的Info命令
+    pub(crate) fn new(section: Option<String>) -> Info {
+        Info { section }
+    }
+
+    /// # decode_info_from_frame() 函数
+    ///
+    /// 将帧解码为info命令
+    pub(crate) fn decode_info_from_frame(parse: &mut Parse) -> crate::Result<Info> {
+        match parse.next_string() {
+            Ok(section) => Ok(Info::new(Some(section.to_lowercase()))),
+            Err(ParseError::EndOfStream) => Ok(Info::default()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// # code_info_into_frame() 函数
+    ///
+    /// 将info命令编码为帧
+    pub(crate) fn code_info_into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("info".as_bytes()));
+        if let Some(section) = self.section {
+            frame.push_bulk(Bytes::from(section.into_bytes()));
+        }
+        frame
+    }
+
+    /// # apply() 函数
+    ///
+    /// 应用info命令，并将响应写入到Connection实例
+    #[instrument(skip(self, db, connection))]
+    pub(crate) async fn apply(
+        self,
+        db: &Database,
+        connection: &mut Connection,
+    ) -> crate::Result<()> {
+        let mut info = String::new();
+        let config = db.config();
+
+        if self.is_section_wanted("server") {
+            info.push_str("# Server\r\n");
+            info.push_str(&format!("tcp_nodelay:{}\r\n", config.tcp_nodelay as u8));
+            info.push_str(&format!("tcp_keepalive:{}\r\n", config.tcp_keepalive));
+        }
+
+        if self.is_section_wanted("clients") {
+            info.push_str("# Clients\r\n");
+            info.push_str(&format!("connected_clients:{}\r\n", db.connected_clients()));
+            info.push_str(&format!("maxclients:{}\r\n", config.max_connections));
+        }
+
+        if self.is_section_wanted("memory") {
+            info.push_str("# Memory\r\n");
+            info.push_str(&format!("used_memory:{}\r\n", db.used_memory()));
+            info.push_str(&format!("maxmemory:{}\r\n", config.maxmemory));
+            info.push_str(&format!(
+                "maxmemory_policy:{}\r\n",
+                config.maxmemory_policy.as_str()
+            ));
+        }
+
+        if self.is_section_wanted("persistence") {
+            let last_save = db
+                .last_save()
+                .1
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default();
+            info.push_str("# Persistence\r\n");
+            info.push_str(&format!(
+                "rdb_changes_since_last_save:{}\r\n",
+                db.changes_since_save()
+            ));
+            info.push_str(&format!(
+                "rdb_bgsave_in_progress:{}\r\n",
+                db.is_bgsave_in_progress() as u8
+            ));
+            info.push_str(&format!("rdb_last_save_time:{}\r\n", last_save.as_secs()));
+            info.push_str(&format!(
+                "rdb_last_bgsave_status:{}\r\n",
+                if db.last_bgsave_failure().is_some() {
+                    "err"
+                } else {
+                    "ok"
+                }
+            ));
+        }
+
+        if self.is_section_wanted("stats") {
+            info.push_str("# Stats\r\n");
+            for (name, value) in db.stats().snapshot() {
+                info.push_str(&format!("{}:{}\r\n", name, value));
+            }
+            info.push_str(&format!("pubsub_channels:{}\r\n", db.pubsub_channels()));
+        }
+
+        if self.is_section_wanted("replication") {
+            info.push_str("# Replication\r\n");
+            match db.role() {
+                Role::Replica { master } => {
+                    let (host, port) = master.rsplit_once(':').unwrap_or((&master, ""));
+                    info.push_str("role:slave\r\n");
+                    info.push_str(&format!("master_host:{}\r\n", host));
+                    info.push_str(&format!("master_port:{}\r\n", port));
+                    info.push_str(&format!(
+                        "slave_read_only:{}\r\n",
+                        config.replica_read_only as u8
+                    ));
+                }
+                Role::Master => info.push_str("role:master\r\n"),
+            }
+            info.push_str(&format!("connected_slaves:{}\r\n", db.connected_replicas()));
+        }
+
+        if self.is_section_wanted("commandstats") {
+            info.push_str("# Commandstats\r\n");
+            for (name, stat) in db.stats().command_stats() {
+                info.push_str(&format!(
+                    "cmdstat_{}:calls={},usec={},usec_per_call={:.2},failed_calls={}\r\n",
+                    name,
+                    stat.calls,
+                    stat.usec(),
+                    stat.usec_per_call(),
+                    stat.failed_calls
+                ));
+            }
+        }
+
+        let response = Frame::Bulk(Bytes::from(info));
+        debug!(?response);
+
+        connection.write_frame_nowait(&response).await?;
+
+        Ok(())
+    }
+
+    /// # is_section_wanted() 函数
+    ///
+    /// 判断某个section是否需要返回
+    fn is_section_wanted(&self, section: &str) -> bool {
+        match self.section.as_deref() {
+            None | Some("all") | Some("default") | Some("everything") => true,
+            Some(wanted) => wanted == section,
+        }
+    }
+}
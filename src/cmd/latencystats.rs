@@ -0,0 +1,75 @@
+//! latencystats命令的实现
+
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+use crate::{
+    networking::{connection::Connection, frame::Frame},
+    persistence::database::Database,
+};
+
+/// # LatencyStats 结构体
+///
+/// 返回每个被调用过的命令的统计，回复为命令名称和统计数组交替的数组，统计数组的格式为：
+///
+/// calls <n> failed_calls <n> usec <n> histogram_usec [<上界> <累计次数> ...]
+///
+/// # 语法
+///
+/// LATENCYSTATS
+#[derive(Debug, Default)]
+pub struct LatencyStats;
+
+impl LatencyStats {
+    pub(crate) fn new() -> LatencyStats {
+        LatencyStats
+    }
+
+    /// # code_latencystats_into_frame() 函数
+    ///
+    /// 将latencystats命令编码为帧
+    pub(crate) fn code_latencystats_into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("latencystats".as_bytes()));
+        frame
+    }
+
+    /// # apply() 函数
+    ///
+    /// 应用latencystats命令，并将响应写入到Connection实例
+    #[instrument(skip(self, db, connection))]
+    pub(crate) async fn apply(
+        self,
+        db: &Database,
+        connection: &mut Connection,
+    ) -> crate::Result<()> {
+        let mut response = Frame::array();
+
+        for (name, stat) in db.stats().command_stats() {
+            let mut histogram = Frame::array();
+            for (bound, count) in stat.cumulative_histogram() {
+                histogram.push_int(bound as i64);
+                histogram.push_int(count as i64);
+            }
+
+            let mut entry = Frame::array();
+            entry.push_bulk(Bytes::from_static(b"calls"));
+            entry.push_int(stat.calls as i64);
+            entry.push_bulk(Bytes::from_static(b"failed_calls"));
+            entry.push_int(stat.failed_calls as i64);
+            entry.push_bulk(Bytes::from_static(b"usec"));
+            entry.push_int(stat.usec() as i64);
+            entry.push_bulk(Bytes::from_static(b"histogram_usec"));
+            entry.push_frame(histogram);
+
+            response.push_bulk(Bytes::from(name));
+            response.push_frame(entry);
+        }
+
+        debug!(?response);
+
+        connection.write_frame_nowait(&response).await?;
+
+        Ok(())
+    }
+}
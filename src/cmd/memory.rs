@@ -0,0 +1,82 @@
+//! memory命令的实现
+
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+use crate::{
+    networking::{connection::Connection, frame::Frame, parse::Parse},
+    persistence::database::Database,
+};
+
+/// # Memory 枚举
+///
+/// 查看内存的使用情况
+///
+/// # 语法
+///
+/// - MEMORY USAGE key [SAMPLES count]：返回键和值占用的字节数的估计值，键不存在时返回nil。
+///   值都是字符串，不需要抽样，SAMPLES只是为了兼容redis而接受
+#[derive(Debug)]
+pub enum Memory {
+    /// MEMORY USAGE key
+    Usage(String),
+}
+
+impl Memory {
+    /// # decode_memory_from_frame() 函数
+    ///
+    /// 将帧解码为memory命令
+    pub(crate) fn decode_memory_from_frame(parse: &mut Parse) -> crate::Result<Memory> {
+        let subcommand = parse.next_string()?.to_lowercase();
+        match subcommand.as_str() {
+            "usage" => {
+                let key = parse.next_string()?;
+                if let Ok(option) = parse.next_string() {
+                    if !option.eq_ignore_ascii_case("samples") {
+                        return Err("ERR syntax error".into());
+                    }
+                    parse.next_int()?;
+                }
+                Ok(Memory::Usage(key))
+            }
+            _ => Err(format!("ERR unknown subcommand '{}'", subcommand).into()),
+        }
+    }
+
+    /// # code_memory_into_frame() 函数
+    ///
+    /// 将memory命令编码为帧
+    pub(crate) fn code_memory_into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("memory".as_bytes()));
+        match self {
+            Memory::Usage(key) => {
+                frame.push_bulk(Bytes::from("usage".as_bytes()));
+                frame.push_bulk(Bytes::from(key.into_bytes()));
+            }
+        }
+        frame
+    }
+
+    /// # apply() 函数
+    ///
+    /// 应用memory命令，并将响应写入到Connection实例
+    #[instrument(skip(self, db, connection))]
+    pub(crate) async fn apply(
+        self,
+        db: &Database,
+        connection: &mut Connection,
+    ) -> crate::Result<()> {
+        let response = match self {
+            Memory::Usage(key) => match db.memory_usage(&key) {
+                Some(bytes) => Frame::Integer(bytes as i64),
+                None => Frame::Null,
+            },
+        };
+        debug!(?response);
+
+        connection.write_frame_nowait(&response).await?;
+
+        Ok(())
+    }
+}
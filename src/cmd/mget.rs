@@ -0,0 +1,90 @@
+//! mget命令实现
+
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+use crate::{
+    networking::{
+        connection::Connection,
+        frame::Frame,
+        parse::{Parse, ParseError},
+    },
+    persistence::database::Database,
+};
+
+/// # MGet 结构体
+///
+/// 获取多个key的值
+///
+/// # 语法
+///
+/// MGET key [key ...]
+#[derive(Debug)]
+pub struct MGet {
+    /// 键
+    keys: Vec<String>,
+}
+
+impl MGet {
+    /// # new() 函数
+    ///
+    /// 创建一个新的MGet命令
+    pub(crate) fn new(keys: Vec<String>) -> MGet {
+        MGet { keys }
+    }
+
+    /// # decode_mget_from_frame() 函数
+    ///
+    /// 将帧解码为mget命令
+    pub(crate) fn decode_mget_from_frame(parse: &mut Parse) -> crate::Result<MGet> {
+        // 至少需要一个键
+        let mut keys = vec![parse.next_string()?];
+        loop {
+            match parse.next_string() {
+                Ok(key) => keys.push(key),
+                Err(ParseError::EndOfStream) => break,
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        Ok(MGet::new(keys))
+    }
+
+    /// # code_mget_into_frame() 函数
+    ///
+    /// 将mget命令编码为帧
+    pub(crate) fn code_mget_into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("mget".as_bytes()));
+        for key in self.keys {
+            frame.push_bulk(Bytes::from(key.into_bytes()));
+        }
+        frame
+    }
+
+    /// # apply() 函数
+    ///
+    /// 应用MGet命令，按照键的顺序回复每个键的值，不存在的键回复nil
+    #[instrument(skip(self, db, connection))]
+    pub(crate) async fn apply(
+        self,
+        db: &Database,
+        connection: &mut Connection,
+    ) -> crate::Result<()> {
+        let response = Frame::Array(
+            self.keys
+                .iter()
+                .map(|key| match db.get(key) {
+                    Some(value) => Frame::Bulk(value),
+                    None => Frame::Null,
+                })
+                .collect(),
+        );
+
+        debug!(?response);
+
+        connection.write_frame_nowait(&response).await?;
+
+        Ok(())
+    }
+}
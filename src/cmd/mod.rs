@@ -1,24 +1,79 @@
+pub mod auth;
+pub mod client;
+pub mod command;
+pub mod config;
+pub mod debug;
+pub mod del;
+pub mod expire;
+pub mod expiretime;
+pub mod flush;
 pub mod get;
+pub mod getrange;
+pub mod hello;
+pub mod info;
+pub mod latencystats;
+pub mod memory;
+pub mod mget;
+pub mod monitor;
+pub mod object;
 pub mod ping;
+pub mod psync;
 pub mod publish;
+#[cfg(feature = "cmd-pubsub")]
+mod pubsub;
+pub mod quit;
+pub mod readonly;
+pub mod replicaof;
 pub mod save;
+pub mod scan;
+pub mod select;
 pub mod set;
+pub mod stats;
 pub mod subscribe;
-pub mod del;
+pub(crate) mod table;
 mod unknown;
+pub(crate) mod util;
 
 use crate::{
-    networking::{connection::Connection, frame::Frame, parse::Parse},
+    networking::{
+        connection::Connection,
+        frame::Frame,
+        parse::{Parse, ParseError},
+    },
     persistence::database::Database,
-    server::shutdown::Shutdown,
+    server::{session::Session, shutdown::Shutdown},
+    RustisError,
 };
+use auth::Auth;
+use client::Client;
+use command::CommandCmd;
+use config::Config;
+use debug::Debug;
+use del::Del;
+use expire::{Expire, ExpireUnit};
+use expiretime::ExpireTime;
+use flush::{FlushAll, FlushDb};
 use get::Get;
+use getrange::GetRange;
+use hello::Hello;
+use info::Info;
+use latencystats::LatencyStats;
+use memory::Memory;
+use mget::MGet;
+use monitor::Monitor;
+use object::Object;
 use ping::Ping;
-use publish::Publish;
-use save::Save;
+use psync::Psync;
+#[cfg(feature = "cmd-pubsub")]
+use pubsub::PubSub;
+use quit::Quit;
+use readonly::{ReadOnly, ReadWrite};
+use replicaof::ReplicaOf;
+use save::{BgSave, Save};
+use scan::Scan;
+use select::Select;
 use set::Set;
-use del::Del;
-use subscribe::{ExitSubscribe, Subscribe, Unsubscribe};
+use stats::Stats;
 use tracing::instrument;
 use unknown::Unknown;
 
@@ -28,26 +83,23 @@ pub enum Command {
     ///
     /// 获取key的值
     Get(Get),
+    /// # MGet 命令
+    ///
+    /// 获取多个key的值
+    MGet(MGet),
+    /// # GetRange 命令
+    ///
+    /// 获取key的值的子串
+    GetRange(GetRange),
     /// # Set 命令
     ///
     /// 从key映射到value，如果key已经映射到了一个值，那么旧值将被替换
     Set(Set),
-    /// # Publish 命令
-    ///
-    /// 将消息发布到指定的频道
-    Publish(Publish),
-    /// # Subscribe 命令
-    ///
-    /// 订阅一个或多个频道
-    Subscribe(Subscribe),
-    /// # Unsubscribe 命令
+    /// # PubSub 命令
     ///
-    /// 退订一个或多个频道
-    Unsubscribe(Unsubscribe),
-    /// # ExitSubscribe 命令
-    ///
-    /// 退出订阅
-    ExitSubscribe(ExitSubscribe),
+    /// PUBLISH、SUBSCRIBE等发布/订阅命令，只在开启cmd-pubsub特性时存在
+    #[cfg(feature = "cmd-pubsub")]
+    PubSub(PubSub),
     /// # Ping 命令
     ///
     /// 检查服务器是否存活
@@ -56,10 +108,106 @@ pub enum Command {
     ///
     /// 保存数据库到RDB文件
     Save(Save),
+    /// # BgSave 命令
+    ///
+    /// 在后台保存数据库到RDB文件
+    BgSave(BgSave),
     /// # Del 命令
     /// 
     /// 删除key
     Del(Del),
+    /// # Expire 命令
+    ///
+    /// 为键设置过期时间，包括EXPIRE、PEXPIRE、EXPIREAT和PEXPIREAT
+    Expire(Expire),
+    /// # ExpireTime 命令
+    ///
+    /// 返回键过期的unix时间戳，包括EXPIRETIME和PEXPIRETIME
+    ExpireTime(ExpireTime),
+    /// # Command 命令
+    ///
+    /// 查看命令的元信息
+    Introspect(CommandCmd),
+    /// # Memory 命令
+    ///
+    /// 查看键占用的内存
+    Memory(Memory),
+    /// # Object 命令
+    ///
+    /// 查看键的内部信息，目前支持OBJECT IDLETIME、VERSION和FREQ
+    Object(Object),
+    /// # Scan 命令
+    ///
+    /// 增量遍历当前数据库中的键
+    Scan(Scan),
+    /// # Quit 命令
+    ///
+    /// 请求服务器关闭连接
+    Quit(Quit),
+    /// # Monitor 命令
+    ///
+    /// 把连接切换为监控模式，推送服务器执行的每一个命令
+    Monitor(Monitor),
+    /// # Info 命令
+    ///
+    /// 返回服务器的信息和统计数据
+    Info(Info),
+    /// # Config 命令
+    ///
+    /// 在运行时获取或修改服务器配置
+    Config(Config),
+    /// # ReadOnly 命令
+    ///
+    /// 将当前连接切换为只读模式
+    ReadOnly(ReadOnly),
+    /// # ReadWrite 命令
+    ///
+    /// 关闭当前连接的只读模式
+    ReadWrite(ReadWrite),
+    /// # Auth 命令
+    ///
+    /// 使用密码对当前连接进行认证
+    Auth(Auth),
+    /// # Hello 命令
+    ///
+    /// 切换连接使用的协议版本，并返回服务器的信息
+    Hello(Hello),
+    /// # Select 命令
+    ///
+    /// 切换当前连接使用的逻辑数据库
+    Select(Select),
+    /// # FlushDb 命令
+    ///
+    /// 清空当前逻辑数据库
+    FlushDb(FlushDb),
+    /// # FlushAll 命令
+    ///
+    /// 清空所有逻辑数据库
+    FlushAll(FlushAll),
+    /// # Stats 命令
+    ///
+    /// 返回服务器的统计数据
+    Stats(Stats),
+    /// # LatencyStats 命令
+    ///
+    /// 返回每个命令的调用次数和延迟直方图
+    LatencyStats(LatencyStats),
+    /// # Debug 命令
+    ///
+    /// 调试用的非标准命令
+    Debug(Debug),
+    /// # Client 命令
+    ///
+    /// 查看或管理客户端连接
+    Client(Client),
+    /// # Psync 命令
+    ///
+    /// 副本请求全量同步和之后的写命令
+    Psync(Psync),
+    /// # ReplicaOf 命令
+    ///
+    /// 成为另一个服务器的副本，或者停止复制
+    ReplicaOf(ReplicaOf),
     /// # Unknown命令
     ///
     /// 未知命令
@@ -75,19 +223,75 @@ impl Command {
             Command::Set(_) => "set",
             Command::Unknown(cmd) => cmd.get_name(),
             Command::Get(_) => "get",
-            Command::Publish(_) => "publish",
-            Command::Subscribe(_) => "subscribe",
-            Command::Unsubscribe(_) => "unsubscribe",
-            Command::ExitSubscribe(_) => "exitsubscribe",
+            Command::MGet(_) => "mget",
+            Command::GetRange(_) => "getrange",
+            #[cfg(feature = "cmd-pubsub")]
+            Command::PubSub(cmd) => cmd.get_name(),
             Command::Ping(_) => "ping",
             Command::Save(_) => "save",
+            Command::BgSave(_) => "bgsave",
             Command::Del(_) => "del",
+            Command::Expire(cmd) => cmd.get_name(),
+            Command::ExpireTime(cmd) => cmd.get_name(),
+            Command::Object(_) => "object",
+            Command::Scan(_) => "scan",
+            Command::Memory(_) => "memory",
+            Command::Introspect(_) => "command",
+            Command::Monitor(_) => "monitor",
+            Command::Quit(_) => "quit",
+            Command::Info(_) => "info",
+            Command::Config(_) => "config",
+            Command::ReadOnly(_) => "readonly",
+            Command::ReadWrite(_) => "readwrite",
+            Command::Auth(_) => "auth",
+            Command::Hello(_) => "hello",
+            Command::Select(_) => "select",
+            Command::FlushDb(_) => "flushdb",
+            Command::FlushAll(_) => "flushall",
+            Command::Stats(_) => "stats",
+            Command::LatencyStats(_) => "latencystats",
+            Command::Debug(_) => "debug",
+            Command::Client(_) => "client",
+            Command::Psync(_) => "psync",
+            Command::ReplicaOf(_) => "replicaof",
         }
     }
 
+    /// # is_write() 函数
+    ///
+    /// 根据命令表判断命令是否会修改数据
+    pub(crate) fn is_write(&self) -> bool {
+        table::lookup(self.get_name())
+            .map(|spec| spec.is_write())
+            .unwrap_or(false)
+    }
+
+    /// # is_deny_oom() 函数
+    ///
+    /// 根据命令表判断命令是否会在使用的内存超过maxmemory时被拒绝
+    pub(crate) fn is_deny_oom(&self) -> bool {
+        table::lookup(self.get_name())
+            .map(|spec| spec.is_deny_oom())
+            .unwrap_or(false)
+    }
+
+    /// # is_no_auth() 函数
+    ///
+    /// 根据命令表判断命令是否可以在认证之前执行
+    pub(crate) fn is_no_auth(&self) -> bool {
+        table::lookup(self.get_name())
+            .map(|spec| spec.is_no_auth())
+            .unwrap_or(false)
+    }
+
     /// # decode_cmd_from_frame() 函数
     ///
     /// 从数据帧中解码出命令
+    ///
+    /// # 错误处理
+    ///
+    /// - 帧不是数组帧或者命令名称无法解析时，返回协议错误，调用者应该关闭连接
+    /// - 命令参数错误时，返回带有命令名称的RustisError::Command，调用者可以把错误回复给客户端后继续处理下一个命令
     pub(crate) fn decode_cmd_from_frame(frame: Frame) -> crate::Result<Command> {
         // 帧必须是Array帧
         let mut parse = Parse::new(frame)?;
@@ -95,29 +299,87 @@ impl Command {
         // 获取命令名称，要将其转换为小写
         let cmd_name = parse.next_string()?.to_lowercase();
 
-        // 模式匹配命令
-        let cmd = match &cmd_name[..] {
-            "get" => Command::Get(Get::decode_get_from_frame(&mut parse)?),
-            "ping" => Command::Ping(Ping::decode_ping_from_frame(&mut parse)?),
-            "publish" => Command::Publish(Publish::decode_publish_from_frame(&mut parse)?),
-            "set" => Command::Set(Set::decode_set_from_frame(&mut parse)?),
-            "subscribe" => Command::Subscribe(Subscribe::decode_subscribe_from_frame(&mut parse)?),
-            "unsubscribe" => {
-                Command::Unsubscribe(Unsubscribe::decode_unsubscribe_from_frame(&mut parse)?)
+        // 先按照命令表检查参数个数，所有命令的参数个数错误都是同样的回复
+        if let Some(spec) = table::lookup(&cmd_name) {
+            if !spec.check_arity(parse.remaining() + 1) {
+                return Err(wrong_arity_error(&cmd_name));
             }
-            "exitsubscribe" => {
-                Command::ExitSubscribe(ExitSubscribe::decode_exit_subscribe_from_frame(&mut parse)?)
+        }
+
+        Self::decode_args(&cmd_name, &mut parse).map_err(|err| argument_error(&cmd_name, err))
+    }
+
+    /// # decode_args() 函数
+    ///
+    /// 根据命令名称解码命令的参数
+    fn decode_args(cmd_name: &str, parse: &mut Parse) -> crate::Result<Command> {
+        // 模式匹配命令
+        let cmd = match cmd_name {
+            "get" => Command::Get(Get::decode_get_from_frame(parse)?),
+            "mget" => Command::MGet(MGet::decode_mget_from_frame(parse)?),
+            "getrange" => Command::GetRange(GetRange::decode_getrange_from_frame(parse)?),
+            "ping" => Command::Ping(Ping::decode_ping_from_frame(parse)?),
+            #[cfg(feature = "cmd-pubsub")]
+            "publish" | "subscribe" | "unsubscribe" | "exitsubscribe" => {
+                Command::PubSub(PubSub::decode_pubsub_from_frame(cmd_name, parse)?)
             }
+            "set" => Command::Set(Set::decode_set_from_frame(parse)?),
             "save" => Command::Save(Save::decode_save_from_frame()?),
-            "del" => Command::Del(Del::decode_del_from_frame(&mut parse)?),
+            "bgsave" => Command::BgSave(BgSave::new()),
+            "del" => Command::Del(Del::decode_del_from_frame(parse)?),
+            "expire" => Command::Expire(Expire::decode_expire_from_frame(
+                ExpireUnit::Seconds,
+                parse,
+            )?),
+            "pexpire" => Command::Expire(Expire::decode_expire_from_frame(
+                ExpireUnit::Milliseconds,
+                parse,
+            )?),
+            "expireat" => Command::Expire(Expire::decode_expire_from_frame(
+                ExpireUnit::UnixSeconds,
+                parse,
+            )?),
+            "expiretime" => {
+                Command::ExpireTime(ExpireTime::decode_expiretime_from_frame(parse, false)?)
+            }
+            "pexpiretime" => {
+                Command::ExpireTime(ExpireTime::decode_expiretime_from_frame(parse, true)?)
+            }
+            "pexpireat" => Command::Expire(Expire::decode_expire_from_frame(
+                ExpireUnit::UnixMilliseconds,
+                parse,
+            )?),
+            "object" => Command::Object(Object::decode_object_from_frame(parse)?),
+            "scan" => Command::Scan(Scan::decode_scan_from_frame(parse)?),
+            "memory" => Command::Memory(Memory::decode_memory_from_frame(parse)?),
+            "command" => Command::Introspect(CommandCmd::decode_command_from_frame(parse)?),
+            "monitor" => Command::Monitor(Monitor::new()),
+            "quit" => Command::Quit(Quit::new()),
+            "info" => Command::Info(Info::decode_info_from_frame(parse)?),
+            "config" => Command::Config(Config::decode_config_from_frame(parse)?),
+            "readonly" => Command::ReadOnly(ReadOnly::new()),
+            "readwrite" => Command::ReadWrite(ReadWrite::new()),
+            "auth" => Command::Auth(Auth::decode_auth_from_frame(parse)?),
+            "hello" => Command::Hello(Hello::decode_hello_from_frame(parse)?),
+            "select" => Command::Select(Select::decode_select_from_frame(parse)?),
+            "flushdb" => Command::FlushDb(FlushDb::decode_flushdb_from_frame(parse)?),
+            "flushall" => Command::FlushAll(FlushAll::decode_flushall_from_frame(parse)?),
+            "stats" => Command::Stats(Stats::new()),
+            "latencystats" => Command::LatencyStats(LatencyStats::new()),
+            "debug" => Command::Debug(Debug::decode_debug_from_frame(parse)?),
+            "client" => Command::Client(Client::decode_client_from_frame(parse)?),
+            "psync" => Command::Psync(Psync::new()),
+            "replicaof" => Command::ReplicaOf(ReplicaOf::decode_replicaof_from_frame(parse)?),
             _ => {
                 // 如果命令未知，那么返回Unknown命令
                 return Ok(Command::Unknown(Unknown::new(cmd_name)));
             }
         };
 
-        // 检查parse中是否有未使用的帧
-        parse.is_finish()?;
+        // 检查parse中是否有未使用的帧，多余的参数和缺少参数一样按参数个数错误处理
+        if parse.is_finish().is_err() {
+            return Err(ParseError::EndOfStream.into());
+        }
 
         // 返回命令
         Ok(cmd)
@@ -126,24 +388,73 @@ impl Command {
     /// # apply() 函数
     ///
     /// 对特定数据库应用命令，这函数由服务器执行
-    #[instrument(skip(self, database, connection, shutdown))]
+    #[instrument(skip(self, database, connection, shutdown, session))]
     pub(crate) async fn apply(
         self,
         database: &Database,
         connection: &mut Connection,
         shutdown: &mut Shutdown,
+        session: &mut Session,
     ) -> crate::Result<()> {
         match self {
             Command::Set(cmd) => cmd.apply(database, connection).await,
             Command::Get(cmd) => cmd.apply(database, connection).await,
-            Command::Publish(cmd) => cmd.apply(database, connection).await,
-            Command::Subscribe(cmd) => cmd.apply(database, connection, shutdown).await,
-            Command::Unsubscribe(_) => Err("Unsubscribe is unsupported in this context".into()),
+            Command::MGet(cmd) => cmd.apply(database, connection).await,
+            Command::GetRange(cmd) => cmd.apply(database, connection).await,
+            #[cfg(feature = "cmd-pubsub")]
+            Command::PubSub(cmd) => cmd.apply(database, connection, shutdown).await,
             Command::Ping(cmd) => cmd.apply(connection).await,
             Command::Unknown(cmd) => cmd.apply(connection).await,
-            Command::ExitSubscribe(_) => Ok(()),
             Command::Save(cmd) => cmd.apply(database, connection).await,
+            Command::BgSave(cmd) => cmd.apply(database, connection).await,
             Command::Del(cmd) => cmd.apply(database, connection).await,
+            Command::Expire(cmd) => cmd.apply(database, connection).await,
+            Command::ExpireTime(cmd) => cmd.apply(database, connection).await,
+            Command::Object(cmd) => cmd.apply(database, connection).await,
+            Command::Scan(cmd) => cmd.apply(database, connection).await,
+            Command::Memory(cmd) => cmd.apply(database, connection).await,
+            Command::Introspect(cmd) => cmd.apply(connection).await,
+            Command::Monitor(cmd) => cmd.apply(database, connection, shutdown).await,
+            Command::Quit(cmd) => cmd.apply(connection, session).await,
+            Command::Info(cmd) => cmd.apply(database, connection).await,
+            Command::Config(cmd) => cmd.apply(database, connection).await,
+            Command::ReadOnly(cmd) => cmd.apply(connection, session).await,
+            Command::ReadWrite(cmd) => cmd.apply(connection, session).await,
+            Command::Auth(cmd) => cmd.apply(database, connection, session).await,
+            Command::Hello(cmd) => cmd.apply(database, connection, session).await,
+            Command::Select(cmd) => cmd.apply(database, connection, session).await,
+            Command::FlushDb(cmd) => cmd.apply(database, connection).await,
+            Command::FlushAll(cmd) => cmd.apply(database, connection).await,
+            Command::Stats(cmd) => cmd.apply(database, connection).await,
+            Command::LatencyStats(cmd) => cmd.apply(database, connection).await,
+            Command::Debug(cmd) => cmd.apply(database, connection).await,
+            Command::Client(cmd) => cmd.apply(connection).await,
+            Command::Psync(cmd) => cmd.apply(database, connection, shutdown).await,
+            Command::ReplicaOf(cmd) => cmd.apply(database, connection).await,
         }
     }
 }
+
+/// # argument_error() 函数
+///
+/// 把命令参数的解码错误转换为错误回复，只有参数个数错误的回复带有命令名称，其他错误与redis一样原样回复
+fn argument_error(cmd_name: &str, err: crate::Error) -> crate::Error {
+    if let Some(ParseError::EndOfStream) = err.downcast_ref::<ParseError>() {
+        return wrong_arity_error(cmd_name);
+    }
+
+    let msg = err.to_string();
+    let msg = msg.strip_prefix("ERR ").unwrap_or(&msg);
+    RustisError::Command(format!("ERR {}", msg)).into()
+}
+
+/// # wrong_arity_error() 函数
+///
+/// 参数个数错误的回复，与redis的错误信息相同
+fn wrong_arity_error(cmd_name: &str) -> crate::Error {
+    RustisError::Command(format!(
+        "ERR wrong number of arguments for '{}' command",
+        cmd_name
+    ))
+    .into()
+}
@@ -0,0 +1,221 @@
+//! monitor命令的实现
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use bytes::Bytes;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{debug, instrument, warn};
+
+use crate::{
+    networking::{connection::Connection, frame::Frame},
+    persistence::database::Database,
+    server::shutdown::Shutdown,
+};
+
+/// # Monitor 结构体
+///
+/// 把连接切换为监控模式，之后服务器执行的每一个命令都会以一行文本推送给这个连接，
+/// 格式与redis相同：`时间戳 [数据库编号 客户端地址] "命令" "参数"...`
+///
+/// 监控模式下连接不能再执行其他命令
+///
+/// # 语法
+///
+/// MONITOR
+#[derive(Debug, Default)]
+pub struct Monitor;
+
+impl Monitor {
+    pub(crate) fn new() -> Monitor {
+        Monitor
+    }
+
+    /// # code_monitor_into_frame() 函数
+    ///
+    /// 将monitor命令编码为帧
+    pub(crate) fn code_monitor_into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("monitor".as_bytes()));
+        frame
+    }
+
+    /// # apply() 函数
+    ///
+    /// 应用monitor命令，直到连接关闭或者服务器关闭
+    #[instrument(skip(self, db, connection, shutdown))]
+    pub(crate) async fn apply(
+        self,
+        db: &Database,
+        connection: &mut Connection,
+        shutdown: &mut Shutdown,
+    ) -> crate::Result<()> {
+        let mut lines = db.subscribe_monitor();
+        connection
+            .write_frame(&Frame::Simple("OK".to_string()))
+            .await?;
+
+        loop {
+            tokio::select! {
+                line = lines.recv() => match line {
+                    Ok(line) => {
+                        connection.write_frame_nowait(&Frame::Simple(line)).await?;
+                        // 没有更多等待推送的命令时再flush
+                        if lines.is_empty() {
+                            connection.flush().await?;
+                        }
+                    }
+                    Err(RecvError::Lagged(count)) => {
+                        warn!(count, "monitor lagged, commands skipped");
+                    }
+                    Err(RecvError::Closed) => return Ok(()),
+                },
+                frame = connection.read_frame() => match frame? {
+                    Some(frame) => {
+                        debug!(?frame, "command rejected in monitor mode");
+                        let response = Frame::Error("ERR Command not allowed in MONITOR mode".to_string());
+                        connection.write_frame(&response).await?;
+                    }
+                    // 客户端关闭了连接
+                    None => return Ok(()),
+                },
+                _ = shutdown.receiving() => return Ok(()),
+            }
+        }
+    }
+}
+
+/// 推送给监控连接时替换密码参数的文本，与redis相同
+const REDACTED: &[u8] = b"(redacted)";
+
+/// 值是密码的配置参数，CONFIG SET时不把值推送给监控连接
+const SECRET_PARAMETERS: &[&str] = &["requirepass", "masterauth"];
+
+/// # format_monitor_line() 函数
+///
+/// 把一个命令帧格式化为推送给监控连接的一行文本，参数中的引号、反斜杠和不可打印字符会被转义。
+/// HELLO的AUTH参数和CONFIG SET设置的密码被替换为`(redacted)`
+pub(crate) fn format_monitor_line(db: usize, peer: &str, frame: &Frame) -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let mut line = format!(
+        "{}.{:06} [{} {}]",
+        now.as_secs(),
+        now.subsec_micros(),
+        db,
+        peer
+    );
+
+    if let Frame::Array(args) = frame {
+        let args: Vec<Bytes> = args
+            .iter()
+            .map(|arg| match arg {
+                Frame::Bulk(bytes) => bytes.clone(),
+                Frame::Simple(s) => Bytes::from(s.clone()),
+                arg => Bytes::from(arg.to_string()),
+            })
+            .collect();
+
+        for (index, arg) in args.iter().enumerate() {
+            line.push(' ');
+            if is_secret_arg(&args, index) {
+                push_quoted(&mut line, REDACTED);
+            } else {
+                push_quoted(&mut line, arg);
+            }
+        }
+    }
+
+    line
+}
+
+/// # is_secret_arg() 函数
+///
+/// 命令的第index个参数是否是密码：`HELLO <ver> AUTH <user> <pass>`中AUTH之后的两个参数，
+/// 以及`CONFIG SET`中密码类配置参数的值
+fn is_secret_arg(args: &[Bytes], index: usize) -> bool {
+    let is = |i: usize, name: &str| {
+        args.get(i)
+            .is_some_and(|arg| arg.eq_ignore_ascii_case(name.as_bytes()))
+    };
+
+    if is(0, "hello") {
+        return (2..index).any(|i| is(i, "auth") && index - i <= 2);
+    }
+
+    if is(0, "config") && is(1, "set") && index >= 3 && index % 2 == 1 {
+        return SECRET_PARAMETERS.iter().any(|name| is(index - 1, name));
+    }
+
+    false
+}
+
+/// # push_quoted() 函数
+///
+/// 把参数用双引号括起来追加到line中
+fn push_quoted(line: &mut String, arg: &[u8]) {
+    line.push('"');
+    for &byte in arg {
+        match byte {
+            b'"' => line.push_str("\\\""),
+            b'\\' => line.push_str("\\\\"),
+            b'\n' => line.push_str("\\n"),
+            b'\r' => line.push_str("\\r"),
+            b'\t' => line.push_str("\\t"),
+            0x20..=0x7e => line.push(byte as char),
+            _ => line.push_str(&format!("\\x{:02x}", byte)),
+        }
+    }
+    line.push('"');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 测试参数被引号括起来，特殊字符被转义
+    #[test]
+    fn test_format_monitor_line() {
+        let frame = Frame::Array(vec![
+            Frame::Bulk(Bytes::from("set")),
+            Frame::Bulk(Bytes::from("key")),
+            Frame::Bulk(Bytes::from(&b"say \"hi\"\n\xff"[..])),
+            Frame::Integer(100),
+        ]);
+        let line = format_monitor_line(3, "127.0.0.1:6000", &frame);
+
+        let (timestamp, rest) = line.split_once(' ').unwrap();
+        assert!(timestamp.parse::<f64>().is_ok());
+        assert_eq!(
+            rest,
+            r#"[3 127.0.0.1:6000] "set" "key" "say \"hi\"\n\xff" "100""#
+        );
+    }
+
+    /// 测试HELLO的AUTH参数和CONFIG SET设置的密码不会出现在推送的文本中
+    #[test]
+    fn test_format_monitor_line_redacts_secrets() {
+        let line = |args: &[&str]| {
+            let frame = Frame::Array(
+                args.iter()
+                    .map(|arg| Frame::Bulk(Bytes::from(arg.to_string())))
+                    .collect(),
+            );
+            let line = format_monitor_line(0, "127.0.0.1:6000", &frame);
+            line.split_once("] ").unwrap().1.to_string()
+        };
+
+        assert_eq!(
+            line(&["HELLO", "3", "AUTH", "default", "secret", "SETNAME", "conn"]),
+            r#""HELLO" "3" "AUTH" "(redacted)" "(redacted)" "SETNAME" "conn""#
+        );
+        assert_eq!(
+            line(&["config", "SET", "MasterAuth", "secret"]),
+            r#""config" "SET" "MasterAuth" "(redacted)""#
+        );
+        assert_eq!(
+            line(&["config", "set", "maxmemory", "100"]),
+            r#""config" "set" "maxmemory" "100""#
+        );
+    }
+}
@@ -0,0 +1,96 @@
+//! object命令的实现
+
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+use crate::{
+    networking::{connection::Connection, frame::Frame, parse::Parse},
+    persistence::database::Database,
+};
+
+/// # Object 枚举
+///
+/// 查看键的内部信息
+///
+/// # 语法
+///
+/// - OBJECT IDLETIME key：返回键距离上次被访问的秒数，键不存在时返回nil
+/// - OBJECT VERSION key：返回键最后一次被修改时的版本号，键不存在时返回nil
+/// - OBJECT FREQ key：返回键的LFU访问频率计数器，键不存在时返回nil
+#[derive(Debug)]
+pub enum Object {
+    /// OBJECT IDLETIME key
+    IdleTime(String),
+    /// OBJECT VERSION key
+    Version(String),
+    /// OBJECT FREQ key
+    Freq(String),
+}
+
+impl Object {
+    /// # decode_object_from_frame() 函数
+    ///
+    /// 将帧解码为object命令
+    pub(crate) fn decode_object_from_frame(parse: &mut Parse) -> crate::Result<Object> {
+        let subcommand = parse.next_string()?.to_lowercase();
+        match subcommand.as_str() {
+            "idletime" => Ok(Object::IdleTime(parse.next_string()?)),
+            "version" => Ok(Object::Version(parse.next_string()?)),
+            "freq" => Ok(Object::Freq(parse.next_string()?)),
+            _ => Err(format!("ERR unknown subcommand '{}'", subcommand).into()),
+        }
+    }
+
+    /// # code_object_into_frame() 函数
+    ///
+    /// 将object命令编码为帧
+    pub(crate) fn code_object_into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("object".as_bytes()));
+        match self {
+            Object::IdleTime(key) => {
+                frame.push_bulk(Bytes::from("idletime".as_bytes()));
+                frame.push_bulk(Bytes::from(key.into_bytes()));
+            }
+            Object::Version(key) => {
+                frame.push_bulk(Bytes::from("version".as_bytes()));
+                frame.push_bulk(Bytes::from(key.into_bytes()));
+            }
+            Object::Freq(key) => {
+                frame.push_bulk(Bytes::from("freq".as_bytes()));
+                frame.push_bulk(Bytes::from(key.into_bytes()));
+            }
+        }
+        frame
+    }
+
+    /// # apply() 函数
+    ///
+    /// 应用object命令，并将响应写入到Connection实例
+    #[instrument(skip(self, db, connection))]
+    pub(crate) async fn apply(
+        self,
+        db: &Database,
+        connection: &mut Connection,
+    ) -> crate::Result<()> {
+        let response = match self {
+            Object::IdleTime(key) => match db.idle_time(&key) {
+                Some(idle) => Frame::Integer(idle.as_secs() as i64),
+                None => Frame::Null,
+            },
+            Object::Version(key) => match db.key_version(&key) {
+                Some(version) => Frame::Integer(version as i64),
+                None => Frame::Null,
+            },
+            Object::Freq(key) => match db.access_frequency(&key) {
+                Some(freq) => Frame::Integer(freq as i64),
+                None => Frame::Null,
+            },
+        };
+        debug!(?response);
+
+        connection.write_frame_nowait(&response).await?;
+
+        Ok(())
+    }
+}
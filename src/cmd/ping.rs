@@ -59,7 +59,7 @@ impl Ping {
         debug!(?response);
 
         // 将响应写入到Connection实例
-        connection.write_frame(&response).await?;
+        connection.write_frame_nowait(&response).await?;
 
         Ok(())
     }
@@ -0,0 +1,120 @@
+//! psync命令的实现
+
+use bytes::Bytes;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{debug, instrument};
+
+use crate::{
+    cmd::{select::Select, set::Set},
+    networking::{connection::Connection, frame::Frame},
+    persistence::database::Database,
+    server::shutdown::Shutdown,
+};
+
+/// # Psync 结构体
+///
+/// 副本向主节点发送的内部命令，请求全量同步和之后的写命令
+///
+/// 主节点先回复+FULLRESYNC，然后以SELECT和SET命令的形式发送所有数据，之后转发写操作的结果，
+/// 直到连接关闭。副本落后太多时连接会被关闭，副本重新连接后再进行一次全量同步
+///
+/// # 语法
+///
+/// PSYNC
+#[derive(Debug, Default)]
+pub struct Psync;
+
+impl Psync {
+    pub(crate) fn new() -> Psync {
+        Psync
+    }
+
+    /// # code_psync_into_frame() 函数
+    ///
+    /// 将psync命令编码为帧
+    pub(crate) fn code_psync_into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("psync".as_bytes()));
+        frame
+    }
+
+    /// # apply() 函数
+    ///
+    /// 应用psync命令，连接之后只用于向副本发送命令
+    #[instrument(skip(self, db, connection, shutdown))]
+    pub(crate) async fn apply(
+        self,
+        db: &Database,
+        connection: &mut Connection,
+        shutdown: &mut Shutdown,
+    ) -> crate::Result<()> {
+        // 先注册再生成快照，快照之后的写命令一定会被转发。注册之后、快照之前的写命令可能会在快照之后重复执行，
+        // 转发的是写入的结果（SET的过期时间使用PXAT，EXPIRE系列使用PEXPIREAT），不带条件，
+        // 过期时间也是绝对的时间戳，所以按顺序重复执行后的结果与主节点相同
+        let mut commands = db.subscribe_replication();
+
+        connection
+            .write_frame_nowait(&Frame::Simple("FULLRESYNC".to_string()))
+            .await?;
+
+        let mut selected = None;
+        for (index, entries) in db.snapshot().into_iter().enumerate() {
+            for (key, value, expire_at) in entries {
+                select_db(connection, &mut selected, index).await?;
+                let set = Set::new(key, value, None);
+                let set = match expire_at {
+                    Some(unix_millis) => set.expire_at(unix_millis),
+                    None => set,
+                };
+                let frame = set.code_set_into_frame();
+                connection.write_frame_nowait(&frame).await?;
+            }
+        }
+        connection.flush().await?;
+        debug!("full resync sent to replica");
+
+        loop {
+            tokio::select! {
+                command = commands.recv() => match command {
+                    Ok((index, frame)) => {
+                        select_db(connection, &mut selected, index).await?;
+                        connection.write_frame_nowait(&frame).await?;
+                        // 没有更多等待转发的命令时再flush
+                        if commands.is_empty() {
+                            connection.flush().await?;
+                        }
+                    }
+                    Err(RecvError::Lagged(skipped)) => {
+                        return Err(format!("replica lagged behind by {} commands", skipped).into());
+                    }
+                    Err(RecvError::Closed) => return Ok(()),
+                },
+                // 副本执行命令后的响应，直接丢弃
+                res = connection.read_frame() => {
+                    if res?.is_none() {
+                        return Ok(());
+                    }
+                }
+                _ = shutdown.receiving() => {
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+/// # select_db() 函数
+///
+/// 副本当前选择的逻辑数据库与命令的不同时，先发送一个SELECT命令
+async fn select_db(
+    connection: &mut Connection,
+    selected: &mut Option<usize>,
+    index: usize,
+) -> crate::Result<()> {
+    if *selected != Some(index) {
+        let frame = Select::new(index as u64).code_select_into_frame();
+        connection.write_frame_nowait(&frame).await?;
+        *selected = Some(index);
+    }
+    Ok(())
+}
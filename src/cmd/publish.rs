@@ -1,12 +1,10 @@
 //! publish命令实现
+//!
+//! 这里只有客户端使用的编码，不受cmd-pubsub特性影响，服务端的解码和执行在pubsub模块中
 
 use bytes::Bytes;
-use tracing::instrument;
 
-use crate::{
-    networking::{connection::Connection, frame::Frame, parse::Parse},
-    persistence::database::Database,
-};
+use crate::networking::frame::Frame;
 
 /// # Publish 结构体
 ///
@@ -14,9 +12,9 @@ use crate::{
 #[derive(Debug)]
 pub struct Publish {
     /// 发布消息的channel名
-    channel: String,
+    pub(super) channel: String,
     /// 发布的消息内容
-    message: Bytes,
+    pub(super) message: Bytes,
 }
 
 impl Publish {
@@ -27,16 +25,6 @@ impl Publish {
         }
     }
 
-    /// # decode_publish_from_frame() 函数
-    ///
-    /// 将帧解码为publish命令
-    pub(crate) fn decode_publish_from_frame(parse: &mut Parse) -> crate::Result<Self> {
-        let channel = parse.next_string()?;
-        let message = parse.next_bytes()?;
-
-        Ok(Self::new(channel, message))
-    }
-
     /// # code_publish_into_frame() 函数
     ///
     /// 将publish命令编码为帧
@@ -48,21 +36,4 @@ impl Publish {
 
         frame
     }
-
-    /// # apply() 函数
-    ///
-    /// 应用publish命令，并将响应写入到Connection实例
-    #[instrument(skip(self, database, connection))]
-    pub(crate) async fn apply(
-        self,
-        database: &Database,
-        connection: &mut Connection,
-    ) -> crate::Result<()> {
-        let num_subscribers = database.publish(&self.channel, self.message);
-
-        let response = Frame::Integer(num_subscribers as u64);
-        connection.write_frame(&response).await?;
-
-        Ok(())
-    }
 }
@@ -0,0 +1,609 @@
+//! 发布/订阅命令在服务端的实现：命令的解码和执行，以及订阅模式下的消息投递
+//!
+//! 整个模块只在开启cmd-pubsub特性时编译，关闭时这些命令被当作未知命令处理。
+//! 客户端使用的编码在publish和subscribe模块中，不受特性影响
+
+use std::{future::Future, io, pin::Pin, vec};
+
+use bytes::Bytes;
+use futures::FutureExt;
+use tokio::{
+    sync::broadcast,
+    time::{self, Duration, Instant, Interval},
+};
+use tokio_stream::{Stream, StreamExt, StreamMap};
+use tracing::{debug, instrument, warn};
+
+use crate::{
+    networking::{
+        connection::Connection,
+        frame::Frame,
+        parse::{
+            Parse,
+            ParseError::{self, EndOfStream},
+        },
+    },
+    persistence::database::Database,
+    server::{config::PubSubDelivery, shutdown::Shutdown},
+    RustisError,
+};
+
+use super::{
+    publish::Publish,
+    subscribe::{ExitSubscribe, Subscribe, Unsubscribe},
+    table::CommandSpec,
+    Command, Unknown,
+};
+
+/// 一次最多连续写入多少条已经到达的消息再flush，避免消息源源不断时饿死客户端的请求和关闭信号
+const MAX_DELIVERY_BATCH: usize = 128;
+
+/// 发布/订阅命令的元信息，开启cmd-pubsub特性时加入命令表
+pub(crate) const COMMAND_TABLE: &[CommandSpec] = &[
+    CommandSpec {
+        name: "publish",
+        flags: &["pubsub", "fast"],
+        arity: 3,
+        first_key: 0,
+        last_key: 0,
+        key_step: 0,
+    },
+    CommandSpec {
+        name: "subscribe",
+        flags: &["pubsub"],
+        arity: -2,
+        first_key: 0,
+        last_key: 0,
+        key_step: 0,
+    },
+    CommandSpec {
+        name: "unsubscribe",
+        flags: &["pubsub"],
+        arity: -1,
+        first_key: 0,
+        last_key: 0,
+        key_step: 0,
+    },
+    CommandSpec {
+        name: "exitsubscribe",
+        flags: &["pubsub"],
+        arity: 1,
+        first_key: 0,
+        last_key: 0,
+        key_step: 0,
+    },
+];
+
+/// # PubSub 枚举
+///
+/// 发布/订阅相关的命令
+#[derive(Debug)]
+pub enum PubSub {
+    /// # Publish 命令
+    ///
+    /// 将消息发布到指定的频道
+    Publish(Publish),
+    /// # Subscribe 命令
+    ///
+    /// 订阅一个或多个频道
+    Subscribe(Subscribe),
+    /// # Unsubscribe 命令
+    ///
+    /// 退订一个或多个频道
+    Unsubscribe(Unsubscribe),
+    /// # ExitSubscribe 命令
+    ///
+    /// 退出订阅
+    ExitSubscribe(ExitSubscribe),
+}
+
+impl PubSub {
+    /// # decode_pubsub_from_frame() 函数
+    ///
+    /// 根据命令名称解码发布/订阅命令的参数，cmd_name必须是COMMAND_TABLE中的一个命令
+    pub(crate) fn decode_pubsub_from_frame(
+        cmd_name: &str,
+        parse: &mut Parse,
+    ) -> crate::Result<PubSub> {
+        let cmd = match cmd_name {
+            "publish" => PubSub::Publish(Publish::decode_publish_from_frame(parse)?),
+            "subscribe" => PubSub::Subscribe(Subscribe::decode_subscribe_from_frame(parse)?),
+            "unsubscribe" => {
+                PubSub::Unsubscribe(Unsubscribe::decode_unsubscribe_from_frame(parse)?)
+            }
+            "exitsubscribe" => {
+                PubSub::ExitSubscribe(ExitSubscribe::decode_exit_subscribe_from_frame(parse)?)
+            }
+            _ => unreachable!("{} is not a pubsub command", cmd_name),
+        };
+        Ok(cmd)
+    }
+
+    /// # get_name() 函数
+    ///
+    /// 返回命令的名称
+    pub(crate) fn get_name(&self) -> &str {
+        match self {
+            PubSub::Publish(_) => "publish",
+            PubSub::Subscribe(_) => "subscribe",
+            PubSub::Unsubscribe(_) => "unsubscribe",
+            PubSub::ExitSubscribe(_) => "exitsubscribe",
+        }
+    }
+
+    /// # apply() 函数
+    ///
+    /// 在普通模式下应用发布/订阅命令，SUBSCRIBE会让连接进入订阅模式，直到退订所有channel或者连接关闭
+    pub(crate) async fn apply(
+        self,
+        database: &Database,
+        connection: &mut Connection,
+        shutdown: &mut Shutdown,
+    ) -> crate::Result<()> {
+        match self {
+            PubSub::Publish(cmd) => cmd.apply(database, connection).await,
+            PubSub::Subscribe(cmd) => cmd.apply(database, connection, shutdown).await,
+            // 只能在订阅模式下使用的命令回复错误，连接保持可用
+            cmd @ (PubSub::Unsubscribe(_) | PubSub::ExitSubscribe(_)) => {
+                reply_outside_subscribe_mode(cmd.get_name(), connection).await
+            }
+        }
+    }
+}
+
+/// # reply_outside_subscribe_mode() 函数
+///
+/// 在普通模式下收到只能在订阅模式下使用的命令时回复错误
+async fn reply_outside_subscribe_mode(
+    cmd_name: &str,
+    connection: &mut Connection,
+) -> crate::Result<()> {
+    let response = Frame::Error(format!(
+        "ERR {} can't be used outside subscribe mode",
+        cmd_name.to_uppercase()
+    ));
+    debug!(?response);
+
+    connection.write_frame_nowait(&response).await?;
+
+    Ok(())
+}
+
+impl Publish {
+    /// # decode_publish_from_frame() 函数
+    ///
+    /// 将帧解码为publish命令
+    pub(crate) fn decode_publish_from_frame(parse: &mut Parse) -> crate::Result<Self> {
+        let channel = parse.next_string()?;
+        let message = parse.next_bytes()?;
+
+        Ok(Self::new(channel, message))
+    }
+
+    /// # apply() 函数
+    ///
+    /// 应用publish命令，并将响应写入到Connection实例
+    #[instrument(skip(self, database, connection))]
+    pub(crate) async fn apply(
+        self,
+        database: &Database,
+        connection: &mut Connection,
+    ) -> crate::Result<()> {
+        let num_subscribers = database.publish(&self.channel, self.message).await;
+
+        let response = Frame::Integer(num_subscribers as i64);
+        connection.write_frame_nowait(&response).await?;
+
+        Ok(())
+    }
+}
+
+impl Subscribe {
+    /// # decode_subscribe_from_frame() 函数
+    ///
+    /// 将帧解码为subscribe命令
+    pub(crate) fn decode_subscribe_from_frame(parse: &mut Parse) -> crate::Result<Subscribe> {
+        use ParseError::EndOfStream;
+
+        let mut channels = vec![parse.next_string()?];
+
+        loop {
+            match parse.next_string() {
+                Ok(channel) => channels.push(channel),
+                Err(EndOfStream) => break,
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        Ok(Self::new(channels))
+    }
+
+    /// # apply() 函数
+    ///
+    /// 应用Subscribe命令，并将响应写入到Connection实例
+    #[instrument(skip(self, database, connection, shutdown))]
+    pub(crate) async fn apply(
+        mut self,
+        database: &Database,
+        connection: &mut Connection,
+        shutdown: &mut Shutdown,
+    ) -> crate::Result<()> {
+        // 一个客户端可以订阅多个channel，并且可以动态地添加和移除其订阅的频道
+        // 为了处理多个channel的订阅，使用StreamMap来跟踪活跃的订阅
+        // 无论以何种方式退出订阅模式，guard被drop时都会清理没有订阅者的channel
+        let mut guard = SubscriptionsGuard {
+            database,
+            subscriptions: StreamMap::new(),
+        };
+        let subscriptions = &mut guard.subscriptions;
+
+        // 开启心跳时，定期向订阅者推送pong帧，让失效的连接尽快暴露出写错误。
+        // 退订了所有channel之后不再推送，客户端读完最后一个退订确认后，连接上不会再有推送的帧
+        let heartbeat = database.config().subscriber_heartbeat;
+        let mut ticker = (heartbeat > 0).then(|| {
+            let period = Duration::from_secs(heartbeat);
+            time::interval_at(Instant::now() + period, period)
+        });
+
+        loop {
+            // 将需要订阅的channel添加到StreamMap中，所有channel的确认只flush一次
+            let was_empty = subscriptions.is_empty();
+            connection.cork(true);
+            for channel_name in self.channels.drain(..) {
+                subscribe_to_channel(channel_name.clone(), subscriptions, database, connection)
+                    .await?;
+            }
+            connection.cork(false);
+            connection.flush().await?;
+
+            // 重新开始订阅时，下一次心跳从现在开始计算
+            if was_empty && !subscriptions.is_empty() {
+                if let Some(ticker) = ticker.as_mut() {
+                    ticker.reset();
+                }
+            }
+
+            // 等待以下事件发生：
+            // - 从一个订阅channel接收消息
+            // - 从客户端接收订阅或取消订阅的请求
+            // - 服务器关闭信号
+            tokio::select! {
+                // 从订阅channel接收消息
+                Some((channel_name, delivery)) = subscriptions.next() => {
+                    let deliveries = write_deliveries(connection, subscriptions, channel_name, delivery);
+                    with_heartbeat(heartbeat, deliveries).await?;
+                }
+                // 心跳，没有任何订阅时停止
+                _ = tick(&mut ticker), if !subscriptions.is_empty() => {
+                    with_heartbeat(heartbeat, connection.write_frame(&create_pong_frame())).await?;
+                }
+                // 从客户端接收订阅或取消订阅的请求
+                res = connection.read_frame() => {
+                    let frame = match res? {
+                        Some(frame) => frame,
+                        // 如果远程客户端已经关闭连接，则走到这里
+                        None => return Ok(())
+                    };
+
+                    if !handle_command(frame, &mut self.channels, subscriptions, database, connection).await? {
+                        return Ok(());
+                    }
+                }
+                _ = shutdown.receiving() => {
+                    return Ok(());
+                }
+            };
+        }
+    }
+}
+
+impl Unsubscribe {
+    /// # decode_unsubscribe_from_frame() 函数
+    ///
+    /// 将帧解码为unsubscribe命令
+    pub(crate) fn decode_unsubscribe_from_frame(
+        parse: &mut Parse,
+    ) -> Result<Unsubscribe, ParseError> {
+        let mut channels = vec![];
+
+        loop {
+            match parse.next_string() {
+                Ok(s) => channels.push(s),
+                Err(EndOfStream) => break,
+                Err(err) => return Err(err),
+            }
+        }
+
+        Ok(Unsubscribe::new(channels))
+    }
+}
+
+impl ExitSubscribe {
+    /// # decode_exit_subscribe_from_frame() 函数
+    ///
+    /// 将帧解码为exit_subscribe命令
+    pub(crate) fn decode_exit_subscribe_from_frame(
+        parse: &mut Parse,
+    ) -> Result<ExitSubscribe, ParseError> {
+        parse.is_finish()?;
+        Ok(ExitSubscribe::new())
+    }
+}
+
+type Messages = Pin<Box<dyn Stream<Item = Delivery> + Send>>;
+
+/// # SubscriptionsGuard 结构体
+///
+/// 持有一个连接的所有订阅，被drop时（正常退出、连接出错或者任务被取消）先释放所有的Receiver，
+/// 再删除已经没有订阅者的channel，避免订阅过的channel一直留在pub_sub中
+struct SubscriptionsGuard<'a> {
+    database: &'a Database,
+    subscriptions: StreamMap<String, Messages>,
+}
+
+impl Drop for SubscriptionsGuard<'_> {
+    fn drop(&mut self) {
+        let channels: Vec<String> = self.subscriptions.keys().cloned().collect();
+        self.subscriptions.clear();
+
+        for channel in channels {
+            self.database.remove_unused_channel(&channel);
+        }
+    }
+}
+
+/// # Delivery 枚举
+///
+/// 订阅的channel中产生的事件
+enum Delivery {
+    /// 收到一条消息
+    Message(Bytes),
+    /// 订阅者跟不上发布的速度，有count条消息被丢弃
+    Dropped(u64),
+}
+
+/// # create_subscribe_response_frame() 函数
+///
+/// 创建一个subscribe响应帧
+fn create_subscribe_response_frame(channel_name: String, num_subs: usize) -> Frame {
+    let mut response = Frame::array();
+    response.push_bulk(Bytes::from_static(b"subscribe"));
+    response.push_bulk(Bytes::from(channel_name));
+    response.push_int(num_subs as i64);
+    response
+}
+
+/// # create_unsubscribe_response_frame() 函数
+///
+/// 创建一个unsubscribe响应帧
+fn create_unsubscribe_response_frame(channel_name: String, num_subs: usize) -> Frame {
+    let mut response = Frame::array();
+    response.push_bulk(Bytes::from_static(b"unsubscribe"));
+    response.push_bulk(Bytes::from(channel_name));
+    response.push_int(num_subs as i64);
+    response
+}
+
+/// # create_message_frame() 函数
+///
+/// 创建一个消息帧
+fn create_message_frame(channel_name: String, message: Bytes) -> Frame {
+    let mut response = Frame::array();
+    response.push_bulk(Bytes::from_static(b"message"));
+    response.push_bulk(Bytes::from(channel_name));
+    response.push_bulk(message);
+    response
+}
+
+/// # create_message_dropped_frame() 函数
+///
+/// 创建一个消息丢失的通知帧，告诉订阅者channel中有count条消息没有被投递
+fn create_message_dropped_frame(channel_name: String, count: u64) -> Frame {
+    let mut response = Frame::array();
+    response.push_bulk(Bytes::from_static(b"message-dropped"));
+    response.push_bulk(Bytes::from(channel_name));
+    response.push_int(count as i64);
+    response
+}
+
+/// # create_pong_frame() 函数
+///
+/// 创建一个心跳帧，格式与订阅模式下PING的响应相同
+fn create_pong_frame() -> Frame {
+    let mut response = Frame::array();
+    response.push_bulk(Bytes::from_static(b"pong"));
+    response.push_bulk(Bytes::new());
+    response
+}
+
+/// # tick() 函数
+///
+/// 等待下一次心跳，没有开启心跳时永远不会返回
+async fn tick(ticker: &mut Option<Interval>) {
+    match ticker {
+        Some(ticker) => {
+            ticker.tick().await;
+        }
+        None => std::future::pending().await,
+    }
+}
+
+/// # create_delivery_frame() 函数
+///
+/// 根据channel中产生的事件创建推送给订阅者的帧
+fn create_delivery_frame(channel_name: String, delivery: Delivery) -> Frame {
+    match delivery {
+        Delivery::Message(msg) => create_message_frame(channel_name, msg),
+        Delivery::Dropped(count) => create_message_dropped_frame(channel_name, count),
+    }
+}
+
+/// # write_deliveries() 函数
+///
+/// 写入收到的第一条消息，以及所有channel中已经到达、不需要等待的消息，最后只flush一次。
+/// 一批最多MAX_DELIVERY_BATCH条，剩下的消息留到select!的下一轮，让读取请求和关闭信号也有机会被处理
+async fn write_deliveries(
+    connection: &mut Connection,
+    subscriptions: &mut StreamMap<String, Messages>,
+    channel_name: String,
+    delivery: Delivery,
+) -> io::Result<()> {
+    connection
+        .write_frame_nowait(&create_delivery_frame(channel_name, delivery))
+        .await?;
+
+    for _ in 1..MAX_DELIVERY_BATCH {
+        let Some(Some((channel_name, delivery))) = subscriptions.next().now_or_never() else {
+            break;
+        };
+        connection
+            .write_frame_nowait(&create_delivery_frame(channel_name, delivery))
+            .await?;
+    }
+
+    connection.flush().await
+}
+
+/// # with_heartbeat() 函数
+///
+/// 执行一次向订阅者的写入，开启心跳时，如果在一个心跳周期内都无法写完，就认为连接已经失效
+async fn with_heartbeat(
+    heartbeat: u64,
+    write: impl Future<Output = io::Result<()>>,
+) -> crate::Result<()> {
+    if heartbeat == 0 {
+        write.await?;
+        return Ok(());
+    }
+
+    match time::timeout(Duration::from_secs(heartbeat), write).await {
+        Ok(res) => Ok(res?),
+        Err(_) => Err(io::Error::new(
+            io::ErrorKind::TimedOut,
+            "订阅者在一个心跳周期内没有读取数据",
+        )
+        .into()),
+    }
+}
+
+/// # subscribe_to_channel() 函数
+///
+/// 订阅一个channel
+async fn subscribe_to_channel(
+    channel_name: String,
+    subscriptions: &mut StreamMap<String, Messages>,
+    database: &Database,
+    connection: &mut Connection,
+) -> crate::Result<()> {
+    // 按照当前配置的投递方式订阅一个channel
+    let rx: Messages = match database.config().pubsub_delivery {
+        PubSubDelivery::Broadcast => {
+            let mut rx = database.subscribe(channel_name.clone());
+            let database = database.clone();
+            let channel = channel_name.clone();
+            Box::pin(async_stream::stream! {
+                loop {
+                    match rx.recv().await {
+                        Ok(msg) => yield Delivery::Message(msg),
+                        // 订阅者落后超过了channel的容量，最旧的消息已经被覆盖，通知订阅者而不是静默丢弃
+                        Err(broadcast::error::RecvError::Lagged(count)) => {
+                            warn!(%channel, count, "subscriber lagged, messages dropped");
+                            database.stats().pubsub_messages_dropped(count);
+                            yield Delivery::Dropped(count);
+                        }
+                        Err(_) => break,
+                    }
+                }
+            })
+        }
+        PubSubDelivery::Backpressure => {
+            let mut rx = database.subscribe_bounded(channel_name.clone());
+            Box::pin(async_stream::stream! {
+                while let Some(msg) = rx.recv().await {
+                    yield Delivery::Message(msg);
+                }
+            })
+        }
+    };
+
+    // 跟踪客户端订阅集合中的订阅
+    subscriptions.insert(channel_name.clone(), rx);
+
+    // 响应客户端
+    let response = create_subscribe_response_frame(channel_name, subscriptions.len());
+    connection.write_frame(&response).await?;
+
+    Ok(())
+}
+
+/// # handle_command() 函数
+///
+/// 处理在Subcriber::apply中的命令
+async fn handle_command(
+    frame: Frame,
+    subscribe_to: &mut Vec<String>,
+    subcriptions: &mut StreamMap<String, Messages>,
+    database: &Database,
+    connection: &mut Connection,
+) -> crate::Result<bool> {
+    // 命令参数错误只回复错误，继续保持订阅
+    let command = match Command::decode_cmd_from_frame(frame) {
+        Ok(command) => command,
+        Err(err) => match err.downcast_ref::<RustisError>() {
+            Some(RustisError::Command(msg)) => {
+                connection.write_frame(&Frame::Error(msg.clone())).await?;
+                return Ok(true);
+            }
+            _ => return Err(err),
+        },
+    };
+
+    match command {
+        Command::PubSub(PubSub::Subscribe(subscribe)) => {
+            subscribe_to.extend(subscribe.channels);
+        }
+        Command::PubSub(PubSub::Unsubscribe(mut unsubscribe)) => {
+            // 如果unsubscribe为空，则要取消所有的channel订阅
+            if unsubscribe.channels.is_empty() {
+                // 已经没有任何订阅时，与redis一样回复一个channel为空的确认，客户端不会一直等待
+                if subcriptions.is_empty() {
+                    let mut response = Frame::array();
+                    response.push_bulk(Bytes::from_static(b"unsubscribe"));
+                    response.push_frame(Frame::Null);
+                    response.push_int(0);
+                    connection.write_frame(&response).await?;
+                    return Ok(true);
+                }
+
+                unsubscribe.channels = subcriptions
+                    .keys()
+                    .map(|channel_name| channel_name.to_string())
+                    .collect();
+            }
+
+            // 所有channel的确认只flush一次
+            connection.cork(true);
+            for channel_name in unsubscribe.channels {
+                // 先释放Receiver，再检查channel是否还有其他订阅者
+                subcriptions.remove(&channel_name);
+                database.remove_unused_channel(&channel_name);
+
+                let response = create_unsubscribe_response_frame(channel_name, subcriptions.len());
+                connection.write_frame(&response).await?;
+            }
+            connection.cork(false);
+            connection.flush().await?;
+        }
+        Command::PubSub(PubSub::ExitSubscribe(_)) => {
+            debug!("exit subscribe");
+            return Ok(false);
+        }
+        command => {
+            let cmd = Unknown::new(command.get_name());
+            cmd.apply(connection).await?;
+            // 订阅模式下不会经过Handler的flush，需要立即写入socket
+            connection.flush().await?;
+        }
+    }
+    Ok(true)
+}
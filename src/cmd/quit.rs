@@ -0,0 +1,43 @@
+//! quit命令的实现
+
+use tracing::{debug, instrument};
+
+use crate::{
+    networking::{connection::Connection, frame::Frame},
+    server::session::Session,
+};
+
+/// # Quit 结构体
+///
+/// 请求服务器关闭连接，服务器回复OK并写出之前所有命令的响应后关闭连接
+///
+/// # 语法
+///
+/// QUIT
+#[derive(Debug, Default)]
+pub struct Quit;
+
+impl Quit {
+    pub(crate) fn new() -> Quit {
+        Quit
+    }
+
+    /// # apply() 函数
+    ///
+    /// 应用quit命令，标记连接需要关闭，由Handler在写出响应后关闭连接
+    #[instrument(skip(self, connection, session))]
+    pub(crate) async fn apply(
+        self,
+        connection: &mut Connection,
+        session: &mut Session,
+    ) -> crate::Result<()> {
+        session.quit = true;
+
+        let response = Frame::Simple("OK".to_string());
+        debug!(?response);
+
+        connection.write_frame_nowait(&response).await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,97 @@
+//! readonly和readwrite命令的实现
+
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+use crate::{
+    networking::{connection::Connection, frame::Frame},
+    server::session::Session,
+};
+
+/// # ReadOnly 结构体
+///
+/// 将当前连接切换为只读模式，只读模式下所有会修改数据的命令都会被拒绝
+///
+/// # 语法
+///
+/// READONLY
+#[derive(Debug, Default)]
+pub struct ReadOnly;
+
+impl ReadOnly {
+    pub(crate) fn new() -> ReadOnly {
+        ReadOnly
+    }
+
+    /// # code_readonly_into_frame() 函数
+    ///
+    /// 将readonly命令编码为帧
+    pub(crate) fn code_readonly_into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("readonly".as_bytes()));
+        frame
+    }
+
+    /// # apply() 函数
+    ///
+    /// 应用readonly命令，并将响应写入到Connection实例
+    #[instrument(skip(self, connection, session))]
+    pub(crate) async fn apply(
+        self,
+        connection: &mut Connection,
+        session: &mut Session,
+    ) -> crate::Result<()> {
+        session.readonly = true;
+
+        let response = Frame::Simple("OK".to_string());
+        debug!(?response);
+
+        connection.write_frame_nowait(&response).await?;
+
+        Ok(())
+    }
+}
+
+/// # ReadWrite 结构体
+///
+/// 关闭当前连接的只读模式
+///
+/// # 语法
+///
+/// READWRITE
+#[derive(Debug, Default)]
+pub struct ReadWrite;
+
+impl ReadWrite {
+    pub(crate) fn new() -> ReadWrite {
+        ReadWrite
+    }
+
+    /// # code_readwrite_into_frame() 函数
+    ///
+    /// 将readwrite命令编码为帧
+    pub(crate) fn code_readwrite_into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("readwrite".as_bytes()));
+        frame
+    }
+
+    /// # apply() 函数
+    ///
+    /// 应用readwrite命令，并将响应写入到Connection实例
+    #[instrument(skip(self, connection, session))]
+    pub(crate) async fn apply(
+        self,
+        connection: &mut Connection,
+        session: &mut Session,
+    ) -> crate::Result<()> {
+        session.readonly = false;
+
+        let response = Frame::Simple("OK".to_string());
+        debug!(?response);
+
+        connection.write_frame_nowait(&response).await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,96 @@
+//! replicaof命令的实现
+
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+use crate::{
+    networking::{connection::Connection, frame::Frame, parse::Parse},
+    persistence::database::Database,
+    server::replication,
+};
+
+/// # ReplicaOf 枚举
+///
+/// 让服务器成为另一个服务器的副本，或者停止复制
+///
+/// # 语法
+///
+/// - REPLICAOF host port：从host:port复制数据，当前的数据会被清空
+/// - REPLICAOF NO ONE：停止复制，保留已经复制的数据，之后可以接受写命令
+#[derive(Debug)]
+pub enum ReplicaOf {
+    /// 从主节点复制数据
+    Master {
+        /// 主节点的地址
+        host: String,
+        /// 主节点的端口
+        port: u64,
+    },
+    /// 停止复制
+    NoOne,
+}
+
+impl ReplicaOf {
+    /// # decode_replicaof_from_frame() 函数
+    ///
+    /// 将帧解码为replicaof命令
+    pub(crate) fn decode_replicaof_from_frame(parse: &mut Parse) -> crate::Result<ReplicaOf> {
+        let host = parse.next_string()?;
+        let port = parse.next_string()?;
+
+        if host.eq_ignore_ascii_case("no") && port.eq_ignore_ascii_case("one") {
+            return Ok(ReplicaOf::NoOne);
+        }
+
+        match port.parse::<u16>() {
+            Ok(port) => Ok(ReplicaOf::Master {
+                host,
+                port: port as u64,
+            }),
+            Err(_) => Err("ERR Invalid master port".into()),
+        }
+    }
+
+    /// # code_replicaof_into_frame() 函数
+    ///
+    /// 将replicaof命令编码为帧
+    pub(crate) fn code_replicaof_into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("replicaof".as_bytes()));
+        match self {
+            ReplicaOf::Master { host, port } => {
+                frame.push_bulk(Bytes::from(host.into_bytes()));
+                frame.push_bulk(Bytes::from(port.to_string()));
+            }
+            ReplicaOf::NoOne => {
+                frame.push_bulk(Bytes::from("no".as_bytes()));
+                frame.push_bulk(Bytes::from("one".as_bytes()));
+            }
+        }
+        frame
+    }
+
+    /// # apply() 函数
+    ///
+    /// 应用replicaof命令，并将响应写入到Connection实例
+    #[instrument(skip(self, db, connection))]
+    pub(crate) async fn apply(
+        self,
+        db: &Database,
+        connection: &mut Connection,
+    ) -> crate::Result<()> {
+        match self {
+            ReplicaOf::Master { host, port } => {
+                replication::start_replication(db.clone(), format!("{}:{}", host, port));
+            }
+            ReplicaOf::NoOne => replication::stop_replication(db),
+        }
+
+        let response = Frame::Simple("OK".to_string());
+        debug!(?response);
+
+        connection.write_frame_nowait(&response).await?;
+
+        Ok(())
+    }
+}
@@ -1,4 +1,4 @@
-//! save命令的实现
+//! save和bgsave命令的实现
 
 use crate::networking::{connection::Connection, frame::Frame};
 use bytes::Bytes;
@@ -46,15 +46,61 @@ impl Save {
         db: &Database,
         connection: &mut Connection,
     ) -> crate::Result<()> {
-        // 保存到RDB文件，文件名先写死了
-        db.save_to_rdb("rustis.rdb")?;
+        // 保存到配置中指定的RDB文件
+        db.save_to_rdb(db.config().rdb_path())?;
 
         // 服务器响应
         let response = Frame::Simple("OK".to_string());
         debug!(?response);
 
         // 写入响应
-        connection.write_frame(&response).await?;
+        connection.write_frame_nowait(&response).await?;
+
+        Ok(())
+    }
+}
+
+/// # BgSave 结构体
+///
+/// 在后台进行一次RDB快照，命令立即返回
+///
+/// # 语法
+///
+/// bgsave
+#[derive(Debug, Default)]
+pub struct BgSave;
+
+impl BgSave {
+    pub(crate) fn new() -> BgSave {
+        BgSave
+    }
+
+    /// # code_bgsave_into_frame() 函数
+    ///
+    /// 将bgsave命令编码为帧
+    pub(crate) fn code_bgsave_into_frame(&self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("bgsave".as_bytes()));
+        frame
+    }
+
+    /// # apply() 函数
+    ///
+    /// 应用bgsave命令，并将响应写入到Connection实例
+    #[instrument(skip(self, db, connection))]
+    pub(crate) async fn apply(
+        self,
+        db: &Database,
+        connection: &mut Connection,
+    ) -> crate::Result<()> {
+        let response = if db.bgsave() {
+            Frame::Simple("Background saving started".to_string())
+        } else {
+            Frame::Error("ERR Background save already in progress".to_string())
+        };
+        debug!(?response);
+
+        connection.write_frame_nowait(&response).await?;
 
         Ok(())
     }
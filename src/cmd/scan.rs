@@ -0,0 +1,133 @@
+//! scan命令的实现
+
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+use crate::{
+    networking::{
+        connection::Connection,
+        frame::Frame,
+        parse::{Parse, ParseError},
+    },
+    persistence::database::Database,
+};
+
+use super::util::glob_match;
+
+/// 没有指定COUNT时每次遍历的键数量，与redis相同
+const DEFAULT_COUNT: u64 = 10;
+
+/// # Scan 结构体
+///
+/// 增量遍历当前数据库中的键
+///
+/// # 语法
+///
+/// SCAN cursor [MATCH pattern] [COUNT count]
+///
+/// 回复下一次调用使用的游标和这一次遍历到的键，游标为0时遍历结束。
+/// MATCH在遍历之后过滤键，所以一次调用可能返回少于COUNT个键，甚至没有键
+#[derive(Debug)]
+pub struct Scan {
+    /// 游标，第一次调用为0
+    cursor: u64,
+    /// 键需要匹配的glob模式
+    pattern: Option<String>,
+    /// 每次遍历的键数量
+    count: Option<u64>,
+}
+
+impl Scan {
+    /// # new() 函数
+    ///
+    /// 创建一个新的Scan命令
+    pub(crate) fn new(cursor: u64, pattern: Option<String>, count: Option<u64>) -> Scan {
+        Scan {
+            cursor,
+            pattern,
+            count,
+        }
+    }
+
+    /// # decode_scan_from_frame() 函数
+    ///
+    /// 将帧解码为scan命令
+    pub(crate) fn decode_scan_from_frame(parse: &mut Parse) -> crate::Result<Scan> {
+        let cursor = parse.next_string()?;
+        let cursor = cursor.parse().map_err(|_| "invalid cursor")?;
+        let mut pattern = None;
+        let mut count = None;
+
+        loop {
+            match parse.next_string() {
+                Ok(option) if option.eq_ignore_ascii_case("match") => {
+                    pattern = Some(parse.next_string()?);
+                }
+                Ok(option) if option.eq_ignore_ascii_case("count") => {
+                    let value = parse
+                        .next_int()
+                        .map_err(|_| "value is not an integer or out of range")?;
+                    if value == 0 {
+                        return Err("syntax error".into());
+                    }
+                    count = Some(value);
+                }
+                Ok(_) => return Err("syntax error".into()),
+                Err(ParseError::EndOfStream) => break,
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        Ok(Scan::new(cursor, pattern, count))
+    }
+
+    /// # code_scan_into_frame() 函数
+    ///
+    /// 将scan命令编码为帧
+    pub(crate) fn code_scan_into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("scan".as_bytes()));
+        frame.push_bulk(Bytes::from(self.cursor.to_string()));
+        if let Some(pattern) = self.pattern {
+            frame.push_bulk(Bytes::from("match".as_bytes()));
+            frame.push_bulk(Bytes::from(pattern.into_bytes()));
+        }
+        if let Some(count) = self.count {
+            frame.push_bulk(Bytes::from("count".as_bytes()));
+            frame.push_bulk(Bytes::from(count.to_string()));
+        }
+        frame
+    }
+
+    /// # apply() 函数
+    ///
+    /// 应用scan命令，回复由游标和键组成的数组
+    #[instrument(skip(self, db, connection))]
+    pub(crate) async fn apply(
+        self,
+        db: &Database,
+        connection: &mut Connection,
+    ) -> crate::Result<()> {
+        let count = self.count.unwrap_or(DEFAULT_COUNT);
+        let (next, keys) = db.scan(self.cursor, usize::try_from(count).unwrap_or(usize::MAX));
+
+        let keys = keys
+            .into_iter()
+            .filter(|key| {
+                self.pattern
+                    .as_deref()
+                    .is_none_or(|pattern| glob_match(pattern.as_bytes(), key.as_bytes()))
+            })
+            .map(|key| Frame::Bulk(Bytes::from(key.into_bytes())))
+            .collect();
+        let response = Frame::Array(vec![
+            Frame::Bulk(Bytes::from(next.to_string())),
+            Frame::Array(keys),
+        ]);
+        debug!(?response);
+
+        connection.write_frame_nowait(&response).await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,75 @@
+//! select命令的实现
+
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+use crate::{
+    networking::{connection::Connection, frame::Frame, parse::Parse},
+    persistence::database::Database,
+    server::session::Session,
+};
+
+/// # Select 结构体
+///
+/// 切换当前连接使用的逻辑数据库
+///
+/// # 语法
+///
+/// SELECT index
+#[derive(Debug)]
+pub struct Select {
+    /// 逻辑数据库编号
+    index: u64,
+}
+
+impl Select {
+    /// # new() 函数
+    ///
+    /// 创建一个新的Select命令
+    pub fn new(index: u64) -> Select {
+        Select { index }
+    }
+
+    /// # decode_select_from_frame() 函数
+    ///
+    /// 将帧解码为select命令
+    pub(crate) fn decode_select_from_frame(parse: &mut Parse) -> crate::Result<Select> {
+        let index = parse.next_int()?;
+        Ok(Select::new(index))
+    }
+
+    /// # code_select_into_frame() 函数
+    ///
+    /// 将select命令编码为帧
+    pub(crate) fn code_select_into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("select".as_bytes()));
+        frame.push_bulk(Bytes::from(self.index.to_string()));
+        frame
+    }
+
+    /// # apply() 函数
+    ///
+    /// 应用select命令，并将响应写入到Connection实例
+    #[instrument(skip(self, db, connection, session))]
+    pub(crate) async fn apply(
+        self,
+        db: &Database,
+        connection: &mut Connection,
+        session: &mut Session,
+    ) -> crate::Result<()> {
+        let response = match usize::try_from(self.index) {
+            Ok(index) if index < db.databases() => {
+                session.db = index;
+                Frame::Simple("OK".to_string())
+            }
+            _ => Frame::Error("ERR DB index is out of range".to_string()),
+        };
+
+        debug!(?response);
+
+        connection.write_frame_nowait(&response).await?;
+
+        Ok(())
+    }
+}
@@ -1,5 +1,7 @@
 //! set命令的实现
 
+use std::time::{SystemTime, UNIX_EPOCH};
+
 use bytes::Bytes;
 use tokio::time::Duration;
 use tracing::{debug, instrument};
@@ -13,6 +15,101 @@ use crate::{
     persistence::database::Database,
 };
 
+use super::util::expire_millis;
+
+/// # SetCondition 枚举
+///
+/// 写入键的条件，条件的检查和写入在同一个锁内完成
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SetCondition {
+    /// 只在键不存在时写入
+    Nx,
+    /// 只在键已经存在时写入
+    Xx,
+}
+
+impl SetCondition {
+    /// # name() 函数
+    ///
+    /// 返回条件在命令中的名称
+    fn name(self) -> &'static str {
+        match self {
+            SetCondition::Nx => "nx",
+            SetCondition::Xx => "xx",
+        }
+    }
+}
+
+/// # SetOptions 结构体
+///
+/// Client::set_opts()使用的SET选项，对应EX/PX、NX/XX、KEEPTTL和GET
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SetOptions {
+    /// 过期时间
+    pub expire: Option<Duration>,
+    /// 写入的条件，None表示总是写入
+    pub condition: Option<SetCondition>,
+    /// 保留键原来的过期时间，不能和expire同时使用
+    pub keep_ttl: bool,
+    /// 返回键原来的值
+    pub get: bool,
+}
+
+impl SetOptions {
+    /// # expire() 函数
+    ///
+    /// 设置过期时间
+    pub fn expire(mut self, expire: Duration) -> Self {
+        self.expire = Some(expire);
+        self
+    }
+
+    /// # nx() 函数
+    ///
+    /// 只在键不存在时写入
+    pub fn nx(mut self) -> Self {
+        self.condition = Some(SetCondition::Nx);
+        self
+    }
+
+    /// # xx() 函数
+    ///
+    /// 只在键已经存在时写入
+    pub fn xx(mut self) -> Self {
+        self.condition = Some(SetCondition::Xx);
+        self
+    }
+
+    /// # keepttl() 函数
+    ///
+    /// 保留键原来的过期时间
+    pub fn keepttl(mut self) -> Self {
+        self.keep_ttl = true;
+        self
+    }
+
+    /// # get() 函数
+    ///
+    /// 返回键原来的值
+    pub fn get(mut self) -> Self {
+        self.get = true;
+        self
+    }
+}
+
+/// # SetResult 枚举
+///
+/// Client::set_opts()的结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SetResult {
+    /// 写入了新值
+    Set,
+    /// 条件不满足，没有写入
+    NotSet,
+    /// 使用了GET选项时键原来的值，不存在时为None，无论是否写入都会返回
+    Previous(Option<Bytes>),
+}
+
 /// # set 命令
 ///
 /// 从key映射到value，如果key已经映射到了一个值，那么旧值将被替换
@@ -24,6 +121,16 @@ pub struct Set {
     value: Bytes,
     /// 过期时间
     expire: Option<Duration>,
+    /// 过期的unix时间戳（毫秒），对应EXAT/PXAT，与expire只会有一个
+    expire_at: Option<u64>,
+    /// 写入的条件，None表示总是写入
+    condition: Option<SetCondition>,
+    /// 幂等令牌，同一个令牌的重复写入会被忽略
+    idempotency_token: Option<String>,
+    /// 保留键原来的过期时间
+    keep_ttl: bool,
+    /// 回复键原来的值
+    get: bool,
 }
 
 impl Set {
@@ -44,9 +151,50 @@ impl Set {
             key: key.to_string(),
             value,
             expire,
+            expire_at: None,
+            condition: None,
+            idempotency_token: None,
+            keep_ttl: false,
+            get: false,
         }
     }
 
+    /// # with_options() 函数
+    ///
+    /// 根据SetOptions创建Set命令
+    pub(crate) fn with_options(key: impl ToString, value: Bytes, options: SetOptions) -> Self {
+        let mut set = Self::new(key, value, options.expire);
+        set.condition = options.condition;
+        set.keep_ttl = options.keep_ttl;
+        set.get = options.get;
+        set
+    }
+
+    /// # condition() 函数
+    ///
+    /// 为Set命令设置写入条件
+    pub(crate) fn condition(mut self, condition: SetCondition) -> Self {
+        self.condition = Some(condition);
+        self
+    }
+
+    /// # expire_at() 函数
+    ///
+    /// 为Set命令设置过期的unix时间戳（毫秒），替换之前设置的过期时间
+    pub(crate) fn expire_at(mut self, unix_millis: u64) -> Self {
+        self.expire = None;
+        self.expire_at = Some(unix_millis);
+        self
+    }
+
+    /// # idempotent() 函数
+    ///
+    /// 为Set命令设置幂等令牌，服务器最近见过这个令牌时不会重复写入
+    pub(crate) fn idempotent(mut self, token: impl ToString) -> Self {
+        self.idempotency_token = Some(token.to_string());
+        self
+    }
+
     /// # key() 函数
     ///
     /// 获取key
@@ -95,35 +243,100 @@ impl Set {
         let value = parse.next_bytes()?;
         // 过期时间
         let mut expire = None;
+        // 过期的unix时间戳
+        let mut expire_at = None;
+        // 写入条件
+        let mut condition = None;
+        // 幂等令牌
+        let mut idempotency_token = None;
+        // 是否保留原来的过期时间
+        let mut keep_ttl = false;
+        // 是否回复原来的值
+        let mut get = false;
 
-        // 处理过期时间选项
-        match parse.next_string() {
-            // 过期时间以秒为单位
-            Ok(str) if str.to_lowercase() == "ex" => {
-                // 读取过期时间
-                let seconds = parse.next_int()?;
-                // 设置过期时间
-                expire = Some(Duration::from_secs(seconds));
-            }
-            // 过期时间以毫秒为单位
-            Ok(str) if str.to_lowercase() == "px" => {
-                // 读取过期时间
-                let milliseconds = parse.next_int()?;
-                // 设置过期时间
-                expire = Some(Duration::from_millis(milliseconds));
-            }
-            // 未知选项
-            Ok(_) => {
-                return Err("Currently, the set command only supports EX and PX options".into());
+        // 处理选项，选项可以以任意顺序出现
+        loop {
+            match parse.next_string() {
+                // 过期时间以秒为单位
+                Ok(str) if str.to_lowercase() == "ex" => {
+                    // 读取过期时间
+                    let seconds = parse.next_int()?;
+                    // 设置过期时间，换算成毫秒后溢出的时间是无效的
+                    expire = Some(Duration::from_millis(expire_millis(seconds, 1000)?));
+                    expire_at = None;
+                }
+                // 过期时间以毫秒为单位
+                Ok(str) if str.to_lowercase() == "px" => {
+                    // 读取过期时间
+                    let milliseconds = parse.next_int()?;
+                    // 设置过期时间
+                    expire = Some(Duration::from_millis(expire_millis(milliseconds, 1)?));
+                    expire_at = None;
+                }
+                // 过期时间是unix时间戳，以秒或毫秒为单位
+                Ok(str) if matches!(str.to_lowercase().as_str(), "exat" | "pxat") => {
+                    let millis_per_unit = if str.to_lowercase() == "exat" {
+                        1000
+                    } else {
+                        1
+                    };
+                    expire_at = Some(expire_millis(parse.next_int()?, millis_per_unit)?);
+                    expire = None;
+                }
+                // 只在键不存在或已经存在时写入，NX和XX不能同时出现
+                Ok(str) if matches!(str.to_lowercase().as_str(), "nx" | "xx") => {
+                    let new = if str.to_lowercase() == "nx" {
+                        SetCondition::Nx
+                    } else {
+                        SetCondition::Xx
+                    };
+                    if condition.is_some_and(|condition| condition != new) {
+                        return Err("syntax error".into());
+                    }
+                    condition = Some(new);
+                }
+                // 幂等令牌
+                Ok(str) if str.to_lowercase() == "idempotent" => {
+                    idempotency_token = Some(parse.next_string()?);
+                }
+                // 保留原来的过期时间
+                Ok(str) if str.to_lowercase() == "keepttl" => {
+                    keep_ttl = true;
+                }
+                // 回复原来的值
+                Ok(str) if str.to_lowercase() == "get" => {
+                    get = true;
+                }
+                // 未知选项
+                Ok(_) => {
+                    return Err("syntax error".into());
+                }
+                // 读取结束
+                Err(EndOfStream) => break,
+                // 其他错误
+                Err(err) => return Err(err.into()),
             }
-            // 读取结束
-            Err(EndOfStream) => {}
-            // 其他错误
-            Err(err) => return Err(err.into()),
+        }
+
+        // KEEPTTL不能和过期时间同时使用。重复的请求不会执行，无法回复原来的值，也无法知道条件是否满足，
+        // 所以IDEMPOTENT不能和GET、NX、XX同时使用
+        if (keep_ttl && (expire.is_some() || expire_at.is_some()))
+            || (idempotency_token.is_some() && (get || condition.is_some()))
+        {
+            return Err("syntax error".into());
         }
 
         // 返回Set命令
-        Ok(Set::new(key, value, expire))
+        Ok(Set {
+            key,
+            value,
+            expire,
+            expire_at,
+            condition,
+            idempotency_token,
+            keep_ttl,
+            get,
+        })
     }
 
     /// # code_set_into_frame() 函数
@@ -139,7 +352,29 @@ impl Set {
         if let Some(expire) = self.expire {
             // 选择px，它允许更高的精度
             frame.push_bulk(Bytes::from("px".as_bytes()));
-            frame.push_int(expire.as_millis() as u64);
+            frame.push_int(expire.as_millis() as i64);
+        }
+
+        if let Some(unix_millis) = self.expire_at {
+            frame.push_bulk(Bytes::from("pxat".as_bytes()));
+            frame.push_int(unix_millis as i64);
+        }
+
+        if let Some(condition) = self.condition {
+            frame.push_bulk(Bytes::from(condition.name().as_bytes()));
+        }
+
+        if self.keep_ttl {
+            frame.push_bulk(Bytes::from("keepttl".as_bytes()));
+        }
+
+        if self.get {
+            frame.push_bulk(Bytes::from("get".as_bytes()));
+        }
+
+        if let Some(token) = self.idempotency_token {
+            frame.push_bulk(Bytes::from("idempotent".as_bytes()));
+            frame.push_bulk(Bytes::from(token.into_bytes()));
         }
 
         frame
@@ -154,15 +389,41 @@ impl Set {
         database: &Database,
         connection: &mut Connection,
     ) -> crate::Result<()> {
-        // 往数据库中设置键值对
-        database.set(self.key, self.value, self.expire);
+        // 带有令牌时，只有第一次见到这个令牌才写入，重复的请求直接返回OK（带有令牌的SET不能带条件，第一次执行时一定会写入）
+        let is_new = self
+            .idempotency_token
+            .as_deref()
+            .is_none_or(|token| database.record_idempotency_token(token));
+
+        // 已经过去的时间戳换算为0，写入的键立即过期
+        let expire = match self.expire_at {
+            Some(unix_millis) => Some(
+                (UNIX_EPOCH + Duration::from_millis(unix_millis))
+                    .duration_since(SystemTime::now())
+                    .unwrap_or_default(),
+            ),
+            None => self.expire,
+        };
+
+        let (written, previous) = if is_new {
+            // 往数据库中设置键值对，条件不满足时不写入
+            database.set_and_get(self.key, self.value, expire, self.keep_ttl, self.condition)
+        } else {
+            debug!(token = ?self.idempotency_token, "duplicate idempotency token, skipping");
+            (true, None)
+        };
 
-        // 创建一个成功的响应
-        let response = Frame::Simple("OK".to_string());
+        // 带有GET时回复原来的值，否则写入成功时回复OK，条件不满足时回复nil
+        let response = match (self.get, previous) {
+            (true, Some(previous)) => Frame::Bulk(previous),
+            (true, None) => Frame::Null,
+            (false, _) if written => Frame::Simple("OK".to_string()),
+            (false, _) => Frame::Null,
+        };
         debug!(?response);
 
         // 往流中写入响应
-        connection.write_frame(&response).await?;
+        connection.write_frame_nowait(&response).await?;
 
         Ok(())
     }
@@ -0,0 +1,58 @@
+//! stats命令的实现
+
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+use crate::{
+    networking::{connection::Connection, frame::Frame},
+    persistence::database::Database,
+};
+
+/// # Stats 结构体
+///
+/// 返回服务器的统计数据，内容与INFO的Stats部分相同，但是以名称和值交替的数组返回，方便程序处理
+///
+/// # 语法
+///
+/// STATS
+#[derive(Debug, Default)]
+pub struct Stats;
+
+impl Stats {
+    pub(crate) fn new() -> Stats {
+        Stats
+    }
+
+    /// # code_stats_into_frame() 函数
+    ///
+    /// 将stats命令编码为帧
+    pub(crate) fn code_stats_into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("stats".as_bytes()));
+        frame
+    }
+
+    /// # apply() 函数
+    ///
+    /// 应用stats命令，并将响应写入到Connection实例
+    #[instrument(skip(self, db, connection))]
+    pub(crate) async fn apply(
+        self,
+        db: &Database,
+        connection: &mut Connection,
+    ) -> crate::Result<()> {
+        let mut response = Frame::array();
+        response.push_bulk(Bytes::from_static(b"connected_clients"));
+        response.push_int(db.connected_clients() as i64);
+        for (name, value) in db.stats().snapshot() {
+            response.push_bulk(Bytes::from(name));
+            response.push_int(value as i64);
+        }
+
+        debug!(?response);
+
+        connection.write_frame_nowait(&response).await?;
+
+        Ok(())
+    }
+}
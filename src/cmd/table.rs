@@ -0,0 +1,432 @@
+//! 命令表，记录每个命令的元信息（标志等）
+
+/// # CommandSpec 结构体
+///
+/// 命令的元信息
+#[derive(Debug)]
+pub(crate) struct CommandSpec {
+    /// 命令名称（小写）
+    pub(crate) name: &'static str,
+    /// 命令标志，与redis的COMMAND命令输出的标志含义相同
+    /// - write: 会修改数据的命令
+    /// - denyoom: 可能增加内存用量的命令，使用的内存超过maxmemory且无法淘汰键时拒绝执行
+    /// - readonly: 只读取数据的命令
+    /// - admin: 管理命令
+    /// - pubsub: 发布/订阅相关的命令
+    /// - fast: 时间复杂度为O(1)或O(log(N))的命令
+    /// - no-auth: 连接认证之前也可以执行的命令
+    pub(crate) flags: &'static [&'static str],
+    /// 参数个数（包括命令名称），负数表示至少需要-arity个参数，与redis的arity含义相同
+    pub(crate) arity: i32,
+    /// 第一个键的位置，0表示命令没有键
+    pub(crate) first_key: usize,
+    /// 最后一个键的位置，负数表示从末尾倒数，-1为最后一个参数
+    pub(crate) last_key: i32,
+    /// 相邻两个键之间的距离，例如MSET key value key value的步长为2
+    pub(crate) key_step: usize,
+}
+
+impl CommandSpec {
+    /// # is_write() 函数
+    ///
+    /// 命令是否会修改数据
+    pub(crate) fn is_write(&self) -> bool {
+        self.flags.contains(&"write")
+    }
+
+    /// # is_deny_oom() 函数
+    ///
+    /// 命令是否会在使用的内存超过maxmemory时被拒绝
+    pub(crate) fn is_deny_oom(&self) -> bool {
+        self.flags.contains(&"denyoom")
+    }
+
+    /// # is_no_auth() 函数
+    ///
+    /// 命令是否可以在认证之前执行
+    pub(crate) fn is_no_auth(&self) -> bool {
+        self.flags.contains(&"no-auth")
+    }
+
+    /// # check_arity() 函数
+    ///
+    /// 检查参数个数（包括命令名称）是否符合arity
+    pub(crate) fn check_arity(&self, argc: usize) -> bool {
+        let arity = self.arity.unsigned_abs() as usize;
+        if self.arity < 0 {
+            argc >= arity
+        } else {
+            argc == arity
+        }
+    }
+
+    /// # key_indexes() 函数
+    ///
+    /// 根据first_key、last_key和key_step返回argc个参数（包括命令名称）中键的位置
+    pub(crate) fn key_indexes(&self, argc: usize) -> Vec<usize> {
+        if self.first_key == 0 {
+            return Vec::new();
+        }
+
+        let last = if self.last_key < 0 {
+            argc as i64 + self.last_key as i64
+        } else {
+            self.last_key as i64
+        };
+        if last < self.first_key as i64 {
+            return Vec::new();
+        }
+
+        (self.first_key..=last as usize)
+            .step_by(self.key_step.max(1))
+            .filter(|&index| index < argc)
+            .collect()
+    }
+}
+
+/// 所有已知命令的元信息
+pub(crate) const COMMAND_TABLE: &[CommandSpec] = &[
+    CommandSpec {
+        name: "get",
+        flags: &["readonly", "fast"],
+        arity: 2,
+        first_key: 1,
+        last_key: 1,
+        key_step: 1,
+    },
+    CommandSpec {
+        name: "mget",
+        flags: &["readonly", "fast"],
+        arity: -2,
+        first_key: 1,
+        last_key: -1,
+        key_step: 1,
+    },
+    CommandSpec {
+        name: "getrange",
+        flags: &["readonly"],
+        arity: 4,
+        first_key: 1,
+        last_key: 1,
+        key_step: 1,
+    },
+    CommandSpec {
+        name: "set",
+        flags: &["write", "denyoom"],
+        arity: -3,
+        first_key: 1,
+        last_key: 1,
+        key_step: 1,
+    },
+    CommandSpec {
+        name: "del",
+        flags: &["write"],
+        arity: 2,
+        first_key: 1,
+        last_key: 1,
+        key_step: 1,
+    },
+    CommandSpec {
+        name: "expire",
+        flags: &["write", "fast"],
+        arity: -3,
+        first_key: 1,
+        last_key: 1,
+        key_step: 1,
+    },
+    CommandSpec {
+        name: "pexpire",
+        flags: &["write", "fast"],
+        arity: -3,
+        first_key: 1,
+        last_key: 1,
+        key_step: 1,
+    },
+    CommandSpec {
+        name: "expireat",
+        flags: &["write", "fast"],
+        arity: -3,
+        first_key: 1,
+        last_key: 1,
+        key_step: 1,
+    },
+    CommandSpec {
+        name: "pexpireat",
+        flags: &["write", "fast"],
+        arity: -3,
+        first_key: 1,
+        last_key: 1,
+        key_step: 1,
+    },
+    CommandSpec {
+        name: "expiretime",
+        flags: &["readonly", "fast"],
+        arity: 2,
+        first_key: 1,
+        last_key: 1,
+        key_step: 1,
+    },
+    CommandSpec {
+        name: "pexpiretime",
+        flags: &["readonly", "fast"],
+        arity: 2,
+        first_key: 1,
+        last_key: 1,
+        key_step: 1,
+    },
+    CommandSpec {
+        name: "object",
+        flags: &["readonly"],
+        arity: 3,
+        first_key: 2,
+        last_key: 2,
+        key_step: 1,
+    },
+    CommandSpec {
+        name: "scan",
+        flags: &["readonly"],
+        arity: -2,
+        first_key: 0,
+        last_key: 0,
+        key_step: 0,
+    },
+    CommandSpec {
+        name: "memory",
+        flags: &["readonly"],
+        arity: -3,
+        first_key: 2,
+        last_key: 2,
+        key_step: 1,
+    },
+    CommandSpec {
+        name: "command",
+        flags: &[],
+        arity: -1,
+        first_key: 0,
+        last_key: 0,
+        key_step: 0,
+    },
+    CommandSpec {
+        name: "ping",
+        flags: &["fast"],
+        arity: -1,
+        first_key: 0,
+        last_key: 0,
+        key_step: 0,
+    },
+    CommandSpec {
+        name: "save",
+        flags: &["admin"],
+        arity: 1,
+        first_key: 0,
+        last_key: 0,
+        key_step: 0,
+    },
+    CommandSpec {
+        name: "bgsave",
+        flags: &["admin"],
+        arity: 1,
+        first_key: 0,
+        last_key: 0,
+        key_step: 0,
+    },
+    CommandSpec {
+        name: "info",
+        flags: &[],
+        arity: -1,
+        first_key: 0,
+        last_key: 0,
+        key_step: 0,
+    },
+    CommandSpec {
+        name: "config",
+        flags: &["admin"],
+        arity: -2,
+        first_key: 0,
+        last_key: 0,
+        key_step: 0,
+    },
+    CommandSpec {
+        name: "readonly",
+        flags: &["fast"],
+        arity: 1,
+        first_key: 0,
+        last_key: 0,
+        key_step: 0,
+    },
+    CommandSpec {
+        name: "readwrite",
+        flags: &["fast"],
+        arity: 1,
+        first_key: 0,
+        last_key: 0,
+        key_step: 0,
+    },
+    CommandSpec {
+        name: "auth",
+        flags: &["fast", "no-auth"],
+        arity: -2,
+        first_key: 0,
+        last_key: 0,
+        key_step: 0,
+    },
+    CommandSpec {
+        name: "hello",
+        flags: &["fast", "no-auth"],
+        arity: -1,
+        first_key: 0,
+        last_key: 0,
+        key_step: 0,
+    },
+    CommandSpec {
+        name: "quit",
+        flags: &["fast", "no-auth"],
+        arity: 1,
+        first_key: 0,
+        last_key: 0,
+        key_step: 0,
+    },
+    CommandSpec {
+        name: "select",
+        flags: &["fast"],
+        arity: 2,
+        first_key: 0,
+        last_key: 0,
+        key_step: 0,
+    },
+    CommandSpec {
+        name: "flushdb",
+        flags: &["write"],
+        arity: -1,
+        first_key: 0,
+        last_key: 0,
+        key_step: 0,
+    },
+    CommandSpec {
+        name: "flushall",
+        flags: &["write"],
+        arity: -1,
+        first_key: 0,
+        last_key: 0,
+        key_step: 0,
+    },
+    CommandSpec {
+        name: "stats",
+        flags: &["admin"],
+        arity: 1,
+        first_key: 0,
+        last_key: 0,
+        key_step: 0,
+    },
+    CommandSpec {
+        name: "latencystats",
+        flags: &["admin"],
+        arity: 1,
+        first_key: 0,
+        last_key: 0,
+        key_step: 0,
+    },
+    CommandSpec {
+        name: "debug",
+        flags: &["admin"],
+        arity: -2,
+        first_key: 0,
+        last_key: 0,
+        key_step: 0,
+    },
+    CommandSpec {
+        name: "client",
+        flags: &[],
+        arity: -2,
+        first_key: 0,
+        last_key: 0,
+        key_step: 0,
+    },
+    CommandSpec {
+        name: "psync",
+        flags: &["admin"],
+        arity: 1,
+        first_key: 0,
+        last_key: 0,
+        key_step: 0,
+    },
+    CommandSpec {
+        name: "monitor",
+        flags: &["admin"],
+        arity: 1,
+        first_key: 0,
+        last_key: 0,
+        key_step: 0,
+    },
+    CommandSpec {
+        name: "replicaof",
+        flags: &["admin"],
+        arity: 3,
+        first_key: 0,
+        last_key: 0,
+        key_step: 0,
+    },
+];
+
+/// # lookup() 函数
+///
+/// 根据命令名称（小写）查找命令的元信息
+pub(crate) fn lookup(name: &str) -> Option<&'static CommandSpec> {
+    commands().find(|spec| spec.name == name)
+}
+
+/// # commands() 函数
+///
+/// 返回所有已知命令的元信息，开启cmd-pubsub特性时包括发布/订阅命令
+pub(crate) fn commands() -> impl Iterator<Item = &'static CommandSpec> {
+    let commands = COMMAND_TABLE.iter();
+    #[cfg(feature = "cmd-pubsub")]
+    let commands = commands.chain(super::pubsub::COMMAND_TABLE);
+    commands
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 测试命令的读写分类
+    #[test]
+    fn test_write_flag() {
+        assert!(lookup("set").unwrap().is_write());
+        assert!(lookup("del").unwrap().is_write());
+        assert!(!lookup("get").unwrap().is_write());
+        assert!(!lookup("ping").unwrap().is_write());
+        assert!(lookup("foo").is_none());
+        assert!(lookup("auth").unwrap().is_no_auth());
+        assert!(!lookup("get").unwrap().is_no_auth());
+    }
+
+    /// 测试arity的检查，负数表示参数个数的下限
+    #[test]
+    fn test_check_arity() {
+        assert!(lookup("get").unwrap().check_arity(2));
+        assert!(!lookup("get").unwrap().check_arity(3));
+        assert!(!lookup("set").unwrap().check_arity(2));
+        assert!(lookup("set").unwrap().check_arity(5));
+    }
+
+    /// 测试根据键的位置描述提取键
+    #[test]
+    fn test_key_indexes() {
+        assert_eq!(lookup("get").unwrap().key_indexes(2), vec![1]);
+        assert_eq!(lookup("object").unwrap().key_indexes(3), vec![2]);
+        assert!(lookup("ping").unwrap().key_indexes(2).is_empty());
+
+        // MSET key value [key value ...]
+        let mset = CommandSpec {
+            name: "mset",
+            flags: &["write"],
+            arity: -3,
+            first_key: 1,
+            last_key: -1,
+            key_step: 2,
+        };
+        assert_eq!(mset.key_indexes(5), vec![1, 3]);
+        assert_eq!(mset.key_indexes(7), vec![1, 3, 5]);
+    }
+}
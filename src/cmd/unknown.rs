@@ -36,7 +36,7 @@ impl Unknown {
         // ?将使用Debug trait来格式化值，而不是使用Display trait
         debug!(?response);
 
-        connection.write_frame(&response).await?;
+        connection.write_frame_nowait(&response).await?;
 
         Ok(())
     }
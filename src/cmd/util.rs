@@ -0,0 +1,204 @@
+//! 多个命令共用的工具函数
+
+/// # expire_millis() 函数
+///
+/// 把以秒（millis_per_unit为1000）或毫秒（millis_per_unit为1）为单位的过期时间换算成毫秒，
+/// 与redis相同，换算后超出i64范围的时间回复invalid expire time
+pub(crate) fn expire_millis(time: u64, millis_per_unit: u64) -> crate::Result<u64> {
+    time.checked_mul(millis_per_unit)
+        .filter(|millis| *millis <= i64::MAX as u64)
+        .ok_or_else(|| "invalid expire time".into())
+}
+
+/// # normalize_range() 函数
+///
+/// 按照redis中GETRANGE、LRANGE等命令的规则，把闭区间[start, stop]转换成长度为len的序列中的下标。
+/// 负数下标从末尾开始计数（-1是最后一个元素），超出序列的部分被截断到两端。
+/// 区间为空（start在stop之后、整个区间都在序列之外或者序列为空）时返回None，否则返回包含两端的下标
+pub(crate) fn normalize_range(start: i64, stop: i64, len: usize) -> Option<(usize, usize)> {
+    let len = i64::try_from(len).unwrap_or(i64::MAX);
+
+    let start = if start < 0 {
+        (len + start).max(0)
+    } else {
+        start
+    };
+    let stop = if stop < 0 {
+        len + stop
+    } else {
+        stop.min(len - 1)
+    };
+
+    if start > stop || start >= len {
+        return None;
+    }
+
+    Some((start as usize, stop as usize))
+}
+
+/// # glob_match() 函数
+///
+/// redis风格的glob匹配，支持以下通配符：
+/// - `*`：匹配任意数量的字符
+/// - `?`：匹配单个字符
+/// - `[abc]`/`[a-z]`/`[^a]`：匹配字符集合
+/// - `\x`：转义
+pub(crate) fn glob_match(pattern: &[u8], string: &[u8]) -> bool {
+    let (mut p, mut s) = (0, 0);
+    // 最近一次遇到`*`时pattern和string的位置，用于回溯
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while s < string.len() {
+        if p < pattern.len() {
+            match pattern[p] {
+                b'*' => {
+                    backtrack = Some((p, s));
+                    p += 1;
+                    continue;
+                }
+                b'?' => {
+                    p += 1;
+                    s += 1;
+                    continue;
+                }
+                b'[' => {
+                    if let Some((matched, next)) = match_class(pattern, p, string[s]) {
+                        if matched {
+                            p = next;
+                            s += 1;
+                            continue;
+                        }
+                    }
+                }
+                b'\\' if p + 1 < pattern.len() => {
+                    if pattern[p + 1] == string[s] {
+                        p += 2;
+                        s += 1;
+                        continue;
+                    }
+                }
+                c => {
+                    if c == string[s] {
+                        p += 1;
+                        s += 1;
+                        continue;
+                    }
+                }
+            }
+        }
+
+        // 当前字符不匹配，回溯到上一个`*`，让它多匹配一个字符
+        match backtrack {
+            Some((star_p, star_s)) => {
+                backtrack = Some((star_p, star_s + 1));
+                p = star_p + 1;
+                s = star_s + 1;
+            }
+            None => return false,
+        }
+    }
+
+    // string已经匹配完，pattern剩下的只能是`*`
+    pattern[p..].iter().all(|&c| c == b'*')
+}
+
+/// # match_class() 函数
+///
+/// 匹配`[...]`字符集合，返回是否匹配以及`]`之后的位置，集合没有闭合时返回None
+fn match_class(pattern: &[u8], start: usize, c: u8) -> Option<(bool, usize)> {
+    let mut i = start + 1;
+    let negate = pattern.get(i) == Some(&b'^');
+    if negate {
+        i += 1;
+    }
+
+    let mut matched = false;
+    while i < pattern.len() && pattern[i] != b']' {
+        if pattern[i] == b'\\' && i + 1 < pattern.len() {
+            matched |= pattern[i + 1] == c;
+            i += 2;
+        } else if i + 2 < pattern.len() && pattern[i + 1] == b'-' && pattern[i + 2] != b']' {
+            let (low, high) = if pattern[i] <= pattern[i + 2] {
+                (pattern[i], pattern[i + 2])
+            } else {
+                (pattern[i + 2], pattern[i])
+            };
+            matched |= low <= c && c <= high;
+            i += 3;
+        } else {
+            matched |= pattern[i] == c;
+            i += 1;
+        }
+    }
+
+    if i >= pattern.len() {
+        return None;
+    }
+
+    Some((matched != negate, i + 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 测试非负下标，stop超出序列时截断到最后一个元素
+    #[test]
+    fn test_normalize_range_positive() {
+        assert_eq!(normalize_range(0, 4, 5), Some((0, 4)));
+        assert_eq!(normalize_range(1, 2, 5), Some((1, 2)));
+        assert_eq!(normalize_range(3, 3, 5), Some((3, 3)));
+        assert_eq!(normalize_range(2, 100, 5), Some((2, 4)));
+        assert_eq!(normalize_range(0, i64::MAX, 5), Some((0, 4)));
+    }
+
+    /// 测试负数下标从末尾开始计数，超出序列开头时截断到第一个元素
+    #[test]
+    fn test_normalize_range_negative() {
+        assert_eq!(normalize_range(0, -1, 5), Some((0, 4)));
+        assert_eq!(normalize_range(-3, -2, 5), Some((2, 3)));
+        assert_eq!(normalize_range(-1, -1, 5), Some((4, 4)));
+        assert_eq!(normalize_range(-100, 1, 5), Some((0, 1)));
+        assert_eq!(normalize_range(-100, -1, 5), Some((0, 4)));
+        assert_eq!(normalize_range(i64::MIN, i64::MAX, 5), Some((0, 4)));
+        assert_eq!(normalize_range(1, -2, 5), Some((1, 3)));
+    }
+
+    /// 测试产生空区间的情况
+    #[test]
+    fn test_normalize_range_empty() {
+        // start在stop之后
+        assert_eq!(normalize_range(3, 1, 5), None);
+        assert_eq!(normalize_range(-1, -2, 5), None);
+        assert_eq!(normalize_range(4, -3, 5), None);
+        // 整个区间在序列之外
+        assert_eq!(normalize_range(5, 10, 5), None);
+        assert_eq!(normalize_range(-100, -50, 5), None);
+        assert_eq!(normalize_range(-100, -6, 5), None);
+        assert_eq!(normalize_range(i64::MIN, i64::MIN, 5), None);
+        // 序列为空
+        assert_eq!(normalize_range(0, -1, 0), None);
+        assert_eq!(normalize_range(0, 0, 0), None);
+        assert_eq!(normalize_range(-1, 100, 0), None);
+    }
+
+    /// 测试glob匹配
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match(b"*", b"anything"));
+        assert!(glob_match(b"*", b""));
+        assert!(glob_match(b"read-*", b"read-only"));
+        assert!(!glob_match(b"read-*", b"maxclients"));
+        assert!(glob_match(b"h?llo", b"hello"));
+        assert!(!glob_match(b"h?llo", b"hllo"));
+        assert!(glob_match(b"h*llo", b"heeeello"));
+        assert!(glob_match(b"h[ae]llo", b"hallo"));
+        assert!(!glob_match(b"h[ae]llo", b"hillo"));
+        assert!(glob_match(b"h[^e]llo", b"hallo"));
+        assert!(!glob_match(b"h[^e]llo", b"hello"));
+        assert!(glob_match(b"h[a-c]llo", b"hbllo"));
+        assert!(glob_match(b"h\\*llo", b"h*llo"));
+        assert!(!glob_match(b"h\\*llo", b"hello"));
+        assert!(glob_match(b"*max*", b"maxclients"));
+    }
+}
@@ -0,0 +1,150 @@
+//! RustisError枚举，结构化的错误类型
+
+use std::{fmt, io};
+
+use bytes::Bytes;
+
+use crate::networking::{frame, parse::ParseError};
+
+/// # RustisError 枚举
+///
+/// 结构化的错误类型，调用者可以通过`downcast_ref::<RustisError>()`匹配具体的错误类别，
+/// 而不是只能拿到一个错误字符串
+#[derive(Debug)]
+pub enum RustisError {
+    /// 对持有错误类型值的键进行操作
+    WrongType,
+    /// 键不存在
+    NoSuchKey,
+    /// 协议错误：帧格式错误、命令参数无法解析等
+    Protocol(String),
+    /// 服务器执行命令时返回的错误
+    Command(String),
+    /// RDB文件存在但是内容无法解析
+    CorruptRdb(String),
+    /// RDB文件完好，但是格式版本无法被当前服务器加载（比如由更新版本的服务器保存）
+    UnsupportedRdb(String),
+    /// 键的值无法反序列化为请求的类型，value是服务器返回的原始值
+    Deserialize { value: Bytes, reason: String },
+    /// IO错误
+    Io(io::Error),
+}
+
+impl RustisError {
+    /// # from_error_message() 函数
+    ///
+    /// 将服务器返回的错误帧内容转换为对应的错误类型
+    pub(crate) fn from_error_message(msg: String) -> RustisError {
+        if msg.starts_with("WRONGTYPE") {
+            RustisError::WrongType
+        } else if msg == "ERR no such key" {
+            RustisError::NoSuchKey
+        } else {
+            RustisError::Command(msg)
+        }
+    }
+}
+
+impl std::error::Error for RustisError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RustisError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for RustisError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RustisError::WrongType => {
+                "WRONGTYPE Operation against a key holding the wrong kind of value".fmt(f)
+            }
+            RustisError::NoSuchKey => "ERR no such key".fmt(f),
+            RustisError::Protocol(msg) => write!(f, "ERR Protocol error: {}", msg),
+            RustisError::Command(msg) => msg.fmt(f),
+            RustisError::CorruptRdb(msg) => write!(f, "Bad RDB file format: {}", msg),
+            RustisError::UnsupportedRdb(msg) => write!(f, "Can't handle RDB file: {}", msg),
+            RustisError::Deserialize { reason, .. } => {
+                write!(f, "Value can't be deserialized: {}", reason)
+            }
+            RustisError::Io(err) => err.fmt(f),
+        }
+    }
+}
+
+impl From<io::Error> for RustisError {
+    fn from(err: io::Error) -> RustisError {
+        RustisError::Io(err)
+    }
+}
+
+impl From<frame::Error> for RustisError {
+    fn from(err: frame::Error) -> RustisError {
+        RustisError::Protocol(err.to_string())
+    }
+}
+
+impl From<ParseError> for RustisError {
+    fn from(err: ParseError) -> RustisError {
+        RustisError::Protocol(err.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 测试错误帧内容到错误类型的映射
+    #[test]
+    fn test_from_error_message() {
+        let err = RustisError::from_error_message(
+            "WRONGTYPE Operation against a key holding the wrong kind of value".to_string(),
+        );
+        assert!(matches!(err, RustisError::WrongType));
+
+        let err = RustisError::from_error_message("ERR no such key".to_string());
+        assert!(matches!(err, RustisError::NoSuchKey));
+
+        let err = RustisError::from_error_message("ERR unknown command 'foo'".to_string());
+        match err {
+            RustisError::Command(msg) => assert_eq!(msg, "ERR unknown command 'foo'"),
+            _ => panic!("错误类型不是Command"),
+        }
+    }
+
+    /// 测试所有错误类型输出的文本都是英文，与Redis的错误信息风格一致
+    #[test]
+    fn test_display() {
+        assert_eq!(
+            RustisError::CorruptRdb("short read".to_string()).to_string(),
+            "Bad RDB file format: short read"
+        );
+        assert_eq!(
+            RustisError::UnsupportedRdb("version 9".to_string()).to_string(),
+            "Can't handle RDB file: version 9"
+        );
+        let err = RustisError::Deserialize {
+            value: Bytes::from("x"),
+            reason: "expected value".to_string(),
+        };
+        assert_eq!(
+            err.to_string(),
+            "Value can't be deserialized: expected value"
+        );
+    }
+
+    /// 测试解析帧和命令参数的错误转换为协议错误时只有一个前缀
+    #[test]
+    fn test_protocol_error_message() {
+        let mut src = std::io::Cursor::new(&b":abc\r\n"[..]);
+        let err = RustisError::from(frame::Frame::parse(&mut src).unwrap_err());
+        assert_eq!(err.to_string(), "ERR Protocol error: invalid frame format");
+
+        let err = RustisError::from(ParseError::EndOfStream);
+        assert_eq!(
+            err.to_string(),
+            "ERR Protocol error: no more frames to read"
+        );
+    }
+}
@@ -1,9 +1,12 @@
 pub mod client;
 mod cmd;
+pub mod error;
 mod networking;
 mod persistence;
 pub mod server;
 
+pub use error::RustisError;
+
 pub type Error = Box<dyn std::error::Error + Send + Sync>;
 pub type Result<T> = std::result::Result<T, Error>;
 
@@ -0,0 +1,163 @@
+//! CIDR地址段，用于限制允许连接的客户端地址
+
+use std::{
+    fmt,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    str::FromStr,
+};
+
+use serde::Deserialize;
+
+/// # Cidr 结构体
+///
+/// 一个IPv4或IPv6地址段，例如`10.0.0.0/8`，只写地址时表示单个地址
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Cidr {
+    /// 网络地址，主机位已经清零
+    addr: IpAddr,
+    /// 前缀长度
+    prefix: u8,
+}
+
+impl Cidr {
+    /// # contains() 函数
+    ///
+    /// 判断地址是否在这个地址段内，IPv4映射的IPv6地址（::ffff:a.b.c.d）按IPv4地址匹配
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        let ip = match ip {
+            IpAddr::V6(v6) => v6.to_ipv4_mapped().map_or(ip, IpAddr::V4),
+            ip => ip,
+        };
+
+        match (self.addr, ip) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                u32::from(net) == u32::from(ip) & v4_mask(self.prefix)
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                u128::from(net) == u128::from(ip) & v6_mask(self.prefix)
+            }
+            _ => false,
+        }
+    }
+}
+
+/// # is_allowed() 函数
+///
+/// 判断地址是否被允许连接，allowlist为空时允许所有地址
+pub(crate) fn is_allowed(allowlist: &[Cidr], ip: IpAddr) -> bool {
+    allowlist.is_empty() || allowlist.iter().any(|cidr| cidr.contains(ip))
+}
+
+/// # v4_mask() 函数
+///
+/// 返回IPv4前缀长度对应的掩码
+fn v4_mask(prefix: u8) -> u32 {
+    u32::MAX.checked_shl(32 - prefix as u32).unwrap_or(0)
+}
+
+/// # v6_mask() 函数
+///
+/// 返回IPv6前缀长度对应的掩码
+fn v6_mask(prefix: u8) -> u128 {
+    u128::MAX.checked_shl(128 - prefix as u32).unwrap_or(0)
+}
+
+impl FromStr for Cidr {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("无效的地址段 '{}'", s);
+
+        let (addr, prefix) = match s.split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (s, None),
+        };
+        let addr: IpAddr = addr.parse().map_err(|_| invalid())?;
+
+        let max = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(prefix) => prefix.parse::<u8>().map_err(|_| invalid())?,
+            None => max,
+        };
+        if prefix > max {
+            return Err(invalid());
+        }
+
+        // 清零主机位，让10.1.2.3/8和10.0.0.0/8表示同一个地址段
+        let addr = match addr {
+            IpAddr::V4(v4) => IpAddr::V4(Ipv4Addr::from(u32::from(v4) & v4_mask(prefix))),
+            IpAddr::V6(v6) => IpAddr::V6(Ipv6Addr::from(u128::from(v6) & v6_mask(prefix))),
+        };
+
+        Ok(Cidr { addr, prefix })
+    }
+}
+
+impl TryFrom<String> for Cidr {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl fmt::Display for Cidr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    /// 测试IPv4和IPv6地址段的匹配
+    #[test]
+    fn test_contains() {
+        let cidr: Cidr = "10.0.0.0/8".parse().unwrap();
+        assert!(cidr.contains(ip("10.1.2.3")));
+        assert!(!cidr.contains(ip("11.0.0.1")));
+        assert!(cidr.contains(ip("::ffff:10.0.0.1")));
+
+        let single: Cidr = "127.0.0.1".parse().unwrap();
+        assert!(single.contains(ip("127.0.0.1")));
+        assert!(!single.contains(ip("127.0.0.2")));
+
+        let any: Cidr = "0.0.0.0/0".parse().unwrap();
+        assert!(any.contains(ip("192.168.1.1")));
+        assert!(!any.contains(ip("::1")));
+
+        let v6: Cidr = "fd00::/8".parse().unwrap();
+        assert!(v6.contains(ip("fd12::1")));
+        assert!(!v6.contains(ip("fe80::1")));
+    }
+
+    /// 测试主机位被清零，无效的地址段会返回错误
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            "10.1.2.3/8".parse::<Cidr>().unwrap().to_string(),
+            "10.0.0.0/8"
+        );
+        assert_eq!("::1".parse::<Cidr>().unwrap().to_string(), "::1/128");
+        assert!("10.0.0.0/33".parse::<Cidr>().is_err());
+        assert!("10.0.0/8".parse::<Cidr>().is_err());
+        assert!("localhost".parse::<Cidr>().is_err());
+    }
+
+    /// 测试空的allowlist允许所有地址
+    #[test]
+    fn test_is_allowed() {
+        assert!(is_allowed(&[], ip("8.8.8.8")));
+
+        let allowlist = ["127.0.0.0/8".parse().unwrap(), "::1".parse().unwrap()];
+        assert!(is_allowed(&allowlist, ip("127.0.0.1")));
+        assert!(is_allowed(&allowlist, ip("::1")));
+        assert!(!is_allowed(&allowlist, ip("192.168.0.1")));
+    }
+}
@@ -1,51 +1,268 @@
 //! Connection结构体，用于从远程peer发送和向远程peer接收Frame
 
-use std::io::Cursor;
+use std::{fmt, io::Cursor, net::SocketAddr};
 
-use bytes::{Buf, BytesMut};
+use bytes::{Buf, Bytes, BytesMut};
 use tokio::{
-    io::{self, AsyncReadExt, AsyncWriteExt, BufWriter},
+    io::{self, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufWriter, DuplexStream},
     net::TcpStream,
 };
 
-use crate::networking::frame::{Error::Incomplete, Frame};
+use crate::{
+    networking::frame::{format_double, CheckProgress, Error::Incomplete, Frame, FrameLimits},
+    RustisError,
+};
+
+/// 内联命令一行允许的最大长度（64KB），与redis的PROTO_INLINE_MAX_SIZE保持一致
+const MAX_INLINE_LEN: usize = 64 * 1024;
+
+/// 读缓冲区的默认初始容量（4KB）
+pub(crate) const DEFAULT_READ_BUFFER_SIZE: usize = 4096;
+
+/// # Stream trait
+///
+/// Connection底层的字节流，通常是TcpStream，测试中也可以是内存中的DuplexStream
+pub trait Stream: AsyncRead + AsyncWrite + Unpin + Send + Sync + fmt::Debug {
+    /// # try_write() 函数
+    ///
+    /// 不等待流可写，立即写入数据，返回写入的字节数，不支持同步写入的流返回WouldBlock
+    fn try_write(&self, buf: &[u8]) -> io::Result<usize>;
+}
 
-/// 用于从远程peer发送和接收Frame，Connection的目的是在底层的TcpStream上读取和写入帧
+impl Stream for TcpStream {
+    fn try_write(&self, buf: &[u8]) -> io::Result<usize> {
+        TcpStream::try_write(self, buf)
+    }
+}
+
+impl Stream for DuplexStream {
+    fn try_write(&self, _buf: &[u8]) -> io::Result<usize> {
+        Err(io::ErrorKind::WouldBlock.into())
+    }
+}
+
+/// 用于从远程peer发送和接收Frame，Connection的目的是在底层的TcpStream（或者其他字节流）上读取和写入帧
 #[derive(Debug)]
 pub struct Connection {
     /// BufWriter，当write方法被调用时，不会直接写入到socket，而是先写入到缓冲区中，
     /// 当缓冲区填满时，会自动刷新到内部的socket中，然后再将缓冲区清空
     /// 这样做的目的是为了减少系统调用的次数，提高性能
-    stream: BufWriter<TcpStream>,
+    stream: BufWriter<Box<dyn Stream>>,
     /// 为了读取帧，Connection使用一个内部缓冲区，该缓冲区会被填充，直到有足够的字节来创建一个完整的帧，一旦缓冲区
     /// 中有足够的数据，Connection就会创建帧并将其返回给调用者
     buffer: BytesMut,
+    /// 上次调用take_net_bytes()之后从socket读取的字节数
+    bytes_read: u64,
+    /// 上次调用take_net_bytes()之后写入socket的字节数
+    bytes_written: u64,
+    /// 写入的错误回复总数
+    error_replies: u64,
+    /// 连接的唯一标识，由服务器在accept时分配，客户端的连接为0
+    id: u64,
+    /// 对端的地址
+    peer: Option<SocketAddr>,
+    /// 本端的地址
+    local: Option<SocketAddr>,
+    /// 连接建立以来从socket读取的字节总数
+    total_bytes_read: u64,
+    /// 连接建立以来写入socket的字节总数
+    total_bytes_written: u64,
+    /// 写缓冲区中是否有还没有flush的数据
+    unflushed: bool,
+    /// 上次调用take_net_bytes()之后flush的次数
+    flushes: u64,
+    /// 与对端协商的协议版本，2或3，RESP2连接收到的RESP3帧会被降级编码
+    protocol: u8,
+    /// 解析对端发来的帧时的长度上限
+    limits: FrameLimits,
+    /// 是否接受内联命令，服务器的连接为true，不以`*`开头的请求按内联命令解析；客户端读取回复时为false
+    inline: bool,
+    /// 读缓冲区的初始容量，读取过大的帧之后缓冲区会缩小回这个容量
+    read_buffer_size: usize,
+    /// 是否暂停自动flush，为true时write_frame()只写入写缓冲区
+    corked: bool,
+    /// 通过CLIENT SETNAME设置的连接名称
+    name: Option<String>,
+    /// 缓冲区开头还不完整的帧已经检查到的位置，新数据到达后从这里继续检查
+    check_progress: CheckProgress,
 }
 
 impl Connection {
     pub fn new(stream: TcpStream) -> Self {
+        Self::with_capacity(stream, DEFAULT_READ_BUFFER_SIZE)
+    }
+
+    /// # with_capacity() 函数
+    ///
+    /// 创建一个读缓冲区初始容量为capacity的连接
+    pub(crate) fn with_capacity(stream: TcpStream, capacity: usize) -> Self {
+        let peer = stream.peer_addr().ok();
+        let local = stream.local_addr().ok();
+
+        Self {
+            peer,
+            local,
+            ..Self::from_stream(stream, capacity)
+        }
+    }
+
+    /// # from_stream() 函数
+    ///
+    /// 在任意的字节流上创建连接，读缓冲区初始容量为capacity，没有对端和本端的地址
+    pub(crate) fn from_stream(stream: impl Stream + 'static, capacity: usize) -> Self {
         Self {
-            stream: BufWriter::new(stream),
-            // 分配一个缓冲区，具有4KB的缓冲长度
-            buffer: BytesMut::with_capacity(4096),
+            stream: BufWriter::new(Box::new(stream)),
+            buffer: BytesMut::with_capacity(capacity),
+            bytes_read: 0,
+            bytes_written: 0,
+            error_replies: 0,
+            id: 0,
+            peer: None,
+            local: None,
+            total_bytes_read: 0,
+            total_bytes_written: 0,
+            unflushed: false,
+            flushes: 0,
+            protocol: 2,
+            // 客户端读取回复时不限制长度，GET或者LRANGE的回复可能超过服务器接受请求的上限；
+            // 服务器通过with_limits()使用配置的上限
+            limits: FrameLimits::unbounded(),
+            inline: false,
+            read_buffer_size: capacity,
+            corked: false,
+            name: None,
+            check_progress: CheckProgress::default(),
         }
     }
 
+    /// # with_id() 函数
+    ///
+    /// 为连接设置唯一标识
+    pub(crate) fn with_id(mut self, id: u64) -> Self {
+        self.id = id;
+        self
+    }
+
+    /// # with_limits() 函数
+    ///
+    /// 设置解析帧时的长度上限
+    pub(crate) fn with_limits(mut self, limits: FrameLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// # with_inline_commands() 函数
+    ///
+    /// 接受telnet风格的内联命令，服务器读取请求的连接使用
+    pub(crate) fn with_inline_commands(mut self) -> Self {
+        self.inline = true;
+        self
+    }
+
+    /// # id() 函数
+    ///
+    /// 返回连接的唯一标识
+    pub(crate) fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// # name() 函数
+    ///
+    /// 返回通过CLIENT SETNAME设置的连接名称
+    pub(crate) fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// # set_name() 函数
+    ///
+    /// 设置连接名称，None表示清除名称
+    pub(crate) fn set_name(&mut self, name: Option<String>) {
+        self.name = name;
+    }
+
+    /// # set_protocol() 函数
+    ///
+    /// 设置与对端协商的协议版本，之后写入的帧按照这个版本编码
+    pub(crate) fn set_protocol(&mut self, protocol: u8) {
+        self.protocol = protocol;
+    }
+
+    /// # protocol() 函数
+    ///
+    /// 返回与对端协商的协议版本
+    pub(crate) fn protocol(&self) -> u8 {
+        self.protocol
+    }
+
+    /// # try_write_now() 函数
+    ///
+    /// 不等待socket可写，立即把数据写入socket，用于Drop等不能执行异步操作的地方。
+    /// 写缓冲区中还有没有flush的数据、socket暂时不可写或者只写入了一部分时返回错误
+    pub(crate) fn try_write_now(&self, buf: &[u8]) -> io::Result<()> {
+        if !self.stream.buffer().is_empty() {
+            return Err(io::ErrorKind::WouldBlock.into());
+        }
+
+        match self.stream.get_ref().try_write(buf)? {
+            n if n == buf.len() => Ok(()),
+            _ => Err(io::ErrorKind::WriteZero.into()),
+        }
+    }
+
+    /// # peer_addr() 函数
+    ///
+    /// 返回对端的地址
+    pub(crate) fn peer_addr(&self) -> Option<SocketAddr> {
+        self.peer
+    }
+
+    /// # local_addr() 函数
+    ///
+    /// 返回本端的地址
+    pub(crate) fn local_addr(&self) -> Option<SocketAddr> {
+        self.local
+    }
+
+    /// # total_bytes_read() 函数
+    ///
+    /// 返回连接建立以来从socket读取的字节总数，不受take_net_bytes()影响
+    pub(crate) fn total_bytes_read(&self) -> u64 {
+        self.total_bytes_read
+    }
+
+    /// # total_bytes_written() 函数
+    ///
+    /// 返回连接建立以来写入的字节总数，按照帧编码后的长度统计，不受take_net_bytes()影响
+    pub(crate) fn total_bytes_written(&self) -> u64 {
+        self.total_bytes_written
+    }
+
     /// # parse_frame() 函数
     ///
     /// 从缓冲区中解析出一个完整的帧
     fn parse_frame(&mut self) -> crate::Result<Option<Frame>> {
-        // 创建T: Buf类型
-        let mut buf = Cursor::new(&self.buffer[..]);
+        // 与redis一样，服务器收到的请求不以`*`开头时，按照telnet风格的内联命令解析，空行直接跳过
+        while let Some(&byte) = self.buffer.first() {
+            if !self.inline || byte == b'*' {
+                break;
+            }
 
-        // 检查是否读取了一个足够解析出一个帧的数据
-        match Frame::check(&mut buf) {
-            Ok(_) => {
-                // 获取组成该帧的字节数
-                let len = buf.position() as usize;
+            match self.parse_inline()? {
+                None => return Ok(None),
+                Some(args) if args.is_empty() => continue,
+                Some(args) => {
+                    let args = args.into_iter().map(Frame::Bulk).collect();
+                    return Ok(Some(Frame::Array(args)));
+                }
+            }
+        }
 
-                // 在解析开始前，重置内部的游标位置
-                buf.set_position(0);
+        // 检查是否读取了一个足够解析出一个帧的数据，从上次检查停下的位置继续，
+        // 大的帧分成很多次到达时不会重复扫描已经检查过的部分
+        match self.check_progress.check(&self.buffer, &self.limits) {
+            Ok(len) => {
+                // 创建T: Buf类型
+                let mut buf = Cursor::new(&self.buffer[..]);
 
                 // 解析帧
                 let frame = Frame::parse(&mut buf)?;
@@ -53,13 +270,75 @@ impl Connection {
                 // 解析完成，从缓冲去中移除已经解析的数据
                 self.buffer.advance(len); // advance()用于前进buf的内部游标，从而丢弃这些字节
 
+                // 读取过大的帧时缓冲区被扩容了，剩下的数据不多时换回初始容量的缓冲区，
+                // 避免大量空闲连接一直占用很大的缓冲区
+                if len > self.read_buffer_size && self.buffer.len() <= self.read_buffer_size {
+                    self.shrink_buffer();
+                }
+
                 // 返回解析出的帧
                 Ok(Some(frame))
             }
-            // 缓冲区的数据不足以解析出一个完整的帧
+            // 缓冲区的数据不足以解析出一个完整的帧，超过整个帧的上限时不再继续缓冲
+            Err(Incomplete) if self.buffer.len() as u64 > self.limits.max_frame_len => {
+                Err(RustisError::Protocol("too big request".to_string()).into())
+            }
             Err(Incomplete) => Ok(None),
-            // 解析出错
-            Err(e) => Err(e.into()),
+            // 解析出错，属于协议错误
+            Err(e) => Err(RustisError::from(e).into()),
+        }
+    }
+
+    /// # shrink_buffer() 函数
+    ///
+    /// 把读缓冲区换成初始容量的新缓冲区，复制还没有解析的数据
+    fn shrink_buffer(&mut self) {
+        let mut buffer = BytesMut::with_capacity(self.read_buffer_size);
+        buffer.extend_from_slice(&self.buffer);
+        self.buffer = buffer;
+    }
+
+    /// # read_buffer_capacity() 函数
+    ///
+    /// 返回读缓冲区当前的容量
+    #[cfg(test)]
+    pub(crate) fn read_buffer_capacity(&self) -> usize {
+        self.buffer.capacity()
+    }
+
+    /// # parse_inline() 函数
+    ///
+    /// 从缓冲区中解析出一行内联命令，例如`SET foo bar\r\n`，返回按空白分隔的参数。
+    /// 超过MAX_INLINE_LEN还没有换行时返回协议错误，避免无限缓冲
+    fn parse_inline(&mut self) -> crate::Result<Option<Vec<Bytes>>> {
+        let end = match self.inline_line_end()? {
+            Some(end) => end,
+            None => return Ok(None),
+        };
+
+        let line = self.buffer.split_to(end + 1);
+        let line = line[..end].strip_suffix(b"\r").unwrap_or(&line[..end]);
+
+        Ok(Some(split_inline_args(line)?))
+    }
+
+    /// # inline_line_end() 函数
+    ///
+    /// 返回缓冲区中第一个换行符的位置，还没有收到完整的一行时返回None，
+    /// 缓冲的数据超过整个帧的上限或者MAX_INLINE_LEN时返回协议错误
+    fn inline_line_end(&self) -> crate::Result<Option<usize>> {
+        match self
+            .buffer
+            .iter()
+            .take(MAX_INLINE_LEN + 1)
+            .position(|&byte| byte == b'\n')
+        {
+            Some(end) => Ok(Some(end)),
+            None if self.buffer.len() as u64 > self.limits.max_frame_len => {
+                Err(RustisError::Protocol("too big request".to_string()).into())
+            }
+            None if self.buffer.len() <= MAX_INLINE_LEN => Ok(None),
+            None => Err(RustisError::Protocol("too big inline request".to_string()).into()),
         }
     }
 
@@ -77,7 +356,11 @@ impl Connection {
 
             // 如果缓冲区中的数据还不足以被解析为一个数据帧
             // 就需要从socket中读取更多的数据
-            if self.stream.read_buf(&mut self.buffer).await? == 0 {
+            let n = self.stream.read_buf(&mut self.buffer).await?;
+            self.bytes_read += n as u64;
+            self.total_bytes_read += n as u64;
+
+            if n == 0 {
                 // 读取成功时，会返回读取到的字节数，0代表读到了stream的末尾
                 // 这时候对端已经关闭了连接
 
@@ -87,92 +370,246 @@ impl Connection {
                     return Ok(None);
                 } else {
                     // 缓冲区中还有数据，说明对端关闭了连接，但是还有数据没有被处理
-                    return Err("连接被对端重置".into());
+                    let error = io::Error::new(io::ErrorKind::ConnectionReset, "连接被对端重置");
+                    return Err(RustisError::Io(error).into());
                 }
             }
         }
     }
 
+    /// # has_buffered_frame() 函数
+    ///
+    /// 读缓冲区中是否已经有一个完整的帧，此时下一次read_frame()不需要等待socket
+    ///
+    /// 缓冲区中的数据有协议错误时也返回true，让read_frame()尽快返回这个错误
+    pub(crate) fn has_buffered_frame(&self) -> bool {
+        if let Some(&byte) = self.buffer.first() {
+            if self.inline && byte != b'*' {
+                return !matches!(self.inline_line_end(), Ok(None));
+            }
+        }
+
+        let mut progress = self.check_progress.clone();
+        !matches!(progress.check(&self.buffer, &self.limits), Err(Incomplete))
+    }
+
     /// # write_frame() 函数
     ///
-    /// redis协议编码过程：将一个完整的数据帧写入到socket中
+    /// redis协议编码过程：将一个完整的数据帧写入到socket中，并立即flush
     pub async fn write_frame(&mut self, frame: &Frame) -> io::Result<()> {
-        match frame {
-            Frame::Array(val) => {
-                // 编码帧类型前缀
-                self.stream.write_u8(b'*').await?;
+        self.write_frame_nowait(frame).await?;
+        if self.corked {
+            return Ok(());
+        }
+        self.flush().await
+    }
 
-                // 编码帧的长度
-                self.write_decimal(val.len() as u64).await?;
+    /// # cork() 函数
+    ///
+    /// 暂停或恢复write_frame()的自动flush。连续写入多个帧（例如订阅多个channel的确认）时先cork，
+    /// 全部写完后取消cork再调用一次flush()，这些帧只需要一次写入socket。取消cork本身不会flush
+    #[cfg(any(test, feature = "cmd-pubsub"))]
+    pub(crate) fn cork(&mut self, corked: bool) {
+        self.corked = corked;
+    }
 
-                // 迭代并编码数组中的每一个元素
-                for item in &**val {
-                    self.write_value(item).await?;
-                }
-            }
-            // 帧类型为字面量，直接对值进行编码并写入到socket
-            _ => self.write_value(frame).await?,
+    /// # write_frame_nowait() 函数
+    ///
+    /// 将一个完整的数据帧写入到写缓冲区，不进行flush
+    ///
+    /// 处理pipeline时，多个响应可以先写入缓冲区，再通过flush()一次性写入socket，减少系统调用的次数。
+    /// 缓冲区写满时BufWriter会自动写入socket，但是最后一部分数据需要调用者flush
+    pub(crate) async fn write_frame_nowait(&mut self, frame: &Frame) -> io::Result<()> {
+        // 写入的字节数由write_bytes()累加
+        self.write_value(frame).await?;
+        if let Frame::Error(_) = frame {
+            self.error_replies += 1;
         }
+        self.unflushed = true;
+        Ok(())
+    }
+
+    /// # flush() 函数
+    ///
+    /// 将写缓冲区剩余的内容写入socket，没有未flush的数据时什么也不做
+    pub(crate) async fn flush(&mut self) -> io::Result<()> {
+        if !self.unflushed {
+            return Ok(());
+        }
+
+        self.stream.flush().await?;
+        self.unflushed = false;
+        self.flushes += 1;
+        Ok(())
+    }
 
-        // 调用flush()将缓冲区剩余的内容写入socket
-        // TODO: 当帧比较小的时候，每写一次帧就flush一次的模式性能开销比较大
-        // 此时我们可以选择在Connection中实现flush函数，然后等帧积累多个后，
-        // 再一次性在Connection中进行flush
-        self.stream.flush().await
+    /// # error_replies() 函数
+    ///
+    /// 返回这个连接上写入的错误回复总数，用于判断命令是否执行失败
+    pub(crate) fn error_replies(&self) -> u64 {
+        self.error_replies
+    }
+
+    /// # take_net_bytes() 函数
+    ///
+    /// 返回自上次调用以来读取和写入的字节数，以及flush的次数，并将计数清零
+    pub(crate) fn take_net_bytes(&mut self) -> (u64, u64, u64) {
+        let bytes = (self.bytes_read, self.bytes_written, self.flushes);
+        self.bytes_read = 0;
+        self.bytes_written = 0;
+        self.flushes = 0;
+        bytes
     }
 
     /// # write_value() 函数
     ///
-    /// redis协议编码过程：将字面值帧写入stream
+    /// redis协议编码过程：将帧写入stream，聚合类型的帧递归编码其中的每一个元素
+    ///
+    /// RESP2连接不认识RESP3的类型，按照redis的方式降级编码：map编码为键值交替的数组，set编码为数组，
+    /// 浮点数、大整数和带格式的字符串编码为bulk字符串，布尔值编码为整数
     async fn write_value(&mut self, frame: &Frame) -> io::Result<()> {
+        let resp3 = self.protocol >= 3;
+
         match frame {
             Frame::Simple(val) => {
                 // 编码帧类型前缀
-                self.stream.write_u8(b'+').await?;
+                self.write_bytes(b"+").await?;
                 // 编码帧的值
-                self.stream.write_all(val.as_bytes()).await?;
+                self.write_bytes(val.as_bytes()).await?;
                 // 编码帧的结束符
-                self.stream.write_all(b"\r\n").await?;
+                self.write_bytes(b"\r\n").await?;
             }
             Frame::Error(val) => {
                 //  编码帧类型前缀
-                self.stream.write_u8(b'-').await?;
+                self.write_bytes(b"-").await?;
                 // 编码帧的值
-                self.stream.write_all(val.as_bytes()).await?;
+                self.write_bytes(val.as_bytes()).await?;
                 // 编码帧的结束符
-                self.stream.write_all(b"\r\n").await?;
+                self.write_bytes(b"\r\n").await?;
             }
             Frame::Integer(val) => {
                 // 编码帧类型前缀
-                self.stream.write_u8(b':').await?;
+                self.write_bytes(b":").await?;
                 // 编码帧的值
                 self.write_decimal(*val).await?;
             }
+            Frame::Null | Frame::NullArray if resp3 => {
+                self.write_bytes(b"_\r\n").await?;
+            }
             Frame::Null => {
-                self.stream.write_all(b"$-1\r\n").await?;
+                self.write_bytes(b"$-1\r\n").await?;
+            }
+            Frame::NullArray => {
+                self.write_bytes(b"*-1\r\n").await?;
             }
             Frame::Bulk(val) => {
-                let len = val.len();
+                self.write_bulk(val).await?;
+            }
+            // 数组帧，递归编码其中的每一个元素
+            Frame::Array(val) => {
+                self.write_bytes(b"*").await?;
+                self.write_decimal(val.len() as i64).await?;
 
-                // 编码帧类型前缀
-                self.stream.write_u8(b'$').await?;
-                // 编码Bulk帧的长度
-                self.write_decimal(len as u64).await?;
-                // 编码Bulk帧的值
-                self.stream.write_all(val).await?;
-                // 编码帧的结束符
-                self.stream.write_all(b"\r\n").await?;
+                for item in val {
+                    Box::pin(self.write_value(item)).await?;
+                }
+            }
+            Frame::Set(val) => {
+                self.write_bytes(if resp3 { b"~" } else { b"*" }).await?;
+                self.write_decimal(val.len() as i64).await?;
+
+                for item in val {
+                    Box::pin(self.write_value(item)).await?;
+                }
+            }
+            Frame::Map(pairs) => {
+                // RESP2下编码为长度翻倍的数组
+                if resp3 {
+                    self.write_bytes(b"%").await?;
+                    self.write_decimal(pairs.len() as i64).await?;
+                } else {
+                    self.write_bytes(b"*").await?;
+                    self.write_decimal(pairs.len() as i64 * 2).await?;
+                }
+
+                for (key, value) in pairs {
+                    Box::pin(self.write_value(key)).await?;
+                    Box::pin(self.write_value(value)).await?;
+                }
+            }
+            Frame::Double(val) => {
+                let val = format_double(*val);
+                if resp3 {
+                    self.write_bytes(b",").await?;
+                    self.write_bytes(val.as_bytes()).await?;
+                    self.write_bytes(b"\r\n").await?;
+                } else {
+                    self.write_bulk(val.as_bytes()).await?;
+                }
+            }
+            Frame::Boolean(val) if resp3 => {
+                self.write_bytes(if *val { b"#t\r\n" } else { b"#f\r\n" })
+                    .await?;
+            }
+            Frame::Boolean(val) => {
+                self.write_bytes(b":").await?;
+                self.write_decimal(*val as i64).await?;
+            }
+            Frame::BigNumber(val) if resp3 => {
+                self.write_bytes(b"(").await?;
+                self.write_bytes(val.as_bytes()).await?;
+                self.write_bytes(b"\r\n").await?;
+            }
+            Frame::BigNumber(val) => {
+                self.write_bulk(val.as_bytes()).await?;
+            }
+            Frame::Verbatim { format, text } if resp3 => {
+                // 长度包括3个字符的格式和冒号
+                self.write_bytes(b"=").await?;
+                self.write_decimal((text.len() + 4) as i64).await?;
+                self.write_bytes(format.as_bytes()).await?;
+                self.write_bytes(b":").await?;
+                self.write_bytes(text).await?;
+                self.write_bytes(b"\r\n").await?;
+            }
+            Frame::Verbatim { text, .. } => {
+                self.write_bulk(text).await?;
             }
-            Frame::Array(_val) => unreachable!(),
         }
 
         Ok(())
     }
 
+    /// # write_bulk() 函数
+    ///
+    /// redis协议编码过程：将一个bulk字符串写入stream
+    async fn write_bulk(&mut self, val: &[u8]) -> io::Result<()> {
+        // 编码帧类型前缀
+        self.write_bytes(b"$").await?;
+        // 编码Bulk帧的长度
+        self.write_decimal(val.len() as i64).await?;
+        // 编码Bulk帧的值
+        self.write_bytes(val).await?;
+        // 编码帧的结束符
+        self.write_bytes(b"\r\n").await?;
+
+        Ok(())
+    }
+
+    /// # write_bytes() 函数
+    ///
+    /// 将字节写入stream，同时累加写入的字节数，编码帧的所有写入都经过这里
+    async fn write_bytes(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.stream.write_all(bytes).await?;
+        self.bytes_written += bytes.len() as u64;
+        self.total_bytes_written += bytes.len() as u64;
+        Ok(())
+    }
+
     /// # write_decimal() 函数
     ///
     /// redis协议编码过程：将一个十进制帧写入stream
-    async fn write_decimal(&mut self, val: u64) -> io::Result<()> {
+    async fn write_decimal(&mut self, val: i64) -> io::Result<()> {
         use std::io::Write;
 
         // 创建一个20字节的缓冲区
@@ -185,17 +622,109 @@ impl Connection {
         // 获取buf的游标位置，即有数据的最后一个位置
         let pos = buf.position() as usize;
         // 将buf中的数据写入到stream中
-        self.stream.write_all(&buf.get_ref()[..pos]).await?;
+        self.write_bytes(&buf.get_ref()[..pos]).await?;
         // 写入换行符
-        self.stream.write_all(b"\r\n").await?;
+        self.write_bytes(b"\r\n").await?;
 
         Ok(())
     }
 }
 
+/// # split_inline_args() 函数
+///
+/// 按照redis的规则把内联命令分割为参数：参数之间用空白分隔，双引号内支持\n、\xHH等转义，
+/// 单引号内只支持\'，引号不匹配或者右引号后面紧跟其他字符时返回协议错误
+fn split_inline_args(line: &[u8]) -> Result<Vec<Bytes>, RustisError> {
+    let unbalanced = || RustisError::Protocol("unbalanced quotes in request".to_string());
+    let hex = |pos: usize| line.get(pos).and_then(|&byte| (byte as char).to_digit(16));
+    // \xHH转义表示的字节，pos是第一个十六进制字符的位置
+    let hex_escape = |pos: usize| Some((hex(pos)? * 16 + hex(pos + 1)?) as u8);
+
+    let mut args = Vec::new();
+    let mut i = 0;
+
+    loop {
+        while line.get(i).is_some_and(u8::is_ascii_whitespace) {
+            i += 1;
+        }
+        let Some(&first) = line.get(i) else {
+            return Ok(args);
+        };
+
+        let mut arg = Vec::new();
+        match first {
+            b'"' => {
+                i += 1;
+                loop {
+                    match (line.get(i), line.get(i + 1)) {
+                        (None, _) => return Err(unbalanced()),
+                        (Some(b'\\'), Some(b'x')) if hex_escape(i + 2).is_some() => {
+                            arg.extend(hex_escape(i + 2));
+                            i += 4;
+                        }
+                        (Some(b'\\'), Some(&escaped)) => {
+                            arg.push(match escaped {
+                                b'n' => b'\n',
+                                b'r' => b'\r',
+                                b't' => b'\t',
+                                b'b' => 0x08,
+                                b'a' => 0x07,
+                                other => other,
+                            });
+                            i += 2;
+                        }
+                        (Some(b'"'), _) => {
+                            i += 1;
+                            break;
+                        }
+                        (Some(&byte), _) => {
+                            arg.push(byte);
+                            i += 1;
+                        }
+                    }
+                }
+            }
+            b'\'' => {
+                i += 1;
+                loop {
+                    match (line.get(i), line.get(i + 1)) {
+                        (None, _) => return Err(unbalanced()),
+                        (Some(b'\\'), Some(b'\'')) => {
+                            arg.push(b'\'');
+                            i += 2;
+                        }
+                        (Some(b'\''), _) => {
+                            i += 1;
+                            break;
+                        }
+                        (Some(&byte), _) => {
+                            arg.push(byte);
+                            i += 1;
+                        }
+                    }
+                }
+            }
+            _ => {
+                while let Some(&byte) = line.get(i).filter(|byte| !byte.is_ascii_whitespace()) {
+                    arg.push(byte);
+                    i += 1;
+                }
+            }
+        }
+
+        // 右引号后面必须是空白或者行尾
+        if line.get(i).is_some_and(|byte| !byte.is_ascii_whitespace()) {
+            return Err(unbalanced());
+        }
+
+        args.push(Bytes::from(arg));
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::networking::frame::{MAX_ARRAY_LEN, MAX_LINE_LEN};
     use bytes::Bytes;
     use tokio::{
         io::{self, AsyncReadExt, AsyncWriteExt},
@@ -279,6 +808,129 @@ mod tests {
         Ok(())
     }
 
+    /// # write_with_protocol() 函数
+    ///
+    /// 按照给定的协议版本写入帧，返回对端收到的字节和写入时统计的字节数
+    async fn write_with_protocol(
+        protocol: u8,
+        frames: Vec<Frame>,
+    ) -> crate::Result<(Vec<u8>, u64)> {
+        let listener = TcpListener::bind("localhost:0").await?;
+        let addr = listener.local_addr()?;
+
+        let server = tokio::spawn(async move {
+            let (server_stream, _) = listener.accept().await.unwrap();
+            let mut connection = Connection::new(server_stream);
+            connection.set_protocol(protocol);
+
+            for frame in &frames {
+                connection.write_frame(frame).await?;
+            }
+
+            io::Result::Ok(connection.take_net_bytes().1)
+        });
+
+        let mut client = TcpStream::connect(addr).await?;
+        let mut buf = Vec::new();
+        client.read_to_end(&mut buf).await?;
+
+        let written = server.await??;
+        Ok((buf, written))
+    }
+
+    /// # resp3_frames() 函数
+    ///
+    /// 包含所有RESP3类型的帧，其中有嵌套的map
+    fn resp3_frames() -> Vec<Frame> {
+        vec![
+            Frame::Map(vec![
+                (
+                    Frame::Simple("server".into()),
+                    Frame::Bulk(Bytes::from("rustis")),
+                ),
+                (
+                    Frame::Bulk(Bytes::from("nested")),
+                    Frame::Map(vec![(
+                        Frame::Simple("set".into()),
+                        Frame::Set(vec![Frame::Integer(1), Frame::Null]),
+                    )]),
+                ),
+            ]),
+            Frame::Double(1.5),
+            Frame::Double(f64::INFINITY),
+            Frame::Boolean(true),
+            Frame::Boolean(false),
+            Frame::BigNumber("1234567890123456789012345678901234567890".into()),
+            Frame::Verbatim {
+                format: "txt".into(),
+                text: Bytes::from("Some string"),
+            },
+            Frame::Null,
+        ]
+    }
+
+    /// 测试RESP3连接上所有类型的帧编码后可以原样解析回来，并且统计的字节数与实际写入的一致
+    #[tokio::test]
+    async fn test_resp3_round_trip() -> crate::Result<()> {
+        let frames = resp3_frames();
+        let (buf, written) = write_with_protocol(3, frames.clone()).await?;
+        assert_eq!(written as usize, buf.len());
+
+        let expected =
+            b"%2\r\n+server\r\n$6\r\nrustis\r\n$6\r\nnested\r\n%1\r\n+set\r\n~2\r\n:1\r\n_\r\n\
+            ,1.5\r\n,inf\r\n#t\r\n#f\r\n(1234567890123456789012345678901234567890\r\n\
+            =15\r\ntxt:Some string\r\n_\r\n";
+        assert_eq!(
+            String::from_utf8_lossy(&buf),
+            String::from_utf8_lossy(expected)
+        );
+
+        let mut cursor = Cursor::new(&buf[..]);
+        for frame in frames {
+            let start = cursor.position();
+            Frame::check(&mut cursor, &FrameLimits::default())?;
+            cursor.set_position(start);
+            let parsed = Frame::parse(&mut cursor)?;
+            assert_eq!(format!("{:?}", parsed), format!("{:?}", frame));
+        }
+        assert_eq!(cursor.position() as usize, buf.len());
+
+        Ok(())
+    }
+
+    /// 测试RESP2连接收到降级后的编码
+    #[tokio::test]
+    async fn test_resp2_downgrade() -> crate::Result<()> {
+        let (buf, written) = write_with_protocol(2, resp3_frames()).await?;
+        assert_eq!(written as usize, buf.len());
+
+        let expected = b"*4\r\n+server\r\n$6\r\nrustis\r\n$6\r\nnested\r\n*2\r\n+set\r\n*2\r\n:1\r\n$-1\r\n\
+            $3\r\n1.5\r\n$3\r\ninf\r\n:1\r\n:0\r\n$40\r\n1234567890123456789012345678901234567890\r\n\
+            $11\r\nSome string\r\n$-1\r\n";
+        assert_eq!(
+            String::from_utf8_lossy(&buf),
+            String::from_utf8_lossy(expected)
+        );
+
+        Ok(())
+    }
+
+    /// 测试null数组在RESP2下编码为*-1，在RESP3下与null相同
+    #[tokio::test]
+    async fn test_write_null_array() -> crate::Result<()> {
+        let frames = vec![Frame::NullArray, Frame::Array(vec![])];
+
+        let (buf, written) = write_with_protocol(2, frames.clone()).await?;
+        assert_eq!(&buf[..], b"*-1\r\n*0\r\n");
+        assert_eq!(written as usize, buf.len());
+
+        let (buf, written) = write_with_protocol(3, frames).await?;
+        assert_eq!(&buf[..], b"_\r\n*0\r\n");
+        assert_eq!(written as usize, buf.len());
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_read_and_write_frame() -> crate::Result<()> {
         // 创建一个TcpListener
@@ -298,6 +950,8 @@ mod tests {
                     Frame::Integer(12345),
                     Frame::Null,
                     Frame::Bulk(Bytes::from("bulk data")),
+                    Frame::Integer(-2),
+                    Frame::Integer(i64::MIN),
                 ]))
                 .await?;
 
@@ -312,7 +966,7 @@ mod tests {
         if let Some(frame) = connection.read_frame().await? {
             match frame {
                 Frame::Array(val) => {
-                    assert_eq!(val.len(), 5);
+                    assert_eq!(val.len(), 7);
                     match &val[0] {
                         Frame::Simple(val) => assert_eq!(val, "OK"),
                         _ => panic!("帧类型不是Simple"),
@@ -333,6 +987,11 @@ mod tests {
                         Frame::Bulk(val) => assert_eq!(val, &Bytes::from("bulk data")),
                         _ => panic!("帧类型不是Bulk"),
                     }
+                    match &val[5] {
+                        Frame::Integer(val) => assert_eq!(*val, -2),
+                        _ => panic!("帧类型不是Integer"),
+                    }
+                    assert_eq!(val[6], Frame::Integer(i64::MIN));
                 }
                 _ => panic!("帧类型不是Array"),
             }
@@ -345,4 +1004,267 @@ mod tests {
 
         Ok(())
     }
+
+    /// 测试内联命令的参数分割，包括引号和转义
+    #[test]
+    fn test_split_inline_args() {
+        let split = |line: &[u8]| {
+            split_inline_args(line)
+                .map(|args| args.iter().map(|arg| arg.to_vec()).collect::<Vec<_>>())
+        };
+
+        assert_eq!(
+            split(b"  SET  foo\tbar ").unwrap(),
+            vec![b"SET".to_vec(), b"foo".to_vec(), b"bar".to_vec()]
+        );
+        assert_eq!(
+            split(br#"SET "hello world" 'it\'s' "a\n\x41\"""#).unwrap(),
+            vec![
+                b"SET".to_vec(),
+                b"hello world".to_vec(),
+                b"it's".to_vec(),
+                b"a\nA\"".to_vec()
+            ]
+        );
+        assert_eq!(split(br#"SET k """#).unwrap()[2], b"".to_vec());
+        assert!(split(b"").unwrap().is_empty());
+
+        assert!(split(br#"SET "unterminated"#).is_err());
+        assert!(split(br#"SET "a"b"#).is_err());
+        assert!(split(b"SET 'a").is_err());
+    }
+
+    /// 测试读取1MB的bulk时缓冲区扩容，帧被解析之后缓冲区缩小回初始容量，剩下的数据不会丢失
+    #[tokio::test]
+    async fn test_read_buffer_shrinks() -> crate::Result<()> {
+        let listener = TcpListener::bind("localhost:0").await?;
+        let addr = listener.local_addr()?;
+
+        let client = tokio::spawn(async move {
+            let mut stream = TcpStream::connect(addr).await?;
+            let value = vec![b'x'; 1024 * 1024];
+            stream
+                .write_all(format!("${}\r\n", value.len()).as_bytes())
+                .await?;
+            stream.write_all(&value).await?;
+            stream.write_all(b"\r\n+OK\r\n").await?;
+            io::Result::Ok(())
+        });
+
+        let (server_stream, _) = listener.accept().await?;
+        let mut connection = Connection::with_capacity(server_stream, 1024);
+        assert_eq!(connection.read_buffer_capacity(), 1024);
+
+        // 先把整个bulk读入缓冲区，不解析
+        while connection.buffer.len() < 1024 * 1024 + 13 {
+            connection.stream.read_buf(&mut connection.buffer).await?;
+        }
+        assert!(connection.read_buffer_capacity() >= 1024 * 1024);
+
+        let frame = connection.read_frame().await?.unwrap();
+        assert!(matches!(frame, Frame::Bulk(ref data) if data.len() == 1024 * 1024));
+        assert_eq!(connection.read_buffer_capacity(), 1024);
+
+        assert_eq!(
+            connection.read_frame().await?,
+            Some(Frame::Simple("OK".to_string()))
+        );
+
+        client.await??;
+        Ok(())
+    }
+
+    /// 把frame编码后以chunk字节为一块写入socket，每块之后让出执行权，返回读到的帧
+    async fn read_in_chunks(src: Vec<u8>, chunk: usize) -> crate::Result<Frame> {
+        let listener = TcpListener::bind("localhost:0").await?;
+        let addr = listener.local_addr()?;
+
+        let client = tokio::spawn(async move {
+            let mut stream = TcpStream::connect(addr).await?;
+            stream.set_nodelay(true)?;
+            for part in src.chunks(chunk) {
+                stream.write_all(part).await?;
+                tokio::task::yield_now().await;
+            }
+            io::Result::Ok(())
+        });
+
+        let (server_stream, _) = listener.accept().await?;
+        let mut connection = Connection::new(server_stream);
+        let frame = connection.read_frame().await?.unwrap();
+
+        client.await??;
+        Ok(frame)
+    }
+
+    /// 测试分成1KB的块到达的4MB bulk和有很多元素的数组都能完整地读出来
+    #[tokio::test]
+    async fn test_read_frame_in_small_chunks() -> crate::Result<()> {
+        let value = vec![b'x'; 4 * 1024 * 1024];
+        let mut src = format!("${}\r\n", value.len()).into_bytes();
+        src.extend_from_slice(&value);
+        src.extend_from_slice(b"\r\n");
+        let frame = read_in_chunks(src, 1024).await?;
+        assert!(matches!(frame, Frame::Bulk(ref data) if data.len() == value.len()));
+
+        let mut src = b"*50000\r\n".to_vec();
+        for i in 0..50000 {
+            let element = i.to_string();
+            src.extend_from_slice(format!("${}\r\n{}\r\n", element.len(), element).as_bytes());
+        }
+        match read_in_chunks(src, 1024).await? {
+            Frame::Array(frames) => {
+                assert_eq!(frames.len(), 50000);
+                assert_eq!(frames[49999], Frame::Bulk(Bytes::from("49999")));
+            }
+            frame => panic!("unexpected frame {:?}", frame),
+        }
+        Ok(())
+    }
+
+    /// 测试只有服务器的连接接受内联命令，客户端读取回复时不以类型字节开头的数据是协议错误
+    #[tokio::test]
+    async fn test_inline_commands_only_on_server() -> crate::Result<()> {
+        let mut server = Connection::from_stream(io::duplex(64).0, 1024).with_inline_commands();
+        server
+            .buffer
+            .extend_from_slice(b"$3 a\r\n*1\r\n$4\r\nPING\r\n");
+        let expected = Frame::Array(vec![
+            Frame::Bulk(Bytes::from("$3")),
+            Frame::Bulk(Bytes::from("a")),
+        ]);
+        assert_eq!(server.parse_frame()?, Some(expected));
+        assert_eq!(
+            server.parse_frame()?,
+            Some(Frame::Array(vec![Frame::Bulk(Bytes::from("PING"))]))
+        );
+
+        let mut client = Connection::from_stream(io::duplex(64).0, 1024);
+        client.buffer.extend_from_slice(b"PONG\r\n");
+        assert!(client.has_buffered_frame());
+        assert!(client.parse_frame().is_err());
+        Ok(())
+    }
+
+    /// 测试没有通过with_limits()设置上限的连接读取回复时不限制长度，服务器的上限只用于解析请求
+    #[tokio::test]
+    async fn test_client_reads_unbounded_replies() -> crate::Result<()> {
+        let elements = MAX_ARRAY_LEN as usize + 1;
+        let mut src = format!("*{}\r\n", elements).into_bytes();
+        for _ in 0..elements {
+            src.extend_from_slice(b":1\r\n");
+        }
+        match read_in_chunks(src, 64 * 1024).await? {
+            Frame::Array(frames) => assert_eq!(frames.len(), elements),
+            frame => panic!("unexpected frame {:?}", frame),
+        }
+
+        let line = "x".repeat(MAX_LINE_LEN as usize + 1);
+        let frame = read_in_chunks(format!("+{}\r\n", line).into_bytes(), 64 * 1024).await?;
+        assert_eq!(frame, Frame::Simple(line.clone()));
+
+        let mut server =
+            Connection::from_stream(io::duplex(64).0, 1024).with_limits(FrameLimits::default());
+        server
+            .buffer
+            .extend_from_slice(format!("+{}\r\n", line).as_bytes());
+        assert!(server.parse_frame().is_err());
+        Ok(())
+    }
+
+    /// 比较分成1KB的块到达的不同大小的数组的读取时间，时间应该与数据量大致成正比
+    ///
+    /// 默认不运行：cargo test bench_read_frame_in_small_chunks -- --ignored --nocapture
+    #[tokio::test]
+    #[ignore]
+    async fn bench_read_frame_in_small_chunks() -> crate::Result<()> {
+        for elements in [100_000, 200_000, 400_000] {
+            let mut src = format!("*{}\r\n", elements).into_bytes();
+            for _ in 0..elements {
+                src.extend_from_slice(b"$3\r\nabc\r\n");
+            }
+            let bytes = src.len();
+            let start = std::time::Instant::now();
+            read_in_chunks(src, 1024).await?;
+            println!(
+                "{} elements ({} bytes): {:?}",
+                elements,
+                bytes,
+                start.elapsed()
+            );
+        }
+        Ok(())
+    }
+
+    /// 测试cork期间write_frame()不会flush，取消cork之后一次flush写出所有的帧
+    #[tokio::test]
+    async fn test_cork() -> crate::Result<()> {
+        let listener = TcpListener::bind("localhost:0").await?;
+        let addr = listener.local_addr()?;
+        let client = tokio::spawn(async move { TcpStream::connect(addr).await });
+
+        let (server_stream, _) = listener.accept().await?;
+        let mut connection = Connection::new(server_stream);
+        let mut client = Connection::new(client.await??);
+
+        connection.cork(true);
+        for i in 0..3 {
+            connection.write_frame(&Frame::Integer(i)).await?;
+        }
+        assert_eq!(connection.take_net_bytes().2, 0);
+
+        connection.cork(false);
+        connection.flush().await?;
+        assert_eq!(connection.take_net_bytes().2, 1);
+        for i in 0..3 {
+            assert_eq!(client.read_frame().await?, Some(Frame::Integer(i)));
+        }
+
+        // 没有cork时每次write_frame()都会flush
+        connection.write_frame(&Frame::Integer(3)).await?;
+        connection.write_frame(&Frame::Integer(4)).await?;
+        assert_eq!(connection.take_net_bytes().2, 2);
+
+        Ok(())
+    }
+
+    /// 测试一次请求响应之后读写的字节总数与RESP编码的长度一致，并且不会被take_net_bytes()清零
+    #[tokio::test]
+    async fn test_total_bytes() -> crate::Result<()> {
+        let listener = TcpListener::bind("localhost:0").await?;
+        let addr = listener.local_addr()?;
+        let client = tokio::spawn(async move { TcpStream::connect(addr).await });
+
+        let (server_stream, _) = listener.accept().await?;
+        let mut connection = Connection::new(server_stream);
+        let mut client = Connection::new(client.await??);
+
+        assert_eq!(connection.local_addr(), Some(addr));
+        assert_eq!(connection.peer_addr(), client.local_addr());
+        assert_eq!(client.peer_addr(), Some(addr));
+
+        // *1\r\n$4\r\nPING\r\n
+        let ping = Frame::Array(vec![Frame::Bulk(Bytes::from("PING"))]);
+        client.write_frame(&ping).await?;
+        assert_eq!(connection.read_frame().await?, Some(ping));
+        assert_eq!(connection.total_bytes_read(), 14);
+        assert_eq!(connection.take_net_bytes(), (14, 0, 0));
+
+        // +PONG\r\n 和 :100\r\n
+        connection
+            .write_frame(&Frame::Simple("PONG".to_string()))
+            .await?;
+        connection.write_frame(&Frame::Integer(100)).await?;
+        assert_eq!(
+            client.read_frame().await?,
+            Some(Frame::Simple("PONG".to_string()))
+        );
+        assert_eq!(client.read_frame().await?, Some(Frame::Integer(100)));
+        assert_eq!(connection.total_bytes_written(), 13);
+        assert_eq!(connection.total_bytes_read(), 14);
+        assert_eq!(client.total_bytes_written(), 14);
+        assert_eq!(client.total_bytes_read(), 13);
+
+        Ok(())
+    }
 }
@@ -3,20 +3,86 @@
 use bytes::{Buf, Bytes};
 use std::{fmt, io::Cursor, num::TryFromIntError, string::FromUtf8Error};
 
+/// 数组帧默认允许的最大元素个数，与redis对请求的限制保持一致
+pub(crate) const MAX_ARRAY_LEN: u64 = 1024 * 1024;
+
+/// bulk帧默认允许的最大长度（512MB），与redis的proto-max-bulk-len默认值保持一致
+pub(crate) const MAX_BULK_LEN: u64 = 512 * 1024 * 1024;
+
+/// 一个完整的帧默认允许的最大长度（1GB），与redis的client-query-buffer-limit默认值保持一致
+pub(crate) const MAX_FRAME_LEN: u64 = 1024 * 1024 * 1024;
+
+/// 简单字符串、错误、整数和长度行等单行元素默认允许的最大长度（64KB），与redis的PROTO_INLINE_MAX_SIZE保持一致
+pub(crate) const MAX_LINE_LEN: u64 = 64 * 1024;
+
+/// # FrameLimits 结构体
+///
+/// 解析帧时的长度上限，在check()读到长度时就检查，不会等数据到达或者分配内存之后才拒绝
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct FrameLimits {
+    /// bulk帧的最大长度
+    pub(crate) max_bulk_len: u64,
+    /// 数组、set和map帧的最大元素个数
+    pub(crate) max_array_len: u64,
+    /// 一个完整的帧（包括嵌套的所有帧）的最大长度
+    pub(crate) max_frame_len: u64,
+    /// 一行（包括类型字节和\r\n）的最大长度
+    pub(crate) max_line_len: u64,
+}
+
+impl FrameLimits {
+    /// # unbounded() 函数
+    ///
+    /// 不限制任何长度，客户端读取回复时使用，回复的大小由发出的命令决定
+    pub(crate) const fn unbounded() -> Self {
+        Self {
+            max_bulk_len: u64::MAX,
+            max_array_len: u64::MAX,
+            max_frame_len: u64::MAX,
+            max_line_len: u64::MAX,
+        }
+    }
+}
+
+impl Default for FrameLimits {
+    fn default() -> Self {
+        Self {
+            max_bulk_len: MAX_BULK_LEN,
+            max_array_len: MAX_ARRAY_LEN,
+            max_frame_len: MAX_FRAME_LEN,
+            max_line_len: MAX_LINE_LEN,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum Frame {
     /// 简单字符串
     Simple(String),
     /// 错误
     Error(String),
-    /// 整数
-    Integer(u64),
+    /// 整数，可以是负数，例如TTL类命令用-1和-2表示没有过期时间和键不存在
+    Integer(i64),
     /// Bulk
     Bulk(Bytes),
-    /// null
+    /// null，RESP2编码为`$-1`，RESP3编码为`_`
     Null,
+    /// null数组，RESP2编码为`*-1`，RESP3编码为`_`，例如BLPOP超时或者EXEC失败时的回复
+    NullArray,
     /// 数组帧
     Array(Vec<Frame>),
+    /// RESP3的map，保持键值对的顺序，RESP2下编码为键和值交替排列的数组
+    Map(Vec<(Frame, Frame)>),
+    /// RESP3的set，RESP2下编码为数组
+    Set(Vec<Frame>),
+    /// RESP3的浮点数，RESP2下编码为bulk字符串
+    Double(f64),
+    /// RESP3的布尔值，RESP2下编码为整数1或0
+    Boolean(bool),
+    /// RESP3的大整数，保存为十进制字符串，RESP2下编码为bulk字符串
+    BigNumber(String),
+    /// RESP3的带格式的字符串，format是3个字符的格式（如txt、mkd），RESP2下编码为bulk字符串
+    Verbatim { format: String, text: Bytes },
 }
 
 impl Frame {
@@ -48,67 +114,49 @@ impl Frame {
     /// # panic
     ///
     /// 如果self不是一个数组帧，将会panic
-    pub(crate) fn push_int(&mut self, value: u64) {
+    pub(crate) fn push_int(&mut self, value: i64) {
         match self {
             Frame::Array(vec) => vec.push(Frame::Integer(value)),
             _ => panic!("插入整数帧时, 被插入的帧类型不是数组帧"),
         }
     }
 
-    /// # check() 函数
+    /// # push_frame() 函数
     ///
-    /// 检查是否可以从src解码整个消息
-    pub(crate) fn check(src: &mut Cursor<&[u8]>) -> Result<()> {
-        match get_u8(src)? {
-            b'+' => {
-                // Simple(String)
-                get_line(src)?;
-                Ok(())
-            }
-            b'-' => {
-                // Error(String)
-                get_line(src)?;
-                Ok(())
-            }
-            b':' => {
-                // Integer(u64)
-                let _ = get_decimal(src)?;
-                Ok(())
-            }
-            b'$' => {
-                // Bulk(Bytes)
-                if b'-' == peek_u8(src)? {
-                    // 跳过 $<数据长度>\r\n
-                    skip(src, 4)
-                } else {
-                    // 获取Bulk的长度
-                    let len: usize = get_decimal(src)?.try_into()?;
-
-                    // 跳过前面的标识字符+长度（2个字节
-                    skip(src, len + 2)
-                }
-            }
-            b'*' => {
-                // Array(Vec<Frame>)
-                // 获取数组的长度
-                let len = get_decimal(src)?;
-
-                for _ in 0..len {
-                    Frame::check(src)?;
-                }
-
-                Ok(())
-            }
-            actual => Err(format!("协议错误: 无效的帧类型: {}", actual).into()),
+    /// 将任意帧（包括嵌套的数组帧）推入数组，self必须是一个数组帧
+    ///
+    /// # panic
+    ///
+    /// 如果self不是一个数组帧，将会panic
+    pub(crate) fn push_frame(&mut self, frame: Frame) {
+        match self {
+            Frame::Array(vec) => vec.push(frame),
+            _ => panic!("插入帧时, 被插入的帧类型不是数组帧"),
         }
     }
 
+    /// # check() 函数
+    ///
+    /// 检查是否可以从src解码整个消息，长度超过limits时返回错误。检查成功时src的游标位于帧的末尾。
+    /// 读取连接时使用可以恢复的CheckProgress
+    #[cfg(test)]
+    pub(crate) fn check(src: &mut Cursor<&[u8]>, limits: &FrameLimits) -> Result<()> {
+        let mut progress = CheckProgress {
+            start: src.position(),
+            position: src.position(),
+            ..Default::default()
+        };
+        let end = progress.check(src.get_ref(), limits)?;
+        src.set_position(end as u64);
+        Ok(())
+    }
+
     /// # parse() 函数
     ///
     /// redis协议解码过程：从src中解析一个Frame，要先调用check()函数检查是否可以解析
     pub(crate) fn parse(src: &mut Cursor<&[u8]>) -> Result<Frame> {
-        match get_u8(src)? {
-            b'+' => {
+        match FrameType::from_byte(get_u8(src)?)? {
+            FrameType::Simple => {
                 // Simple(String)
                 // 读取行数据，并将其转换为Vec<u8>
                 let line = get_line(src)?.to_vec();
@@ -118,7 +166,7 @@ impl Frame {
 
                 Ok(Frame::Simple(string))
             }
-            b'-' => {
+            FrameType::Error => {
                 // Error(String)
                 // 读取行数据，并将其转换为Vec<u8>
                 let line = get_line(src)?.to_vec();
@@ -128,21 +176,16 @@ impl Frame {
 
                 Ok(Frame::Error(string))
             }
-            b':' => {
-                // Integer(u64)
-                // 获取十进制数
-                let value = get_decimal(src)?;
+            FrameType::Integer => {
+                // Integer(i64)
+                // 获取有符号的十进制数
+                let value = get_integer(src)?;
                 Ok(Frame::Integer(value))
             }
-            b'$' => {
+            FrameType::Bulk => {
                 // Buik(Bytes)
                 if b'-' == peek_u8(src)? {
-                    let line = get_line(src)?;
-
-                    if line != b"-1" {
-                        return Err("协议错误: 无效的帧格式".into());
-                    }
-
+                    check_null_line(src)?;
                     Ok(Frame::Null)
                 } else {
                     // 读取bulk的长度
@@ -162,7 +205,11 @@ impl Frame {
                     Ok(Frame::Bulk(data))
                 }
             }
-            b'*' => {
+            FrameType::Array if b'-' == peek_u8(src)? => {
+                check_null_line(src)?;
+                Ok(Frame::NullArray)
+            }
+            FrameType::Array => {
                 // Array(Vec<Frame>)
                 // 获取数组的长度
                 let len = get_decimal(src)?.try_into()?;
@@ -174,23 +221,264 @@ impl Frame {
 
                 Ok(Frame::Array(out))
             }
-            _ => unimplemented!(),
+            FrameType::Set => {
+                // Set(Vec<Frame>)
+                let len = get_decimal(src)?.try_into()?;
+                let mut out = Vec::with_capacity(len);
+
+                for _ in 0..len {
+                    out.push(Frame::parse(src)?);
+                }
+
+                Ok(Frame::Set(out))
+            }
+            FrameType::Map => {
+                // Map(Vec<(Frame, Frame)>)
+                let len = get_decimal(src)?.try_into()?;
+                let mut out = Vec::with_capacity(len);
+
+                for _ in 0..len {
+                    let key = Frame::parse(src)?;
+                    let value = Frame::parse(src)?;
+                    out.push((key, value));
+                }
+
+                Ok(Frame::Map(out))
+            }
+            FrameType::Null => {
+                // RESP3的Null
+                if !get_line(src)?.is_empty() {
+                    return Err("invalid frame format".into());
+                }
+
+                Ok(Frame::Null)
+            }
+            FrameType::Boolean => {
+                // Boolean(bool)
+                match get_line(src)? {
+                    b"t" => Ok(Frame::Boolean(true)),
+                    b"f" => Ok(Frame::Boolean(false)),
+                    _ => Err("invalid frame format".into()),
+                }
+            }
+            FrameType::Double => {
+                // Double(f64)，包括inf、-inf和nan
+                let line =
+                    std::str::from_utf8(get_line(src)?).map_err(|_| "invalid frame format")?;
+                let value = line.parse().map_err(|_| "invalid frame format")?;
+
+                Ok(Frame::Double(value))
+            }
+            FrameType::BigNumber => {
+                // BigNumber(String)，可以带有负号的十进制数
+                let line = get_line(src)?;
+                let digits = line.strip_prefix(b"-").unwrap_or(line);
+                if digits.is_empty() || !digits.iter().all(u8::is_ascii_digit) {
+                    return Err("invalid frame format".into());
+                }
+
+                Ok(Frame::BigNumber(String::from_utf8(line.to_vec())?))
+            }
+            FrameType::Verbatim => {
+                // Verbatim，数据的前4个字节是格式和冒号，例如"txt:"
+                let len: usize = get_decimal(src)?.try_into()?;
+                let n = len + 2;
+
+                if src.remaining() < n {
+                    return Err(Error::Incomplete);
+                }
+
+                let data = &src.chunk()[..len];
+                if len < 4 || data[3] != b':' {
+                    return Err("invalid frame format".into());
+                }
+                let format = String::from_utf8(data[..3].to_vec())?;
+                let text = Bytes::copy_from_slice(&data[4..]);
+
+                skip(src, n)?;
+
+                Ok(Frame::Verbatim { format, text })
+            }
         }
     }
 
+    /// # as_bytes() 函数
+    ///
+    /// 返回Simple和Bulk帧的内容，其他类型返回None
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            Frame::Simple(s) => Some(s.as_bytes()),
+            Frame::Bulk(data) => Some(data),
+            _ => None,
+        }
+    }
+
+    /// # as_str() 函数
+    ///
+    /// 返回Simple和Bulk帧的内容，其他类型或者内容不是合法的UTF-8时返回None
+    pub fn as_str(&self) -> Option<&str> {
+        self.as_bytes()
+            .and_then(|bytes| std::str::from_utf8(bytes).ok())
+    }
+
+    /// # to_bytes() 函数
+    ///
+    /// 返回Simple和Bulk帧内容的Bytes，Bulk帧不会复制数据
+    pub fn to_bytes(&self) -> Option<Bytes> {
+        match self {
+            Frame::Simple(s) => Some(Bytes::copy_from_slice(s.as_bytes())),
+            Frame::Bulk(data) => Some(data.clone()),
+            _ => None,
+        }
+    }
+
+    /// # as_string() 函数
+    ///
+    /// 返回Simple和Bulk帧的内容，不是合法UTF-8的字节会被替换为U+FFFD
+    pub fn as_string(&self) -> Option<String> {
+        self.as_bytes()
+            .map(|bytes| String::from_utf8_lossy(bytes).into_owned())
+    }
+
+    /// # as_integer() 函数
+    ///
+    /// 返回Integer帧的值，其他类型返回None
+    pub fn as_integer(&self) -> Option<i64> {
+        match self {
+            Frame::Integer(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    /// # as_array() 函数
+    ///
+    /// 返回Array和Set帧的元素，其他类型返回None
+    pub fn as_array(&self) -> Option<&[Frame]> {
+        match self {
+            Frame::Array(parts) | Frame::Set(parts) => Some(parts),
+            _ => None,
+        }
+    }
+
+    /// # decode() 函数
+    ///
+    /// 通过FromFrame把帧转换为T，例如`client.send_command(args).await?.decode::<u64>()`
+    pub fn decode<T: crate::client::FromFrame>(self) -> crate::Result<T> {
+        T::from_frame(self)
+    }
+
+    /// # is_error() 函数
+    ///
+    /// 是否是错误帧
+    pub fn is_error(&self) -> bool {
+        matches!(self, Frame::Error(_))
+    }
+
     /// # to_error() 函数
     ///
     /// 将Frame转换为Error类型
     pub(crate) fn to_error(&self) -> crate::Error {
-        format!("不是预期的帧类型: {:?}", self).into()
+        crate::RustisError::Protocol(format!("unexpected frame: {:?}", self)).into()
+    }
+}
+
+/// # FrameType 枚举
+///
+/// 帧的类型，由帧的第一个字节决定
+///
+/// check()和parse()都先把第一个字节转换为FrameType再匹配，新增类型时两边都必须处理，
+/// 未知的字节在这里统一返回协议错误
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FrameType {
+    Simple,
+    Error,
+    Integer,
+    Bulk,
+    Array,
+    Map,
+    Set,
+    Null,
+    Double,
+    Boolean,
+    BigNumber,
+    Verbatim,
+}
+
+impl FrameType {
+    /// # from_byte() 函数
+    ///
+    /// 将帧的第一个字节转换为帧类型
+    fn from_byte(byte: u8) -> Result<FrameType> {
+        Ok(match byte {
+            b'+' => FrameType::Simple,
+            b'-' => FrameType::Error,
+            b':' => FrameType::Integer,
+            b'$' => FrameType::Bulk,
+            b'*' => FrameType::Array,
+            b'%' => FrameType::Map,
+            b'~' => FrameType::Set,
+            b'_' => FrameType::Null,
+            b',' => FrameType::Double,
+            b'#' => FrameType::Boolean,
+            b'(' => FrameType::BigNumber,
+            b'=' => FrameType::Verbatim,
+            _ => return Err(format!("invalid frame type byte '{}'", [byte].escape_ascii()).into()),
+        })
+    }
+}
+
+/// 结构相等，两个帧的类型和内容都相同时才相等。Double按位比较，
+/// 所以NaN等于自身，0.0和-0.0不相等，与它们编码后的字节一致，因此Frame也可以实现Eq
+impl PartialEq for Frame {
+    fn eq(&self, other: &Frame) -> bool {
+        match (self, other) {
+            (Frame::Simple(a), Frame::Simple(b)) => a == b,
+            (Frame::Error(a), Frame::Error(b)) => a == b,
+            (Frame::Integer(a), Frame::Integer(b)) => a == b,
+            (Frame::Bulk(a), Frame::Bulk(b)) => a == b,
+            (Frame::Null, Frame::Null) => true,
+            (Frame::NullArray, Frame::NullArray) => true,
+            (Frame::Array(a), Frame::Array(b)) => a == b,
+            (Frame::Map(a), Frame::Map(b)) => a == b,
+            (Frame::Set(a), Frame::Set(b)) => a == b,
+            (Frame::Double(a), Frame::Double(b)) => a.to_bits() == b.to_bits(),
+            (Frame::Boolean(a), Frame::Boolean(b)) => a == b,
+            (Frame::BigNumber(a), Frame::BigNumber(b)) => a == b,
+            (
+                Frame::Verbatim {
+                    format: f1,
+                    text: t1,
+                },
+                Frame::Verbatim {
+                    format: f2,
+                    text: t2,
+                },
+            ) => f1 == f2 && t1 == t2,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for Frame {}
+
+/// Simple和Bulk帧的内容与字符串相同时相等
+impl PartialEq<str> for Frame {
+    fn eq(&self, other: &str) -> bool {
+        self.as_bytes() == Some(other.as_bytes())
     }
 }
 
 impl PartialEq<&str> for Frame {
     fn eq(&self, other: &&str) -> bool {
+        self == *other
+    }
+}
+
+/// Integer帧的值与整数相同时相等，负数不等于任何u64
+impl PartialEq<u64> for Frame {
+    fn eq(&self, other: &u64) -> bool {
         match self {
-            Frame::Simple(s) => s.eq(other),
-            Frame::Bulk(s) => s.eq(other),
+            Frame::Integer(n) => u64::try_from(*n) == Ok(*other),
             _ => false,
         }
     }
@@ -208,8 +496,23 @@ impl fmt::Display for Frame {
                 Ok(string) => string.fmt(fmt),
                 Err(_) => write!(fmt, "{:?}", msg),
             },
-            Frame::Null => "(nil)".fmt(fmt),
-            Frame::Array(parts) => {
+            Frame::Null | Frame::NullArray => "(nil)".fmt(fmt),
+            Frame::Double(num) => format_double(*num).fmt(fmt),
+            Frame::Boolean(val) => val.fmt(fmt),
+            Frame::BigNumber(num) => num.fmt(fmt),
+            Frame::Verbatim { text, .. } => Frame::Bulk(text.clone()).fmt(fmt),
+            Frame::Map(pairs) => {
+                for (i, (key, value)) in pairs.iter().enumerate() {
+                    if i > 0 {
+                        write!(fmt, " ")?;
+                    }
+
+                    write!(fmt, "{} => {}", key, value)?;
+                }
+
+                Ok(())
+            }
+            Frame::Array(parts) | Frame::Set(parts) => {
                 for (i, part) in parts.iter().enumerate() {
                     if i > 0 {
                         // use space as the array element display separator
@@ -225,6 +528,186 @@ impl fmt::Display for Frame {
     }
 }
 
+/// # CheckProgress 结构体
+///
+/// 可以恢复的帧检查：数据不足时记住已经检查完的元素，新数据到达后从上次停下的元素继续，
+/// 而不是每次都从头扫描整个缓冲区。已经读到bulk的长度时，数据到齐之前不会再检查
+#[derive(Debug, Clone, Default)]
+pub(crate) struct CheckProgress {
+    /// 帧的起始位置，用来计算已经检查过的部分的长度
+    start: u64,
+    /// 下一个要检查的元素的起始位置，之前的元素都已经检查完了
+    position: u64,
+    /// 还没有检查完的数组、集合和映射中剩余的元素个数，最外层在前
+    remaining: Vec<u64>,
+    /// 缓冲区至少有这么多字节时才继续检查
+    needed: u64,
+}
+
+/// # Element 枚举
+///
+/// check_element()检查完一个元素的开头之后的结果
+enum Element {
+    /// 元素已经完整
+    Complete,
+    /// 数组、集合或映射的开头，后面还有这么多个帧
+    Aggregate(u64),
+    /// bulk或verbatim的长度行，后面还有这么多字节的数据和\r\n
+    Payload(u64),
+}
+
+impl CheckProgress {
+    /// # check() 函数
+    ///
+    /// 从上次停下的位置继续检查src，src必须是上次检查的数据后面追加了新数据。
+    /// 有一个完整的帧时返回帧结束的位置并重置进度，数据不足时返回Incomplete，帧无效时返回错误并重置进度。
+    ///
+    /// 每检查完一个元素的开头就知道它的结束位置，一行超过max_line_len或者帧超过max_frame_len时立即返回错误，
+    /// 不会等后面的数据到达，例如由大量小bulk组成的数组在累计长度超过上限时就被拒绝
+    pub(crate) fn check(&mut self, src: &[u8], limits: &FrameLimits) -> Result<usize> {
+        let available = src.len() as u64;
+        if available < self.needed {
+            return Err(Error::Incomplete);
+        }
+
+        let mut cursor = Cursor::new(src);
+        loop {
+            cursor.set_position(self.position);
+            let element = match check_element(&mut cursor, limits) {
+                Ok(element) => element,
+                // 这一行还没有收到完整，有新数据时再检查，已经收到的部分超过一行的上限时不再等待
+                Err(Error::Incomplete) if available - self.position > limits.max_line_len => {
+                    *self = CheckProgress::default();
+                    return Err("invalid line length".into());
+                }
+                Err(Error::Incomplete) => {
+                    self.needed = available + 1;
+                    return Err(Error::Incomplete);
+                }
+                Err(err) => {
+                    *self = CheckProgress::default();
+                    return Err(err);
+                }
+            };
+
+            if cursor.position() - self.position > limits.max_line_len {
+                *self = CheckProgress::default();
+                return Err("invalid line length".into());
+            }
+            let end = match element {
+                // 客户端不限制长度时len可能接近u64::MAX
+                Element::Payload(len) => cursor.position().saturating_add(len).saturating_add(2),
+                _ => cursor.position(),
+            };
+            if end - self.start > limits.max_frame_len {
+                *self = CheckProgress::default();
+                return Err("invalid frame length".into());
+            }
+
+            match element {
+                Element::Aggregate(len) if len > 0 => {
+                    self.position = cursor.position();
+                    self.remaining.push(len);
+                    continue;
+                }
+                // 已经知道数据的长度，数据到齐之前不需要再检查
+                Element::Payload(_) => {
+                    if available < end {
+                        self.needed = end;
+                        return Err(Error::Incomplete);
+                    }
+                    self.position = end;
+                }
+                _ => self.position = cursor.position(),
+            }
+
+            // 一个元素检查完了，所在的聚合类型的元素都检查完时，聚合类型本身也检查完了
+            loop {
+                match self.remaining.last_mut() {
+                    None => {
+                        let end = self.position as usize;
+                        *self = CheckProgress::default();
+                        return Ok(end);
+                    }
+                    Some(remaining) if *remaining > 1 => {
+                        *remaining -= 1;
+                        break;
+                    }
+                    Some(_) => {
+                        self.remaining.pop();
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// # check_element() 函数
+///
+/// 检查一个元素的开头，长度超过limits时返回错误
+fn check_element(src: &mut Cursor<&[u8]>, limits: &FrameLimits) -> Result<Element> {
+    match FrameType::from_byte(get_u8(src)?)? {
+        FrameType::Integer => {
+            // Integer(i64)
+            let _ = get_integer(src)?;
+            Ok(Element::Complete)
+        }
+        FrameType::Bulk if b'-' == peek_u8(src)? => {
+            // $-1\r\n，其它的负数长度都是无效的
+            check_null_line(src)?;
+            Ok(Element::Complete)
+        }
+        FrameType::Bulk | FrameType::Verbatim => {
+            // Bulk(Bytes)和Verbatim，Verbatim的长度包括格式和冒号
+            // 获取长度，超过上限时直接拒绝，不再等待数据到达
+            Ok(Element::Payload(check_bulk_len(src, limits)? as u64))
+        }
+        FrameType::Array if b'-' == peek_u8(src)? => {
+            // *-1\r\n
+            check_null_line(src)?;
+            Ok(Element::Complete)
+        }
+        kind @ (FrameType::Array | FrameType::Set | FrameType::Map) => {
+            // Array(Vec<Frame>)、Set(Vec<Frame>)和Map(Vec<(Frame, Frame)>)
+            // 获取元素的个数，超过上限时直接拒绝，避免先把整个数组缓冲到内存中
+            let len = get_decimal(src)?;
+            if len > limits.max_array_len {
+                return Err("invalid multibulk length".into());
+            }
+
+            // map的每个元素是一个键值对，包含两个帧
+            Ok(Element::Aggregate(if kind == FrameType::Map {
+                len * 2
+            } else {
+                len
+            }))
+        }
+        FrameType::Simple
+        | FrameType::Error
+        | FrameType::Null
+        | FrameType::Double
+        | FrameType::Boolean
+        | FrameType::BigNumber => {
+            // Simple、Error、Null、Double(f64)、Boolean(bool)和BigNumber(String)都只有一行
+            get_line(src)?;
+            Ok(Element::Complete)
+        }
+    }
+}
+
+/// # format_double() 函数
+///
+/// 按照RESP3的格式将浮点数转换为字符串，无穷大和NaN分别为inf、-inf和nan
+pub(crate) fn format_double(val: f64) -> String {
+    if val.is_nan() {
+        "nan".to_string()
+    } else if val.is_infinite() {
+        if val > 0.0 { "inf" } else { "-inf" }.to_string()
+    } else {
+        val.to_string()
+    }
+}
+
 /// get_u8() 函数
 ///
 /// 从Cursor中获取一个u8类型的字节
@@ -265,7 +748,36 @@ fn get_decimal(src: &mut Cursor<&[u8]>) -> Result<u64> {
 
     let line = get_line(src)?;
 
-    atoi::<u64>(line).ok_or_else(|| "协议错误: 无效的帧格式".into())
+    atoi::<u64>(line).ok_or_else(|| "invalid frame format".into())
+}
+
+/// # get_integer() 函数
+///
+/// 从Cursor中读取一个以新行结尾的有符号十进制数，整行都必须是数字，超出i64范围时返回错误
+fn get_integer(src: &mut Cursor<&[u8]>) -> Result<i64> {
+    use atoi::FromRadix10SignedChecked;
+
+    let line = get_line(src)?;
+
+    match i64::from_radix_10_signed_checked(line) {
+        (Some(value), used)
+            if used == line.len() && line.last().is_some_and(u8::is_ascii_digit) =>
+        {
+            Ok(value)
+        }
+        _ => Err("invalid frame format".into()),
+    }
+}
+
+/// # check_null_line() 函数
+///
+/// 读取null的长度行，只接受-1，`$-2`、`*-10`等其它负数长度是无效的帧
+fn check_null_line(src: &mut Cursor<&[u8]>) -> Result<()> {
+    if get_line(src)? != b"-1" {
+        return Err("invalid frame format".into());
+    }
+
+    Ok(())
 }
 
 /// # peek_u8() 函数
@@ -280,6 +792,18 @@ fn peek_u8(src: &mut Cursor<&[u8]>) -> Result<u8> {
     Ok(src.chunk()[0])
 }
 
+/// # check_bulk_len() 函数
+///
+/// 读取bulk的长度，超过bulk的上限时返回错误，整个帧的上限由CheckProgress检查
+fn check_bulk_len(src: &mut Cursor<&[u8]>, limits: &FrameLimits) -> Result<usize> {
+    let len = get_decimal(src)?;
+    if len > limits.max_bulk_len {
+        return Err("invalid bulk length".into());
+    }
+
+    Ok(len.try_into()?)
+}
+
 /// # skip() 函数
 ///
 /// 从Cursor中跳过n个字节
@@ -327,12 +851,490 @@ impl From<&str> for Error {
 
 impl From<FromUtf8Error> for Error {
     fn from(_err: FromUtf8Error) -> Error {
-        "协议错误: 无效的帧格式".into()
+        "invalid frame format".into()
     }
 }
 
 impl From<TryFromIntError> for Error {
     fn from(_err: TryFromIntError) -> Error {
-        "协议错误: 无效的帧格式".into()
+        "invalid frame format".into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// # parse_all() 函数
+    ///
+    /// 检查并解析一个完整的帧，要求消费掉所有的字节
+    fn parse_all(src: &[u8]) -> Frame {
+        let mut cursor = Cursor::new(src);
+        Frame::check(&mut cursor, &FrameLimits::default()).unwrap();
+        assert_eq!(cursor.position() as usize, src.len());
+
+        cursor.set_position(0);
+        let frame = Frame::parse(&mut cursor).unwrap();
+        assert_eq!(cursor.position() as usize, src.len());
+        frame
+    }
+
+    /// 测试帧之间的结构相等，嵌套的数组逐个元素比较
+    #[test]
+    fn test_frame_eq() {
+        let frame = Frame::Array(vec![
+            Frame::Bulk(Bytes::from("a")),
+            Frame::Array(vec![Frame::Integer(1), Frame::Null]),
+        ]);
+        assert_eq!(frame, frame.clone());
+        assert_ne!(
+            frame,
+            Frame::Array(vec![
+                Frame::Bulk(Bytes::from("a")),
+                Frame::Array(vec![Frame::Integer(2), Frame::Null]),
+            ])
+        );
+
+        // 内容相同但类型不同的帧不相等
+        assert_ne!(Frame::Simple("a".into()), Frame::Bulk(Bytes::from("a")));
+        assert_ne!(Frame::Simple("1".into()), Frame::Integer(1));
+        assert_ne!(Frame::Array(vec![]), Frame::Set(vec![]));
+
+        assert_eq!(Frame::Double(f64::NAN), Frame::Double(f64::NAN));
+        assert_ne!(Frame::Double(0.0), Frame::Double(-0.0));
+    }
+
+    /// 测试帧与字符串、整数之间的比较
+    #[test]
+    fn test_frame_eq_cross_type() {
+        assert_eq!(Frame::Bulk(Bytes::from("subscribe")), "subscribe");
+        assert_eq!(Frame::Simple("OK".into()), *"OK");
+        assert_ne!(Frame::Simple("OK".into()), "ok");
+        assert_ne!(Frame::Error("OK".into()), "OK");
+        assert_ne!(Frame::Integer(1), "1");
+
+        assert_eq!(Frame::Integer(1), 1u64);
+        assert_ne!(Frame::Integer(-1), u64::MAX);
+        assert_ne!(Frame::Simple("1".into()), 1u64);
+
+        assert_eq!(Frame::Bulk(Bytes::from("abc")).as_str(), Some("abc"));
+        assert_eq!(Frame::Bulk(Bytes::from(&b"\xff"[..])).as_str(), None);
+        assert_eq!(
+            Frame::Bulk(Bytes::from(&b"\xff"[..])).as_bytes(),
+            Some(&b"\xff"[..])
+        );
+        assert_eq!(Frame::Integer(1).as_bytes(), None);
+    }
+
+    /// 测试取出帧内容的方法只对对应的类型返回值
+    #[test]
+    fn test_accessors() {
+        let bulk = Frame::Bulk(Bytes::from(&b"ab\xff"[..]));
+        assert_eq!(bulk.to_bytes(), Some(Bytes::from(&b"ab\xff"[..])));
+        assert_eq!(bulk.as_string(), Some("ab\u{fffd}".to_string()));
+        assert_eq!(bulk.as_integer(), None);
+
+        let simple = Frame::Simple("OK".to_string());
+        assert_eq!(simple.to_bytes(), Some(Bytes::from("OK")));
+        assert_eq!(simple.as_string(), Some("OK".to_string()));
+
+        assert_eq!(Frame::Integer(-2).as_integer(), Some(-2));
+        assert_eq!(Frame::Integer(-2).as_string(), None);
+        assert_eq!(Frame::Integer(-2).to_bytes(), None);
+
+        let array = Frame::Array(vec![Frame::Integer(1), Frame::Null]);
+        assert_eq!(
+            array.as_array(),
+            Some(&[Frame::Integer(1), Frame::Null][..])
+        );
+        assert_eq!(Frame::Set(vec![]).as_array(), Some(&[][..]));
+        assert_eq!(Frame::Null.as_array(), None);
+        assert_eq!(array.as_string(), None);
+
+        assert!(Frame::Error("ERR".to_string()).is_error());
+        assert_eq!(Frame::Error("ERR".to_string()).as_string(), None);
+        assert!(!simple.is_error());
+    }
+
+    /// 测试超过上限的长度在读到长度时就被拒绝，不需要等数据到达
+    #[test]
+    fn test_check_limits() {
+        let limits = FrameLimits {
+            max_bulk_len: 8,
+            max_array_len: 2,
+            max_frame_len: 32,
+            max_line_len: 8,
+        };
+        let check = |src: &[u8]| Frame::check(&mut Cursor::new(src), &limits);
+
+        assert!(check(b"$8\r\n12345678\r\n").is_ok());
+        let message = |result: Result<()>| match result {
+            Err(Error::Other(err)) => err.to_string(),
+            result => panic!("unexpected result {:?}", result),
+        };
+        assert_eq!(message(check(b"$9\r\n")), "invalid bulk length");
+        assert_eq!(message(check(b"*3\r\n")), "invalid multibulk length");
+        assert!(matches!(check(b"%3\r\n"), Err(Error::Other(_))));
+
+        // 每个bulk都没有超过上限，但是加起来超过了整个帧的上限
+        assert!(check(b"*2\r\n$8\r\n12345678\r\n$8\r\n").is_err());
+        assert!(matches!(
+            check(b"*2\r\n$8\r\n12345678\r\n$4\r\n"),
+            Err(Error::Incomplete)
+        ));
+    }
+
+    /// 测试超过上限的单行元素被拒绝，没有收到换行时也不会一直等待
+    #[test]
+    fn test_check_line_limit() {
+        let limits = FrameLimits {
+            max_line_len: 8,
+            ..FrameLimits::default()
+        };
+        let check = |src: &[u8]| Frame::check(&mut Cursor::new(src), &limits);
+        let message = |result: Result<()>| match result {
+            Err(Error::Other(err)) => err.to_string(),
+            result => panic!("unexpected result {:?}", result),
+        };
+
+        assert!(check(b"+12345\r\n").is_ok());
+        assert!(check(b"-ERR 1\r\n").is_ok());
+        assert_eq!(message(check(b"+123456\r\n")), "invalid line length");
+        assert_eq!(message(check(b":1234567\r\n")), "invalid line length");
+        assert_eq!(message(check(b"*1\r\n-ERR 123\r\n")), "invalid line length");
+
+        // 还没有收到换行，已经收到的部分没有超过上限时等待，超过时立即拒绝
+        assert!(matches!(check(b"+1234567"), Err(Error::Incomplete)));
+        assert_eq!(message(check(b"+12345678")), "invalid line length");
+        assert_eq!(message(check(b"$123456789")), "invalid line length");
+    }
+
+    /// 测试由小bulk组成的数组在累计长度超过整个帧的上限时就被拒绝，不需要等剩下的元素到达
+    #[test]
+    fn test_check_frame_limit() {
+        let limits = FrameLimits {
+            max_frame_len: 32,
+            ..FrameLimits::default()
+        };
+        let message = |result: Result<usize>| match result {
+            Err(Error::Other(err)) => err.to_string(),
+            result => panic!("unexpected result {:?}", result),
+        };
+
+        // 数组头5字节，每个bulk 9字节，前3个bulk正好达到32字节
+        let mut src = b"*10\r\n".to_vec();
+        for _ in 0..3 {
+            src.extend_from_slice(b"$3\r\nabc\r\n");
+        }
+        let mut progress = CheckProgress::default();
+        assert!(matches!(
+            progress.check(&src, &limits),
+            Err(Error::Incomplete)
+        ));
+
+        // 读到第4个bulk的长度时就知道会超过上限
+        src.extend_from_slice(b"$3\r\n");
+        assert_eq!(
+            message(progress.check(&src, &limits)),
+            "invalid frame length"
+        );
+        assert_eq!(
+            message(Frame::check(&mut Cursor::new(&src[..]), &limits).map(|_| 0)),
+            "invalid frame length"
+        );
+
+        // 整数和简单字符串组成的数组同样受整个帧的上限限制
+        let mut src = b"*100\r\n".to_vec();
+        for _ in 0..10 {
+            src.extend_from_slice(b":1\r\n");
+        }
+        assert_eq!(
+            message(progress.check(&src, &limits)),
+            "invalid frame length"
+        );
+
+        // 帧的长度从帧的起始位置开始计算，前面已经解析过的数据不计入
+        let mut src = vec![b'x'; 100];
+        src.extend_from_slice(b"*2\r\n$3\r\nabc\r\n+ok\r\n");
+        let mut cursor = Cursor::new(&src[..]);
+        cursor.set_position(100);
+        assert!(Frame::check(&mut cursor, &limits).is_ok());
+    }
+
+    /// 测试解析有符号整数，超出i64范围的整数返回错误
+    #[test]
+    fn test_parse_integer() {
+        assert_eq!(parse_all(b":-5\r\n"), Frame::Integer(-5));
+        assert_eq!(
+            parse_all(b":9223372036854775807\r\n"),
+            Frame::Integer(i64::MAX)
+        );
+        assert_eq!(
+            parse_all(b":-9223372036854775808\r\n"),
+            Frame::Integer(i64::MIN)
+        );
+
+        for src in [
+            &b":9223372036854775808\r\n"[..],
+            b":-9223372036854775809\r\n",
+            b":--1\r\n",
+            b":-\r\n",
+            b":\r\n",
+            b":12a\r\n",
+        ] {
+            let mut cursor = Cursor::new(src);
+            assert!(
+                matches!(
+                    Frame::check(&mut cursor, &FrameLimits::default()),
+                    Err(Error::Other(_))
+                ),
+                "{:?}",
+                src
+            );
+        }
+    }
+
+    /// 测试null和null数组只接受-1作为长度，空数组不是null数组
+    #[test]
+    fn test_parse_null() {
+        assert_eq!(parse_all(b"$-1\r\n"), Frame::Null);
+        assert_eq!(parse_all(b"*-1\r\n"), Frame::NullArray);
+        assert_eq!(parse_all(b"*0\r\n"), Frame::Array(vec![]));
+        assert_ne!(Frame::NullArray, Frame::Null);
+
+        for src in [
+            &b"$-2\r\n"[..],
+            b"$-10\r\n",
+            b"$-\r\n",
+            b"*-2\r\n",
+            b"*-10\r\n",
+        ] {
+            let mut cursor = Cursor::new(src);
+            assert!(
+                matches!(
+                    Frame::check(&mut cursor, &FrameLimits::default()),
+                    Err(Error::Other(_))
+                ),
+                "{:?}",
+                src
+            );
+            cursor.set_position(0);
+            assert!(
+                matches!(Frame::parse(&mut cursor), Err(Error::Other(_))),
+                "{:?}",
+                src
+            );
+        }
+
+        // 长度行还没有读完时等待更多的数据
+        let mut cursor = Cursor::new(&b"$-1"[..]);
+        assert!(matches!(
+            Frame::check(&mut cursor, &FrameLimits::default()),
+            Err(Error::Incomplete)
+        ));
+    }
+
+    /// 测试解析RESP3的标量类型
+    #[test]
+    fn test_parse_resp3_scalars() {
+        assert!(matches!(parse_all(b"_\r\n"), Frame::Null));
+        assert!(matches!(parse_all(b"#t\r\n"), Frame::Boolean(true)));
+        assert!(matches!(parse_all(b"#f\r\n"), Frame::Boolean(false)));
+        assert!(matches!(parse_all(b",3.25\r\n"), Frame::Double(val) if val == 3.25));
+        assert!(matches!(parse_all(b",-inf\r\n"), Frame::Double(val) if val == f64::NEG_INFINITY));
+        assert!(matches!(parse_all(b",nan\r\n"), Frame::Double(val) if val.is_nan()));
+        assert!(matches!(
+            parse_all(b"(-3492890328409238509324850943850943825024385\r\n"),
+            Frame::BigNumber(val) if val == "-3492890328409238509324850943850943825024385"
+        ));
+        match parse_all(b"=15\r\ntxt:Some string\r\n") {
+            Frame::Verbatim { format, text } => {
+                assert_eq!(format, "txt");
+                assert_eq!(&text[..], b"Some string");
+            }
+            frame => panic!("unexpected frame: {:?}", frame),
+        }
+    }
+
+    /// 测试解析嵌套的map和set
+    #[test]
+    fn test_parse_resp3_aggregates() {
+        let frame = parse_all(
+            b"%2\r\n+first\r\n:1\r\n$6\r\nsecond\r\n%1\r\n+nested\r\n~2\r\n#t\r\n,1.5\r\n",
+        );
+        let Frame::Map(pairs) = frame else {
+            panic!("帧类型不是Map");
+        };
+        assert_eq!(pairs.len(), 2);
+        assert!(pairs[0].0 == "first");
+        assert!(matches!(pairs[0].1, Frame::Integer(1)));
+        assert!(pairs[1].0 == "second");
+
+        let Frame::Map(nested) = &pairs[1].1 else {
+            panic!("嵌套的帧类型不是Map");
+        };
+        assert!(nested[0].0 == "nested");
+        match &nested[0].1 {
+            Frame::Set(items) => {
+                assert!(
+                    matches!(items[..], [Frame::Boolean(true), Frame::Double(val)] if val == 1.5)
+                );
+            }
+            frame => panic!("unexpected frame: {:?}", frame),
+        }
+    }
+
+    /// 测试任意字节作为帧的第一个字节时，check()和parse()都不会panic，未知的类型返回协议错误
+    #[test]
+    fn test_every_type_byte() {
+        for byte in 0..=u8::MAX {
+            let known = FrameType::from_byte(byte).is_ok();
+
+            for payload in [
+                &b""[..],
+                b"\r\n",
+                b"1\r\n",
+                b"-1\r\n",
+                b"3\r\nabc\r\n",
+                b"1\r\n+a\r\n:1\r\n",
+            ] {
+                let mut src = vec![byte];
+                src.extend_from_slice(payload);
+
+                let mut cursor = Cursor::new(&src[..]);
+                let checked = Frame::check(&mut cursor, &FrameLimits::default());
+                if !known {
+                    assert!(
+                        matches!(&checked, Err(Error::Other(err)) if err.to_string().starts_with("invalid frame type byte")),
+                        "byte {}: {:?}",
+                        byte,
+                        checked
+                    );
+                }
+
+                cursor.set_position(0);
+                let parsed = Frame::parse(&mut cursor);
+                if !known {
+                    assert!(matches!(parsed, Err(Error::Other(_))), "byte {}", byte);
+                }
+            }
+        }
+    }
+
+    /// 测试不完整的map等待更多数据，格式错误的RESP3帧返回错误
+    #[test]
+    fn test_parse_resp3_errors() {
+        // map的长度是键值对的个数，只有一个键时还不完整
+        let mut cursor = Cursor::new(&b"%1\r\n+key\r\n"[..]);
+        assert!(matches!(
+            Frame::check(&mut cursor, &FrameLimits::default()),
+            Err(Error::Incomplete)
+        ));
+
+        for src in [
+            &b"#x\r\n"[..],
+            b",abc\r\n",
+            b"(12a\r\n",
+            b"=3\r\ntxt\r\n",
+            b"_x\r\n",
+        ] {
+            let mut cursor = Cursor::new(src);
+            Frame::check(&mut cursor, &FrameLimits::default()).unwrap();
+            cursor.set_position(0);
+            assert!(
+                matches!(Frame::parse(&mut cursor), Err(Error::Other(_))),
+                "{:?}",
+                src
+            );
+        }
+    }
+
+    /// 测试CheckProgress逐字节追加数据时，只在数据完整时返回帧的长度，结果与从头检查相同
+    #[test]
+    fn test_check_progress_every_prefix() {
+        let limits = FrameLimits::default();
+        let src = b"*4\r\n$5\r\nhello\r\n%1\r\n+key\r\n*2\r\n:1\r\n$-1\r\n*0\r\n=8\r\ntxt:text\r\n+next\r\n";
+        let frame_len = src.len() - b"+next\r\n".len();
+
+        let mut progress = CheckProgress::default();
+        for end in 0..frame_len {
+            assert!(
+                matches!(progress.check(&src[..end], &limits), Err(Error::Incomplete)),
+                "{}",
+                end
+            );
+            assert!(matches!(
+                Frame::check(&mut Cursor::new(&src[..end]), &limits),
+                Err(Error::Incomplete)
+            ));
+        }
+        assert_eq!(
+            progress.check(&src[..frame_len + 1], &limits).unwrap(),
+            frame_len
+        );
+
+        // 检查成功之后进度被重置，可以检查下一个帧
+        assert_eq!(
+            progress.check(&src[frame_len..], &limits).unwrap(),
+            b"+next\r\n".len()
+        );
+
+        // 无效的帧返回错误，进度同样被重置
+        assert!(matches!(
+            progress.check(b"*2\r\n:1\r\n", &limits),
+            Err(Error::Incomplete)
+        ));
+        assert!(matches!(
+            progress.check(b"*2\r\n:1\r\n!x\r\n", &limits),
+            Err(Error::Other(_))
+        ));
+        assert_eq!(progress.check(b":1\r\n", &limits).unwrap(), 4);
+    }
+
+    /// 测试分成1KB的块到达的大数组和大bulk：每次检查都从上次停下的元素继续，
+    /// 已知bulk的长度后数据到齐之前不再检查，检查的总工作量与数据量成线性关系
+    #[test]
+    fn test_check_progress_resumes() {
+        let limits = FrameLimits::default();
+
+        // 100000个小元素组成的数组
+        let mut src = b"*100000\r\n".to_vec();
+        for _ in 0..100000 {
+            src.extend_from_slice(b"$3\r\nabc\r\n");
+        }
+        let mut progress = CheckProgress::default();
+        for end in (1024..src.len()).step_by(1024) {
+            assert!(matches!(
+                progress.check(&src[..end], &limits),
+                Err(Error::Incomplete)
+            ));
+            // 停在最后一个不完整的元素的开头
+            assert!(
+                progress.position as usize > end - 9,
+                "{} {}",
+                progress.position,
+                end
+            );
+        }
+        assert_eq!(progress.check(&src, &limits).unwrap(), src.len());
+
+        // 4MB的bulk，读到长度之后知道需要多少数据
+        let len = 4 * 1024 * 1024;
+        let mut src = format!("${}\r\n", len).into_bytes();
+        src.resize(src.len() + len, b'x');
+        src.extend_from_slice(b"\r\n");
+        assert!(matches!(
+            progress.check(&src[..1024], &limits),
+            Err(Error::Incomplete)
+        ));
+        assert_eq!(progress.needed as usize, src.len());
+        assert_eq!(progress.position, 0);
+        for end in (2048..src.len()).step_by(1024) {
+            assert!(matches!(
+                progress.check(&src[..end], &limits),
+                Err(Error::Incomplete)
+            ));
+        }
+        assert_eq!(progress.check(&src, &limits).unwrap(), src.len());
     }
 }
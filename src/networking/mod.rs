@@ -1,3 +1,5 @@
+pub(crate) mod cidr;
 pub mod connection;
 pub mod frame;
 pub mod parse;
+pub(crate) mod socket;
@@ -27,7 +27,7 @@ impl Parse {
     pub(crate) fn new(frame: Frame) -> Result<Parse> {
         let array = match frame {
             Frame::Array(array) => array,
-            frame => return Err(format!("协议错误: 期望是array帧, 但是获得了{:?}", frame).into()),
+            frame => return Err(format!("expected an array frame, got {:?}", frame).into()),
         };
 
         Ok(Parse {
@@ -54,8 +54,8 @@ impl Parse {
             Frame::Simple(s) => Ok(s),
             Frame::Bulk(data) => str::from_utf8(&data[..])
                 .map(|s| s.to_string())
-                .map_err(|_| "协议错误: 无效的字符串".into()),
-            frame => Err(format!("协议错误: 期望是Simple或Bulk帧, 但是获得了{:?}", frame).into()),
+                .map_err(|_| "invalid UTF-8 string".into()),
+            frame => Err(format!("expected a simple or bulk frame, got {:?}", frame).into()),
         }
     }
 
@@ -66,7 +66,7 @@ impl Parse {
         match self.next()? {
             Frame::Simple(s) => Ok(Bytes::from(s.into_bytes())),
             Frame::Bulk(data) => Ok(data),
-            frame => Err(format!("协议错误: 期望是Simple或Bulk帧, 但是获得了{:?}", frame).into()),
+            frame => Err(format!("expected a simple or bulk frame, got {:?}", frame).into()),
         }
     }
 
@@ -76,20 +76,25 @@ impl Parse {
     pub(crate) fn next_int(&mut self) -> Result<u64> {
         use atoi::atoi;
 
-        const ERR_MSG: &str = "协议错误: 无效的整数";
+        const ERR_MSG: &str = "value is not an integer or out of range";
 
         match self.next()? {
-            Frame::Integer(value) => Ok(value),
+            Frame::Integer(value) => u64::try_from(value).map_err(|_| ERR_MSG.into()),
             Frame::Simple(str) => atoi::<u64>(str.as_bytes()).ok_or_else(|| ERR_MSG.into()),
             Frame::Bulk(data) => atoi::<u64>(&data).ok_or_else(|| ERR_MSG.into()),
-            frame => Err(format!(
-                "协议错误: 期望是Integer, Simple或Bulk帧, 但是获得了{:?}",
-                frame
-            )
-            .into()),
+            frame => {
+                Err(format!("expected an integer, simple or bulk frame, got {:?}", frame).into())
+            }
         }
     }
 
+    /// # remaining() 函数
+    ///
+    /// 返回还没有解析的项数
+    pub(crate) fn remaining(&self) -> usize {
+        self.arr_frame_iter.len()
+    }
+
     /// # is_empty() 函数
     ///
     /// 确保没有剩余的帧
@@ -97,7 +102,7 @@ impl Parse {
         if self.arr_frame_iter.next().is_none() {
             Ok(())
         } else {
-            Err("协议错误: 期望是没有帧了, 但数组帧包含多余的帧".into())
+            Err("syntax error".into())
         }
     }
 }
@@ -128,7 +133,7 @@ impl From<&str> for ParseError {
 impl fmt::Display for ParseError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            ParseError::EndOfStream => "协议错误: 已经不能读取更多的数据帧".fmt(f),
+            ParseError::EndOfStream => "no more frames to read".fmt(f),
             ParseError::Other(err) => err.fmt(f),
         }
     }
@@ -0,0 +1,22 @@
+//! TCP socket选项的设置，服务器和客户端共用
+
+use socket2::{SockRef, TcpKeepalive};
+use tokio::{io, net::TcpStream, time::Duration};
+
+/// # configure_socket() 函数
+///
+/// 设置TCP_NODELAY，并在keepalive不为None时开启TCP keepalive
+pub(crate) fn configure_socket(
+    socket: &TcpStream,
+    nodelay: bool,
+    keepalive: Option<Duration>,
+) -> io::Result<()> {
+    socket.set_nodelay(nodelay)?;
+
+    if let Some(time) = keepalive {
+        let keepalive = TcpKeepalive::new().with_time(time);
+        SockRef::from(socket).set_tcp_keepalive(&keepalive)?;
+    }
+
+    Ok(())
+}
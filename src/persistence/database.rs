@@ -2,18 +2,48 @@
 
 use bincode::{self};
 use bytes::Bytes;
+use indexmap::{IndexMap, IndexSet};
 use serde::{ser::SerializeStruct, Deserialize, Serialize, Serializer};
 use std::{
     collections::{BTreeSet, HashMap},
-    fs::File,
-    io::{ErrorKind, Read},
-    sync::{Arc, Mutex},
+    fs::{self, File},
+    hash::{BuildHasher, RandomState},
+    io::{self, ErrorKind, Read, Write},
+    mem,
+    ops::Deref,
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicU8, AtomicUsize, Ordering},
+        Arc, Mutex, RwLock, RwLockReadGuard, RwLockWriteGuard,
+    },
+    time::{SystemTime, UNIX_EPOCH},
 };
 use tokio::{
-    sync::{broadcast, Notify},
+    sync::{broadcast, mpsc, Notify},
+    task::AbortHandle,
     time::{self, Duration, Instant},
 };
-use tracing::instrument;
+use tracing::{error, info, instrument, warn};
+
+use crate::{
+    cmd::{
+        del::Del,
+        expire::{Expire, ExpireCondition, ExpireUnit},
+        flush::{FlushAll, FlushDb},
+        set::{Set, SetCondition},
+    },
+    error::RustisError,
+    networking::frame::Frame,
+    persistence::idempotency::{IdempotencyTokens, DEFAULT_TOKEN_CAPACITY},
+    server::{
+        config::{KeyspaceEventClass, KeyspaceEvents, MaxMemoryPolicy, ServerConfig},
+        replication::Role,
+        stats::Stats,
+    },
+};
+
+/// 键空间分片的数量，每个分片有自己的锁，不同分片上的键可以被并行读写
+const KEYSPACE_SHARDS: usize = 16;
 
 /// # DatabaseWrapper 结构体
 ///
@@ -27,17 +57,44 @@ pub(crate) struct DatabaseWrapper {
 }
 
 impl DatabaseWrapper {
-    pub(crate) fn new(is_load_rdb: bool) -> DatabaseWrapper {
-        let database = Database::new();
+    ///
+    /// # 错误处理
+    ///
+    /// RDB文件不存在时使用空数据库启动；文件存在但已损坏时返回错误，拒绝启动，
+    /// 除非配置了ignore-corrupt-rdb，此时记录错误日志后使用空数据库启动
+    pub(crate) fn new(config: ServerConfig) -> crate::Result<DatabaseWrapper> {
+        let is_load_rdb = config.load_rdb;
+        let ignore_corrupt_rdb = config.ignore_corrupt_rdb;
+        let rdb_path = config.rdb_path();
+        let database = Database::with_config(config);
 
         // 加载RDB文件
         if is_load_rdb {
-            database
-                .load_from_rdb("rustis.rdb")
-                .expect("Failed to load RDB and the reason is not no such file");
+            if let Err(err) = database.load_from_rdb(&rdb_path) {
+                let corrupt = matches!(
+                    err.downcast_ref::<RustisError>(),
+                    Some(RustisError::CorruptRdb(_))
+                );
+                if !(corrupt && ignore_corrupt_rdb) {
+                    error!(cause = %err, "failed to load RDB, refusing to start");
+                    return Err(err);
+                }
+
+                error!(
+                    cause = %err,
+                    "RDB file is corrupt, starting with an empty database because ignore-corrupt-rdb is set"
+                );
+            }
         }
 
-        DatabaseWrapper { database }
+        Ok(DatabaseWrapper { database })
+    }
+
+    /// # save_rdb() 函数
+    ///
+    /// 将数据保存到配置中指定的RDB文件
+    pub(crate) fn save_rdb(&self) -> crate::Result<()> {
+        self.database.save_to_rdb(self.database.config().rdb_path())
     }
 
     /// # database() 函数
@@ -52,119 +109,517 @@ impl Drop for DatabaseWrapper {
     fn drop(&mut self) {
         // 向database实例发送关闭信号
         self.database.shutdown_clean_task();
+        // 停止复制任务
+        self.database.set_replica_link(None);
     }
 }
 
 /// # Database 结构体
+///
+/// 指向某一个逻辑数据库的句柄，所有句柄共享同一份数据，通过select()切换到其他逻辑数据库
 #[derive(Debug, Clone)]
 pub(crate) struct Database {
     /// shared需要在多线程中共享
     shared: Arc<Shared>,
+    /// 当前句柄指向的逻辑数据库编号
+    index: usize,
 }
 
 impl Database {
     /// # new() 函数
     ///
-    /// 创建一个新的Database实例，并运行一个后台人物去管理密钥的过期
+    /// 创建一个新的Database实例，使用默认配置
+    #[allow(dead_code)]
     pub(crate) fn new() -> Self {
+        Self::with_config(ServerConfig::default())
+    }
+
+    /// # with_config() 函数
+    ///
+    /// 根据服务器配置创建一个新的Database实例，并运行一个后台人物去管理密钥的过期
+    pub(crate) fn with_config(config: ServerConfig) -> Self {
+        Self::with_shards(config, KEYSPACE_SHARDS)
+    }
+
+    /// # with_shards() 函数
+    ///
+    /// 创建一个键空间分为shards个分片的Database实例
+    fn with_shards(config: ServerConfig, shards: usize) -> Self {
+        // 至少需要一个逻辑数据库
+        let databases = config.databases.max(1);
+        let shards = (0..shards.max(1))
+            .map(|_| RwLock::new(Shard::new(databases)))
+            .collect();
+
         let shared = Arc::new(Shared::new(
-            Mutex::new(State::new(
-                HashMap::new(),
-                HashMap::new(),
-                BTreeSet::new(),
-                false,
-            )),
+            Mutex::new(State::new(HashMap::new(), false)),
+            shards,
             Notify::new(),
+            Mutex::new(config),
         ));
 
         // 开启一个后台任务，用来清除过期的密钥
-        tokio::spawn(clean_expired_keys(shared.clone()));
+        let expiry_task = tokio::spawn(clean_expired_keys(shared.clone()));
+        *shared.expiry_task.lock().unwrap() = Some(expiry_task.abort_handle());
+
+        Self { shared, index: 0 }
+    }
+
+    /// # select() 函数
+    ///
+    /// 返回一个指向第index个逻辑数据库的句柄，调用者需要保证index小于databases()
+    pub(crate) fn select(&self, index: usize) -> Database {
+        Database {
+            shared: self.shared.clone(),
+            index,
+        }
+    }
 
-        Self { shared }
+    /// # databases() 函数
+    ///
+    /// 返回逻辑数据库的数量
+    pub(crate) fn databases(&self) -> usize {
+        self.shared.databases
     }
 
     /// # get() 函数
     ///
-    /// 获取一个键的值
+    /// 获取一个键的值，并更新键的最后访问时间
+    ///
+    /// 只持有分片的共享锁，最后访问时间通过Entry中的原子变量更新，多个GET可以并行执行。
+    /// 读到已经过期但还没有被后台任务清理的键时，换成写锁删除这个键，并发送expired通知
     pub(crate) fn get(&self, key: &str) -> Option<Bytes> {
-        // 获取state锁
-        let state = self.shared.state.lock().unwrap();
-        state.entries.get(key).map(|entry| entry.data.clone())
+        // 获取键所在分片的共享锁
+        let shard = self.shared.read_shard(key);
+        let (value, expired) = match shard.dbs[self.index].entries.get(key) {
+            Some(entry) if entry.is_expired(Instant::now()) => (None, true),
+            Some(entry) => {
+                entry.touch();
+                (Some(entry.data.clone()), false)
+            }
+            None => (None, false),
+        };
+        drop(shard);
+
+        if expired {
+            self.remove_expired(key);
+        }
+
+        self.shared.stats.keyspace_lookup(value.is_some());
+        value
+    }
+
+    /// # remove_expired() 函数
+    ///
+    /// 删除一个已经过期的键，并发送expired通知。在拿到写锁之前键可能已经被后台任务清理或者被重新设置，
+    /// 所以需要重新检查
+    fn remove_expired(&self, key: &str) {
+        let mut shard = self.shared.shard(key);
+        let db = &mut shard.dbs[self.index];
+
+        let when = match db.entries.get(key) {
+            Some(entry) if entry.is_expired(Instant::now()) => entry.expires_at,
+            _ => return,
+        };
+        db.remove(key, &self.shared);
+        if let Some(when) = when {
+            db.expirations.remove(&(when, key.to_string()));
+        }
+        drop(shard);
+
+        self.shared.stats.keys_expired(1);
+        self.shared
+            .notify_keyspace_event(KeyspaceEventClass::Expired, "expired", self.index, key);
+    }
+
+    /// # idle_time() 函数
+    ///
+    /// 返回键距离上次被读写的时间，键不存在时返回None，不会更新键的最后访问时间
+    pub(crate) fn idle_time(&self, key: &str) -> Option<Duration> {
+        let shard = self.shared.read_shard(key);
+        shard.dbs[self.index]
+            .entries
+            .get(key)
+            .filter(|entry| !entry.is_expired(Instant::now()))
+            .map(|entry| entry.idle_time())
+    }
+
+    /// # key_version() 函数
+    ///
+    /// 返回键最后一次被修改（SET、EXPIRE等）时的版本号，键不存在时返回None。
+    /// 版本号只会增大，客户端可以在修改前后比较版本号来实现乐观锁
+    pub(crate) fn key_version(&self, key: &str) -> Option<u64> {
+        let shard = self.shared.read_shard(key);
+        shard.dbs[self.index]
+            .entries
+            .get(key)
+            .filter(|entry| !entry.is_expired(Instant::now()))
+            .map(|entry| entry.version)
+    }
+
+    /// # access_frequency() 函数
+    ///
+    /// 返回键的LFU访问频率计数器，键不存在时返回None，不会更新计数器
+    pub(crate) fn access_frequency(&self, key: &str) -> Option<u8> {
+        let shard = self.shared.read_shard(key);
+        shard.dbs[self.index]
+            .entries
+            .get(key)
+            .filter(|entry| !entry.is_expired(Instant::now()))
+            .map(|entry| entry.freq())
+    }
+
+    /// # memory_usage() 函数
+    ///
+    /// 估算键和值占用的字节数，包括键值对在哈希表和过期索引中的开销，键不存在时返回None
+    pub(crate) fn memory_usage(&self, key: &str) -> Option<usize> {
+        let shard = self.shared.read_shard(key);
+        shard.dbs[self.index]
+            .entries
+            .get(key)
+            .filter(|entry| !entry.is_expired(Instant::now()))
+            .map(|entry| entry.memory_usage(key))
+    }
+
+    /// # used_memory() 函数
+    ///
+    /// 返回所有逻辑数据库中键值对估算占用的字节数之和，估算方式与MEMORY USAGE相同
+    pub(crate) fn used_memory(&self) -> usize {
+        self.shared.used_memory.load(Ordering::Relaxed)
+    }
+
+    /// # free_memory() 函数
+    ///
+    /// 在执行写命令之前调用。设置了maxmemory且内存用量超出时，按照maxmemory-policy淘汰键直到用量不超过maxmemory，
+    /// 返回是否可以继续执行写命令；策略为noeviction或者找不到可以淘汰的键时返回false
+    ///
+    /// 淘汰策略与Redis一样是近似的LFU：每次随机采样EVICTION_SAMPLES个候选键，淘汰其中访问频率最低的一个，
+    /// 频率相同时淘汰空闲时间更长的。被淘汰的键以DEL的形式转发给副本
+    pub(crate) fn free_memory(&self) -> bool {
+        let maxmemory = self.shared.maxmemory.load(Ordering::Relaxed);
+        if maxmemory == 0 {
+            return true;
+        }
+        let policy = MaxMemoryPolicy::from_u8(self.shared.maxmemory_policy.load(Ordering::Relaxed));
+
+        while self.used_memory() as u64 > maxmemory {
+            let volatile = match policy {
+                MaxMemoryPolicy::NoEviction => return false,
+                MaxMemoryPolicy::AllkeysLfu => false,
+                MaxMemoryPolicy::VolatileLfu => true,
+            };
+            let Some((index, key)) = self.shared.eviction_candidate(volatile) else {
+                return false;
+            };
+            self.shared.evict(index, &key);
+        }
+        true
     }
 
     /// # set() 函数
     ///
-    /// 设置一个键的值
+    /// 设置一个键的值，condition不满足时不写入，返回是否写入了
+    ///
+    /// 条件的检查和写入都在键所在分片的写锁内完成，多个客户端同时用SET NX EX争抢同一个键时只有一个会成功，
+    /// 已经过期但还没有被清理的键视为不存在
+    #[cfg(test)]
+    pub(crate) fn set(
+        &self,
+        key: String,
+        value: Bytes,
+        expire: Option<Duration>,
+        condition: Option<SetCondition>,
+    ) -> bool {
+        self.set_and_get(key, value, expire, false, condition).0
+    }
+
+    /// # set_and_get() 函数
+    ///
+    /// 与set()相同，返回是否写入了和键原来的值（不存在或者已经过期时为None），条件不满足时同样返回原来的值。
+    /// keep_ttl为true时保留键原来的过期时间（SET KEEPTTL），此时expire应该为None
     #[instrument(skip(self, key, value, expire))]
-    pub(crate) fn set(&self, key: String, value: Bytes, expire: Option<Duration>) {
-        // 获取state锁
-        let mut state = self.shared.state.lock().unwrap();
+    pub(crate) fn set_and_get(
+        &self,
+        key: String,
+        value: Bytes,
+        expire: Option<Duration>,
+        keep_ttl: bool,
+        condition: Option<SetCondition>,
+    ) -> (bool, Option<Bytes>) {
+        // 获取键所在分片的锁
+        let mut shard = self.shared.shard(&key);
+
+        let current = shard.dbs[self.index]
+            .entries
+            .get(&key)
+            .filter(|entry| !entry.is_expired(Instant::now()));
+        let previous = current.map(|entry| entry.data.clone());
+        let kept_expire_at = current
+            .filter(|_| keep_ttl)
+            .and_then(|entry| entry.expires_at);
+
+        if let Some(condition) = condition {
+            let met = match condition {
+                SetCondition::Nx => previous.is_none(),
+                SetCondition::Xx => previous.is_some(),
+            };
+            if !met {
+                return (false, previous);
+            }
+        }
 
-        // 如果这个设置的键是快要过期的，需要通知后台任务更新状态
+        // 如果这个设置的键是快要过期的，需要通知后台任务更新状态，保留的过期时间已经在过期索引中了
         let mut notify = false;
 
-        let expire_at = expire.map(|duration| {
-            let when = Instant::now() + duration;
+        let expire_at = kept_expire_at.or_else(|| {
+            expire.map(|duration| {
+                let when = deadline_after(Instant::now(), duration);
 
-            notify = state
-                .next_expiration()
-                .map(|expiration| expiration > when)
-                .unwrap_or(true);
+                notify = shard
+                    .next_expiration()
+                    .map(|expiration| expiration > when)
+                    .unwrap_or(true);
 
-            when
+                when
+            })
         });
 
+        // 以写入的结果转发给副本，过期时间转换为unix时间戳，副本重复执行的结果也相同
+        self.shared.replicate(self.index, || {
+            let set = Set::new(&key, value.clone(), None);
+            match expire_at {
+                Some(when) => set.expire_at(unix_millis(when)),
+                None => set,
+            }
+            .code_set_into_frame()
+        });
+
+        let db = &mut shard.dbs[self.index];
+
         // 将entry插入到entries中，prev是在插入新entry后返回的旧entry
-        let prev = state
-            .entries
-            .insert(key.clone(), Entry::new(value, expire_at));
+        let mut entry = Entry::new(value, expire_at);
+        entry.version = self.shared.next_key_version();
+        let prev = db.insert(key.clone(), entry, &self.shared);
 
         // 去除旧的过期时间
         if let Some(prev) = prev {
             // 如果旧条目有过期时间，在expirations中去除掉
             if let Some(when) = prev.expires_at {
                 // 清除过期的键
-                state.expirations.remove(&(when, key.clone()));
+                db.expirations.remove(&(when, key.clone()));
             }
         }
 
         // 插入新的过期时间
         if let Some(when) = expire_at {
-            state.expirations.insert((when, key));
+            db.expirations.insert((when, key));
         }
 
-        drop(state);
+        drop(shard);
+        self.shared.mark_dirty(1);
 
         if notify {
             // 通知后台任务更新状态
             self.shared.notify_background_task.notify_one();
         }
+
+        (true, previous)
+    }
+
+    /// # expire() 函数
+    ///
+    /// 为一个存在的键设置过期时间，conditions中的条件都满足时才会设置，返回是否设置了过期时间。
+    /// 过期时间已经过去时直接删除这个键
+    pub(crate) fn expire(&self, key: &str, when: Instant, conditions: &[ExpireCondition]) -> bool {
+        let mut shard = self.shared.shard(key);
+
+        let current = match shard.dbs[self.index].entries.get(key) {
+            // 已经过期但还没有被后台任务清理的键视为不存在，删除它，不能被新的过期时间复活
+            Some(entry) if entry.is_expired(Instant::now()) => {
+                drop(shard);
+                self.remove_expired(key);
+                return false;
+            }
+            Some(entry) => entry.expires_at,
+            None => return false,
+        };
+        if !conditions
+            .iter()
+            .all(|condition| condition.is_met(current, when))
+        {
+            return false;
+        }
+
+        // 新的过期时间早于分片中已有的过期时间时，需要通知后台任务
+        let notify = shard
+            .next_expiration()
+            .map(|expiration| expiration > when)
+            .unwrap_or(true);
+
+        let db = &mut shard.dbs[self.index];
+        if let Some(current) = current {
+            db.expirations.remove(&(current, key.to_string()));
+        }
+
+        if when <= Instant::now() {
+            db.remove(key, &self.shared);
+            self.shared
+                .replicate(self.index, || Del::new(key).code_del_into_frame());
+            drop(shard);
+            self.shared.mark_dirty(1);
+            self.shared
+                .notify_keyspace_event(KeyspaceEventClass::Generic, "del", self.index, key);
+            return true;
+        }
+
+        // 重新插入，由insert()更新设置了过期时间的键和内存用量
+        if let Some(mut entry) = db.remove(key, &self.shared) {
+            entry.expires_at = Some(when);
+            entry.version = self.shared.next_key_version();
+            db.insert(key.to_string(), entry, &self.shared);
+        }
+        db.expirations.insert((when, key.to_string()));
+
+        // 相对的过期时间转换为unix时间戳再转发给副本，副本重复执行时不会推迟过期时间
+        self.shared.replicate(self.index, || {
+            Expire::new(
+                ExpireUnit::UnixMilliseconds,
+                key,
+                unix_millis(when),
+                Vec::new(),
+            )
+            .code_expire_into_frame()
+        });
+
+        drop(shard);
+        self.shared.mark_dirty(1);
+
+        if notify {
+            self.shared.notify_background_task.notify_one();
+        }
+        true
+    }
+
+    /// # expire_time() 函数
+    ///
+    /// 返回键过期的unix时间戳（毫秒），键不存在时返回None，键没有过期时间时返回Some(None)
+    pub(crate) fn expire_time(&self, key: &str) -> Option<Option<u64>> {
+        let shard = self.shared.read_shard(key);
+        let entry = shard.dbs[self.index].entries.get(key)?;
+
+        match entry.expires_at {
+            // 已经过期但还没有被后台任务清理的键视为不存在
+            Some(when) if when <= Instant::now() => None,
+            Some(when) => Some(Some(unix_millis(when))),
+            None => Some(None),
+        }
+    }
+
+    /// # debug_object() 函数
+    ///
+    /// 返回DEBUG OBJECT需要的键的内部信息，键不存在时返回None，不会更新键的访问时间
+    pub(crate) fn debug_object(&self, key: &str) -> Option<KeyDebugInfo> {
+        let shard = self.shared.read_shard(key);
+        let now = Instant::now();
+        let entry = shard.dbs[self.index]
+            .entries
+            .get(key)
+            .filter(|entry| !entry.is_expired(now))?;
+
+        Some(KeyDebugInfo {
+            serialized_length: entry.data.len(),
+            idle: entry.idle_time(),
+            freq: entry.freq(),
+            version: entry.version,
+            ttl: entry
+                .expires_at
+                .map(|when| when.saturating_duration_since(now)),
+        })
     }
 
     /// # del() 函数
     ///
-    /// 删除一个键
-    pub(crate) fn del(&self, key: &str) {
-        // 获取state锁
-        let mut state = self.shared.state.lock().unwrap();
+    /// 删除一个键，返回键是否存在，删除了键时发送del通知
+    pub(crate) fn del(&self, key: &str) -> bool {
+        // 获取键所在分片的锁
+        let mut shard = self.shared.shard(key);
+        let db = &mut shard.dbs[self.index];
 
         // 从entries中删除key
-        let entry = state.entries.remove(key);
+        let Some(entry) = db.remove(key, &self.shared) else {
+            return false;
+        };
 
-        // 如果entry存在，那么删除expirations中的过期时间
-        if let Some(entry) = entry {
-            if let Some(when) = entry.expires_at {
-                state.expirations.remove(&(when, key.to_string()));
-            }
+        // 删除expirations中的过期时间
+        if let Some(when) = entry.expires_at {
+            db.expirations.remove(&(when, key.to_string()));
         }
+        self.shared
+            .replicate(self.index, || Del::new(key).code_del_into_frame());
+        drop(shard);
+        self.shared.mark_dirty(1);
+
+        // 已经过期但还没有被清理的键视为不存在
+        if entry.is_expired(Instant::now()) {
+            self.shared.stats.keys_expired(1);
+            self.shared.notify_keyspace_event(
+                KeyspaceEventClass::Expired,
+                "expired",
+                self.index,
+                key,
+            );
+            return false;
+        }
+
+        self.shared
+            .notify_keyspace_event(KeyspaceEventClass::Generic, "del", self.index, key);
+        true
+    }
+
+    /// # flush_db() 函数
+    ///
+    /// 清空当前逻辑数据库，lazy为true时在后台线程中释放数据
+    pub(crate) fn flush_db(&self, lazy: bool) {
+        let mut shards = self.shared.lock_all_shards();
+        let dbs = shards
+            .iter_mut()
+            .map(|shard| std::mem::take(&mut shard.dbs[self.index]))
+            .collect();
+        self.shared
+            .replicate(self.index, || FlushDb::new(false).code_flushdb_into_frame());
+
+        // 释放锁之后再释放数据
+        drop(shards);
+        drop_keyspaces(&self.shared, dbs, lazy);
+    }
+
+    /// # flush_all() 函数
+    ///
+    /// 清空所有逻辑数据库，发布/订阅的状态不受影响，lazy为true时在后台线程中释放数据
+    pub(crate) fn flush_all(&self, lazy: bool) {
+        let mut shards = self.shared.lock_all_shards();
+        let dbs = shards
+            .iter_mut()
+            .flat_map(|shard| shard.dbs.iter_mut().map(std::mem::take))
+            .collect();
+        self.shared.replicate(self.index, || {
+            FlushAll::new(false).code_flushall_into_frame()
+        });
+
+        // 释放锁之后再释放数据
+        drop(shards);
+        drop_keyspaces(&self.shared, dbs, lazy);
     }
 
     /// # subscribe() 函数
     ///
     /// 返回一个Receiver，用于接收publish命令广播的值
+    #[cfg(any(test, feature = "cmd-pubsub"))]
     pub(crate) fn subscribe(&self, key: String) -> broadcast::Receiver<Bytes> {
         use std::collections::hash_map::Entry;
 
+        let capacity = self.config().pubsub_channel_capacity.max(1);
+
         // 获取state锁
         let mut state = self.shared.state.lock().unwrap();
 
@@ -173,30 +628,357 @@ impl Database {
         match state.pub_sub.entry(key) {
             Entry::Occupied(e) => e.get().subscribe(),
             Entry::Vacant(e) => {
-                let (tx, rx) = broadcast::channel(1024);
+                let (tx, rx) = broadcast::channel(capacity);
                 e.insert(tx);
                 rx
             }
         }
     }
 
+    /// # subscribe_bounded() 函数
+    ///
+    /// 返回一个有界的Receiver，用于backpressure投递方式，订阅者跟不上时publish会等待
+    #[cfg(feature = "cmd-pubsub")]
+    pub(crate) fn subscribe_bounded(&self, key: String) -> mpsc::Receiver<Bytes> {
+        let (tx, rx) = mpsc::channel(self.config().pubsub_channel_capacity.max(1));
+
+        let mut state = self.shared.state.lock().unwrap();
+        state.bounded_pub_sub.entry(key).or_default().push(tx);
+
+        rx
+    }
+
+    /// # remove_unused_channel() 函数
+    ///
+    /// channel已经没有订阅者时，从pub_sub和bounded_pub_sub中删除它，在订阅者退订或者断开连接后调用。
+    /// 检查和删除都在state锁内完成，不会删掉同时新建的订阅
+    #[cfg(feature = "cmd-pubsub")]
+    pub(crate) fn remove_unused_channel(&self, channel: &str) {
+        let mut state = self.shared.state.lock().unwrap();
+
+        if state
+            .pub_sub
+            .get(channel)
+            .is_some_and(|tx| tx.receiver_count() == 0)
+        {
+            state.pub_sub.remove(channel);
+        }
+
+        if let Some(senders) = state.bounded_pub_sub.get_mut(channel) {
+            senders.retain(|tx| !tx.is_closed());
+            if senders.is_empty() {
+                state.bounded_pub_sub.remove(channel);
+            }
+        }
+    }
+
+    /// # pubsub_channels() 函数
+    ///
+    /// 返回当前存在的channel数量
+    pub(crate) fn pubsub_channels(&self) -> usize {
+        let state = self.shared.state.lock().unwrap();
+        let bounded_only = state
+            .bounded_pub_sub
+            .keys()
+            .filter(|channel| !state.pub_sub.contains_key(*channel))
+            .count();
+
+        state.pub_sub.len() + bounded_only
+    }
+
     /// # publish() 函数
     ///
-    /// 将消息发布到channel，返回在channel上侦听的subscriber数量
+    /// 将消息发布到channel，返回收到消息的subscriber数量
+    ///
+    /// broadcast订阅者立即收到消息，backpressure订阅者的队列满时会等待，
+    /// 超过pubsub-backpressure-timeout仍然没有空间的订阅者会丢失这条消息
     ///
     /// # 参数
     ///
     /// - channel: channel在pub_sub中的key
     /// - message：要发送的消息
-    pub(crate) fn publish(&self, channel: &str, message: Bytes) -> usize {
-        // 获取state锁
-        let state = self.shared.state.lock().unwrap();
+    #[cfg(any(test, feature = "cmd-pubsub"))]
+    pub(crate) async fn publish(&self, channel: &str, message: Bytes) -> usize {
+        use tokio::sync::mpsc::error::SendTimeoutError;
+
+        let (mut num, senders) = {
+            // 获取state锁
+            let mut state = self.shared.state.lock().unwrap();
+
+            let num = state
+                .pub_sub
+                .get(channel)
+                .map(|tx| tx.send(message.clone()).unwrap_or(0))
+                .unwrap_or(0);
+
+            // 清理已经退订的backpressure订阅者，复制剩下的Sender，在锁外等待发送
+            let senders = match state.bounded_pub_sub.get_mut(channel) {
+                Some(senders) => {
+                    senders.retain(|tx| !tx.is_closed());
+                    senders.clone()
+                }
+                None => Vec::new(),
+            };
+
+            (num, senders)
+        };
 
-        state
-            .pub_sub
-            .get(channel)
-            .map(|tx| tx.send(message).unwrap_or(0))
-            .unwrap_or(0)
+        if senders.is_empty() {
+            return num;
+        }
+
+        let timeout = Duration::from_millis(self.config().pubsub_backpressure_timeout);
+        for tx in senders {
+            match tx.send_timeout(message.clone(), timeout).await {
+                Ok(()) => num += 1,
+                Err(SendTimeoutError::Timeout(_)) => {
+                    warn!(
+                        channel,
+                        "backpressure subscriber timed out, message dropped"
+                    );
+                    self.shared.stats.pubsub_messages_dropped(1);
+                }
+                // 订阅者已经退订
+                Err(SendTimeoutError::Closed(_)) => {}
+            }
+        }
+
+        num
+    }
+
+    /// # config() 函数
+    ///
+    /// 返回当前服务器配置的拷贝
+    pub(crate) fn config(&self) -> ServerConfig {
+        self.shared.config.lock().unwrap().clone()
+    }
+
+    /// # requirepass() 函数
+    ///
+    /// 返回连接需要的密码，None表示不需要认证
+    pub(crate) fn requirepass(&self) -> Option<String> {
+        self.shared.config.lock().unwrap().requirepass.clone()
+    }
+
+    /// # requires_auth() 函数
+    ///
+    /// 连接是否需要先认证才能执行命令，requirepass不能在运行时修改，只在连接建立时调用一次
+    pub(crate) fn requires_auth(&self) -> bool {
+        self.shared.config.lock().unwrap().requirepass.is_some()
+    }
+
+    /// # update_config() 函数
+    ///
+    /// 在配置锁内修改服务器配置
+    pub(crate) fn update_config<T>(&self, f: impl FnOnce(&mut ServerConfig) -> T) -> T {
+        let mut config = self.shared.config.lock().unwrap();
+        let result = f(&mut config);
+        self.shared.cache_config(&config);
+        result
+    }
+
+    /// # is_read_only() 函数
+    ///
+    /// 服务器是否处于只读模式
+    ///
+    /// 配置了read-only，或者作为副本并且开启了replica-read-only时，服务器都是只读的。
+    /// 只读只限制普通客户端，复制连接上的命令直接在数据库上执行
+    pub(crate) fn is_read_only(&self) -> bool {
+        self.shared.read_only.load(Ordering::Relaxed)
+            || (self.shared.replica_read_only.load(Ordering::Relaxed)
+                && self.shared.is_replica.load(Ordering::Relaxed))
+    }
+
+    /// # client_connected() 函数
+    ///
+    /// 记录一个新的客户端连接
+    pub(crate) fn client_connected(&self) {
+        self.shared.connected_clients.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// # client_disconnected() 函数
+    ///
+    /// 记录一个客户端断开连接
+    pub(crate) fn client_disconnected(&self) {
+        self.shared.connected_clients.fetch_sub(1, Ordering::SeqCst);
+    }
+
+    /// # connected_clients() 函数
+    ///
+    /// 返回当前已连接的客户端数量
+    pub(crate) fn connected_clients(&self) -> usize {
+        self.shared.connected_clients.load(Ordering::SeqCst)
+    }
+
+    /// # dump_all() 函数
+    ///
+    /// 按键排序返回当前数据库中未过期的键值对，最多返回limit个
+    pub(crate) fn dump_all(&self, limit: usize) -> Vec<(String, Bytes)> {
+        let shards = self.shared.read_all_shards();
+        let now = Instant::now();
+
+        let mut entries: Vec<_> = shards
+            .iter()
+            .flat_map(|shard| shard.dbs[self.index].entries.iter())
+            .filter(|(_, entry)| entry.expires_at.is_none_or(|when| when > now))
+            .map(|(key, entry)| (key.clone(), entry.data.clone()))
+            .collect();
+
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+        entries.truncate(limit);
+        entries
+    }
+
+    /// # scan() 函数
+    ///
+    /// 从cursor开始返回未过期的键，以及下一次调用使用的游标，遍历结束时游标为0
+    ///
+    /// 分片按照下标依次遍历，分片内的键按照哈希值的顺序遍历。键的哈希值除以分片数量的余数就是分片的下标，
+    /// 所以游标直接使用下一个键的哈希值，同时表示分片和分片内的位置，在遍历期间一直存在的键正好返回一次。
+    /// 每次调用大约访问count个键（包括已经过期的键），哈希值相同的键总是在同一次调用中返回，
+    /// 同一时间只以共享的方式锁住一个分片
+    pub(crate) fn scan(&self, cursor: u64, count: usize) -> (u64, Vec<String>) {
+        let shards = self.shared.shards.len() as u64;
+        let count = count.max(1);
+        let now = Instant::now();
+        let mut keys = Vec::new();
+        // 已经访问的键的数量
+        let mut visited = 0;
+        let mut cursor = cursor;
+
+        loop {
+            let index = cursor % shards;
+            let shard = self.shared.shards[index as usize].read().unwrap();
+            let db = &shard.dbs[self.index];
+
+            let mut last = None;
+            for (hash, key) in db.scan_order.range((cursor, String::new())..) {
+                if visited >= count && last != Some(*hash) {
+                    return (*hash, keys);
+                }
+                visited += 1;
+                last = Some(*hash);
+
+                if db
+                    .entries
+                    .get(key)
+                    .is_some_and(|entry| !entry.is_expired(now))
+                {
+                    keys.push(key.clone());
+                }
+            }
+            drop(shard);
+
+            // 这个分片已经遍历完，从下一个分片的第一个键继续
+            cursor = index + 1;
+            if cursor == shards {
+                return (0, keys);
+            }
+            if visited >= count {
+                return (cursor, keys);
+            }
+        }
+    }
+
+    /// # record_idempotency_token() 函数
+    ///
+    /// 记录一个SET IDEMPOTENT令牌，返回令牌是否是第一次出现，重复的令牌不应该再次写入
+    pub(crate) fn record_idempotency_token(&self, token: &str) -> bool {
+        self.shared.idempotency_tokens.lock().unwrap().insert(token)
+    }
+
+    /// # stats() 函数
+    ///
+    /// 返回服务器的运行统计数据
+    pub(crate) fn stats(&self) -> &Stats {
+        &self.shared.stats
+    }
+
+    /// # snapshot() 函数
+    ///
+    /// 返回所有逻辑数据库中未过期的键值对和过期的unix时间戳（毫秒），用于副本的全量同步
+    pub(crate) fn snapshot(&self) -> Vec<Vec<(String, Bytes, Option<u64>)>> {
+        let shards = self.shared.read_all_shards();
+        let now = Instant::now();
+
+        (0..self.shared.databases)
+            .map(|index| {
+                shards
+                    .iter()
+                    .flat_map(|shard| shard.dbs[index].entries.iter())
+                    .filter(|(_, entry)| entry.expires_at.is_none_or(|when| when > now))
+                    .map(|(key, entry)| {
+                        let expire_at = entry.expires_at.map(unix_millis);
+                        (key.clone(), entry.data.clone(), expire_at)
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// # connected_replicas() 函数
+    ///
+    /// 返回已连接的副本数量
+    pub(crate) fn connected_replicas(&self) -> usize {
+        self.shared.replication_tx.receiver_count()
+    }
+
+    /// # subscribe_replication() 函数
+    ///
+    /// 注册一个副本，返回接收写命令的Receiver，每一项为(逻辑数据库编号, 命令帧)
+    pub(crate) fn subscribe_replication(&self) -> broadcast::Receiver<(usize, Frame)> {
+        self.shared.replication_tx.subscribe()
+    }
+
+    /// # has_monitors() 函数
+    ///
+    /// 是否有连接处于监控模式
+    pub(crate) fn has_monitors(&self) -> bool {
+        self.shared.monitor_tx.receiver_count() > 0
+    }
+
+    /// # feed_monitors() 函数
+    ///
+    /// 把一行命令文本推送给所有监控连接
+    pub(crate) fn feed_monitors(&self, line: String) {
+        // 没有监控连接时发送会失败，直接忽略
+        let _ = self.shared.monitor_tx.send(line);
+    }
+
+    /// # subscribe_monitor() 函数
+    ///
+    /// 注册一个监控连接，返回接收命令文本的Receiver
+    pub(crate) fn subscribe_monitor(&self) -> broadcast::Receiver<String> {
+        self.shared.monitor_tx.subscribe()
+    }
+
+    /// # set_replica_link() 函数
+    ///
+    /// 设置作为副本时的复制任务，之前的复制任务会被停止，传入None表示不再作为副本
+    pub(crate) fn set_replica_link(&self, link: Option<(String, AbortHandle)>) {
+        let link = link.map(|(master, task)| ReplicaLink { master, task });
+        let mut replica_link = self.shared.replica_link.lock().unwrap();
+        self.shared
+            .is_replica
+            .store(link.is_some(), Ordering::Relaxed);
+        let prev = std::mem::replace(&mut *replica_link, link);
+        drop(replica_link);
+
+        if let Some(prev) = prev {
+            prev.task.abort();
+        }
+    }
+
+    /// # role() 函数
+    ///
+    /// 返回服务器当前在主从复制中的角色
+    pub(crate) fn role(&self) -> Role {
+        match self.shared.replica_link.lock().unwrap().as_ref() {
+            Some(link) => Role::Replica {
+                master: link.master.clone(),
+            },
+            None => Role::Master,
+        }
     }
 
     /// # shutdown_clean_task() 函数
@@ -217,19 +999,133 @@ impl Database {
     /// # save_to_rdb() 函数
     ///
     /// 将数据库的数据保存到RDB文件（目前只实现了键值的保存）
-    pub fn save_to_rdb(&self, file_path: &str) -> crate::Result<()> {
-        let state = self.shared.state.lock().unwrap();
+    pub fn save_to_rdb(&self, file_path: impl AsRef<Path>) -> crate::Result<()> {
+        // 同一时间只进行一次保存，避免SAVE和后台保存同时写同一个文件
+        let _saving = self.shared.save_lock.lock().unwrap();
+
+        // 在所有分片的共享锁内复制键值对，得到同一时刻的快照，释放锁之后再序列化。
+        // 复制之后的修改仍然算作下一次保存之前的修改
+        let (dirty, keyspaces) = {
+            let shards = self.shared.read_all_shards();
+            let dirty = self.shared.dirty.load(Ordering::SeqCst);
+            (dirty, self.shared.copy_keyspaces(&shards))
+        };
+        let payload = serialize_keyspaces(&keyspaces)?;
+        drop(keyspaces);
 
-        let file = File::create(file_path)?;
-        bincode::serialize_into(file, &state.entries)?;
+        write_rdb_file(file_path, RDB_VERSION, &payload)?;
 
+        self.shared.dirty.fetch_sub(dirty, Ordering::SeqCst);
+        *self.shared.last_save.lock().unwrap() = (Instant::now(), SystemTime::now());
+        Ok(())
+    }
+
+    /// # debug_reload() 函数
+    ///
+    /// 与SAVE使用同样的编码（文件头、格式版本和CRC32）把数据保存到内存中，再与加载RDB文件一样校验并解码，
+    /// 替换内存中的数据，不会读写RDB文件。整个过程持有所有分片的写锁，其它连接的写命令不会在保存和替换之间丢失，
+    /// 看到的要么是替换前的数据，要么是替换后的数据
+    pub(crate) fn debug_reload(&self) -> crate::Result<()> {
+        let mut shards = self.shared.lock_all_shards();
+
+        let keyspaces = self.shared.copy_keyspaces(&shards);
+        let buffer = encode_rdb(RDB_VERSION, &serialize_keyspaces(&keyspaces)?);
+        drop(keyspaces);
+
+        let (version, payload) = check_rdb_header(&buffer).map_err(RustisError::CorruptRdb)?;
+        let data = decode_rdb_payload(version, payload).map_err(|err| match err {
+            RdbDecodeError::Unsupported(msg) | RdbDecodeError::Corrupt(msg) => {
+                RustisError::CorruptRdb(msg)
+            }
+        })?;
+        drop(buffer);
+        self.replace_keyspaces(&mut shards, data);
+        drop(shards);
+
+        // 过期索引被重建，让后台任务重新计算下一次清理的时间
+        self.shared.notify_background_task.notify_one();
         Ok(())
     }
 
+    /// # bgsave() 函数
+    ///
+    /// 在后台线程中将数据保存到配置中指定的RDB文件，已经有后台保存在进行时返回false
+    pub(crate) fn bgsave(&self) -> bool {
+        if self.shared.bgsave_in_progress.swap(true, Ordering::SeqCst) {
+            return false;
+        }
+
+        let database = self.clone();
+        tokio::task::spawn_blocking(move || {
+            let result = database.save_to_rdb(database.config().rdb_path());
+            let mut last_failure = database.shared.last_bgsave_failure.lock().unwrap();
+            match result {
+                Ok(()) => {
+                    info!("background saving terminated with success");
+                    *last_failure = None;
+                }
+                Err(err) => {
+                    error!(cause = %err, "background saving failed");
+                    *last_failure = Some(Instant::now());
+                }
+            }
+            drop(last_failure);
+            database
+                .shared
+                .bgsave_in_progress
+                .store(false, Ordering::SeqCst);
+        });
+
+        true
+    }
+
+    /// # changes_since_save() 函数
+    ///
+    /// 返回上次保存RDB之后的修改次数
+    pub(crate) fn changes_since_save(&self) -> u64 {
+        self.shared.dirty.load(Ordering::SeqCst)
+    }
+
+    /// # last_bgsave_failure() 函数
+    ///
+    /// 返回最近一次后台保存失败的时刻，最近一次后台保存成功或者还没有进行过后台保存时返回None
+    pub(crate) fn last_bgsave_failure(&self) -> Option<Instant> {
+        *self.shared.last_bgsave_failure.lock().unwrap()
+    }
+
+    /// # last_save() 函数
+    ///
+    /// 返回上次成功保存RDB的时刻，服务器启动后还没有保存过时为启动的时刻
+    pub(crate) fn last_save(&self) -> (Instant, SystemTime) {
+        *self.shared.last_save.lock().unwrap()
+    }
+
+    /// # is_expiry_task_alive() 函数
+    ///
+    /// 清除过期键的后台任务是否还在运行，任务panic或者数据库关闭之后返回false
+    pub(crate) fn is_expiry_task_alive(&self) -> bool {
+        self.shared
+            .expiry_task
+            .lock()
+            .unwrap()
+            .as_ref()
+            .is_some_and(|task| !task.is_finished())
+    }
+
+    /// # is_bgsave_in_progress() 函数
+    ///
+    /// 是否有后台保存正在进行
+    pub(crate) fn is_bgsave_in_progress(&self) -> bool {
+        self.shared.bgsave_in_progress.load(Ordering::SeqCst)
+    }
+
     /// # load_from_rdb() 函数
     ///
     /// 从RDB文件加载数据库数据（目前只实现了键值的加载）
-    pub fn load_from_rdb(&self, file_path: &str) -> crate::Result<()> {
+    ///
+    /// 文件不存在时直接返回Ok，文件内容无法解析时返回RustisError::CorruptRdb
+    pub fn load_from_rdb(&self, file_path: impl AsRef<Path>) -> crate::Result<()> {
+        let file_path = file_path.as_ref();
         let mut file = match File::open(file_path) {
             Ok(f) => f,
             Err(ref e) if e.kind() == ErrorKind::NotFound => {
@@ -240,72 +1136,606 @@ impl Database {
         };
         let mut buffer = Vec::new();
         file.read_to_end(&mut buffer)?;
-        let data: HashMap<String, Entry> = bincode::deserialize(&buffer)?;
 
+        let corrupt =
+            |msg: String| RustisError::CorruptRdb(format!("'{}': {}", file_path.display(), msg));
+
+        let (version, payload) = check_rdb_header(&buffer).map_err(corrupt)?;
+        let data = decode_rdb_payload(version, payload).map_err(|err| match err {
+            RdbDecodeError::Unsupported(msg) => {
+                RustisError::UnsupportedRdb(format!("'{}': {}", file_path.display(), msg))
+            }
+            RdbDecodeError::Corrupt(msg) => corrupt(msg),
+        })?;
+
+        // 配置的databases比保存时少，超出的逻辑数据库中还有键时拒绝加载，否则下一次保存会永久丢失这些键
+        if let Some(index) = (self.shared.databases..data.len())
+            .rev()
+            .find(|&index| !data[index].is_empty())
+        {
+            return Err(RustisError::UnsupportedRdb(format!(
+                "'{}': data file has keys in database {}, but only {} databases are configured; increase databases before loading it",
+                file_path.display(),
+                index,
+                self.shared.databases
+            ))
+            .into());
+        }
+
+        let mut shards = self.shared.lock_all_shards();
+        self.replace_keyspaces(&mut shards, data);
+
+        Ok(())
+    }
+
+    /// # replace_keyspaces() 函数
+    ///
+    /// 用加载的数据替换逻辑数据库中的键，调用者需要持有所有分片的写锁，并保证超出databases的逻辑数据库中没有键。
+    /// 过期的键被过滤掉，其余的键按照哈希值放入对应的分片，并分配新的版本号，保证键的版本号只会增加
+    fn replace_keyspaces(
+        &self,
+        shards: &mut [RwLockWriteGuard<'_, Shard>],
+        data: Vec<HashMap<String, Entry>>,
+    ) {
         // 获取当前时间
         let now = Instant::now();
-        // 过滤掉过期的键
-        let data = data
-            .into_iter()
-            .filter(|(_, entry)| entry.expires_at.map(|when| when > now).unwrap_or(true))
-            .collect();
 
-        let mut state = self.shared.state.lock().unwrap();
+        for (index, entries) in data.into_iter().enumerate().take(self.shared.databases) {
+            for shard in shards.iter_mut() {
+                let old = mem::take(&mut shard.dbs[index]);
+                self.shared
+                    .used_memory
+                    .fetch_sub(old.used_memory, Ordering::Relaxed);
+            }
+
+            for (key, mut entry) in entries {
+                if entry.is_expired(now) {
+                    continue;
+                }
+
+                entry.version = self.shared.next_key_version();
+                let db = &mut shards[self.shared.shard_index(&key)].dbs[index];
+                if let Some(when) = entry.expires_at {
+                    db.expirations.insert((when, key.clone()));
+                }
+                db.insert(key, entry, &self.shared);
+            }
+        }
+    }
+}
 
-        state.entries = data;
+/// 副本最多可以落后多少个写命令，超过后副本的连接会被关闭，重新进行全量同步
+const REPLICATION_BACKLOG: usize = 16384;
 
-        Ok(())
+/// 等待推送给监控连接的命令数量，监控连接落后太多时会跳过一部分命令
+const MONITOR_BACKLOG: usize = 1024;
+
+/// RDB文件头的魔数
+const RDB_MAGIC: &[u8; 6] = b"RUSTIS";
+
+/// RDB文件的格式版本，修改Entry的序列化格式时需要增加版本号，并在decode_rdb_payload()中处理旧版本的迁移
+///
+/// - 版本1：过期时间保存为相对的秒数
+/// - 版本2：过期时间保存为unix时间戳（毫秒），重启后键的剩余时间不变
+const RDB_VERSION: u32 = 2;
+
+/// RDB文件头的长度：魔数 + 版本(u32) + CRC32(u32)
+const RDB_HEADER_LEN: usize = RDB_MAGIC.len() + 4 + 4;
+
+/// # rdb_header() 函数
+///
+/// 返回payload的RDB文件头：魔数 + 格式版本 + 数据的CRC32
+fn rdb_header(version: u32, payload: &[u8]) -> [u8; RDB_HEADER_LEN] {
+    let mut header = [0; RDB_HEADER_LEN];
+    let (magic, rest) = header.split_at_mut(RDB_MAGIC.len());
+    magic.copy_from_slice(RDB_MAGIC);
+    rest[..4].copy_from_slice(&version.to_le_bytes());
+    rest[4..].copy_from_slice(&crc32fast::hash(payload).to_le_bytes());
+    header
+}
+
+/// # encode_rdb() 函数
+///
+/// 返回与write_rdb_file()写入的文件内容相同的RDB数据，用于不读写文件的DEBUG RELOAD
+fn encode_rdb(version: u32, payload: &[u8]) -> Vec<u8> {
+    let mut buffer = Vec::with_capacity(RDB_HEADER_LEN + payload.len());
+    buffer.extend_from_slice(&rdb_header(version, payload));
+    buffer.extend_from_slice(payload);
+    buffer
+}
+
+/// # write_rdb_file() 函数
+///
+/// 写入RDB文件，文件头为魔数 + 格式版本 + 数据的CRC32，加载时先校验再反序列化
+///
+/// 与redis一样先写入同一目录下的临时文件并fsync，再重命名为目标文件。写入过程中进程崩溃或者磁盘写满时，
+/// 原来的RDB文件保持不变，不会留下一个被截断、导致服务器无法启动的文件
+fn write_rdb_file(file_path: impl AsRef<Path>, version: u32, payload: &[u8]) -> crate::Result<()> {
+    let file_path = file_path.as_ref();
+    let file_name = file_path.file_name().unwrap_or_default().to_string_lossy();
+    let temp_path = file_path.with_file_name(format!("temp-{}-{}", std::process::id(), file_name));
+
+    let write = || -> io::Result<()> {
+        let mut file = File::create(&temp_path)?;
+        file.write_all(&rdb_header(version, payload))?;
+        file.write_all(payload)?;
+        file.sync_all()?;
+        fs::rename(&temp_path, file_path)
+    };
+
+    if let Err(err) = write() {
+        let _ = fs::remove_file(&temp_path);
+        return Err(err.into());
+    }
+
+    Ok(())
+}
+
+/// # SavedKeyspace 结构体
+///
+/// 一个逻辑数据库中需要保存的键值对，按照`HashMap<String, Entry>`的格式序列化，与decode_rdb_payload()读取的格式相同
+struct SavedKeyspace(Vec<(String, Entry)>);
+
+impl Serialize for SavedKeyspace {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_map(self.0.iter().map(|(key, entry)| (key, entry)))
+    }
+}
+
+/// # serialize_keyspaces() 函数
+///
+/// 按照RDB格式序列化copy_keyspaces()复制的数据，不需要持有分片的锁
+fn serialize_keyspaces(keyspaces: &[SavedKeyspace]) -> crate::Result<Vec<u8>> {
+    Ok(bincode::serialize(keyspaces)?)
+}
+
+/// # check_rdb_header() 函数
+///
+/// 校验RDB文件头的魔数和数据的CRC32，校验通过后返回格式版本和文件头之后的数据
+fn check_rdb_header(buffer: &[u8]) -> Result<(u32, &[u8]), String> {
+    if buffer.len() < RDB_HEADER_LEN || &buffer[..RDB_MAGIC.len()] != RDB_MAGIC {
+        return Err("Wrong signature trying to load DB from file, it may be an RDB file in an old unsupported format".to_string());
+    }
+
+    let (header, payload) = buffer.split_at(RDB_HEADER_LEN);
+    let (version, checksum) = header[RDB_MAGIC.len()..].split_at(4);
+    let version = u32::from_le_bytes(version.try_into().unwrap());
+    let checksum = u32::from_le_bytes(checksum.try_into().unwrap());
+
+    let actual = crc32fast::hash(payload);
+    if actual != checksum {
+        return Err(format!(
+            "Wrong RDB checksum expected: ({:08x}) got ({:08x}), the file may be corrupt or truncated",
+            checksum, actual
+        ));
+    }
+
+    Ok((version, payload))
+}
+
+/// # RdbDecodeError 枚举
+///
+/// 解码RDB数据时的错误
+enum RdbDecodeError {
+    /// 格式版本无法被当前服务器加载
+    Unsupported(String),
+    /// 数据无法反序列化
+    Corrupt(String),
+}
+
+/// # decode_rdb_payload() 函数
+///
+/// 按照格式版本反序列化RDB数据，旧版本的数据会被迁移到当前版本，比当前版本新的数据会被拒绝
+fn decode_rdb_payload(
+    version: u32,
+    payload: &[u8],
+) -> Result<Vec<HashMap<String, Entry>>, RdbDecodeError> {
+    let corrupt = |err: bincode::Error| RdbDecodeError::Corrupt(err.to_string());
+
+    match version {
+        RDB_VERSION => bincode::deserialize(payload).map_err(corrupt),
+        // 版本1的过期时间是相对的秒数，迁移时从加载的时刻开始计算
+        1 => {
+            let data: Vec<HashMap<String, EntryV1>> = bincode::deserialize(payload).map_err(corrupt)?;
+            Ok(data
+                .into_iter()
+                .map(|entries| entries.into_iter().map(|(key, entry)| (key, entry.into())).collect())
+                .collect())
+        }
+        version if version > RDB_VERSION => Err(RdbDecodeError::Unsupported(format!(
+            "Can't handle RDB format version {}, newer than the supported version {}; load it with a newer server",
+            version, RDB_VERSION
+        ))),
+        version => Err(RdbDecodeError::Unsupported(format!(
+            "Can't handle RDB format version {}, it can't be migrated to version {}",
+            version, RDB_VERSION
+        ))),
     }
 }
 
 #[derive(Debug)]
 struct Shared {
-    /// 状态
+    /// 发布/订阅和关闭状态
     state: Mutex<State>,
+    /// 键空间的分片，键按照哈希值分布到各个分片中，同时锁住多个分片时必须按照下标从小到大的顺序加锁
+    shards: Vec<RwLock<Shard>>,
+    /// 计算键所在分片使用的哈希函数
+    hasher: RandomState,
+    /// 逻辑数据库的数量
+    databases: usize,
     /// 通知后台任务处理过期Entry
     notify_background_task: Notify,
+    /// 服务器配置
+    config: Mutex<ServerConfig>,
+    /// 配置中的read-only，与config同时更新，执行写命令时不需要加配置锁
+    read_only: AtomicBool,
+    /// 配置中的replica-read-only，与config同时更新
+    replica_read_only: AtomicBool,
+    /// 是否作为副本运行，与replica_link同时更新
+    is_replica: AtomicBool,
+    /// 配置中的maxmemory，与config同时更新，为0时执行写命令前不检查内存
+    maxmemory: AtomicU64,
+    /// 配置中的maxmemory-policy，使用MaxMemoryPolicy::to_u8()编码
+    maxmemory_policy: AtomicU8,
+    /// 配置中的notify-keyspace-events，使用KeyspaceEvents::to_bits()编码，删除和过期键时不需要加配置锁
+    keyspace_events: AtomicU8,
+    /// 当前已连接的客户端数量
+    connected_clients: AtomicUsize,
+    /// 运行统计数据
+    stats: Stats,
+    /// 最近见过的SET IDEMPOTENT令牌
+    idempotency_tokens: Mutex<IdempotencyTokens>,
+    /// 主节点向副本转发写命令的channel，每个已连接的副本持有一个Receiver
+    replication_tx: broadcast::Sender<(usize, Frame)>,
+    /// 作为副本时，与主节点之间的复制连接
+    replica_link: Mutex<Option<ReplicaLink>>,
+    /// 向MONITOR连接推送命令的channel，每个监控连接持有一个Receiver
+    monitor_tx: broadcast::Sender<String>,
+    /// 上次保存RDB之后的修改次数
+    dirty: AtomicU64,
+    /// 上次成功保存RDB的时刻，同时记录单调时钟和系统时间
+    last_save: Mutex<(Instant, SystemTime)>,
+    /// 最近一次后台保存失败的时刻，成功后清空，自动保存在失败后等待一段时间再重试
+    last_bgsave_failure: Mutex<Option<Instant>>,
+    /// 保存RDB时持有的锁
+    save_lock: Mutex<()>,
+    /// 是否有后台保存正在进行
+    bgsave_in_progress: AtomicBool,
+    /// 最近一次分配给键的修改版本号
+    key_version: AtomicU64,
+    /// 所有逻辑数据库中键值对估算占用的字节数之和，与每个Keyspace的used_memory同时更新，检查maxmemory时不需要加锁
+    used_memory: AtomicUsize,
+    /// 清除过期键的后台任务，健康检查时用来判断任务是否还在运行
+    expiry_task: Mutex<Option<AbortHandle>>,
+}
+
+/// # ReplicaLink 结构体
+///
+/// 副本与主节点之间的复制连接
+#[derive(Debug)]
+struct ReplicaLink {
+    /// 主节点的地址（host:port）
+    master: String,
+    /// 复制任务，断开复制时abort
+    task: AbortHandle,
 }
 
 impl Shared {
-    fn new(state: Mutex<State>, notify_background_task: Notify) -> Self {
-        Self {
+    fn new(
+        state: Mutex<State>,
+        shards: Vec<RwLock<Shard>>,
+        notify_background_task: Notify,
+        config: Mutex<ServerConfig>,
+    ) -> Self {
+        let databases = config.lock().unwrap().databases.max(1);
+
+        let shared = Self {
             state,
+            shards,
+            hasher: RandomState::new(),
+            databases,
             notify_background_task,
+            config,
+            read_only: AtomicBool::new(false),
+            replica_read_only: AtomicBool::new(false),
+            is_replica: AtomicBool::new(false),
+            maxmemory: AtomicU64::new(0),
+            maxmemory_policy: AtomicU8::new(0),
+            keyspace_events: AtomicU8::new(0),
+            connected_clients: AtomicUsize::new(0),
+            stats: Stats::default(),
+            idempotency_tokens: Mutex::new(IdempotencyTokens::new(DEFAULT_TOKEN_CAPACITY)),
+            replication_tx: broadcast::channel(REPLICATION_BACKLOG).0,
+            monitor_tx: broadcast::channel(MONITOR_BACKLOG).0,
+            replica_link: Mutex::new(None),
+            dirty: AtomicU64::new(0),
+            last_save: Mutex::new((Instant::now(), SystemTime::now())),
+            last_bgsave_failure: Mutex::new(None),
+            save_lock: Mutex::new(()),
+            bgsave_in_progress: AtomicBool::new(false),
+            key_version: AtomicU64::new(0),
+            used_memory: AtomicUsize::new(0),
+            expiry_task: Mutex::new(None),
+        };
+        shared.cache_config(&shared.config.lock().unwrap());
+        shared
+    }
+
+    /// cache_config() 函数
+    ///
+    /// 把执行每个命令时都要检查的配置项同步到原子变量中，修改配置之后在配置锁内调用
+    fn cache_config(&self, config: &ServerConfig) {
+        self.read_only.store(config.read_only, Ordering::Relaxed);
+        self.replica_read_only
+            .store(config.replica_read_only, Ordering::Relaxed);
+        self.maxmemory.store(config.maxmemory, Ordering::Relaxed);
+        self.maxmemory_policy
+            .store(config.maxmemory_policy.to_u8(), Ordering::Relaxed);
+        self.keyspace_events
+            .store(config.notify_keyspace_events.to_bits(), Ordering::Relaxed);
+    }
+
+    /// shard_index() 函数
+    ///
+    /// 返回键所在分片的下标
+    fn shard_index(&self, key: &str) -> usize {
+        (self.hasher.hash_one(key) % self.shards.len() as u64) as usize
+    }
+
+    /// shard() 函数
+    ///
+    /// 以独占的方式锁住键所在的分片，用于修改数据
+    fn shard(&self, key: &str) -> RwLockWriteGuard<'_, Shard> {
+        self.shards[self.shard_index(key)].write().unwrap()
+    }
+
+    /// read_shard() 函数
+    ///
+    /// 以共享的方式锁住键所在的分片，多个读命令可以同时读取同一个分片
+    fn read_shard(&self, key: &str) -> RwLockReadGuard<'_, Shard> {
+        self.shards[self.shard_index(key)].read().unwrap()
+    }
+
+    /// next_key_version() 函数
+    ///
+    /// 分配一个新的键版本号，所有逻辑数据库共用一个递增的计数器，删除后重新创建的键也会得到更大的版本号
+    fn next_key_version(&self) -> u64 {
+        self.key_version.fetch_add(1, Ordering::SeqCst) + 1
+    }
+
+    /// mark_dirty() 函数
+    ///
+    /// 记录n次修改，用于判断是否满足自动保存的规则
+    fn mark_dirty(&self, n: u64) {
+        self.dirty.fetch_add(n, Ordering::SeqCst);
+    }
+
+    /// copy_keyspaces() 函数
+    ///
+    /// 复制所有逻辑数据库的键值对，用于保存RDB。调用者需要持有所有分片的锁，复制的结果是同一时刻的快照，
+    /// 复制只增加Bytes的引用计数，耗时远小于序列化
+    fn copy_keyspaces<S: Deref<Target = Shard>>(&self, shards: &[S]) -> Vec<SavedKeyspace> {
+        (0..self.databases)
+            .map(|index| {
+                SavedKeyspace(
+                    shards
+                        .iter()
+                        .flat_map(|shard| shard.dbs[index].entries.iter())
+                        .map(|(key, entry)| (key.clone(), entry.copy_for_save()))
+                        .collect(),
+                )
+            })
+            .collect()
+    }
+
+    /// lock_all_shards() 函数
+    ///
+    /// 按照下标从小到大的顺序以独占的方式锁住所有分片，用于清空数据库和加载RDB
+    fn lock_all_shards(&self) -> Vec<RwLockWriteGuard<'_, Shard>> {
+        self.shards
+            .iter()
+            .map(|shard| shard.write().unwrap())
+            .collect()
+    }
+
+    /// read_all_shards() 函数
+    ///
+    /// 按照下标从小到大的顺序以共享的方式锁住所有分片，用于需要一致视图的读操作
+    fn read_all_shards(&self) -> Vec<RwLockReadGuard<'_, Shard>> {
+        self.shards
+            .iter()
+            .map(|shard| shard.read().unwrap())
+            .collect()
+    }
+
+    /// eviction_candidate() 函数
+    ///
+    /// 随机采样EVICTION_SAMPLES个键，返回其中访问频率最低的键及其所在的逻辑数据库，没有可以淘汰的键时返回None。
+    /// volatile为true时只从设置了过期时间的键中采样
+    ///
+    /// 每次采样只以共享的方式锁住一个分片，采样的结果在淘汰时可能已经被删除，由evict()处理
+    fn eviction_candidate(&self, volatile: bool) -> Option<(usize, String)> {
+        let mut best: Option<(u8, Duration, usize, String)> = None;
+
+        for _ in 0..EVICTION_SAMPLES {
+            // 从随机的分片开始找到第一个有候选键的分片，避免大部分分片为空时采样不到键，所有分片都没有时返回None
+            let start = random_u64() as usize % self.shards.len();
+            let sample = (0..self.shards.len()).find_map(|offset| {
+                let shard = self.shards[(start + offset) % self.shards.len()]
+                    .read()
+                    .unwrap();
+                let dbs: Vec<usize> = (0..shard.dbs.len())
+                    .filter(|&index| {
+                        let db = &shard.dbs[index];
+                        if volatile {
+                            !db.volatile.is_empty()
+                        } else {
+                            !db.entries.is_empty()
+                        }
+                    })
+                    .collect();
+                if dbs.is_empty() {
+                    return None;
+                }
+                let index = dbs[random_u64() as usize % dbs.len()];
+                let db = &shard.dbs[index];
+                let key = if volatile {
+                    db.volatile
+                        .get_index(random_u64() as usize % db.volatile.len())?
+                } else {
+                    db.entries
+                        .get_index(random_u64() as usize % db.entries.len())?
+                        .0
+                };
+                let entry = &db.entries[key];
+                Some((entry.freq(), entry.idle_time(), index, key.clone()))
+            })?;
+            let better = match &best {
+                None => true,
+                Some((freq, idle, _, _)) => {
+                    sample.0 < *freq || (sample.0 == *freq && sample.1 > *idle)
+                }
+            };
+            if better {
+                best = Some(sample);
+            }
+        }
+
+        best.map(|(_, _, index, key)| (index, key))
+    }
+
+    /// evict() 函数
+    ///
+    /// 淘汰逻辑数据库index中的键，并以DEL的形式转发给副本，键已经不存在时什么都不做
+    fn evict(&self, index: usize, key: &str) {
+        {
+            let mut shard = self.shard(key);
+            let db = &mut shard.dbs[index];
+            let Some(entry) = db.remove(key, self) else {
+                return;
+            };
+            if let Some(when) = entry.expires_at {
+                db.expirations.remove(&(when, key.to_string()));
+            }
+            self.replicate(index, || Del::new(key).code_del_into_frame());
+        }
+
+        self.stats.keys_evicted(1);
+        self.mark_dirty(1);
+    }
+
+    /// replicate() 函数
+    ///
+    /// 把一次写操作转发给所有副本，没有副本时不会构造命令帧
+    ///
+    /// 调用者必须持有被修改的键所在分片的写锁，同一个键上的写操作按照在主节点上执行的顺序进入channel，
+    /// 副本执行后的结果与主节点相同
+    fn replicate(&self, index: usize, frame: impl FnOnce() -> Frame) {
+        if self.replication_tx.receiver_count() > 0 {
+            // 副本在发送前断开时发送会失败，直接忽略
+            let _ = self.replication_tx.send((index, frame()));
         }
     }
 
     /// clean_expired_keys() 函数
     ///
     /// 清除所有过期的键并返回下一个密钥到期的instant，后台任务将一直休眠到这个时刻
+    ///
+    /// 每次只以独占的方式锁住一个分片，清理过程中其他分片上的命令不会被阻塞。
+    /// 读命令只持有共享锁，不能删除键，所以读到已经过期但还没有被清理的键时，当作键不存在处理
     fn clean_expired_keys(&self) -> Option<Instant> {
-        // 获取state锁
-        let mut state = self.state.lock().unwrap();
-
         // 如果数据库已经关闭，则返回None
-        if state.shutdown {
+        if self.is_shutdown() {
             return None;
         }
 
-        // rust的借用检查器无法通过MutexGuard来确定同时访问expirations和entries是安全的
-        // 为了让借用检查器能够正常工作，需要获取一个真正的可变引用
-        let state = &mut *state;
-
         // 获取当前时间
         let now = Instant::now();
-
-        // 从expirations中找到所有已经过期的键
-        while let Some(&(when, ref key)) = state.expirations.iter().next() {
-            // 如果返回的时间大于now，直接返回下一个键过期的时间
-            if when > now {
-                return Some(when);
+        // 所有分片中最早的下一个过期时间
+        let mut next = None;
+        // 需要发送expired通知时，记录被删除的键，释放分片的锁之后再发送
+        let notify = self
+            .keyspace_events()
+            .is_enabled(KeyspaceEventClass::Expired);
+        let mut expired = Vec::new();
+
+        for shard in &self.shards {
+            let mut shard = shard.write().unwrap();
+            for (index, db) in shard.dbs.iter_mut().enumerate() {
+                // 从expirations中找到所有已经过期的键
+                while let Some(&(when, ref key)) = db.expirations.iter().next() {
+                    // 如果返回的时间大于now，记录这个数据库下一个键过期的时间
+                    if when > now {
+                        next = Some(next.map_or(when, |next: Instant| next.min(when)));
+                        break;
+                    }
+
+                    // 如果返回的时间小于now，那么从entries中删除这个键
+                    let key = key.clone();
+                    db.remove(&key, self);
+                    db.expirations.remove(&(when, key.clone()));
+                    self.stats.keys_expired(1);
+                    if notify {
+                        expired.push((index, key));
+                    }
+                }
             }
+        }
+
+        for (index, key) in expired {
+            self.notify_keyspace_event(KeyspaceEventClass::Expired, "expired", index, &key);
+        }
+
+        next
+    }
+
+    /// keyspace_events() 函数
+    ///
+    /// 返回notify-keyspace-events配置，不需要加配置锁
+    fn keyspace_events(&self) -> KeyspaceEvents {
+        KeyspaceEvents::from_bits(self.keyspace_events.load(Ordering::Relaxed))
+    }
 
-            // 如果返回的时间小于now，那么从entries中删除这个键
-            state.entries.remove(key);
-            state.expirations.remove(&(when, key.clone()));
+    /// notify_keyspace_event() 函数
+    ///
+    /// 按照notify-keyspace-events配置发送一个键空间通知，调用时不能持有分片的锁
+    fn notify_keyspace_event(&self, class: KeyspaceEventClass, event: &str, db: usize, key: &str) {
+        let events = self.keyspace_events();
+        if !events.is_enabled(class) {
+            return;
         }
 
-        None
+        if events.keyspace() {
+            let channel = format!("__keyspace@{}__:{}", db, key);
+            self.publish_now(&channel, Bytes::from(event.to_string()));
+        }
+        if events.keyevent() {
+            let channel = format!("__keyevent@{}__:{}", db, event);
+            self.publish_now(&channel, Bytes::from(key.to_string()));
+        }
+    }
+
+    /// publish_now() 函数
+    ///
+    /// 不等待地把消息发布到channel，backpressure订阅者的队列满时丢弃这条消息，
+    /// 用于在同步的代码中发送键空间通知
+    fn publish_now(&self, channel: &str, message: Bytes) {
+        let mut state = self.state.lock().unwrap();
+
+        if let Some(tx) = state.pub_sub.get(channel) {
+            let _ = tx.send(message.clone());
+        }
+        if let Some(senders) = state.bounded_pub_sub.get_mut(channel) {
+            senders.retain(|tx| !tx.is_closed());
+            for tx in senders.iter() {
+                if tx.try_send(message.clone()).is_err() {
+                    self.stats.pubsub_messages_dropped(1);
+                }
+            }
+        }
     }
 
     /// is_shutdown() 函数
@@ -318,53 +1748,221 @@ impl Shared {
 
 #[derive(Debug)]
 struct State {
-    /// 键值数据
-    entries: HashMap<String, Entry>,
     /// 发布/订阅的键空间，redis中为其单独使用一个键值空间
     pub_sub: HashMap<String, broadcast::Sender<Bytes>>,
+    /// backpressure投递方式的订阅者，每个订阅者一个有界的Sender
+    bounded_pub_sub: HashMap<String, Vec<mpsc::Sender<Bytes>>>,
+    /// db实例的开启/关闭状态
+    shutdown: bool,
+}
+
+impl State {
+    fn new(pub_sub: HashMap<String, broadcast::Sender<Bytes>>, shutdown: bool) -> Self {
+        Self {
+            pub_sub,
+            bounded_pub_sub: HashMap::new(),
+            shutdown,
+        }
+    }
+}
+
+/// # Shard 结构体
+///
+/// 键空间的一个分片，保存每个逻辑数据库中哈希到这个分片的键
+#[derive(Debug)]
+struct Shard {
+    /// 逻辑数据库，通过SELECT切换
+    dbs: Vec<Keyspace>,
+}
+
+impl Shard {
+    fn new(databases: usize) -> Self {
+        Self {
+            dbs: (0..databases).map(|_| Keyspace::default()).collect(),
+        }
+    }
+
+    /// next_expiration() 函数
+    ///
+    /// 返回分片的所有逻辑数据库中下一个密钥到期的时间
+    fn next_expiration(&self) -> Option<Instant> {
+        self.dbs
+            .iter()
+            .filter_map(|db| db.expirations.iter().next().map(|expiration| expiration.0))
+            .min()
+    }
+}
+
+/// # Keyspace 结构体
+///
+/// 一个逻辑数据库的数据
+#[derive(Debug, Default)]
+struct Keyspace {
+    /// 键值数据，可以按下标O(1)访问，淘汰键时用来随机采样
+    entries: IndexMap<String, Entry>,
     /// 维护keys的过期时间
     expirations: BTreeSet<(Instant, String)>,
-    /// db实例的开启/关闭状态
-    shutdown: bool,
+    /// 设置了过期时间的键，volatile-lfu淘汰键时从中随机采样，插入和删除键时通过insert()和remove()更新
+    volatile: IndexSet<String>,
+    /// 按照哈希值排序的键，SCAN从游标处的哈希值开始顺序遍历，插入和删除键时通过insert()和remove()更新
+    scan_order: BTreeSet<(u64, String)>,
+    /// 所有键值对估算占用的字节数，插入和删除键时通过insert()和remove()更新
+    used_memory: usize,
 }
 
-impl State {
-    fn new(
-        entries: HashMap<String, Entry>,
-        pub_sub: HashMap<String, broadcast::Sender<Bytes>>,
-        expirations: BTreeSet<(Instant, String)>,
-        shutdown: bool,
-    ) -> Self {
-        Self {
-            entries,
-            pub_sub,
-            expirations,
-            shutdown,
+impl Keyspace {
+    /// insert() 函数
+    ///
+    /// 插入一个键值对，返回被替换的旧值，同时更新这个逻辑数据库和所有数据库合计的内存用量，过期索引由调用者维护
+    fn insert(&mut self, key: String, entry: Entry, shared: &Shared) -> Option<Entry> {
+        use indexmap::map::Entry as MapEntry;
+
+        if entry.expires_at.is_some() {
+            self.volatile.insert(key.clone());
+        } else {
+            self.volatile.swap_remove(&key);
         }
+
+        let added = entry.memory_usage(&key);
+        let (removed, prev) = match self.entries.entry(key) {
+            MapEntry::Occupied(mut occupied) => {
+                let removed = occupied.get().memory_usage(occupied.key());
+                (removed, Some(occupied.insert(entry)))
+            }
+            MapEntry::Vacant(vacant) => {
+                let hash = shared.hasher.hash_one(vacant.key());
+                self.scan_order.insert((hash, vacant.key().clone()));
+                vacant.insert(entry);
+                (0, None)
+            }
+        };
+
+        self.used_memory = self.used_memory + added - removed;
+        shared.used_memory.fetch_add(added, Ordering::Relaxed);
+        shared.used_memory.fetch_sub(removed, Ordering::Relaxed);
+        prev
     }
 
-    /// next_expiration() 函数
+    /// remove() 函数
     ///
-    /// 返回下一个密钥到期的时间
-    fn next_expiration(&self) -> Option<Instant> {
-        self.expirations
-            .iter()
-            .next()
-            .map(|expiration| expiration.0)
+    /// 删除一个键，同时更新这个逻辑数据库和所有数据库合计的内存用量，过期索引由调用者维护
+    fn remove(&mut self, key: &str, shared: &Shared) -> Option<Entry> {
+        let entry = self.entries.swap_remove(key)?;
+        if entry.expires_at.is_some() {
+            self.volatile.swap_remove(key);
+        }
+        self.scan_order
+            .remove(&(shared.hasher.hash_one(key), key.to_string()));
+
+        let removed = entry.memory_usage(key);
+        self.used_memory -= removed;
+        shared.used_memory.fetch_sub(removed, Ordering::Relaxed);
+        Some(entry)
     }
 }
 
+/// # KeyDebugInfo 结构体
+///
+/// DEBUG OBJECT返回的键的内部信息
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct KeyDebugInfo {
+    /// 值的字节数
+    pub(crate) serialized_length: usize,
+    /// 距离最后一次访问的时间
+    pub(crate) idle: Duration,
+    /// LFU访问频率计数器
+    pub(crate) freq: u8,
+    /// 最后一次修改时的版本号
+    pub(crate) version: u64,
+    /// 剩余的生存时间，没有过期时间时为None
+    pub(crate) ttl: Option<Duration>,
+}
+
 #[derive(Debug)]
 struct Entry {
     /// 存储数据
     data: Bytes,
     /// 数据的过期时间
     expires_at: Option<Instant>,
+    /// 最后一次被访问的unix时间戳（毫秒），读命令只持有共享锁，所以使用原子变量更新，不会被保存到RDB文件
+    last_access: AtomicU64,
+    /// LFU访问频率计数器，对数增长，随着空闲时间衰减，不会被保存到RDB文件
+    freq: AtomicU8,
+    /// 最后一次修改时分配的版本号，不会被保存到RDB文件，加载的键会分配新的版本号
+    version: u64,
 }
 
 impl Entry {
     fn new(data: Bytes, expires_at: Option<Instant>) -> Self {
-        Self { data, expires_at }
+        Self {
+            data,
+            expires_at,
+            last_access: AtomicU64::new(now_millis()),
+            freq: AtomicU8::new(LFU_INIT_VAL),
+            version: 0,
+        }
+    }
+
+    /// copy_for_save() 函数
+    ///
+    /// 复制保存到RDB文件的部分（数据和过期时间），访问统计和版本号不会被保存，使用初始值
+    fn copy_for_save(&self) -> Entry {
+        Self {
+            data: self.data.clone(),
+            expires_at: self.expires_at,
+            last_access: AtomicU64::new(0),
+            freq: AtomicU8::new(0),
+            version: 0,
+        }
+    }
+
+    /// is_expired() 函数
+    ///
+    /// 键在now时是否已经过期
+    fn is_expired(&self, now: Instant) -> bool {
+        self.expires_at.is_some_and(|when| when <= now)
+    }
+
+    /// touch() 函数
+    ///
+    /// 更新最后访问时间，并按概率增加访问频率计数器
+    ///
+    /// 计数器先按照空闲时间衰减再增加，并发的访问可能会少计一次，对于近似的频率来说可以接受
+    fn touch(&self) {
+        self.freq
+            .store(lfu_log_incr(self.freq()), Ordering::Relaxed);
+        self.last_access.store(now_millis(), Ordering::Relaxed);
+    }
+
+    /// freq() 函数
+    ///
+    /// 返回衰减后的访问频率计数器，每空闲LFU_DECAY_MINUTES分钟减1
+    fn freq(&self) -> u8 {
+        let periods = self.idle_time().as_secs() / 60 / LFU_DECAY_MINUTES;
+        let counter = self.freq.load(Ordering::Relaxed);
+        counter.saturating_sub(periods.min(u8::MAX as u64) as u8)
+    }
+
+    /// memory_usage() 函数
+    ///
+    /// 估算键值对占用的字节数，设置了过期时间的键在过期索引中还保存了一份键
+    fn memory_usage(&self, key: &str) -> usize {
+        let entry =
+            mem::size_of::<String>() + key.len() + mem::size_of::<Entry>() + self.data.len();
+        let expiration = match self.expires_at {
+            Some(_) => mem::size_of::<(Instant, String)>() + key.len(),
+            None => 0,
+        };
+
+        entry + expiration
+    }
+
+    /// idle_time() 函数
+    ///
+    /// 返回距离最后一次访问的时间
+    fn idle_time(&self) -> Duration {
+        let last_access = self.last_access.load(Ordering::Relaxed);
+        Duration::from_millis(now_millis().saturating_sub(last_access))
     }
 }
 
@@ -376,8 +1974,8 @@ impl Serialize for Entry {
     {
         // 先将Bytes类型转换为Vec<u8>类型（可序列化）
         let data = self.data.clone().to_vec();
-        // 将Intant类型转换为u64类型（可序列化）
-        let expires_at = self.expires_at.map(|instant| instant.elapsed().as_secs());
+        // 将Instant类型转换为unix时间戳（毫秒），重启后仍然表示同一个时刻
+        let expires_at = self.expires_at.map(unix_millis);
         let mut state = serializer.serialize_struct("Entry", 2)?;
         state.serialize_field("data", &data)?;
         state.serialize_field("expires_at", &expires_at)?;
@@ -398,16 +1996,137 @@ impl<'de> Deserialize<'de> for Entry {
         }
         // 反序列化EntryData
         let entry_data = EntryData::deserialize(deserializer)?;
-        let expire_at_instant = if let Some(expires_at) = entry_data.expires_at {
-            Some(Instant::now() + Duration::from_secs(expires_at))
-        } else {
-            None
-        };
+        // 已经过去的时刻转换为当前时刻，加载后会被当作过期的键过滤掉
+        let expire_at_instant = entry_data.expires_at.map(|expires_at| {
+            let deadline = UNIX_EPOCH + Duration::from_millis(expires_at);
+            deadline_after(
+                Instant::now(),
+                deadline
+                    .duration_since(SystemTime::now())
+                    .unwrap_or_default(),
+            )
+        });
 
-        Ok(Self {
-            data: Bytes::from(entry_data.data),
-            expires_at: expire_at_instant,
-        })
+        Ok(Self::new(Bytes::from(entry_data.data), expire_at_instant))
+    }
+}
+
+/// # EntryV1 结构体
+///
+/// 版本1的RDB格式中的Entry，过期时间保存为相对的秒数
+#[derive(Serialize, Deserialize)]
+struct EntryV1 {
+    data: Vec<u8>,
+    expires_at: Option<u64>,
+}
+
+impl From<EntryV1> for Entry {
+    fn from(entry: EntryV1) -> Entry {
+        Entry::new(
+            Bytes::from(entry.data),
+            entry
+                .expires_at
+                .map(|seconds| deadline_after(Instant::now(), Duration::from_secs(seconds))),
+        )
+    }
+}
+
+/// Instant无法表示now之后duration的时刻时使用的过期时间，实际上等于永不过期
+const FAR_FUTURE: Duration = Duration::from_secs(100 * 365 * 24 * 60 * 60);
+
+/// # deadline_after() 函数
+///
+/// 返回now之后duration的时刻，超出Instant能表示的范围时不会panic，而是取now之后的FAR_FUTURE
+pub(crate) fn deadline_after(now: Instant, duration: Duration) -> Instant {
+    now.checked_add(duration)
+        .unwrap_or_else(|| now + FAR_FUTURE)
+}
+
+/// 淘汰键时每轮随机采样的候选键数量，越大越接近精确的LFU，但每次淘汰的开销也越大
+const EVICTION_SAMPLES: usize = 5;
+
+/// 新键的LFU计数器初始值，新写入的键不会因为计数为0而被当作最少访问的键
+const LFU_INIT_VAL: u8 = 5;
+
+/// LFU计数器的对数因子，越大计数器增长越慢，为10时大约一百万次访问计数器才会达到255
+const LFU_LOG_FACTOR: f64 = 10.0;
+
+/// LFU计数器衰减的周期（分钟）
+const LFU_DECAY_MINUTES: u64 = 1;
+
+/// # lfu_log_incr() 函数
+///
+/// 按照1 / ((counter - LFU_INIT_VAL) * LFU_LOG_FACTOR + 1)的概率把计数器加1，计数器越大越难增长
+fn lfu_log_incr(counter: u8) -> u8 {
+    if counter == u8::MAX {
+        return counter;
+    }
+    let base = counter.saturating_sub(LFU_INIT_VAL) as f64;
+    let probability = 1.0 / (base * LFU_LOG_FACTOR + 1.0);
+    let random = (random_u64() >> 11) as f64 / (1u64 << 53) as f64;
+    if random < probability {
+        counter + 1
+    } else {
+        counter
+    }
+}
+
+/// # random_u64() 函数
+///
+/// 返回一个伪随机数，用于LFU计数器的概率增长和淘汰键时的采样，不能用于安全相关的场景
+///
+/// 每个线程有自己的xorshift64*状态，只在第一次调用时用RandomState取一个随机种子，之后每次调用只需要几次位运算
+fn random_u64() -> u64 {
+    thread_local! {
+        static STATE: std::cell::Cell<u64> = std::cell::Cell::new(RandomState::new().hash_one(()) | 1);
+    }
+
+    STATE.with(|state| {
+        let mut x = state.get();
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        state.set(x);
+        x.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    })
+}
+
+/// # unix_millis() 函数
+///
+/// 将Instant转换为unix时间戳（毫秒），已经过去的时刻转换为现在
+///
+/// 两个时钟不是同时读取的，结果四舍五入到毫秒，避免EXPIREAT设置的整秒时间戳读回来时少了1毫秒
+fn unix_millis(instant: Instant) -> u64 {
+    let remaining = instant.saturating_duration_since(Instant::now());
+    let micros = (SystemTime::now() + remaining)
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_micros();
+    ((micros + 500) / 1000) as u64
+}
+
+/// # now_millis() 函数
+///
+/// 返回当前的unix时间戳（毫秒）
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+/// # drop_keyspaces() 函数
+///
+/// 释放被清空的逻辑数据库，lazy为true时交给后台线程释放，避免阻塞当前任务
+fn drop_keyspaces(shared: &Shared, dbs: Vec<Keyspace>, lazy: bool) {
+    // 清空的每个键都算作一次修改
+    let keys = dbs.iter().map(|db| db.entries.len() as u64).sum();
+    shared.mark_dirty(keys);
+    let memory = dbs.iter().map(|db| db.used_memory).sum();
+    shared.used_memory.fetch_sub(memory, Ordering::Relaxed);
+
+    if lazy {
+        tokio::task::spawn_blocking(move || drop(dbs));
     }
 }
 
@@ -448,7 +2167,7 @@ mod tests {
         let key = "test_key".to_string();
         let value = Bytes::from("test_value");
         // 调用set方法
-        db.set(key.clone(), value.clone(), None);
+        db.set(key.clone(), value.clone(), None, None);
 
         // 调用get方法
         let result = db.get(&key);
@@ -456,6 +2175,552 @@ mod tests {
         assert_eq!(result, Some(value));
     }
 
+    /// 测试Instant无法表示的过期时间不会panic，键按照永不过期处理
+    #[tokio::test]
+    async fn test_set_enormous_expire() {
+        let db = Database::new();
+        db.set(
+            "key".to_string(),
+            Bytes::from("value"),
+            Some(Duration::MAX),
+            None,
+        );
+        assert_eq!(db.get("key"), Some(Bytes::from("value")));
+
+        let expires_at = db.shared.shard("key").dbs[0].entries["key"]
+            .expires_at
+            .unwrap();
+        assert!(expires_at > Instant::now() + Duration::from_secs(50 * 365 * 24 * 60 * 60));
+
+        assert!(db.expire("key", deadline_after(Instant::now(), Duration::MAX), &[]));
+        assert_eq!(db.get("key"), Some(Bytes::from("value")));
+    }
+
+    /// 测试expire的GT和LT条件不会朝相反的方向修改已有的过期时间
+    #[tokio::test]
+    async fn test_expire_conditions() {
+        let db = Database::new();
+        db.set(
+            "key".to_string(),
+            Bytes::from("value"),
+            Some(Duration::from_secs(1000)),
+            None,
+        );
+        let expires_at = |db: &Database| db.shared.shard("key").dbs[0].entries["key"].expires_at;
+        let before = expires_at(&db).unwrap();
+
+        let sooner = Instant::now() + Duration::from_secs(100);
+        assert!(!db.expire("key", sooner, &[ExpireCondition::Gt]));
+        assert_eq!(expires_at(&db), Some(before));
+
+        assert!(db.expire("key", sooner, &[ExpireCondition::Lt]));
+        assert_eq!(expires_at(&db), Some(sooner));
+        assert!(!db.expire("key", sooner, &[ExpireCondition::Nx]));
+
+        // 不存在的键不会被设置过期时间
+        assert!(!db.expire("missing", sooner, &[]));
+
+        // 已经过去的时间会直接删除键
+        assert!(db.expire("key", Instant::now(), &[]));
+        assert_eq!(db.get("key"), None);
+        assert!(db.shared.shard("key").dbs[0].expirations.is_empty());
+    }
+
+    /// 测试已经过期但还没有被清理的键不会被EXPIRE复活，NX等条件也不会用旧的过期时间判断
+    #[tokio::test]
+    async fn test_expire_stale_key() {
+        let db = Database::new();
+        // 关闭后台任务，让过期的键留在分片中
+        db.shutdown_clean_task();
+
+        db.set("key".to_string(), Bytes::from("value"), None, None);
+        assert!(db.expire("key", Instant::now() + Duration::from_millis(10), &[]));
+        time::sleep(Duration::from_millis(20)).await;
+
+        let later = Instant::now() + Duration::from_secs(100);
+        assert!(!db.expire("key", later, &[]));
+        assert!(!db.expire("key", later, &[ExpireCondition::Gt]));
+        assert_eq!(db.get("key"), None);
+        assert_eq!(db.expire_time("key"), None);
+        assert!(db.shared.shard("key").dbs[0].entries.is_empty());
+        assert!(db.shared.shard("key").dbs[0].expirations.is_empty());
+    }
+
+    /// # notifying_database() 函数
+    ///
+    /// 创建一个发送所有键空间通知的Database实例
+    fn notifying_database() -> Database {
+        Database::with_config(ServerConfig {
+            notify_keyspace_events: "KEA".parse().unwrap(),
+            ..Default::default()
+        })
+    }
+
+    /// 测试DEL和EXPIRE删除键时发送del通知，删除不存在的键不发送通知
+    #[tokio::test]
+    async fn test_notify_del() {
+        let db = notifying_database();
+        let mut keyevent = db.subscribe("__keyevent@0__:del".to_string());
+        let mut keyspace = db.subscribe("__keyspace@0__:key".to_string());
+
+        db.set("key".to_string(), Bytes::from("value"), None, None);
+        assert!(db.del("key"));
+        assert!(!db.del("key"));
+        assert_eq!(keyevent.try_recv().unwrap(), "key");
+        assert_eq!(keyspace.try_recv().unwrap(), "del");
+
+        db.set("key".to_string(), Bytes::from("value"), None, None);
+        assert!(db.expire("key", Instant::now(), &[]));
+        assert_eq!(keyevent.try_recv().unwrap(), "key");
+        assert!(keyevent.try_recv().is_err());
+    }
+
+    /// 测试GET读到过期的键时删除它并发送expired通知
+    #[tokio::test]
+    async fn test_notify_lazy_expired() {
+        let db = notifying_database();
+        // 关闭后台任务，只留下GET触发的过期
+        db.shutdown_clean_task();
+        let mut expired = db.subscribe("__keyevent@0__:expired".to_string());
+
+        db.set(
+            "key".to_string(),
+            Bytes::from("value"),
+            Some(Duration::from_millis(10)),
+            None,
+        );
+        time::sleep(Duration::from_millis(20)).await;
+        assert!(expired.try_recv().is_err());
+
+        assert_eq!(db.get("key"), None);
+        assert_eq!(expired.try_recv().unwrap(), "key");
+        assert!(db.shared.shard("key").dbs[0].entries.is_empty());
+        assert!(db.shared.shard("key").dbs[0].expirations.is_empty());
+
+        // 已经删除的键不会再次发送通知
+        assert_eq!(db.get("key"), None);
+        assert!(expired.try_recv().is_err());
+    }
+
+    /// 测试后台任务清理过期的键时发送expired通知，通知发送到键所在的逻辑数据库
+    #[tokio::test]
+    async fn test_notify_active_expired() {
+        let db = notifying_database().select(2);
+        let mut expired = db.subscribe("__keyevent@2__:expired".to_string());
+
+        db.set(
+            "key".to_string(),
+            Bytes::from("value"),
+            Some(Duration::from_millis(10)),
+            None,
+        );
+        let key = time::timeout(Duration::from_secs(1), expired.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(key, "key");
+    }
+
+    /// 测试转发给副本的是写入的结果，相对的过期时间被转换为unix时间戳，条件不满足的写入不会被转发
+    #[tokio::test]
+    async fn test_replicate_absolute_expiration() {
+        let db = Database::new().select(1);
+        let mut commands = db.subscribe_replication();
+        let args = |frame: Frame| match frame {
+            Frame::Array(args) => args
+                .into_iter()
+                .map(|arg| arg.to_string())
+                .collect::<Vec<_>>(),
+            frame => panic!("unexpected frame {:?}", frame),
+        };
+
+        // 两次换算成unix时间戳之间系统时钟可能有细微的偏差
+        let assert_expire_at = |arg: &str, db: &Database| {
+            let expire_at = db.expire_time("key").unwrap().unwrap();
+            assert!(arg.parse::<u64>().unwrap().abs_diff(expire_at) <= 1);
+        };
+
+        db.set(
+            "key".to_string(),
+            Bytes::from("value"),
+            Some(Duration::from_secs(100)),
+            None,
+        );
+        let (index, frame) = commands.try_recv().unwrap();
+        let sent = args(frame);
+        assert_eq!(index, 1);
+        assert_eq!(sent[..4], ["set", "key", "value", "pxat"]);
+        assert_expire_at(&sent[4], &db);
+
+        assert!(!db.set(
+            "key".to_string(),
+            Bytes::from("other"),
+            None,
+            Some(SetCondition::Nx)
+        ));
+        assert!(commands.try_recv().is_err());
+
+        assert!(db.expire("key", Instant::now() + Duration::from_secs(50), &[]));
+        let sent = args(commands.try_recv().unwrap().1);
+        assert_eq!(sent[..2], ["pexpireat", "key"]);
+        assert_expire_at(&sent[2], &db);
+
+        assert!(db.del("key"));
+        assert_eq!(args(commands.try_recv().unwrap().1), ["del", "key"]);
+        assert!(!db.del("key"));
+        assert!(commands.try_recv().is_err());
+    }
+
+    /// 测试键分布到多个分片后，需要遍历所有分片的操作仍然能看到全部的键
+    #[tokio::test]
+    async fn test_sharded_keyspace() {
+        let db = Database::new();
+        for i in 0..1000 {
+            db.set(format!("key{}", i), Bytes::from("value"), None, None);
+        }
+
+        let used = db
+            .shared
+            .lock_all_shards()
+            .iter()
+            .filter(|shard| !shard.dbs[0].entries.is_empty())
+            .count();
+        assert_eq!(used, KEYSPACE_SHARDS);
+
+        assert_eq!(db.dump_all(usize::MAX).len(), 1000);
+        assert_eq!(db.snapshot()[0].len(), 1000);
+
+        db.flush_db(false);
+        assert!(db.dump_all(usize::MAX).is_empty());
+    }
+
+    /// 比较单个锁和分片锁在多线程并发读写时的吞吐量
+    ///
+    /// 结果依赖于机器的核数，默认不运行：cargo test bench_sharded_throughput -- --ignored --nocapture
+    #[test]
+    #[ignore]
+    fn bench_sharded_throughput() {
+        const THREADS: usize = 8;
+        const OPS: usize = 200_000;
+
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let _guard = runtime.enter();
+
+        let run = |shards: usize| {
+            let db = Database::with_shards(ServerConfig::default(), shards);
+            let start = std::time::Instant::now();
+            std::thread::scope(|scope| {
+                for t in 0..THREADS {
+                    let db = db.clone();
+                    scope.spawn(move || {
+                        for i in 0..OPS {
+                            let key = format!("key:{}:{}", t, i % 1024);
+                            if i % 2 == 0 {
+                                db.set(key, Bytes::from("value"), None, None);
+                            } else {
+                                db.get(&key);
+                            }
+                        }
+                    });
+                }
+            });
+            (THREADS * OPS) as f64 / start.elapsed().as_secs_f64()
+        };
+
+        let single = run(1);
+        let sharded = run(KEYSPACE_SHARDS);
+        println!(
+            "1 shard: {:.0} ops/s, {} shards: {:.0} ops/s",
+            single, KEYSPACE_SHARDS, sharded
+        );
+
+        if std::thread::available_parallelism().map_or(1, |n| n.get()) > 1 {
+            assert!(sharded > single);
+        }
+    }
+
+    /// 测试GET只持有分片的共享锁时，清理过期键的任务仍然可以拿到写锁删除过期的键，
+    /// 并且过期但还没有被清理的键对读操作不可见
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_readers_with_expiration() {
+        let db = Database::new();
+        for i in 0..100 {
+            let expire = Some(Duration::from_millis(50));
+            db.set(format!("key{}", i), Bytes::from("value"), expire, None);
+        }
+
+        let readers: Vec<_> = (0..4)
+            .map(|_| {
+                let db = db.clone();
+                tokio::task::spawn_blocking(move || {
+                    let deadline = std::time::Instant::now() + Duration::from_millis(300);
+                    while std::time::Instant::now() < deadline {
+                        for i in 0..100 {
+                            db.get(&format!("key{}", i));
+                        }
+                    }
+                })
+            })
+            .collect();
+        for reader in readers {
+            reader.await.unwrap();
+        }
+
+        for i in 0..100 {
+            assert_eq!(db.get(&format!("key{}", i)), None);
+        }
+        assert!(db.dump_all(usize::MAX).is_empty());
+        assert!(db.shared.stats.snapshot().contains(&("expired_keys", 100)));
+    }
+
+    /// 测试GET会更新键的最后访问时间，idle_time不会
+    #[tokio::test]
+    async fn test_idle_time() {
+        let db = Database::new();
+        assert_eq!(db.idle_time("key"), None);
+
+        db.set("key".to_string(), Bytes::from("value"), None, None);
+        db.shared.shard("key").dbs[0].entries["key"]
+            .last_access
+            .fetch_sub(5000, Ordering::Relaxed);
+        assert!(db.idle_time("key").unwrap() >= Duration::from_secs(5));
+        assert!(db.idle_time("key").unwrap() >= Duration::from_secs(5));
+
+        db.get("key");
+        assert!(db.idle_time("key").unwrap() < Duration::from_secs(1));
+    }
+
+    /// 测试SCAN分多次返回所有的键，每个键只返回一次，游标为0时结束
+    #[tokio::test]
+    async fn test_scan() {
+        let db = Database::new();
+        assert_eq!(db.scan(0, 10), (0, vec![]));
+
+        for i in 0..100 {
+            db.set(format!("key{}", i), Bytes::from("value"), None, None);
+        }
+
+        // 遍历期间删除和新增键，一直存在的键仍然正好返回一次
+        let mut cursor = 0;
+        let mut keys = Vec::new();
+        for round in 0.. {
+            let (next, page) = db.scan(cursor, 7);
+            assert!(page.len() <= 7);
+            keys.extend(page);
+            if next == 0 {
+                break;
+            }
+            cursor = next;
+
+            db.del(&format!("key{}", round % 10));
+            db.set(format!("new{}", round), Bytes::from("value"), None, None);
+        }
+        keys.retain(|key| !key.starts_with("new"));
+        keys.sort();
+        let mut persistent: Vec<_> = keys
+            .iter()
+            .filter(|key| key[3..].parse::<usize>().unwrap() >= 10)
+            .collect();
+        assert_eq!(persistent.len(), 90);
+        persistent.dedup();
+        assert_eq!(persistent.len(), 90);
+
+        db.flush_db(false);
+        for i in 0..100 {
+            db.set(format!("key{}", i), Bytes::from("value"), None, None);
+        }
+
+        let (next, page) = db.scan(0, 1000);
+        assert_eq!((next, page.len()), (0, 100));
+    }
+
+    /// 测试频繁访问的键计数器更大，空闲后计数器衰减
+    #[tokio::test]
+    async fn test_access_frequency() {
+        let db = Database::new();
+        assert_eq!(db.access_frequency("hot"), None);
+
+        db.set("hot".to_string(), Bytes::from("value"), None, None);
+        db.set("cold".to_string(), Bytes::from("value"), None, None);
+        assert_eq!(db.access_frequency("cold"), Some(LFU_INIT_VAL));
+
+        for _ in 0..1000 {
+            db.get("hot");
+        }
+        let hot = db.access_frequency("hot").unwrap();
+        assert!(hot > LFU_INIT_VAL, "{}", hot);
+
+        // 空闲3分钟后衰减3
+        db.shared.shard("hot").dbs[0].entries["hot"]
+            .last_access
+            .fetch_sub(3 * 60 * 1000, Ordering::Relaxed);
+        assert_eq!(db.access_frequency("hot"), Some(hot - 3));
+    }
+
+    /// 测试计数器从初始值开始一定增长，达到255后不再增长
+    #[test]
+    fn test_lfu_log_incr() {
+        assert_eq!(lfu_log_incr(0), 1);
+        assert_eq!(lfu_log_incr(LFU_INIT_VAL), LFU_INIT_VAL + 1);
+        assert_eq!(lfu_log_incr(u8::MAX), u8::MAX);
+    }
+
+    /// # evicted_keys() 函数
+    ///
+    /// 返回统计数据中被淘汰的键总数
+    fn evicted_keys(db: &Database) -> u64 {
+        db.stats()
+            .snapshot()
+            .into_iter()
+            .find(|(name, _)| *name == "evicted_keys")
+            .map(|(_, value)| value)
+            .unwrap()
+    }
+
+    /// 测试内存用量在写入、覆盖、设置过期时间和删除键时正确更新，所有键删除后回到0
+    #[tokio::test]
+    async fn test_used_memory() {
+        let db = Database::new();
+        assert_eq!(db.used_memory(), 0);
+
+        db.set("a".to_string(), Bytes::from("value"), None, None);
+        db.set("b".to_string(), Bytes::from("value"), None, None);
+        assert_eq!(
+            db.used_memory(),
+            db.memory_usage("a").unwrap() + db.memory_usage("b").unwrap()
+        );
+
+        db.set("a".to_string(), Bytes::from("a longer value"), None, None);
+        db.expire("b", Instant::now() + Duration::from_secs(100), &[]);
+        assert_eq!(
+            db.used_memory(),
+            db.memory_usage("a").unwrap() + db.memory_usage("b").unwrap()
+        );
+
+        db.del("a");
+        db.expire("b", Instant::now(), &[]);
+        assert_eq!(db.used_memory(), 0);
+
+        db.set("c".to_string(), Bytes::from("value"), None, None);
+        db.flush_all(false);
+        assert_eq!(db.used_memory(), 0);
+    }
+
+    /// 测试allkeys-lfu淘汰访问频率低的键，保留经常访问的键
+    #[tokio::test]
+    async fn test_evict_allkeys_lfu() {
+        let db = Database::with_config(ServerConfig {
+            maxmemory_policy: MaxMemoryPolicy::AllkeysLfu,
+            ..Default::default()
+        });
+        db.set("hot".to_string(), Bytes::from("value"), None, None);
+        for _ in 0..1000 {
+            db.get("hot");
+        }
+        for i in 0..20 {
+            db.set(format!("cold{}", i), Bytes::from("value"), None, None);
+        }
+        assert!(db.free_memory());
+
+        let limit = db.used_memory() / 2;
+        db.update_config(|config| config.maxmemory = limit as u64);
+        assert!(db.free_memory());
+        assert!(db.used_memory() <= limit);
+        assert!(db.get("hot").is_some());
+        assert!(evicted_keys(&db) > 0);
+    }
+
+    /// 测试volatile-lfu只淘汰设置了过期时间的键，没有这样的键时无法释放内存
+    #[tokio::test]
+    async fn test_evict_volatile_lfu() {
+        let db = Database::with_config(ServerConfig {
+            maxmemory: 1,
+            maxmemory_policy: MaxMemoryPolicy::VolatileLfu,
+            ..Default::default()
+        });
+        db.set("persistent".to_string(), Bytes::from("value"), None, None);
+        db.set(
+            "volatile".to_string(),
+            Bytes::from("value"),
+            Some(Duration::from_secs(100)),
+            None,
+        );
+
+        assert!(!db.free_memory());
+        assert!(db.get("volatile").is_none());
+        assert!(db.get("persistent").is_some());
+        assert_eq!(evicted_keys(&db), 1);
+    }
+
+    /// 测试noeviction策略下超出maxmemory时不淘汰任何键
+    #[tokio::test]
+    async fn test_noeviction() {
+        let db = Database::with_config(ServerConfig {
+            maxmemory: 1,
+            ..Default::default()
+        });
+        db.set("key".to_string(), Bytes::from("value"), None, None);
+
+        assert!(!db.free_memory());
+        assert!(db.get("key").is_some());
+        assert_eq!(evicted_keys(&db), 0);
+    }
+
+    /// 比较8个线程并发读取时，使用共享锁和独占锁访问分片的吞吐量
+    ///
+    /// 结果依赖于机器的核数，默认不运行：cargo test bench_read_lock_throughput -- --ignored --nocapture
+    #[test]
+    #[ignore]
+    fn bench_read_lock_throughput() {
+        const THREADS: usize = 8;
+        const OPS: usize = 200_000;
+
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let _guard = runtime.enter();
+
+        // 所有线程读同一个分片中的热点键
+        let db = Database::with_shards(ServerConfig::default(), 1);
+        for i in 0..1024 {
+            db.set(format!("key:{}", i), Bytes::from("value"), None, None);
+        }
+
+        let run = |exclusive: bool| {
+            let start = std::time::Instant::now();
+            std::thread::scope(|scope| {
+                for _ in 0..THREADS {
+                    let db = db.clone();
+                    scope.spawn(move || {
+                        for i in 0..OPS {
+                            let key = format!("key:{}", i % 1024);
+                            if exclusive {
+                                let shard = db.shared.shard(&key);
+                                let value = shard.dbs[0].entries.get(&key).map(|entry| {
+                                    entry.touch();
+                                    entry.data.clone()
+                                });
+                                assert!(value.is_some());
+                            } else {
+                                assert!(db.get(&key).is_some());
+                            }
+                        }
+                    });
+                }
+            });
+            (THREADS * OPS) as f64 / start.elapsed().as_secs_f64()
+        };
+
+        let exclusive = run(true);
+        let shared = run(false);
+        println!(
+            "write lock: {:.0} ops/s, read lock: {:.0} ops/s",
+            exclusive, shared
+        );
+
+        if std::thread::available_parallelism().map_or(1, |n| n.get()) > 1 {
+            assert!(shared > exclusive);
+        }
+    }
+
     /// 测试获取不存在的键
     #[tokio::test]
     async fn test_get_nonexistent_key() {
@@ -477,7 +2742,7 @@ mod tests {
         let mut subscriber2 = db.subscribe(channel.clone());
 
         // 发布消息到channle
-        let subscribers_number = db.publish(&channel, message.clone());
+        let subscribers_number = db.publish(&channel, message.clone()).await;
 
         // 检查subscriber数量
         assert_eq!(subscribers_number, 2);
@@ -499,7 +2764,7 @@ mod tests {
         let channel = "test_channel".to_string();
         let message = Bytes::from("No subscribers");
 
-        let subscribers_number = db.publish(&channel, message.clone());
+        let subscribers_number = db.publish(&channel, message.clone()).await;
 
         assert_eq!(subscribers_number, 0);
     }
@@ -510,8 +2775,18 @@ mod tests {
         let db = Database::new();
 
         // 添加测试数据
-        db.set("test_key1".to_string(), Bytes::from("test_value1"), None);
-        db.set("test_key2".to_string(), Bytes::from("test_value2"), None);
+        db.set(
+            "test_key1".to_string(),
+            Bytes::from("test_value1"),
+            None,
+            None,
+        );
+        db.set(
+            "test_key2".to_string(),
+            Bytes::from("test_value2"),
+            None,
+            None,
+        );
 
         // 保存到rdb
         let file_path = "test.rdb";
@@ -530,4 +2805,269 @@ mod tests {
         // 删除rdb文件
         fs::remove_file(file_path).expect("Failed to remove RDB file");
     }
+
+    /// 测试RDB中超出配置的databases的逻辑数据库有键时拒绝加载，超出的逻辑数据库为空时可以加载
+    #[tokio::test]
+    async fn test_rdb_more_databases_than_configured() {
+        let file_path = format!("test-databases-{}.rdb", std::process::id());
+        let db = Database::new();
+        db.set("key".to_string(), Bytes::from("value"), None, None);
+        db.save_to_rdb(&file_path).unwrap();
+
+        let fewer = Database::with_config(ServerConfig {
+            databases: 2,
+            ..Default::default()
+        });
+        fewer.load_from_rdb(&file_path).unwrap();
+        assert_eq!(fewer.get("key"), Some(Bytes::from("value")));
+
+        db.select(5)
+            .set("other".to_string(), Bytes::from("value"), None, None);
+        db.save_to_rdb(&file_path).unwrap();
+        let err = fewer.load_from_rdb(&file_path).unwrap_err();
+        assert!(
+            matches!(
+                err.downcast_ref::<RustisError>(),
+                Some(RustisError::UnsupportedRdb(_))
+            ),
+            "{}",
+            err
+        );
+        assert_eq!(fewer.get("key"), Some(Bytes::from("value")));
+
+        fs::remove_file(&file_path).unwrap();
+    }
+
+    /// 测试复制的快照序列化的结果与一次性序列化所有逻辑数据库的结果格式相同，可以被解码
+    #[tokio::test]
+    async fn test_serialize_keyspaces() {
+        let db = Database::with_config(ServerConfig {
+            databases: 3,
+            ..Default::default()
+        });
+        for i in 0..100 {
+            db.set(
+                format!("key{}", i),
+                Bytes::from(format!("value{}", i)),
+                None,
+                None,
+            );
+        }
+        db.select(2)
+            .set("other".to_string(), Bytes::from("value"), None, None);
+
+        let keyspaces = db.shared.copy_keyspaces(&db.shared.read_all_shards());
+        let payload = serialize_keyspaces(&keyspaces).unwrap();
+        let data = decode_rdb_payload(RDB_VERSION, &payload).ok().unwrap();
+        assert_eq!(data.len(), 3);
+        assert_eq!(data[0].len(), 100);
+        assert!(data[1].is_empty());
+        assert_eq!(&data[2]["other"].data[..], b"value");
+
+        let entries: Vec<HashMap<&String, &Entry>> =
+            data.iter().map(|db| db.iter().collect()).collect();
+        let expected = bincode::serialize(&entries).unwrap();
+        assert_eq!(payload.len(), expected.len());
+    }
+
+    /// 测试RDB文件先写入临时文件再重命名，写入失败时删除临时文件，不会留下不完整的文件
+    #[tokio::test]
+    async fn test_write_rdb_file_atomically() {
+        let dir = std::env::temp_dir().join(format!("rustis-atomic-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let file_path = dir.join("dump.rdb");
+
+        write_rdb_file(&file_path, RDB_VERSION, b"old").unwrap();
+        write_rdb_file(&file_path, RDB_VERSION, b"new").unwrap();
+        assert!(fs::read(&file_path).unwrap().ends_with(b"new"));
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+
+        // 目标是一个非空目录，重命名失败
+        let blocked = dir.join("blocked.rdb");
+        fs::create_dir_all(blocked.join("child")).unwrap();
+        assert!(write_rdb_file(&blocked, RDB_VERSION, b"data").is_err());
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// 测试DEBUG RELOAD使用的内存编码与SAVE写入的文件内容相同，可以通过文件头校验
+    #[tokio::test]
+    async fn test_encode_rdb_matches_file() {
+        let file_path =
+            std::env::temp_dir().join(format!("rustis-encode-test-{}.rdb", std::process::id()));
+        write_rdb_file(&file_path, RDB_VERSION, b"payload").unwrap();
+
+        let buffer = encode_rdb(RDB_VERSION, b"payload");
+        assert_eq!(fs::read(&file_path).unwrap(), buffer);
+        assert_eq!(
+            check_rdb_header(&buffer).unwrap(),
+            (RDB_VERSION, &b"payload"[..])
+        );
+
+        fs::remove_file(&file_path).unwrap();
+    }
+
+    /// 测试后台保存失败时记录失败的时刻，自动保存据此推迟重试
+    #[tokio::test]
+    async fn test_bgsave_failure() {
+        let db = Database::with_config(ServerConfig {
+            dir: std::env::temp_dir()
+                .join("rustis-missing-dir")
+                .join("nested"),
+            ..Default::default()
+        });
+        db.set("key".to_string(), Bytes::from("value"), None, None);
+        assert_eq!(db.last_bgsave_failure(), None);
+
+        assert!(db.bgsave());
+        while db.is_bgsave_in_progress() {
+            time::sleep(Duration::from_millis(10)).await;
+        }
+        assert!(db.last_bgsave_failure().is_some());
+        assert_eq!(db.changes_since_save(), 1);
+    }
+
+    /// 测试RDB文件中的数据被修改后，加载时返回校验和错误
+    #[tokio::test]
+    async fn test_rdb_checksum() {
+        let db = Database::new();
+        db.set("key".to_string(), Bytes::from("value"), None, None);
+
+        let file_path =
+            std::env::temp_dir().join(format!("rustis-checksum-test-{}.rdb", std::process::id()));
+        db.save_to_rdb(&file_path).unwrap();
+
+        // 修改数据的最后一个字节
+        let mut content = fs::read(&file_path).unwrap();
+        *content.last_mut().unwrap() ^= 0xff;
+        fs::write(&file_path, &content).unwrap();
+
+        let err = Database::new().load_from_rdb(&file_path).unwrap_err();
+        assert!(
+            matches!(
+                err.downcast_ref::<RustisError>(),
+                Some(RustisError::CorruptRdb(_))
+            ),
+            "{}",
+            err
+        );
+        assert!(err.to_string().contains("Wrong RDB checksum"), "{}", err);
+
+        // 没有文件头的旧格式文件
+        fs::write(&file_path, &content[RDB_HEADER_LEN..]).unwrap();
+        let err = Database::new().load_from_rdb(&file_path).unwrap_err();
+        assert!(err.to_string().contains("Wrong signature"), "{}", err);
+
+        fs::remove_file(&file_path).unwrap();
+    }
+
+    /// 测试版本1的RDB文件会被迁移，比当前版本新的RDB文件会被拒绝
+    #[tokio::test]
+    async fn test_rdb_version() {
+        let file_path =
+            std::env::temp_dir().join(format!("rustis-version-test-{}.rdb", std::process::id()));
+
+        let v1: Vec<HashMap<String, EntryV1>> = vec![HashMap::from([(
+            "key".to_string(),
+            EntryV1 {
+                data: b"value".to_vec(),
+                expires_at: Some(3600),
+            },
+        )])];
+        write_rdb_file(&file_path, 1, &bincode::serialize(&v1).unwrap()).unwrap();
+
+        let db = Database::new();
+        db.load_from_rdb(&file_path).unwrap();
+        assert_eq!(db.get("key"), Some(Bytes::from("value")));
+
+        // 比当前版本新的文件
+        write_rdb_file(
+            &file_path,
+            RDB_VERSION + 1,
+            &bincode::serialize(&v1).unwrap(),
+        )
+        .unwrap();
+        let err = Database::new().load_from_rdb(&file_path).unwrap_err();
+        assert!(
+            matches!(
+                err.downcast_ref::<RustisError>(),
+                Some(RustisError::UnsupportedRdb(_))
+            ),
+            "{}",
+            err
+        );
+        assert!(
+            err.to_string().contains("newer than the supported version"),
+            "{}",
+            err
+        );
+
+        fs::remove_file(&file_path).unwrap();
+    }
+
+    /// 测试带有过期时间的键保存后重新加载，剩余时间不变
+    #[tokio::test]
+    async fn test_rdb_keeps_expiration() {
+        let file_path =
+            std::env::temp_dir().join(format!("rustis-expire-test-{}.rdb", std::process::id()));
+
+        let db = Database::new();
+        db.set(
+            "key".to_string(),
+            Bytes::from("value"),
+            Some(Duration::from_secs(3600)),
+            None,
+        );
+        db.save_to_rdb(&file_path).unwrap();
+
+        let loaded = Database::new();
+        loaded.load_from_rdb(&file_path).unwrap();
+        assert_eq!(loaded.get("key"), Some(Bytes::from("value")));
+
+        let when = loaded.shared.shard("key").dbs[0].entries["key"]
+            .expires_at
+            .unwrap();
+        let remaining = when - Instant::now();
+        assert!(remaining > Duration::from_secs(3590) && remaining <= Duration::from_secs(3600));
+
+        fs::remove_file(&file_path).unwrap();
+    }
+
+    /// 测试RDB文件不存在时正常启动，文件损坏时拒绝启动，配置了ignore-corrupt-rdb时使用空数据库启动
+    #[tokio::test]
+    async fn test_load_corrupt_rdb() {
+        let dir = std::env::temp_dir();
+        let config = ServerConfig {
+            dir: dir.clone(),
+            dbfilename: format!("rustis-corrupt-test-{}.rdb", std::process::id()),
+            ..Default::default()
+        };
+        let rdb_path = config.rdb_path();
+
+        // 文件不存在
+        let _ = fs::remove_file(&rdb_path);
+        assert!(DatabaseWrapper::new(config.clone()).is_ok());
+
+        // 文件损坏
+        fs::write(&rdb_path, b"\xffthis is not an rdb file").unwrap();
+        let err = DatabaseWrapper::new(config.clone()).unwrap_err();
+        assert!(
+            matches!(
+                err.downcast_ref::<RustisError>(),
+                Some(RustisError::CorruptRdb(_))
+            ),
+            "{}",
+            err
+        );
+
+        let wrapper = DatabaseWrapper::new(ServerConfig {
+            ignore_corrupt_rdb: true,
+            ..config
+        })
+        .unwrap();
+        assert_eq!(wrapper.database().get("anything"), None);
+
+        fs::remove_file(&rdb_path).unwrap();
+    }
 }
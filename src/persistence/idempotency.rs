@@ -0,0 +1,84 @@
+//! IdempotencyTokens结构体，记录最近见过的SET IDEMPOTENT令牌
+
+use std::collections::{BTreeMap, HashMap};
+
+/// 默认最多记录的令牌数量
+pub(crate) const DEFAULT_TOKEN_CAPACITY: usize = 10000;
+
+/// # IdempotencyTokens 结构体
+///
+/// 有界的LRU令牌缓存，超过容量时淘汰最久没有用到的令牌
+///
+/// 每个令牌记录一个递增的序号，`order`按序号排序，第一个就是最久没有用到的令牌
+#[derive(Debug)]
+pub(crate) struct IdempotencyTokens {
+    /// 最多记录的令牌数量
+    capacity: usize,
+    /// 令牌到最近一次使用序号的映射
+    tokens: HashMap<String, u64>,
+    /// 序号到令牌的映射，用于找到最久没有用到的令牌
+    order: BTreeMap<u64, String>,
+    /// 下一个序号
+    next_seq: u64,
+}
+
+impl IdempotencyTokens {
+    /// # new() 函数
+    ///
+    /// 创建一个最多记录capacity个令牌的IdempotencyTokens实例
+    pub(crate) fn new(capacity: usize) -> IdempotencyTokens {
+        IdempotencyTokens {
+            capacity,
+            tokens: HashMap::new(),
+            order: BTreeMap::new(),
+            next_seq: 0,
+        }
+    }
+
+    /// # insert() 函数
+    ///
+    /// 记录一个令牌，返回令牌是否是第一次出现
+    ///
+    /// 重复出现的令牌会被标记为最近使用，不会被优先淘汰
+    pub(crate) fn insert(&mut self, token: &str) -> bool {
+        let seq = self.next_seq;
+        self.next_seq += 1;
+
+        if let Some(prev) = self.tokens.get_mut(token) {
+            let token = self.order.remove(prev).unwrap();
+            *prev = seq;
+            self.order.insert(seq, token);
+            return false;
+        }
+
+        self.tokens.insert(token.to_string(), seq);
+        self.order.insert(seq, token.to_string());
+
+        // 超过容量，淘汰最久没有用到的令牌
+        while self.tokens.len() > self.capacity {
+            let (_, oldest) = self.order.pop_first().unwrap();
+            self.tokens.remove(&oldest);
+        }
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 测试重复的令牌被识别，超过容量时淘汰最久没有用到的令牌
+    #[test]
+    fn test_insert_and_evict() {
+        let mut tokens = IdempotencyTokens::new(2);
+        assert!(tokens.insert("a"));
+        assert!(tokens.insert("b"));
+        assert!(!tokens.insert("a"));
+
+        // b是最久没有用到的，被淘汰
+        assert!(tokens.insert("c"));
+        assert!(!tokens.insert("a"));
+        assert!(tokens.insert("b"));
+    }
+}
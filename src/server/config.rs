@@ -0,0 +1,1185 @@
+//! ServerConfig结构体，服务器的运行配置
+
+use std::{
+    fmt,
+    fs::{self, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use serde::Deserialize;
+
+use crate::DEFAULT_PORT;
+
+pub use crate::networking::cidr::Cidr;
+use crate::networking::{
+    connection::DEFAULT_READ_BUFFER_SIZE,
+    frame::{self, FrameLimits},
+};
+
+/// 默认的最大连接数，与redis的maxclients默认值保持一致
+pub const DEFAULT_MAX_CONNECTIONS: usize = 10000;
+
+/// 默认的自动保存规则，与redis的save默认值保持一致
+pub const DEFAULT_SAVE_POINTS: [(u64, u64); 3] = [(3600, 1), (300, 100), (60, 10000)];
+
+/// # PubSubDelivery 枚举
+///
+/// 发布/订阅消息的投递方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PubSubDelivery {
+    /// 使用broadcast channel，订阅者跟不上时丢弃旧消息，发布者不会被阻塞
+    Broadcast,
+    /// 每个订阅者使用一个有界的mpsc channel，订阅者跟不上时发布者等待，直到超时
+    Backpressure,
+}
+
+impl PubSubDelivery {
+    /// # as_str() 函数
+    ///
+    /// 返回投递方式在配置中的名称
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PubSubDelivery::Broadcast => "broadcast",
+            PubSubDelivery::Backpressure => "backpressure",
+        }
+    }
+}
+
+/// # MaxMemoryPolicy 枚举
+///
+/// 使用的内存超过maxmemory时的处理方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum MaxMemoryPolicy {
+    /// 不淘汰键，会修改数据的命令回复OOM错误
+    #[serde(rename = "noeviction")]
+    NoEviction,
+    /// 从所有键中淘汰访问频率最低的键
+    AllkeysLfu,
+    /// 从设置了过期时间的键中淘汰访问频率最低的键，没有这样的键时回复OOM错误
+    VolatileLfu,
+}
+
+impl MaxMemoryPolicy {
+    /// # as_str() 函数
+    ///
+    /// 返回淘汰策略在配置中的名称
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MaxMemoryPolicy::NoEviction => "noeviction",
+            MaxMemoryPolicy::AllkeysLfu => "allkeys-lfu",
+            MaxMemoryPolicy::VolatileLfu => "volatile-lfu",
+        }
+    }
+
+    /// # to_u8() 函数
+    ///
+    /// 把淘汰策略编码为u8，用于保存在原子变量中
+    pub(crate) fn to_u8(self) -> u8 {
+        self as u8
+    }
+
+    /// # from_u8() 函数
+    ///
+    /// 从to_u8()的编码还原淘汰策略
+    pub(crate) fn from_u8(value: u8) -> Self {
+        match value {
+            0 => MaxMemoryPolicy::NoEviction,
+            1 => MaxMemoryPolicy::AllkeysLfu,
+            _ => MaxMemoryPolicy::VolatileLfu,
+        }
+    }
+}
+
+/// # KeyspaceEvents 结构体
+///
+/// 需要发送的键空间通知，使用与redis的notify-keyspace-events相同的标记：
+/// - K：发送到`__keyspace@<db>__:<key>`，消息为事件名称
+/// - E：发送到`__keyevent@<db>__:<event>`，消息为键
+/// - g：通用命令的事件，例如del
+/// - x：键过期的事件expired
+/// - A：g和x的别名
+///
+/// K和E至少需要一个，事件类型至少需要一个，否则不发送任何通知
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct KeyspaceEvents {
+    keyspace: bool,
+    keyevent: bool,
+    generic: bool,
+    expired: bool,
+}
+
+/// # KeyspaceEventClass 枚举
+///
+/// 键空间通知的事件类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum KeyspaceEventClass {
+    /// 通用命令的事件
+    Generic,
+    /// 键过期的事件
+    Expired,
+}
+
+impl KeyspaceEvents {
+    /// # is_enabled() 函数
+    ///
+    /// 判断这个类型的事件是否需要发送通知
+    pub(crate) fn is_enabled(&self, class: KeyspaceEventClass) -> bool {
+        let class = match class {
+            KeyspaceEventClass::Generic => self.generic,
+            KeyspaceEventClass::Expired => self.expired,
+        };
+        class && (self.keyspace || self.keyevent)
+    }
+
+    /// # keyspace() 函数
+    ///
+    /// 是否发送到`__keyspace@<db>__:<key>`
+    pub(crate) fn keyspace(&self) -> bool {
+        self.keyspace
+    }
+
+    /// # keyevent() 函数
+    ///
+    /// 是否发送到`__keyevent@<db>__:<event>`
+    pub(crate) fn keyevent(&self) -> bool {
+        self.keyevent
+    }
+
+    /// # to_bits() 函数
+    ///
+    /// 把标记编码为u8，用于保存在原子变量中，没有任何标记时为0
+    pub(crate) fn to_bits(self) -> u8 {
+        self.keyspace as u8
+            | (self.keyevent as u8) << 1
+            | (self.generic as u8) << 2
+            | (self.expired as u8) << 3
+    }
+
+    /// # from_bits() 函数
+    ///
+    /// 从to_bits()的编码还原标记
+    pub(crate) fn from_bits(bits: u8) -> Self {
+        Self {
+            keyspace: bits & 1 != 0,
+            keyevent: bits & 1 << 1 != 0,
+            generic: bits & 1 << 2 != 0,
+            expired: bits & 1 << 3 != 0,
+        }
+    }
+}
+
+impl FromStr for KeyspaceEvents {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut events = KeyspaceEvents::default();
+        for flag in s.chars() {
+            match flag {
+                'K' => events.keyspace = true,
+                'E' => events.keyevent = true,
+                'g' => events.generic = true,
+                'x' => events.expired = true,
+                'A' => {
+                    events.generic = true;
+                    events.expired = true;
+                }
+                _ => return Err(format!("无效的键空间通知标记 '{}'", flag)),
+            }
+        }
+        Ok(events)
+    }
+}
+
+impl TryFrom<String> for KeyspaceEvents {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl fmt::Display for KeyspaceEvents {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.generic && self.expired {
+            f.write_str("A")?;
+        } else {
+            if self.generic {
+                f.write_str("g")?;
+            }
+            if self.expired {
+                f.write_str("x")?;
+            }
+        }
+        if self.keyspace {
+            f.write_str("K")?;
+        }
+        if self.keyevent {
+            f.write_str("E")?;
+        }
+        Ok(())
+    }
+}
+
+/// # ServerConfig 结构体
+///
+/// 服务器的运行配置，通过`server::run`传入
+///
+/// 可以从TOML配置文件中反序列化，配置项名称使用短横线分隔（如`read-only`），
+/// 文件中没有出现的配置项使用默认值，出现未知的配置项时会报错
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct ServerConfig {
+    /// 监听的端口
+    pub port: u16,
+    /// 绑定的地址，可以有多个
+    pub bind: Vec<String>,
+    /// 允许连接的客户端地址段，为空时允许所有地址，不在其中的连接在accept后立即被关闭
+    pub allowlist: Vec<Cidr>,
+    /// RDB文件所在的目录
+    pub dir: PathBuf,
+    /// RDB文件名
+    pub dbfilename: String,
+    /// 自动保存RDB的规则，每一项为(秒数, 修改次数)，距离上次保存超过了秒数并且至少有修改次数次修改时，
+    /// 在后台保存RDB，为空时不自动保存
+    pub save: Vec<(u64, u64)>,
+    /// 最大内存（字节），0表示不限制，键值对估算占用的内存超过它时按照maxmemory_policy淘汰键或者拒绝写入
+    pub maxmemory: u64,
+    /// 使用的内存超过maxmemory时的淘汰策略
+    pub maxmemory_policy: MaxMemoryPolicy,
+    /// 连接需要的密码，None表示不需要认证
+    pub requirepass: Option<String>,
+    /// 是否开启AOF
+    pub appendonly: bool,
+    /// 日志级别，可以是redis的日志级别（debug、verbose、notice、warning、nothing），也可以使用RUST_LOG的语法
+    pub loglevel: String,
+    /// 启动时是否从RDB文件加载数据
+    pub load_rdb: bool,
+    /// 最大连接数
+    #[serde(rename = "maxclients")]
+    pub max_connections: usize,
+    /// 达到最大连接数时的处理方式：
+    /// - true：接受连接后立即回复`-ERR max number of clients reached`并关闭
+    /// - false：暂停accept，新连接在TCP层排队，直到有连接关闭
+    pub reject_on_max_connections: bool,
+    /// 服务器是否处于只读模式，只读模式下所有会修改数据的命令都会被拒绝
+    pub read_only: bool,
+    /// 是否在接受的连接上开启TCP_NODELAY（关闭Nagle算法）
+    pub tcp_nodelay: bool,
+    /// TCP keepalive的空闲时间（秒），0表示不开启
+    pub tcp_keepalive: u64,
+    /// 关闭服务器时等待连接处理完成的最长时间（秒），超时后剩余的连接会被强制关闭
+    pub shutdown_timeout: u64,
+    /// 逻辑数据库的数量
+    pub databases: usize,
+    /// 向订阅者推送心跳的间隔（秒），0表示不开启
+    pub subscriber_heartbeat: u64,
+    /// 新订阅使用的消息投递方式
+    pub pubsub_delivery: PubSubDelivery,
+    /// backpressure投递方式下，发布者等待订阅者的最长时间（毫秒），超时后该订阅者会丢失这条消息
+    pub pubsub_backpressure_timeout: u64,
+    /// 每个channel（broadcast方式）或每个订阅者（backpressure方式）最多缓存的消息数量，
+    /// 只对修改之后新建的订阅生效
+    pub pubsub_channel_capacity: usize,
+    /// 请求中bulk字符串的最大长度（字节）
+    pub proto_max_bulk_len: u64,
+    /// 请求中数组的最大元素个数
+    pub proto_max_multibulk_len: u64,
+    /// 一个完整请求的最大长度（字节），还没有收完的请求超过这个长度时关闭连接
+    pub client_query_buffer_limit: u64,
+    /// 每个连接的读缓冲区的初始容量（字节），读取过大的请求之后缓冲区会缩小回这个容量
+    #[serde(rename = "client-read-buffer-size")]
+    pub read_buffer_size: usize,
+    /// 是否允许执行DEBUG命令，只能在配置文件中开启
+    pub enable_debug_command: bool,
+    /// RDB文件损坏时是否忽略并使用空数据库启动，默认拒绝启动以免覆盖掉可以修复的数据
+    pub ignore_corrupt_rdb: bool,
+    /// 启动时作为副本连接的主节点，格式为"host port"，运行时通过REPLICAOF命令修改
+    pub replicaof: Option<String>,
+    /// 连接主节点时使用的密码
+    pub masterauth: Option<String>,
+    /// 作为副本时是否拒绝普通客户端的写命令，复制连接上的命令不受影响
+    pub replica_read_only: bool,
+    /// 需要发送的键空间通知，默认不发送
+    pub notify_keyspace_events: KeyspaceEvents,
+    /// 健康检查端口，在与第一个监听器相同的地址上监听，None表示不开启
+    pub health_port: Option<u16>,
+    /// 启动时读取的配置文件，CONFIG REWRITE会把当前配置写回这个文件
+    #[serde(skip)]
+    pub config_file: Option<PathBuf>,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            port: DEFAULT_PORT,
+            bind: vec!["localhost".to_string()],
+            allowlist: Vec::new(),
+            dir: PathBuf::from("."),
+            dbfilename: "rustis.rdb".to_string(),
+            save: DEFAULT_SAVE_POINTS.to_vec(),
+            maxmemory: 0,
+            maxmemory_policy: MaxMemoryPolicy::NoEviction,
+            requirepass: None,
+            appendonly: false,
+            loglevel: "debug".to_string(),
+            load_rdb: true,
+            max_connections: DEFAULT_MAX_CONNECTIONS,
+            reject_on_max_connections: true,
+            read_only: false,
+            tcp_nodelay: true,
+            tcp_keepalive: 300,
+            shutdown_timeout: 10,
+            databases: 16,
+            subscriber_heartbeat: 0,
+            pubsub_delivery: PubSubDelivery::Broadcast,
+            pubsub_backpressure_timeout: 1000,
+            pubsub_channel_capacity: 1024,
+            proto_max_bulk_len: frame::MAX_BULK_LEN,
+            proto_max_multibulk_len: frame::MAX_ARRAY_LEN,
+            client_query_buffer_limit: frame::MAX_FRAME_LEN,
+            read_buffer_size: DEFAULT_READ_BUFFER_SIZE,
+            enable_debug_command: false,
+            ignore_corrupt_rdb: false,
+            replicaof: None,
+            masterauth: None,
+            replica_read_only: true,
+            notify_keyspace_events: KeyspaceEvents::default(),
+            health_port: None,
+            config_file: None,
+        }
+    }
+}
+
+impl ServerConfig {
+    /// # from_toml() 函数
+    ///
+    /// 从TOML字符串中解析配置，错误信息中会包含出错的配置项名称
+    pub fn from_toml(content: &str) -> crate::Result<ServerConfig> {
+        toml::from_str(content).map_err(|err| format!("配置文件解析失败: {}", err).into())
+    }
+
+    /// # from_conf() 函数
+    ///
+    /// 从redis风格的配置文件内容中解析配置：每行是`参数名 值`，值是参数名之后的整行内容，
+    /// `""`表示空字符串，空行和以#开头的行会被忽略。参数名和值的格式与CONFIG GET/SET相同
+    pub fn from_conf(content: &str) -> crate::Result<ServerConfig> {
+        let mut config = ServerConfig::default();
+
+        // 与redis一样，多个save行的规则合并在一起，第一个save行替换默认规则，`save ""`清空之前的规则
+        let mut save_loaded = false;
+        for (index, line) in content.lines().enumerate() {
+            let Some((name, value)) = parse_conf_line(line) else {
+                continue;
+            };
+            let result = if name == "save" {
+                parse_save_points(&name, &value).map(|points| {
+                    if !save_loaded || points.is_empty() {
+                        config.save.clear();
+                    }
+                    config.save.extend(points);
+                    save_loaded = true;
+                })
+            } else {
+                config.load_parameter(&name, &value)
+            };
+            result.map_err(|err| format!("配置文件第{}行解析失败: {}", index + 1, err))?;
+        }
+
+        Ok(config)
+    }
+
+    /// # from_file() 函数
+    ///
+    /// 从配置文件中读取配置，扩展名为.toml的文件按TOML解析，其他文件按`参数名 值`的格式解析，
+    /// 只有后者可以通过CONFIG REWRITE写回
+    pub fn from_file(path: impl AsRef<Path>) -> crate::Result<ServerConfig> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path)
+            .map_err(|err| format!("无法读取配置文件 '{}': {}", path.display(), err))?;
+
+        let mut config = if is_toml_file(path) {
+            Self::from_toml(&content)
+        } else {
+            Self::from_conf(&content)
+        }
+        .map_err(|err| format!("{} ('{}')", err, path.display()))?;
+        config.config_file = Some(path.to_path_buf());
+
+        Ok(config)
+    }
+
+    /// # rewrite_config_file() 函数
+    ///
+    /// 把当前配置写回启动时读取的配置文件，供CONFIG REWRITE使用
+    ///
+    /// 与redis一样保留文件中的注释和参数的顺序：文件中已有的参数改为当前值，重复出现的参数只保留第一行，
+    /// 文件中没有、并且与默认值不同的参数追加到文件末尾。先写入同一目录下的临时文件并fsync，再重命名，
+    /// 最后fsync所在的目录，写入失败或者进程崩溃时原文件不变。使用阻塞的文件IO，在异步任务中需要通过spawn_blocking调用
+    pub(crate) fn rewrite_config_file(&self) -> Result<(), String> {
+        let Some(path) = &self.config_file else {
+            return Err("ERR The server is running without a config file".to_string());
+        };
+        if is_toml_file(path) {
+            return Err("ERR CONFIG REWRITE is not supported for TOML config files".to_string());
+        }
+
+        // 配置文件可能已经被删除，此时重新创建
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(err) => return Err(format!("ERR Rewriting config file: {}", err)),
+        };
+
+        let mut parameters = self.rewritable_parameters();
+        let mut lines = Vec::new();
+        for line in content.lines() {
+            match parse_conf_line(line) {
+                None => lines.push(line.to_string()),
+                Some((name, _)) => {
+                    // 已经写过的参数和未知的参数都不再保留
+                    if let Some(index) = parameters.iter().position(|(n, _)| *n == name) {
+                        let (name, value) = parameters.remove(index);
+                        lines.push(format_conf_line(name, &value));
+                    }
+                }
+            }
+        }
+
+        let defaults = ServerConfig::default().rewritable_parameters();
+        for (name, value) in parameters {
+            if !defaults.contains(&(name, value.clone())) {
+                lines.push(format_conf_line(name, &value));
+            }
+        }
+
+        let mut content = lines.join("\n");
+        content.push('\n');
+
+        write_config_file(path, content.as_bytes())
+            .map_err(|err| format!("ERR Rewriting config file: {}", err))
+    }
+
+    /// # rewritable_parameters() 函数
+    ///
+    /// 返回CONFIG REWRITE写入配置文件的参数，包括CONFIG GET的所有参数和只能在配置文件中设置的参数
+    fn rewritable_parameters(&self) -> Vec<(&'static str, String)> {
+        let mut parameters = self.parameters();
+        parameters.push(("load-rdb", yes_or_no(self.load_rdb).to_string()));
+        parameters.push((
+            "reject-on-max-connections",
+            yes_or_no(self.reject_on_max_connections).to_string(),
+        ));
+        parameters
+    }
+
+    /// # rdb_path() 函数
+    ///
+    /// 返回RDB文件的完整路径
+    pub fn rdb_path(&self) -> PathBuf {
+        self.dir.join(&self.dbfilename)
+    }
+
+    /// # master_addr() 函数
+    ///
+    /// 将replicaof配置的"host port"转换为可以连接的"host:port"
+    pub fn master_addr(&self) -> crate::Result<Option<String>> {
+        let Some(replicaof) = &self.replicaof else {
+            return Ok(None);
+        };
+
+        match replicaof.split_whitespace().collect::<Vec<_>>()[..] {
+            [host, port] if port.parse::<u16>().is_ok() => Ok(Some(format!("{}:{}", host, port))),
+            _ => Err(format!("无效的replicaof配置 '{}'，格式应为\"host port\"", replicaof).into()),
+        }
+    }
+
+    /// # log_filter() 函数
+    ///
+    /// 返回loglevel对应的日志过滤指令，redis的日志级别（debug、verbose、notice、warning、nothing）
+    /// 映射为tracing的级别，其他值按RUST_LOG的语法原样返回
+    pub fn log_filter(&self) -> &str {
+        match self.loglevel.to_lowercase().as_str() {
+            "debug" => "debug",
+            "verbose" | "notice" => "info",
+            "warning" => "warn",
+            "nothing" => "off",
+            _ => &self.loglevel,
+        }
+    }
+
+    /// # frame_limits() 函数
+    ///
+    /// 返回解析请求帧时的长度上限
+    pub(crate) fn frame_limits(&self) -> FrameLimits {
+        FrameLimits {
+            max_bulk_len: self.proto_max_bulk_len,
+            max_array_len: self.proto_max_multibulk_len,
+            max_frame_len: self.client_query_buffer_limit,
+            max_line_len: frame::MAX_LINE_LEN,
+        }
+    }
+
+    /// # parameters() 函数
+    ///
+    /// 返回所有可以通过CONFIG GET获取的参数名称和值
+    pub(crate) fn parameters(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("port", self.port.to_string()),
+            ("bind", self.bind.join(" ")),
+            (
+                "allowlist",
+                self.allowlist
+                    .iter()
+                    .map(|cidr| cidr.to_string())
+                    .collect::<Vec<_>>()
+                    .join(" "),
+            ),
+            ("dir", self.dir.display().to_string()),
+            ("dbfilename", self.dbfilename.clone()),
+            (
+                "save",
+                self.save
+                    .iter()
+                    .map(|(seconds, changes)| format!("{} {}", seconds, changes))
+                    .collect::<Vec<_>>()
+                    .join(" "),
+            ),
+            ("maxmemory", self.maxmemory.to_string()),
+            (
+                "maxmemory-policy",
+                self.maxmemory_policy.as_str().to_string(),
+            ),
+            ("requirepass", self.requirepass.clone().unwrap_or_default()),
+            ("appendonly", yes_or_no(self.appendonly).to_string()),
+            ("loglevel", self.loglevel.clone()),
+            ("maxclients", self.max_connections.to_string()),
+            ("read-only", yes_or_no(self.read_only).to_string()),
+            ("tcp-nodelay", yes_or_no(self.tcp_nodelay).to_string()),
+            ("tcp-keepalive", self.tcp_keepalive.to_string()),
+            ("shutdown-timeout", self.shutdown_timeout.to_string()),
+            ("databases", self.databases.to_string()),
+            (
+                "subscriber-heartbeat",
+                self.subscriber_heartbeat.to_string(),
+            ),
+            ("pubsub-delivery", self.pubsub_delivery.as_str().to_string()),
+            (
+                "pubsub-backpressure-timeout",
+                self.pubsub_backpressure_timeout.to_string(),
+            ),
+            (
+                "pubsub-channel-capacity",
+                self.pubsub_channel_capacity.to_string(),
+            ),
+            ("proto-max-bulk-len", self.proto_max_bulk_len.to_string()),
+            (
+                "proto-max-multibulk-len",
+                self.proto_max_multibulk_len.to_string(),
+            ),
+            (
+                "client-query-buffer-limit",
+                self.client_query_buffer_limit.to_string(),
+            ),
+            ("client-read-buffer-size", self.read_buffer_size.to_string()),
+            (
+                "enable-debug-command",
+                yes_or_no(self.enable_debug_command).to_string(),
+            ),
+            (
+                "ignore-corrupt-rdb",
+                yes_or_no(self.ignore_corrupt_rdb).to_string(),
+            ),
+            ("replicaof", self.replicaof.clone().unwrap_or_default()),
+            ("masterauth", self.masterauth.clone().unwrap_or_default()),
+            (
+                "replica-read-only",
+                yes_or_no(self.replica_read_only).to_string(),
+            ),
+            (
+                "notify-keyspace-events",
+                self.notify_keyspace_events.to_string(),
+            ),
+            (
+                "health-port",
+                self.health_port
+                    .map(|port| port.to_string())
+                    .unwrap_or_default(),
+            ),
+        ]
+    }
+
+    /// # set_parameter() 函数
+    ///
+    /// 在运行时修改一个参数，供CONFIG SET使用
+    pub(crate) fn set_parameter(&mut self, name: &str, value: &str) -> Result<(), String> {
+        match name {
+            "read-only" => self.read_only = parse_yes_or_no(name, value)?,
+            "replica-read-only" => self.replica_read_only = parse_yes_or_no(name, value)?,
+            "tcp-nodelay" => self.tcp_nodelay = parse_yes_or_no(name, value)?,
+            "tcp-keepalive" => self.tcp_keepalive = parse_integer(name, value)?,
+            "shutdown-timeout" => self.shutdown_timeout = parse_integer(name, value)?,
+            "subscriber-heartbeat" => self.subscriber_heartbeat = parse_integer(name, value)?,
+            "pubsub-delivery" => {
+                self.pubsub_delivery = match value.to_lowercase().as_str() {
+                    "broadcast" => PubSubDelivery::Broadcast,
+                    "backpressure" => PubSubDelivery::Backpressure,
+                    _ => {
+                        return Err(format!(
+                            "ERR CONFIG SET failed (possibly related to argument '{}') - argument must be 'broadcast' or 'backpressure'",
+                            name
+                        ))
+                    }
+                }
+            }
+            "pubsub-backpressure-timeout" => {
+                self.pubsub_backpressure_timeout = parse_integer(name, value)?
+            }
+            "maxmemory" => self.maxmemory = parse_integer(name, value)?,
+            "maxmemory-policy" => {
+                self.maxmemory_policy = match value.to_lowercase().as_str() {
+                    "noeviction" => MaxMemoryPolicy::NoEviction,
+                    "allkeys-lfu" => MaxMemoryPolicy::AllkeysLfu,
+                    "volatile-lfu" => MaxMemoryPolicy::VolatileLfu,
+                    _ => {
+                        return Err(format!(
+                            "ERR CONFIG SET failed (possibly related to argument '{}') - argument must be 'noeviction', 'allkeys-lfu' or 'volatile-lfu'",
+                            name
+                        ))
+                    }
+                }
+            }
+            "pubsub-channel-capacity" => {
+                self.pubsub_channel_capacity = parse_positive(name, value)? as usize
+            }
+            // 只对修改之后接受的连接生效
+            "proto-max-bulk-len" => self.proto_max_bulk_len = parse_positive(name, value)?,
+            "proto-max-multibulk-len" => {
+                self.proto_max_multibulk_len = parse_positive(name, value)?
+            }
+            "client-query-buffer-limit" => {
+                self.client_query_buffer_limit = parse_positive(name, value)?
+            }
+            "client-read-buffer-size" => {
+                self.read_buffer_size = parse_positive(name, value)? as usize
+            }
+            // 空字符串表示不需要密码
+            "masterauth" => self.masterauth = (!value.is_empty()).then(|| value.to_string()),
+            "save" => self.save = parse_save_points(name, value)?,
+            "notify-keyspace-events" => {
+                self.notify_keyspace_events = value.parse().map_err(|_| {
+                    format!(
+                        "ERR CONFIG SET failed (possibly related to argument '{}') - Invalid event class character. Use 'AgxKE'.",
+                        name
+                    )
+                })?
+            }
+            // 空字符串表示允许所有地址
+            "allowlist" => {
+                self.allowlist = value
+                    .split_whitespace()
+                    .map(|cidr| cidr.parse())
+                    .collect::<Result<_, _>>()
+                    .map_err(|err| {
+                        format!(
+                            "ERR CONFIG SET failed (possibly related to argument '{}') - {}",
+                            name, err
+                        )
+                    })?
+            }
+            "port" | "bind" | "dir" | "dbfilename" | "requirepass"
+            | "appendonly" | "loglevel" | "maxclients" | "databases" | "enable-debug-command"
+            | "ignore-corrupt-rdb" | "replicaof" | "health-port" => {
+                return Err(format!(
+                    "ERR CONFIG SET failed (possibly related to argument '{}') - can't set immutable config",
+                    name
+                ))
+            }
+            _ => return Err(format!("ERR Unknown option or number of arguments for CONFIG SET - '{}'", name)),
+        }
+
+        Ok(())
+    }
+
+    /// # load_parameter() 函数
+    ///
+    /// 设置配置文件中的一个参数，除了CONFIG SET可以修改的参数之外，也可以设置只能在启动时指定的参数
+    fn load_parameter(&mut self, name: &str, value: &str) -> Result<(), String> {
+        match name {
+            "port" => {
+                self.port = parse_integer(name, value)?
+                    .try_into()
+                    .map_err(|_| invalid_argument(name))?
+            }
+            "bind" => self.bind = value.split_whitespace().map(str::to_string).collect(),
+            "dir" => self.dir = PathBuf::from(value),
+            "dbfilename" => self.dbfilename = value.to_string(),
+            "requirepass" => self.requirepass = (!value.is_empty()).then(|| value.to_string()),
+            "appendonly" => self.appendonly = parse_yes_or_no(name, value)?,
+            "loglevel" => self.loglevel = value.to_string(),
+            "maxclients" => self.max_connections = parse_positive(name, value)? as usize,
+            "databases" => self.databases = parse_positive(name, value)? as usize,
+            "enable-debug-command" => self.enable_debug_command = parse_yes_or_no(name, value)?,
+            "ignore-corrupt-rdb" => self.ignore_corrupt_rdb = parse_yes_or_no(name, value)?,
+            "replicaof" => self.replicaof = (!value.is_empty()).then(|| value.to_string()),
+            "health-port" => {
+                self.health_port = match value {
+                    "" => None,
+                    _ => Some(
+                        parse_integer(name, value)?
+                            .try_into()
+                            .map_err(|_| invalid_argument(name))?,
+                    ),
+                }
+            }
+            "load-rdb" => self.load_rdb = parse_yes_or_no(name, value)?,
+            "reject-on-max-connections" => {
+                self.reject_on_max_connections = parse_yes_or_no(name, value)?
+            }
+            _ => return self.set_parameter(name, value),
+        }
+
+        Ok(())
+    }
+}
+
+/// # is_toml_file() 函数
+///
+/// 配置文件是否按TOML格式解析
+fn is_toml_file(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "toml")
+}
+
+/// 区分同一进程中同时进行的CONFIG REWRITE使用的临时文件
+static REWRITE_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// # write_config_file() 函数
+///
+/// 把content写入同一目录下唯一的临时文件并fsync，重命名为path之后再fsync所在的目录，保证重命名已经落盘
+fn write_config_file(path: &Path, content: &[u8]) -> io::Result<()> {
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let temp_path = path.with_file_name(format!(
+        "temp-rewrite-{}-{}-{}",
+        std::process::id(),
+        REWRITE_COUNTER.fetch_add(1, Ordering::Relaxed),
+        file_name
+    ));
+
+    let write = || -> io::Result<()> {
+        let mut file = File::create(&temp_path)?;
+        file.write_all(content)?;
+        file.sync_all()?;
+        fs::rename(&temp_path, path)
+    };
+    if let Err(err) = write() {
+        let _ = fs::remove_file(&temp_path);
+        return Err(err);
+    }
+
+    // 目录只能在unix上打开并fsync
+    #[cfg(unix)]
+    {
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        File::open(dir)?.sync_all()?;
+    }
+
+    Ok(())
+}
+
+/// # parse_conf_line() 函数
+///
+/// 解析配置文件中的一行，返回小写的参数名和值，空行和注释返回None
+fn parse_conf_line(line: &str) -> Option<(String, String)> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+
+    let (name, value) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+    let value = match value.trim() {
+        "\"\"" => "",
+        value => value,
+    };
+
+    Some((name.to_lowercase(), value.to_string()))
+}
+
+/// # format_conf_line() 函数
+///
+/// 把参数格式化为配置文件中的一行，空字符串写为""
+fn format_conf_line(name: &str, value: &str) -> String {
+    if value.is_empty() {
+        format!("{} \"\"", name)
+    } else {
+        format!("{} {}", name, value)
+    }
+}
+
+/// # invalid_argument() 函数
+///
+/// 参数的值超出范围时的错误
+fn invalid_argument(name: &str) -> String {
+    format!(
+        "ERR CONFIG SET failed (possibly related to argument '{}') - argument is out of range",
+        name
+    )
+}
+
+/// # yes_or_no() 函数
+///
+/// 将布尔值转换为redis配置中使用的yes/no
+fn yes_or_no(value: bool) -> &'static str {
+    if value {
+        "yes"
+    } else {
+        "no"
+    }
+}
+
+/// # parse_yes_or_no() 函数
+///
+/// 解析redis配置中使用的yes/no
+fn parse_yes_or_no(name: &str, value: &str) -> Result<bool, String> {
+    match value.to_lowercase().as_str() {
+        "yes" => Ok(true),
+        "no" => Ok(false),
+        _ => Err(format!(
+            "ERR CONFIG SET failed (possibly related to argument '{}') - argument must be 'yes' or 'no'",
+            name
+        )),
+    }
+}
+
+/// # parse_save_points() 函数
+///
+/// 解析CONFIG SET save的参数，格式为"秒数 修改次数 秒数 修改次数 ..."，空字符串表示关闭自动保存
+fn parse_save_points(name: &str, value: &str) -> Result<Vec<(u64, u64)>, String> {
+    let numbers = value
+        .split_whitespace()
+        .map(|number| parse_integer(name, number))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if numbers.len() % 2 != 0 {
+        return Err(format!(
+            "ERR CONFIG SET failed (possibly related to argument '{}') - Invalid save parameters",
+            name
+        ));
+    }
+
+    Ok(numbers.chunks(2).map(|pair| (pair[0], pair[1])).collect())
+}
+
+/// # parse_integer() 函数
+///
+/// 解析CONFIG SET中的整数参数
+fn parse_integer(name: &str, value: &str) -> Result<u64, String> {
+    value.parse().map_err(|_| {
+        format!(
+            "ERR CONFIG SET failed (possibly related to argument '{}') - argument couldn't be parsed into an integer",
+            name
+        )
+    })
+}
+
+/// # parse_positive() 函数
+///
+/// 解析CONFIG SET中必须大于0的整数参数
+fn parse_positive(name: &str, value: &str) -> Result<u64, String> {
+    match parse_integer(name, value)? {
+        0 => Err(format!(
+            "ERR CONFIG SET failed (possibly related to argument '{}') - argument must be greater than 0",
+            name
+        )),
+        value => Ok(value),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 测试空配置文件使用默认值，部分配置项覆盖默认值
+    #[test]
+    fn test_from_toml() {
+        let config = ServerConfig::from_toml("").unwrap();
+        assert_eq!(config.port, DEFAULT_PORT);
+        assert_eq!(config.rdb_path(), PathBuf::from("./rustis.rdb"));
+        assert_eq!(config.save, DEFAULT_SAVE_POINTS.to_vec());
+
+        let config = ServerConfig::from_toml(
+            "port = 7000\nmaxclients = 5\nsave = [[900, 1], [300, 10]]\nrequirepass = \"secret\"\n",
+        )
+        .unwrap();
+        assert_eq!(config.port, 7000);
+        assert_eq!(config.max_connections, 5);
+        assert_eq!(config.save, vec![(900, 1), (300, 10)]);
+        assert_eq!(config.requirepass.as_deref(), Some("secret"));
+        assert!(!config.read_only);
+    }
+
+    /// 测试allowlist可以从配置文件和CONFIG SET中解析
+    #[test]
+    fn test_allowlist() {
+        let config = ServerConfig::from_toml("allowlist = [\"10.0.0.0/8\", \"::1\"]\n").unwrap();
+        assert_eq!(config.allowlist.len(), 2);
+        assert!(ServerConfig::from_toml("allowlist = [\"10.0.0.0/40\"]\n").is_err());
+
+        let mut config = ServerConfig::default();
+        config
+            .set_parameter("allowlist", "127.0.0.1 192.168.0.0/16")
+            .unwrap();
+        assert_eq!(
+            config
+                .parameters()
+                .into_iter()
+                .find(|(name, _)| *name == "allowlist")
+                .unwrap()
+                .1,
+            "127.0.0.1/32 192.168.0.0/16"
+        );
+        assert!(config.set_parameter("allowlist", "not-an-ip").is_err());
+
+        config.set_parameter("allowlist", "").unwrap();
+        assert!(config.allowlist.is_empty());
+    }
+
+    /// 测试CONFIG SET pubsub-channel-capacity不接受0
+    #[test]
+    fn test_set_pubsub_channel_capacity() {
+        let mut config = ServerConfig::default();
+        config
+            .set_parameter("pubsub-channel-capacity", "16")
+            .unwrap();
+        assert_eq!(config.pubsub_channel_capacity, 16);
+
+        assert!(config
+            .set_parameter("pubsub-channel-capacity", "0")
+            .is_err());
+        assert_eq!(config.pubsub_channel_capacity, 16);
+    }
+
+    /// 测试maxmemory-policy可以通过CONFIG SET修改，也可以在TOML配置文件中设置
+    #[test]
+    fn test_set_maxmemory_policy() {
+        let mut config = ServerConfig::default();
+        assert_eq!(config.maxmemory_policy, MaxMemoryPolicy::NoEviction);
+
+        config
+            .set_parameter("maxmemory-policy", "ALLKEYS-LFU")
+            .unwrap();
+        assert_eq!(config.maxmemory_policy, MaxMemoryPolicy::AllkeysLfu);
+        assert!(config
+            .set_parameter("maxmemory-policy", "allkeys-lru")
+            .is_err());
+        assert_eq!(config.maxmemory_policy, MaxMemoryPolicy::AllkeysLfu);
+
+        let config = ServerConfig::from_toml("maxmemory-policy = \"volatile-lfu\"").unwrap();
+        assert_eq!(config.maxmemory_policy, MaxMemoryPolicy::VolatileLfu);
+        let config = ServerConfig::from_toml("maxmemory-policy = \"noeviction\"").unwrap();
+        assert_eq!(config.maxmemory_policy, MaxMemoryPolicy::NoEviction);
+
+        for policy in [
+            MaxMemoryPolicy::NoEviction,
+            MaxMemoryPolicy::AllkeysLfu,
+            MaxMemoryPolicy::VolatileLfu,
+        ] {
+            assert_eq!(MaxMemoryPolicy::from_u8(policy.to_u8()), policy);
+        }
+    }
+
+    /// 测试请求长度上限和读缓冲区大小可以通过CONFIG SET修改，不接受0
+    #[test]
+    fn test_set_frame_limits() {
+        let mut config = ServerConfig::default();
+        assert_eq!(config.frame_limits(), FrameLimits::default());
+
+        config.set_parameter("proto-max-bulk-len", "1024").unwrap();
+        config
+            .set_parameter("proto-max-multibulk-len", "8")
+            .unwrap();
+        config
+            .set_parameter("client-query-buffer-limit", "4096")
+            .unwrap();
+        assert_eq!(
+            config.frame_limits(),
+            FrameLimits {
+                max_bulk_len: 1024,
+                max_array_len: 8,
+                max_frame_len: 4096,
+                max_line_len: frame::MAX_LINE_LEN,
+            }
+        );
+
+        assert!(config.set_parameter("proto-max-bulk-len", "0").is_err());
+        assert!(config
+            .set_parameter("client-read-buffer-size", "0")
+            .is_err());
+        config
+            .set_parameter("client-read-buffer-size", "65536")
+            .unwrap();
+        assert_eq!(config.read_buffer_size, 65536);
+        assert!(config
+            .set_parameter("client-query-buffer-limit", "big")
+            .is_err());
+    }
+
+    /// 测试CONFIG SET save解析成对的规则，空字符串关闭自动保存
+    #[test]
+    fn test_set_save_points() {
+        let mut config = ServerConfig::default();
+        config.set_parameter("save", "900 1 300 10").unwrap();
+        assert_eq!(config.save, vec![(900, 1), (300, 10)]);
+
+        config.set_parameter("save", "").unwrap();
+        assert!(config.save.is_empty());
+
+        assert!(config.set_parameter("save", "900").is_err());
+        assert!(config.set_parameter("save", "900 many").is_err());
+    }
+
+    /// 测试notify-keyspace-events的解析和输出，A是g和x的别名
+    #[test]
+    fn test_set_notify_keyspace_events() {
+        let mut config = ServerConfig::default();
+        let events = |config: &ServerConfig| config.notify_keyspace_events;
+        assert!(!events(&config).is_enabled(KeyspaceEventClass::Generic));
+
+        config
+            .set_parameter("notify-keyspace-events", "Ex")
+            .unwrap();
+        assert!(events(&config).is_enabled(KeyspaceEventClass::Expired));
+        assert!(!events(&config).is_enabled(KeyspaceEventClass::Generic));
+        assert_eq!(events(&config).to_string(), "xE");
+
+        config
+            .set_parameter("notify-keyspace-events", "KEA")
+            .unwrap();
+        assert!(events(&config).is_enabled(KeyspaceEventClass::Generic));
+        assert_eq!(events(&config).to_string(), "AKE");
+
+        // 没有K和E时不发送任何通知
+        config.set_parameter("notify-keyspace-events", "g").unwrap();
+        assert!(!events(&config).is_enabled(KeyspaceEventClass::Generic));
+
+        assert!(config
+            .set_parameter("notify-keyspace-events", "Kz")
+            .is_err());
+
+        for flags in ["", "g", "xE", "AK", "KEA"] {
+            let events: KeyspaceEvents = flags.parse().unwrap();
+            assert_eq!(KeyspaceEvents::from_bits(events.to_bits()), events);
+        }
+        assert_eq!(KeyspaceEvents::default().to_bits(), 0);
+    }
+
+    /// 测试未知的配置项和类型错误的配置项会在错误信息中指出配置项名称
+    #[test]
+    fn test_from_toml_bad_key() {
+        let err = ServerConfig::from_toml("prot = 7000\n").unwrap_err();
+        assert!(err.to_string().contains("prot"), "{}", err);
+
+        let err = ServerConfig::from_toml("maxclients = \"many\"\n").unwrap_err();
+        assert!(err.to_string().contains("maxclients"), "{}", err);
+    }
+
+    /// 测试解析`参数名 值`格式的配置文件，包括只能在启动时设置的参数、空字符串和注释
+    #[test]
+    fn test_from_conf() {
+        let config = ServerConfig::from_conf(
+            "# comment\n\nport 7000\nbind 127.0.0.1 ::1\nsave \"\"\nMAXCLIENTS 5\nrequirepass secret\nload-rdb no\ntcp-keepalive 60\n",
+        )
+        .unwrap();
+        assert_eq!(config.port, 7000);
+        assert_eq!(
+            config.bind,
+            vec!["127.0.0.1".to_string(), "::1".to_string()]
+        );
+        assert!(config.save.is_empty());
+        assert_eq!(config.max_connections, 5);
+        assert_eq!(config.requirepass.as_deref(), Some("secret"));
+        assert!(!config.load_rdb);
+        assert_eq!(config.tcp_keepalive, 60);
+
+        // 多个save行合并，save ""清空之前的规则
+        let config = ServerConfig::from_conf("save 900 1\nsave 300 10 60 10000\n").unwrap();
+        assert_eq!(config.save, vec![(900, 1), (300, 10), (60, 10000)]);
+        let config = ServerConfig::from_conf("save 900 1\nsave \"\"\nsave 60 5\n").unwrap();
+        assert_eq!(config.save, vec![(60, 5)]);
+
+        let err = ServerConfig::from_conf("port 7000\nprot 7000\n").unwrap_err();
+        assert!(err.to_string().contains("第2行"), "{}", err);
+        assert!(ServerConfig::from_conf("port 70000\n").is_err());
+    }
+
+    /// 测试redis的日志级别映射为tracing的级别，其他值按RUST_LOG的语法处理
+    #[test]
+    fn test_log_filter() {
+        let filter = |loglevel: &str| {
+            let config = ServerConfig {
+                loglevel: loglevel.to_string(),
+                ..Default::default()
+            };
+            config.log_filter().to_string()
+        };
+        assert_eq!(filter("verbose"), "info");
+        assert_eq!(filter("notice"), "info");
+        assert_eq!(filter("Warning"), "warn");
+        assert_eq!(filter("nothing"), "off");
+        assert_eq!(filter("debug"), "debug");
+        assert_eq!(filter("warn,rustis::cmd=trace"), "warn,rustis::cmd=trace");
+    }
+
+    /// 测试CONFIG REWRITE保留注释，更新已有的参数，追加与默认值不同的参数，写回的文件可以重新解析
+    #[test]
+    fn test_rewrite_config_file() {
+        let path =
+            std::env::temp_dir().join(format!("rustis-rewrite-test-{}.conf", std::process::id()));
+        std::fs::write(
+            &path,
+            "# 端口\nport 7000\ntcp-keepalive 60\ntcp-keepalive 70\n",
+        )
+        .unwrap();
+
+        let mut config = ServerConfig::from_file(&path).unwrap();
+        assert_eq!(config.tcp_keepalive, 70);
+        config.set_parameter("tcp-keepalive", "30").unwrap();
+        config.set_parameter("masterauth", "").unwrap();
+        config.set_parameter("save", "").unwrap();
+        config.rewrite_config_file().unwrap();
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(content, "# 端口\nport 7000\ntcp-keepalive 30\nsave \"\"\n");
+
+        // 临时文件已经被重命名，目录中不会留下
+        let file_name = path.file_name().unwrap().to_string_lossy().to_string();
+        let leftover = std::fs::read_dir(std::env::temp_dir())
+            .unwrap()
+            .filter_map(|entry| entry.ok())
+            .any(|entry| {
+                let name = entry.file_name().to_string_lossy().to_string();
+                name.starts_with("temp-rewrite-") && name.ends_with(&file_name)
+            });
+        assert!(!leftover);
+
+        let reloaded = ServerConfig::from_file(&path).unwrap();
+        assert_eq!(reloaded.tcp_keepalive, 30);
+        assert!(reloaded.save.is_empty());
+        std::fs::remove_file(&path).unwrap();
+
+        // 没有配置文件或者配置文件是TOML时不能写回
+        assert!(ServerConfig::default().rewrite_config_file().is_err());
+        let toml = ServerConfig {
+            config_file: Some(PathBuf::from("rustis.toml")),
+            ..Default::default()
+        };
+        assert!(toml.rewrite_config_file().unwrap_err().contains("TOML"));
+    }
+}
@@ -1,29 +1,42 @@
 //! Handler结构体的实现，处理每个来自客户端的连接
 
-use tokio::sync::mpsc;
+use tokio::{
+    sync::{mpsc, OwnedSemaphorePermit},
+    time::Instant,
+};
 use tracing::{debug, instrument};
 
-use crate::cmd::Command;
-use crate::networking::connection::Connection;
+use crate::cmd::{monitor::format_monitor_line, Command};
+use crate::error::RustisError;
+use crate::networking::{connection::Connection, frame::Frame};
 use crate::persistence::database::Database;
 
-use super::shutdown::Shutdown;
+use super::{session::Session, shutdown::Shutdown};
 
-// use crate::persistence::db::Db;
+/// pipeline中最多积累多少个响应后flush一次
+const PIPELINE_BATCH_LIMIT: usize = 128;
 
 /// # 结构体功能
 ///
 /// 处理每个连接，从连接中读取请求并将命令应用到db
 #[derive(Debug)]
 pub(super) struct Handler {
+    /// 连接的唯一标识
+    conn_id: u64,
+    /// 对端的地址，用于日志
+    peer: String,
     /// 共享数据库
     database: Database,
     /// 连接
     connection: Connection,
     /// 监听服务器关闭信号
     shutdown: Shutdown,
+    /// 连接自己的状态
+    session: Session,
     /// 只作为一个标记，当Handler实例drop后，说明Handler已经关闭
     _shutdown_finish_tx: mpsc::Sender<()>,
+    /// 连接数信号量的许可，当Handler实例drop后，许可被归还给Listener
+    _permit: OwnedSemaphorePermit,
 }
 
 impl Handler {
@@ -35,12 +48,29 @@ impl Handler {
         connection: Connection,
         shutdown: Shutdown,
         _shutdown_finish_tx: mpsc::Sender<()>,
+        _permit: OwnedSemaphorePermit,
     ) -> Self {
+        database.client_connected();
+
+        let conn_id = connection.id();
+        let peer = connection
+            .peer_addr()
+            .map(|peer| peer.to_string())
+            .unwrap_or_else(|| "unknown".to_string());
+
+        // requirepass不能在运行时修改，连接建立时确定一次即可，没有配置密码的连接视为已经认证
+        let mut session = Session::new();
+        session.authenticated = !database.requires_auth();
+
         Self {
+            conn_id,
+            peer,
             database,
             connection,
             shutdown,
+            session,
             _shutdown_finish_tx,
+            _permit,
         }
     }
 
@@ -48,18 +78,53 @@ impl Handler {
     ///
     /// 处理一个连接，从socket中读取并处理请求帧，将响应写回套接字
     ///
-    /// 目前没有实现pipeline，pipeline是指在没有交叉帧的情况下， 每个连接同时处理多个请求的能力，更多信息请访问：https://redis.io/topics/pipelining
+    /// 支持pipeline：客户端一次发送多个命令时，响应先写入写缓冲区，直到读缓冲区中没有完整的命令帧，
+    /// 或者积累的响应达到PIPELINE_BATCH_LIMIT时才flush，更多信息请访问：https://redis.io/topics/pipelining
     ///
     /// 当接收到关闭信号时，连接被处理，直到它达到安全状态，此时它被终止。
-    #[instrument(skip(self))]
+    ///
+    /// 请求不符合协议时（包括订阅模式和监控模式下读到的请求），与redis一样先尽力回复
+    /// `-ERR Protocol error: <原因>`，再关闭连接
+    #[instrument(skip(self), fields(conn_id = self.conn_id, peer = %self.peer))]
     pub(super) async fn run(&mut self) -> crate::Result<()> {
+        let result = self.process_requests().await;
+
+        if let Err(err) = &result {
+            if let Some(RustisError::Protocol(_)) = err.downcast_ref::<RustisError>() {
+                self.database.stats().protocol_error();
+                // socket可能已经不可用了，回复失败时仍然返回原来的错误
+                let response = Frame::Error(err.to_string());
+                if let Err(write_err) = self.connection.write_frame(&response).await {
+                    debug!(cause = ?write_err, "failed to reply protocol error");
+                }
+            }
+        }
+
+        result
+    }
+
+    /// # process_requests() 函数
+    ///
+    /// 循环读取并执行请求，直到连接关闭、客户端发送QUIT或者服务器关闭
+    async fn process_requests(&mut self) -> crate::Result<()> {
+        // 写缓冲区中积累的还没有flush的响应数量
+        let mut pending = 0;
+
         // 只要没有收到关闭信号，就一直尝试读取新的请求帧
         while !self.shutdown.is_shutdown() {
+            // 读缓冲区中没有完整的帧时，下一次读取需要等待socket，先把积累的响应写入socket；
+            // 积累的响应达到上限时也写入一次，避免客户端等待太久
+            if !self.connection.has_buffered_frame() || pending >= PIPELINE_BATCH_LIMIT {
+                self.connection.flush().await?;
+                pending = 0;
+            }
+
             // 读取请求帧的同时监听关闭信号（通过select!来执行其中一个任务）
             let frame = tokio::select! {
                 frame = self.connection.read_frame() => frame?,
                 _ = self.shutdown.receiving() => {
-                    // 收到关闭信号，直接返回
+                    // 收到关闭信号，写出已经处理完的命令的响应后返回
+                    self.connection.flush().await?;
                     return Ok(());
                 }
             };
@@ -68,16 +133,133 @@ impl Handler {
                 Some(frame) => frame,
                 None => return Ok(()), // 缓冲区已经没有数据了，直接返回
             };
+            pending += 1;
 
-            let cmd = Command::decode_cmd_from_frame(frame)?;
+            // 有监控连接时在解码之前格式化好命令，通过检查后再推送
+            let monitor_line = self
+                .database
+                .has_monitors()
+                .then(|| format_monitor_line(self.session.db, &self.peer, &frame));
+
+            // 命令参数错误只回复错误，不关闭连接，其他错误（协议错误等）仍然会关闭连接
+            let cmd = match Command::decode_cmd_from_frame(frame) {
+                Ok(cmd) => cmd,
+                Err(err) => match err.downcast_ref::<RustisError>() {
+                    Some(RustisError::Command(msg)) => {
+                        let response = Frame::Error(msg.clone());
+                        debug!(?response);
+                        self.connection.write_frame_nowait(&response).await?;
+                        continue;
+                    }
+                    _ => return Err(err),
+                },
+            };
+            self.database.stats().command_processed();
 
             // ?表示用Debug trait打印出错误信息，而不是Display trait
             debug!(?cmd);
 
-            cmd.apply(&self.database, &mut self.connection, &mut self.shutdown)
-                .await?;
+            // 服务器配置了密码时，认证之前只允许执行no-auth命令
+            if !self.session.authenticated && !cmd.is_no_auth() {
+                let response = Frame::Error("NOAUTH Authentication required.".to_string());
+                debug!(?response);
+                self.connection.write_frame_nowait(&response).await?;
+                continue;
+            }
+
+            // 只读模式下拒绝会修改数据的命令
+            if cmd.is_write() && (self.session.readonly || self.database.is_read_only()) {
+                let response = Frame::Error(
+                    "READONLY You can't write against a read only replica.".to_string(),
+                );
+                debug!(?response);
+                self.connection.write_frame_nowait(&response).await?;
+                continue;
+            }
+
+            // 使用的内存超过maxmemory时先按照淘汰策略淘汰键，仍然超出时拒绝可能增加内存用量的命令
+            if cmd.is_deny_oom() && !self.database.free_memory() {
+                let response = Frame::Error(
+                    "OOM command not allowed when used memory > 'maxmemory'.".to_string(),
+                );
+                debug!(?response);
+                self.connection.write_frame_nowait(&response).await?;
+                continue;
+            }
+
+            // auth命令带有密码，不推送给监控连接，其他命令中的密码已经在format_monitor_line()中被替换
+            if let Some(line) = monitor_line {
+                if !matches!(cmd.get_name(), "auth" | "monitor") {
+                    self.database.feed_monitors(line);
+                }
+            }
+
+            // 在连接当前选择的逻辑数据库上执行命令
+            let database = self.database.select(self.session.db);
+
+            // 记录命令的耗时，命令返回错误或者回复了错误帧都算作失败
+            let name = cmd.get_name().to_string();
+            let error_replies = self.connection.error_replies();
+            let start = Instant::now();
+
+            let result = cmd
+                .apply(
+                    &database,
+                    &mut self.connection,
+                    &mut self.shutdown,
+                    &mut self.session,
+                )
+                .await;
+
+            let failed = result.is_err() || self.connection.error_replies() > error_replies;
+            self.database
+                .stats()
+                .record_command(&name, start.elapsed(), failed);
+            result?;
+
+            // 客户端发送了QUIT，写出所有响应后关闭连接
+            if self.session.quit {
+                self.connection.flush().await?;
+                return Ok(());
+            }
+
+            self.record_net_bytes();
         }
         // 正常收到信号是不会走到这里的
         Ok(())
     }
+
+    /// # quit_requested() 函数
+    ///
+    /// 客户端是否通过QUIT关闭了连接，没有发送QUIT就断开的连接算作异常断开
+    pub(super) fn quit_requested(&self) -> bool {
+        self.session.quit
+    }
+
+    /// # connection_bytes() 函数
+    ///
+    /// 返回连接建立以来读取和写入的字节总数
+    pub(super) fn connection_bytes(&self) -> (u64, u64) {
+        (
+            self.connection.total_bytes_read(),
+            self.connection.total_bytes_written(),
+        )
+    }
+
+    /// # record_net_bytes() 函数
+    ///
+    /// 将连接读写的字节数累加到服务器的统计数据中
+    fn record_net_bytes(&mut self) {
+        let (input, output, writes) = self.connection.take_net_bytes();
+        self.database.stats().net_bytes(input, output, writes);
+    }
+}
+
+impl Drop for Handler {
+    fn drop(&mut self) {
+        // 连接关闭，更新已连接的客户端数量，并记录还没有统计的字节数
+        self.record_net_bytes();
+        self.database.client_disconnected();
+        self.database.stats().connection_closed(self.session.quit);
+    }
 }
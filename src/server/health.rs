@@ -0,0 +1,79 @@
+//! 健康检查端口的实现，供容器编排系统的存活探针使用
+
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    task::AbortHandle,
+    time::{self, Duration},
+};
+use tracing::{debug, warn};
+
+use crate::persistence::database::Database;
+
+/// 等待探针发送请求的最长时间，只做TCP连接检查的探针不会发送任何数据
+const HEALTH_READ_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// 读取探针请求的最大长度，超过之后不再读取，直接回复
+const MAX_HEALTH_REQUEST_LEN: usize = 4096;
+
+/// 服务器正常时的响应
+const HEALTHY_RESPONSE: &[u8] =
+    b"HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nContent-Length: 3\r\nConnection: close\r\n\r\nOK\n";
+
+/// 后台任务已经退出时的响应
+const UNHEALTHY_RESPONSE: &[u8] =
+    b"HTTP/1.1 503 Service Unavailable\r\nContent-Type: text/plain\r\nContent-Length: 5\r\nConnection: close\r\n\r\nFAIL\n";
+
+/// # serve() 函数
+///
+/// 在健康检查端口上接受连接，对每个连接回复一个HTTP响应后关闭，不关心请求的路径和方法：
+/// 清除过期键和检查自动保存规则的后台任务都在运行时回复200，否则回复503
+///
+/// 这个任务只在主监听器接受连接期间运行，服务器开始关闭时被abort，之后探针会连接失败
+pub(crate) async fn serve(listener: TcpListener, database: Database, save_points: AbortHandle) {
+    loop {
+        let socket = match listener.accept().await {
+            Ok((socket, _)) => socket,
+            Err(err) => {
+                warn!(cause = %err, "failed to accept health check connection");
+                time::sleep(Duration::from_millis(100)).await;
+                continue;
+            }
+        };
+
+        let healthy = database.is_expiry_task_alive() && !save_points.is_finished();
+        tokio::spawn(respond(socket, healthy));
+    }
+}
+
+/// # respond() 函数
+///
+/// 读取探针的请求（最多等待HEALTH_READ_TIMEOUT），然后写入响应并关闭连接
+async fn respond(mut socket: TcpStream, healthy: bool) {
+    let mut request = Vec::new();
+    let mut buf = [0u8; 512];
+
+    // 读到请求头结束、连接关闭、超时或者请求过长时停止读取
+    let _ = time::timeout(HEALTH_READ_TIMEOUT, async {
+        while !request.windows(4).any(|window| window == b"\r\n\r\n")
+            && request.len() < MAX_HEALTH_REQUEST_LEN
+        {
+            match socket.read(&mut buf).await {
+                Ok(0) | Err(_) => break,
+                Ok(n) => request.extend_from_slice(&buf[..n]),
+            }
+        }
+    })
+    .await;
+
+    let response = if healthy {
+        HEALTHY_RESPONSE
+    } else {
+        UNHEALTHY_RESPONSE
+    };
+    if let Err(err) = socket.write_all(response).await {
+        debug!(cause = %err, "failed to write health check response");
+        return;
+    }
+    let _ = socket.shutdown().await;
+}
@@ -1,30 +1,57 @@
 //! Listener结构体的实现，监听来自客户端的连接
 
+use std::{net::SocketAddr, sync::Arc};
+
+use futures::future;
 use tokio::{
     net::{TcpListener, TcpStream},
-    sync::{broadcast, mpsc},
-    time::{self, Duration},
+    sync::{broadcast, mpsc, Semaphore},
+    task::JoinSet,
+    time::{self, Duration, Instant},
 };
-use tracing::{error, info, instrument};
+use tracing::{debug, error, info, instrument, warn};
 
 use crate::{
-    networking::connection::Connection, persistence::database::DatabaseWrapper,
+    networking::{cidr, connection::Connection, frame::Frame, socket::configure_socket},
+    persistence::database::DatabaseWrapper,
     server::shutdown::Shutdown,
 };
 
 use super::handler::Handler;
 
+/// 统计并输出accept速率的时间窗口
+const ACCEPT_RATE_INTERVAL: Duration = Duration::from_secs(10);
+
+/// 同时等待回复错误的被拒绝连接的最大数量，超过后新的被拒绝连接直接关闭
+const MAX_PENDING_REJECTIONS: usize = 64;
+
+/// 回复被拒绝连接的超时时间，避免不读取数据的客户端一直占用任务
+const REJECT_TIMEOUT: Duration = Duration::from_secs(1);
+
 /// 监听来自客户端连接
 #[derive(Debug)]
 pub(super) struct Listener {
     /// Database实例的包装器，是为了在实例被删除时，通过后天清除任务发出关闭的信号，允许有序地清理database
-    database_wrapper: DatabaseWrapper,
-    /// TCP监听器
-    listener: TcpListener,
+    pub database_wrapper: DatabaseWrapper,
+    /// TCP监听器，每个绑定的地址对应一个
+    listeners: Vec<TcpListener>,
+    /// 限制最大连接数的信号量，每个连接在处理前都需要获取一个许可，
+    /// 当Handler被drop时，许可会被归还
+    limit_connections: Arc<Semaphore>,
+    /// 达到最大连接数时，是否直接拒绝新连接
+    reject_on_max_connections: bool,
+    /// 限制同时回复错误的被拒绝连接数量，避免连接风暴时生成无限多的任务
+    pending_rejections: Arc<Semaphore>,
+    /// accept速率的统计
+    accept_rate: AcceptRate,
+    /// 所有处理连接的任务，关闭服务器超时后用来强制结束剩余的连接
+    pub connections: JoinSet<()>,
     /// 关闭信号发送者
     pub shutdown_tx: broadcast::Sender<()>,
     /// 只作为一个标记，传递给Handler
     pub shutdown_finish_tx: mpsc::Sender<()>,
+    /// 下一个分配给连接的唯一标识
+    next_conn_id: u64,
 }
 
 impl Listener {
@@ -33,33 +60,69 @@ impl Listener {
     /// 创建一个新的Listener实例
     pub fn new(
         database_wrapper: DatabaseWrapper,
-        listener: TcpListener,
+        listeners: Vec<TcpListener>,
         shutdown_tx: broadcast::Sender<()>,
         shutdown_finish_tx: mpsc::Sender<()>,
     ) -> Self {
+        let config = database_wrapper.database().config();
+
         Self {
             database_wrapper,
-            listener,
+            listeners,
+            limit_connections: Arc::new(Semaphore::new(config.max_connections)),
+            reject_on_max_connections: config.reject_on_max_connections,
+            pending_rejections: Arc::new(Semaphore::new(MAX_PENDING_REJECTIONS)),
+            accept_rate: AcceptRate::new(Instant::now()),
+            connections: JoinSet::new(),
             shutdown_tx,
             shutdown_finish_tx,
+            next_conn_id: 1,
         }
     }
 
     /// # 函数功能
     ///
-    /// 接收入站连接
+    /// 接收入站连接，同时在所有绑定的地址上等待，返回最先到达的连接和对端的地址
+    ///
+    /// 配置了allowlist时，来自其他地址的连接在accept后立即被关闭，不会占用连接数，也不会生成Handler
     ///
     /// # 错误处理
     ///
     /// 采用指数退避的方式解决重试问题，每次指数增长请求之间的间隔时间，直到达到最大重试次数，返回错误
-    async fn accept(&mut self) -> crate::Result<TcpStream> {
+    async fn accept(&mut self) -> crate::Result<(TcpStream, SocketAddr)> {
         // 每次重试请求之间的等待时间
         let mut backoff = 1;
 
         // 尝试去接受连接
         loop {
-            match self.listener.accept().await {
-                Ok((socket, _)) => return Ok(socket),
+            let accepts = self
+                .listeners
+                .iter()
+                .map(|listener| Box::pin(listener.accept()));
+
+            match future::select_all(accepts).await.0 {
+                Ok((socket, peer)) => {
+                    let database = self.database_wrapper.database();
+                    let config = database.config();
+
+                    if !cidr::is_allowed(&config.allowlist, peer.ip()) {
+                        warn!(%peer, "connection from address not in allowlist refused");
+                        continue;
+                    }
+                    database.stats().connection_received();
+                    if let Some((accepted, per_sec)) = self.accept_rate.record(Instant::now()) {
+                        info!(accepted, per_sec, "accept rate");
+                    }
+
+                    // 按照当前配置设置socket选项，设置失败不影响连接的处理
+                    // tcp-keepalive为0表示不开启keepalive
+                    let keepalive = (config.tcp_keepalive > 0)
+                        .then(|| Duration::from_secs(config.tcp_keepalive));
+                    if let Err(err) = configure_socket(&socket, config.tcp_nodelay, keepalive) {
+                        warn!(cause = %err, "failed to set socket options");
+                    }
+                    return Ok((socket, peer));
+                }
                 Err(error) => {
                     if backoff > 64 {
                         // 重试次数太多了，返回错误
@@ -79,32 +142,250 @@ impl Listener {
     /// # 函数功能
     ///
     /// 监听入站连接，对于每个入站连接，生成一个任务来处理连接
+    ///
+    /// 每个连接都需要先获取一个信号量许可，达到最大连接数时：
+    /// - 拒绝模式：接受连接后回复错误并关闭，同时回复错误的连接数量有上限，超过时直接关闭
+    /// - 排队模式：等待许可后再accept，新连接在TCP层的backlog中排队，accept循环不会跑在Handler前面
     #[instrument(skip(self))]
     pub(super) async fn run(&mut self) -> crate::Result<()> {
         info!("waiting for incoming connections");
 
         loop {
-            // 尝试接受连接，获取socket
-            let socket = self.accept().await?;
+            let ((socket, peer), permit) = if self.reject_on_max_connections {
+                // 尝试接受连接，获取socket
+                let accepted = self.accept().await?;
+
+                match self.limit_connections.clone().try_acquire_owned() {
+                    Ok(permit) => (accepted, permit),
+                    Err(_) => {
+                        warn!(peer = %accepted.1, "max number of clients reached, rejecting connection");
+                        self.database_wrapper
+                            .database()
+                            .stats()
+                            .connection_rejected();
+                        // 正在回复的被拒绝连接太多时直接关闭socket，不再生成任务
+                        if let Ok(permit) = self.pending_rejections.clone().try_acquire_owned() {
+                            tokio::spawn(async move {
+                                reject_connection(accepted.0).await;
+                                drop(permit);
+                            });
+                        }
+                        continue;
+                    }
+                }
+            } else {
+                // 先等待许可，semaphore不会被关闭，所以这里unwrap是安全的
+                let permit = match self.limit_connections.clone().try_acquire_owned() {
+                    Ok(permit) => permit,
+                    Err(_) => {
+                        warn!("max number of clients reached, pausing accept");
+                        let permit = self
+                            .limit_connections
+                            .clone()
+                            .acquire_owned()
+                            .await
+                            .unwrap();
+                        info!("resuming accept");
+                        permit
+                    }
+                };
+
+                (self.accept().await?, permit)
+            };
 
+            let conn_id = self.next_conn_id;
+            self.next_conn_id += 1;
+
+            let database = self.database_wrapper.database();
+            let config = database.config();
+            let connection = Connection::with_capacity(socket, config.read_buffer_size)
+                .with_id(conn_id)
+                .with_limits(config.frame_limits())
+                .with_inline_commands();
+            let local = connection
+                .local_addr()
+                .map(|addr| addr.to_string())
+                .unwrap_or_default();
+            info!(conn_id, %peer, local, "accepted connection");
             let mut handler = Handler::new(
-                self.database_wrapper.database(),
-                Connection::new(socket),
+                database,
+                connection,
                 Shutdown::new(self.shutdown_tx.subscribe()),
                 self.shutdown_finish_tx.clone(),
+                permit,
             );
 
+            // 回收已经结束的连接任务，避免JoinSet无限增长
+            while self.connections.try_join_next().is_some() {}
+
             // 生成一个任务来处理连接
-            tokio::spawn(async move {
+            self.connections.spawn(async move {
                 // 处理连接
                 if let Err(err) = handler.run().await {
-                    error!(cause = ?err, "处理连接时发生错误");
+                    error!(conn_id, cause = ?err, "处理连接时发生错误");
                 }
+                let (bytes_read, bytes_written) = handler.connection_bytes();
+                info!(
+                    conn_id,
+                    %peer,
+                    quit = handler.quit_requested(),
+                    bytes_read,
+                    bytes_written,
+                    "connection closed"
+                );
             });
         }
     }
+}
+
+/// # reject_connection() 函数
+///
+/// 达到最大连接数时，回复错误并关闭连接，回复超时的连接直接关闭
+async fn reject_connection(socket: TcpStream) {
+    let mut connection = Connection::new(socket);
+    let response = Frame::Error("ERR max number of clients reached".to_string());
+
+    match time::timeout(REJECT_TIMEOUT, connection.write_frame(&response)).await {
+        Ok(Err(err)) => debug!(cause = ?err, "回复连接数已满时发生错误"),
+        Err(_) => debug!("回复连接数已满时超时"),
+        Ok(Ok(())) => {}
+    }
+}
+
+/// # AcceptRate 结构体
+///
+/// 统计一个时间窗口内accept的连接数，窗口结束时计算速率
+#[derive(Debug)]
+struct AcceptRate {
+    /// 当前窗口的开始时间
+    window_start: Instant,
+    /// 当前窗口内accept的连接数
+    accepted: u64,
+}
+
+impl AcceptRate {
+    fn new(now: Instant) -> AcceptRate {
+        AcceptRate {
+            window_start: now,
+            accepted: 0,
+        }
+    }
+
+    /// # record() 函数
+    ///
+    /// 记录accept了一个连接，窗口结束时返回窗口内的连接数和每秒的速率，并开始新的窗口
+    fn record(&mut self, now: Instant) -> Option<(u64, f64)> {
+        self.accepted += 1;
+
+        let elapsed = now.duration_since(self.window_start);
+        if elapsed < ACCEPT_RATE_INTERVAL {
+            return None;
+        }
+
+        let accepted = self.accepted;
+        *self = AcceptRate::new(now);
+        Some((accepted, accepted as f64 / elapsed.as_secs_f64()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::config::ServerConfig;
+    use socket2::SockRef;
+
+    /// # accept_with_config() 函数
+    ///
+    /// 使用给定配置创建Listener，连接一次并返回服务器端accept到的socket
+    async fn accept_with_config(config: ServerConfig) -> TcpStream {
+        let tcp_listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = tcp_listener.local_addr().unwrap();
+        let (shutdown_tx, _) = broadcast::channel(1);
+        let (shutdown_finish_tx, _) = mpsc::channel(1);
+        let mut listener = Listener::new(
+            DatabaseWrapper::new(config).unwrap(),
+            vec![tcp_listener],
+            shutdown_tx,
+            shutdown_finish_tx,
+        );
+
+        let _client = TcpStream::connect(addr).await.unwrap();
+        listener.accept().await.unwrap().0
+    }
+
+    /// 测试来自allowlist中地址的连接被接受，其他地址的连接被关闭
+    #[tokio::test]
+    async fn test_accept_checks_allowlist() {
+        use tokio::io::AsyncReadExt;
+
+        let config = ServerConfig {
+            load_rdb: false,
+            allowlist: vec!["127.0.0.0/8".parse().unwrap()],
+            ..Default::default()
+        };
+        let socket = accept_with_config(config).await;
+        assert!(socket.peer_addr().unwrap().ip().is_loopback());
+
+        let tcp_listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = tcp_listener.local_addr().unwrap();
+        let (shutdown_tx, _) = broadcast::channel(1);
+        let (shutdown_finish_tx, _) = mpsc::channel(1);
+        let config = ServerConfig {
+            load_rdb: false,
+            allowlist: vec!["10.0.0.0/8".parse().unwrap()],
+            ..Default::default()
+        };
+        let mut listener = Listener::new(
+            DatabaseWrapper::new(config).unwrap(),
+            vec![tcp_listener],
+            shutdown_tx,
+            shutdown_finish_tx,
+        );
+
+        let mut client = TcpStream::connect(addr).await.unwrap();
+        let mut buf = [0; 16];
+        tokio::select! {
+            _ = listener.accept() => panic!("connection from loopback should be refused"),
+            n = client.read(&mut buf) => assert_eq!(n.unwrap(), 0),
+        }
+    }
+
+    /// 测试accept速率只在窗口结束时返回，并开始新的窗口
+    #[test]
+    fn test_accept_rate() {
+        let start = Instant::now();
+        let mut rate = AcceptRate::new(start);
+
+        for _ in 0..19 {
+            assert_eq!(rate.record(start + Duration::from_secs(1)), None);
+        }
+        let (accepted, per_sec) = rate.record(start + ACCEPT_RATE_INTERVAL).unwrap();
+        assert_eq!(accepted, 20);
+        assert_eq!(per_sec, 2.0);
+
+        assert_eq!(rate.record(start + ACCEPT_RATE_INTERVAL), None);
+        assert_eq!(rate.accepted, 1);
+    }
+
+    /// 测试服务器accept的连接默认开启TCP_NODELAY和keepalive，并且可以通过配置关闭
+    #[tokio::test]
+    async fn test_accept_sets_socket_options() {
+        let config = ServerConfig {
+            load_rdb: false,
+            ..Default::default()
+        };
+        let socket = accept_with_config(config).await;
+        assert!(socket.nodelay().unwrap());
+        assert!(SockRef::from(&socket).keepalive().unwrap());
 
-    pub(crate) fn save_rdb(&self) -> crate::Result<()> {
-        self.database_wrapper.database().save_to_rdb("rustis.rdb")
+        let config = ServerConfig {
+            load_rdb: false,
+            tcp_nodelay: false,
+            tcp_keepalive: 0,
+            ..Default::default()
+        };
+        let socket = accept_with_config(config).await;
+        assert!(!socket.nodelay().unwrap());
+        assert!(!SockRef::from(&socket).keepalive().unwrap());
     }
 }
@@ -1,23 +1,64 @@
+pub mod config;
 mod handler;
+mod health;
 mod listener;
+pub(crate) mod replication;
+pub(crate) mod session;
 pub mod shutdown;
+pub(crate) mod stats;
+#[cfg(any(test, feature = "test-util"))]
+pub mod testing;
 
 use std::future::Future;
 use tokio::{
     net::TcpListener,
     sync::{broadcast, mpsc},
+    time::{self, Duration},
 };
-use tracing::{debug, error, info, instrument};
+use tracing::{debug, error, info, instrument, warn};
 
+use config::ServerConfig;
 use listener::Listener;
 
-use crate::persistence::database::DatabaseWrapper;
+use crate::persistence::database::{Database, DatabaseWrapper};
+
+/// 检查自动保存规则的间隔
+const SAVE_POINTS_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// 后台保存失败后，自动保存至少等待这么久再重试，与redis的CONFIG_BGSAVE_RETRY_DELAY保持一致，
+/// 避免磁盘写满或者目录不可写时每次检查都重新保存并打印错误日志
+const BGSAVE_RETRY_DELAY: Duration = Duration::from_secs(5);
 
 /// # run() 函数
 ///
 /// 运行服务器，暴露给crate外的接口
-#[instrument(skip(listener, shutdown))]
-pub async fn run(listener: TcpListener, shutdown: impl Future, is_load_rdb: bool) {
+pub async fn run(
+    listener: TcpListener,
+    shutdown: impl Future,
+    config: ServerConfig,
+) -> crate::Result<()> {
+    run_with_listeners(vec![listener], shutdown, config).await
+}
+
+/// # run_with_listeners() 函数
+///
+/// 在多个已经绑定的监听器上运行服务器，所有监听器接受的连接共享同一个数据库
+///
+/// # 错误处理
+///
+/// 启动时加载RDB文件失败，或者关闭时保存RDB文件失败，都会返回错误
+///
+/// # panic
+///
+/// 如果listeners为空，将会panic
+#[instrument(skip(listeners, shutdown))]
+pub async fn run_with_listeners(
+    listeners: Vec<TcpListener>,
+    shutdown: impl Future,
+    config: ServerConfig,
+) -> crate::Result<()> {
+    assert!(!listeners.is_empty(), "至少需要一个监听器");
+
     // 创建一个广播channel，用来通知所有handler关闭信号
     // Receiver在需要时才创建，通过调用Sender的subscriber()方法创建
     // 当handler收到关闭信号后，会把自己的is_shutdown设置为true，退出handle的run循环
@@ -25,14 +66,46 @@ pub async fn run(listener: TcpListener, shutdown: impl Future, is_load_rdb: bool
     // 创建一个多生产者单消费者channel, 当所有的生产者drop后，channel就会被关闭，说明所有的handler已经关闭，这时候可以优雅地关闭服务器了
     let (shutdown_finish_tx, mut shutdown_finish_rx) = mpsc::channel(1);
 
+    // 配置了replicaof时，启动后作为副本从主节点复制数据
+    let master = config.master_addr()?;
+
+    // 健康检查端口与第一个监听器使用相同的地址
+    let health_listener = match config.health_port {
+        Some(port) => {
+            let ip = listeners[0].local_addr()?.ip();
+            let listener = TcpListener::bind((ip, port))
+                .await
+                .map_err(|err| format!("无法绑定健康检查端口 {port}: {err}"))?;
+            info!(addr = %listener.local_addr()?, "health check listening");
+            Some(listener)
+        }
+        None => None,
+    };
+
     // 初始化Listener
     let mut server = Listener::new(
-        DatabaseWrapper::new(is_load_rdb),
-        listener,
+        DatabaseWrapper::new(config)?,
+        listeners,
         shutdown_tx,
         shutdown_finish_tx,
     );
 
+    if let Some(master) = master {
+        replication::start_replication(server.database_wrapper.database(), master);
+    }
+
+    // 定期检查自动保存规则，满足时在后台保存RDB
+    let save_points = tokio::spawn(check_save_points(server.database_wrapper.database()));
+
+    // 健康检查检查的是清除过期键和自动保存这两个后台任务
+    let health = health_listener.map(|listener| {
+        tokio::spawn(health::serve(
+            listener,
+            server.database_wrapper.database(),
+            save_points.abort_handle(),
+        ))
+    });
+
     // 同时运行服务器和监听关闭信号
     tokio::select! {
         ret = server.run() => {
@@ -46,12 +119,10 @@ pub async fn run(listener: TcpListener, shutdown: impl Future, is_load_rdb: bool
         }
     }
 
-    // 关机前进行一次RDB快照
-    debug!("Save to RDB before shutdown");
-    server.save_rdb().expect("Failed to save RDB");
-
-    // 通过解构赋值从server中取出shutdown_tx, shutdown_finish_tx
+    // 通过解构赋值从server中取出database_wrapper, connections, shutdown_tx, shutdown_finish_tx
     let Listener {
+        database_wrapper,
+        mut connections,
         shutdown_tx,
         shutdown_finish_tx,
         ..
@@ -59,7 +130,74 @@ pub async fn run(listener: TcpListener, shutdown: impl Future, is_load_rdb: bool
     // 先Drop掉shutdown_tx，shutdown_finish_tx
     drop(shutdown_tx);
     drop(shutdown_finish_tx);
+    // 关闭时会进行一次保存，不再需要自动保存
+    save_points.abort();
+    // 服务器不再接受连接，健康检查端口随之关闭
+    if let Some(health) = health {
+        health.abort();
+    }
+
+    // 在宽限期内等待所有的handler关闭，超时后强制结束剩余的连接
+    let grace = Duration::from_secs(database_wrapper.database().config().shutdown_timeout);
+    if time::timeout(grace, shutdown_finish_rx.recv())
+        .await
+        .is_err()
+    {
+        connections.abort_all();
+
+        let mut force_closed = 0;
+        while let Some(result) = connections.join_next().await {
+            if matches!(result, Err(err) if err.is_cancelled()) {
+                force_closed += 1;
+            }
+        }
+        warn!(
+            force_closed,
+            "Shutdown timed out, force closed remaining connections"
+        );
+    }
+
+    // 所有连接都结束后再进行一次RDB快照
+    debug!("Save to RDB before shutdown");
+    database_wrapper.save_rdb()
+}
 
-    // 等待所有的handler关闭
-    let _ = shutdown_finish_rx.recv().await;
+/// # check_save_points() 函数
+///
+/// 后台任务，距离上次保存超过了某条规则的秒数，并且修改次数达到了这条规则的修改次数时，开始一次后台保存。
+/// 上一次后台保存失败时，等待BGSAVE_RETRY_DELAY之后才会再次尝试
+async fn check_save_points(database: Database) {
+    let mut interval = time::interval(SAVE_POINTS_CHECK_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        let changes = database.changes_since_save();
+        if changes == 0 || database.is_bgsave_in_progress() {
+            continue;
+        }
+        if database
+            .last_bgsave_failure()
+            .is_some_and(|failed_at| failed_at.elapsed() < BGSAVE_RETRY_DELAY)
+        {
+            continue;
+        }
+
+        let elapsed = database.last_save().0.elapsed();
+        let save_point = database
+            .config()
+            .save
+            .into_iter()
+            .find(|&(seconds, min_changes)| {
+                changes >= min_changes && elapsed >= Duration::from_secs(seconds)
+            });
+
+        if let Some((seconds, min_changes)) = save_point {
+            info!(
+                changes,
+                seconds, min_changes, "save point reached, saving in background"
+            );
+            database.bgsave();
+        }
+    }
 }
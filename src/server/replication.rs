@@ -0,0 +1,116 @@
+//! 主从复制中副本一侧的实现
+//!
+//! 副本连接主节点后发送PSYNC，主节点先回复+FULLRESYNC，然后以SELECT和SET命令的形式发送全量数据，
+//! 之后持续转发在主节点上执行的写操作的结果。副本通过Command::apply在本地数据库上执行收到的命令，
+//! 命令的响应会写回主节点，主节点读取后直接丢弃
+
+use tokio::{
+    net::TcpStream,
+    sync::broadcast,
+    time::{self, Duration},
+};
+use tracing::{info, instrument, warn};
+
+use crate::{
+    cmd::{auth::Auth, psync::Psync, Command},
+    networking::{connection::Connection, frame::Frame},
+    persistence::database::Database,
+    server::{session::Session, shutdown::Shutdown},
+};
+
+/// # Role 枚举
+///
+/// 服务器在主从复制中的角色
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Role {
+    /// 主节点，接受客户端的写命令，并转发给所有副本
+    Master,
+    /// 副本，从主节点（host:port）复制数据
+    Replica {
+        /// 主节点的地址
+        master: String,
+    },
+}
+
+/// 复制连接断开后，重新连接主节点之前等待的时间
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+/// # start_replication() 函数
+///
+/// 开始从master（host:port）复制数据，之前的复制任务会被停止
+pub(crate) fn start_replication(database: Database, master: String) {
+    let task = tokio::spawn(replicate(database.clone(), master.clone()));
+    database.set_replica_link(Some((master, task.abort_handle())));
+}
+
+/// # stop_replication() 函数
+///
+/// 停止复制，之后服务器作为主节点接受写命令，已经复制的数据会被保留
+pub(crate) fn stop_replication(database: &Database) {
+    database.set_replica_link(None);
+}
+
+/// # replicate() 函数
+///
+/// 复制任务的主循环，连接断开后等待一段时间重新连接并进行全量同步，直到任务被abort
+#[instrument(skip(database))]
+async fn replicate(database: Database, master: String) {
+    loop {
+        match sync_with_master(&database, &master).await {
+            Ok(()) => info!("master closed the replication link"),
+            Err(err) => warn!(cause = %err, "replication link failed"),
+        }
+
+        time::sleep(RECONNECT_DELAY).await;
+    }
+}
+
+/// # sync_with_master() 函数
+///
+/// 连接主节点，进行一次全量同步，然后持续执行主节点转发的写命令，直到连接关闭
+async fn sync_with_master(database: &Database, master: &str) -> crate::Result<()> {
+    let socket = TcpStream::connect(master).await?;
+    let mut connection = Connection::new(socket);
+
+    if let Some(password) = database.config().masterauth {
+        connection
+            .write_frame(&Auth::new(None::<String>, password).code_auth_into_frame())
+            .await?;
+        match connection.read_frame().await? {
+            Some(Frame::Simple(_)) => {}
+            Some(frame) => return Err(frame.to_error()),
+            None => return Ok(()),
+        }
+    }
+
+    connection
+        .write_frame(&Psync::new().code_psync_into_frame())
+        .await?;
+    match connection.read_frame().await? {
+        Some(Frame::Simple(response)) if response == "FULLRESYNC" => {}
+        Some(frame) => return Err(frame.to_error()),
+        None => return Ok(()),
+    }
+
+    info!(master, "full resync with master");
+    database.flush_all(false);
+
+    // 复制任务通过abort结束，不需要关闭信号
+    let (_shutdown_tx, shutdown_rx) = broadcast::channel(1);
+    let mut shutdown = Shutdown::new(shutdown_rx);
+    let mut session = Session::new();
+
+    while let Some(frame) = connection.read_frame().await? {
+        let cmd = Command::decode_cmd_from_frame(frame)?;
+        let db = database.select(session.db);
+        cmd.apply(&db, &mut connection, &mut shutdown, &mut session)
+            .await?;
+
+        // 主节点会读取并丢弃这些响应，积累的响应在没有更多命令时写出
+        if !connection.has_buffered_frame() {
+            connection.flush().await?;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,22 @@
+//! Session结构体，保存每个连接自己的状态
+
+/// # Session 结构体
+///
+/// 每个连接独有的状态，由Handler持有，在执行命令时传入
+#[derive(Debug, Default)]
+pub(crate) struct Session {
+    /// 连接是否处于只读模式，通过READONLY开启，READWRITE关闭
+    pub(crate) readonly: bool,
+    /// 连接是否已经通过AUTH认证，服务器没有配置requirepass时在连接建立时就视为已经认证
+    pub(crate) authenticated: bool,
+    /// 连接当前使用的逻辑数据库编号，通过SELECT切换
+    pub(crate) db: usize,
+    /// 客户端是否发送了QUIT，Handler写出响应后关闭连接
+    pub(crate) quit: bool,
+}
+
+impl Session {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+}
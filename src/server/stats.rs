@@ -0,0 +1,296 @@
+//! Stats结构体，服务器的运行统计数据
+
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
+
+/// 命令延迟直方图的桶上界（微秒）
+pub(crate) const LATENCY_BUCKETS_USEC: [u64; 6] = [10, 100, 1_000, 10_000, 100_000, 1_000_000];
+
+/// # CommandStat 结构体
+///
+/// 单个命令的调用统计
+#[derive(Debug, Clone, Default)]
+pub(crate) struct CommandStat {
+    /// 调用次数
+    pub(crate) calls: u64,
+    /// 执行失败（返回错误）的次数
+    pub(crate) failed_calls: u64,
+    /// 总耗时（纳秒），输出时转换为微秒
+    pub(crate) nanos: u64,
+    /// 延迟直方图，第i项为耗时不超过LATENCY_BUCKETS_USEC[i]的调用次数，最后一项为超过所有上界的调用次数
+    pub(crate) histogram: [u64; LATENCY_BUCKETS_USEC.len() + 1],
+}
+
+impl CommandStat {
+    /// # usec() 函数
+    ///
+    /// 返回总耗时（微秒）
+    pub(crate) fn usec(&self) -> u64 {
+        self.nanos / 1_000
+    }
+
+    /// # usec_per_call() 函数
+    ///
+    /// 返回平均每次调用的耗时（微秒）
+    pub(crate) fn usec_per_call(&self) -> f64 {
+        if self.calls == 0 {
+            return 0.0;
+        }
+        self.nanos as f64 / 1_000.0 / self.calls as f64
+    }
+
+    /// # cumulative_histogram() 函数
+    ///
+    /// 返回每个桶上界和耗时不超过这个上界的累计调用次数
+    pub(crate) fn cumulative_histogram(&self) -> Vec<(u64, u64)> {
+        let mut total = 0;
+        LATENCY_BUCKETS_USEC
+            .iter()
+            .zip(self.histogram.iter())
+            .map(|(bound, count)| {
+                total += count;
+                (*bound, total)
+            })
+            .collect()
+    }
+}
+
+/// # Stats 结构体
+///
+/// 服务器运行过程中的统计数据，所有计数器都是原子的，可以在多个连接之间共享
+#[derive(Debug, Default)]
+pub(crate) struct Stats {
+    /// 服务器接受的连接总数
+    total_connections_received: AtomicU64,
+    /// 因为达到最大连接数而被拒绝的连接总数
+    rejected_connections: AtomicU64,
+    /// 客户端发送QUIT后正常关闭的连接总数
+    total_clean_disconnections: AtomicU64,
+    /// 客户端没有发送QUIT就断开的连接总数
+    total_abrupt_disconnections: AtomicU64,
+    /// 服务器处理的命令总数
+    total_commands_processed: AtomicU64,
+    /// 因为请求不符合协议而被关闭的连接总数
+    total_protocol_errors: AtomicU64,
+    /// 从网络读取的字节总数
+    total_net_input_bytes: AtomicU64,
+    /// 写入网络的字节总数
+    total_net_output_bytes: AtomicU64,
+    /// 向socket写入响应（flush）的总次数，pipeline中的多个响应只需要一次写入
+    total_writes_processed: AtomicU64,
+    /// 因为过期而被删除的键总数
+    expired_keys: AtomicU64,
+    /// 因为使用的内存超过maxmemory而被淘汰的键总数
+    evicted_keys: AtomicU64,
+    /// 因为订阅者跟不上而被丢弃的pub/sub消息总数
+    pubsub_messages_dropped: AtomicU64,
+    /// 查找键成功的次数
+    keyspace_hits: AtomicU64,
+    /// 查找键失败的次数
+    keyspace_misses: AtomicU64,
+    /// 每个命令的调用统计，键为命令名称
+    commands: Mutex<HashMap<String, CommandStat>>,
+}
+
+impl Stats {
+    /// # connection_received() 函数
+    ///
+    /// 记录接受了一个新连接
+    pub(crate) fn connection_received(&self) {
+        self.total_connections_received
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// # connection_rejected() 函数
+    ///
+    /// 记录因为达到最大连接数而拒绝了一个连接
+    pub(crate) fn connection_rejected(&self) {
+        self.rejected_connections.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// # connection_closed() 函数
+    ///
+    /// 记录关闭了一个连接，clean表示客户端在断开之前发送了QUIT
+    pub(crate) fn connection_closed(&self, clean: bool) {
+        let counter = if clean {
+            &self.total_clean_disconnections
+        } else {
+            &self.total_abrupt_disconnections
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// # command_processed() 函数
+    ///
+    /// 记录处理了一个命令
+    pub(crate) fn command_processed(&self) {
+        self.total_commands_processed
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// # protocol_error() 函数
+    ///
+    /// 记录一个连接因为协议错误被关闭
+    pub(crate) fn protocol_error(&self) {
+        self.total_protocol_errors.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// # net_bytes() 函数
+    ///
+    /// 记录从网络读取和写入的字节数，以及写入socket的次数
+    pub(crate) fn net_bytes(&self, input: u64, output: u64, writes: u64) {
+        self.total_writes_processed
+            .fetch_add(writes, Ordering::Relaxed);
+        self.total_net_input_bytes
+            .fetch_add(input, Ordering::Relaxed);
+        self.total_net_output_bytes
+            .fetch_add(output, Ordering::Relaxed);
+    }
+
+    /// # keys_expired() 函数
+    ///
+    /// 记录因为过期而被删除的键
+    pub(crate) fn keys_expired(&self, count: u64) {
+        self.expired_keys.fetch_add(count, Ordering::Relaxed);
+    }
+
+    /// # keys_evicted() 函数
+    ///
+    /// 记录因为内存不足而被淘汰的键
+    pub(crate) fn keys_evicted(&self, count: u64) {
+        self.evicted_keys.fetch_add(count, Ordering::Relaxed);
+    }
+
+    /// # pubsub_messages_dropped() 函数
+    ///
+    /// 记录没有投递给订阅者的pub/sub消息
+    pub(crate) fn pubsub_messages_dropped(&self, count: u64) {
+        self.pubsub_messages_dropped
+            .fetch_add(count, Ordering::Relaxed);
+    }
+
+    /// # keyspace_lookup() 函数
+    ///
+    /// 记录一次键的查找结果
+    pub(crate) fn keyspace_lookup(&self, hit: bool) {
+        if hit {
+            self.keyspace_hits.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.keyspace_misses.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// # record_command() 函数
+    ///
+    /// 记录一次命令调用的耗时和是否失败
+    pub(crate) fn record_command(&self, name: &str, elapsed: Duration, failed: bool) {
+        let mut commands = self.commands.lock().unwrap();
+        let stat = match commands.get_mut(name) {
+            Some(stat) => stat,
+            None => commands.entry(name.to_string()).or_default(),
+        };
+
+        stat.calls += 1;
+        if failed {
+            stat.failed_calls += 1;
+        }
+        stat.nanos += elapsed.as_nanos() as u64;
+
+        let usec = elapsed.as_micros() as u64;
+        let bucket = LATENCY_BUCKETS_USEC
+            .iter()
+            .position(|bound| usec <= *bound)
+            .unwrap_or(LATENCY_BUCKETS_USEC.len());
+        stat.histogram[bucket] += 1;
+    }
+
+    /// # command_stats() 函数
+    ///
+    /// 返回所有被调用过的命令的统计，按命令名称排序
+    pub(crate) fn command_stats(&self) -> Vec<(String, CommandStat)> {
+        let commands = self.commands.lock().unwrap();
+        let mut stats: Vec<_> = commands
+            .iter()
+            .map(|(name, stat)| (name.clone(), stat.clone()))
+            .collect();
+        stats.sort_by(|a, b| a.0.cmp(&b.0));
+        stats
+    }
+
+    /// # snapshot() 函数
+    ///
+    /// 返回所有统计项的名称和当前值，名称与redis INFO的Stats部分保持一致
+    pub(crate) fn snapshot(&self) -> Vec<(&'static str, u64)> {
+        let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+
+        vec![
+            (
+                "total_connections_received",
+                load(&self.total_connections_received),
+            ),
+            ("rejected_connections", load(&self.rejected_connections)),
+            (
+                "total_clean_disconnections",
+                load(&self.total_clean_disconnections),
+            ),
+            (
+                "total_abrupt_disconnections",
+                load(&self.total_abrupt_disconnections),
+            ),
+            (
+                "total_commands_processed",
+                load(&self.total_commands_processed),
+            ),
+            ("total_protocol_errors", load(&self.total_protocol_errors)),
+            ("total_net_input_bytes", load(&self.total_net_input_bytes)),
+            ("total_net_output_bytes", load(&self.total_net_output_bytes)),
+            ("total_writes_processed", load(&self.total_writes_processed)),
+            ("expired_keys", load(&self.expired_keys)),
+            ("evicted_keys", load(&self.evicted_keys)),
+            (
+                "pubsub_messages_dropped",
+                load(&self.pubsub_messages_dropped),
+            ),
+            ("keyspace_hits", load(&self.keyspace_hits)),
+            ("keyspace_misses", load(&self.keyspace_misses)),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 测试命令的调用次数、失败次数和延迟直方图
+    #[test]
+    fn test_record_command() {
+        let stats = Stats::default();
+        stats.record_command("get", Duration::from_micros(5), false);
+        stats.record_command("get", Duration::from_micros(500), true);
+        stats.record_command("set", Duration::from_secs(2), false);
+
+        let commands = stats.command_stats();
+        assert_eq!(commands.len(), 2);
+
+        let (name, get) = &commands[0];
+        assert_eq!(name, "get");
+        assert_eq!(get.calls, 2);
+        assert_eq!(get.failed_calls, 1);
+        assert_eq!(get.usec(), 505);
+        assert_eq!(
+            &get.cumulative_histogram()[..3],
+            &[(10, 1), (100, 1), (1_000, 2)]
+        );
+
+        // 超过所有上界的调用只计入总次数
+        let (_, set) = &commands[1];
+        assert_eq!(set.cumulative_histogram().last(), Some(&(1_000_000, 0)));
+        assert_eq!(set.histogram[LATENCY_BUCKETS_USEC.len()], 1);
+    }
+}
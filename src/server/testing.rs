@@ -0,0 +1,62 @@
+//! 测试工具：在内存中的双工管道上运行服务器，不需要绑定TCP端口
+//!
+//! 只在crate自己的测试中，或者开启test-util feature时编译
+
+use std::sync::Arc;
+
+use tokio::{
+    io,
+    sync::{broadcast, mpsc, Semaphore},
+};
+use tracing::debug;
+
+pub use crate::networking::connection::Connection;
+
+use super::{config::ServerConfig, handler::Handler, shutdown::Shutdown};
+use crate::{
+    networking::connection::DEFAULT_READ_BUFFER_SIZE, persistence::database::DatabaseWrapper,
+};
+
+/// 双工管道每个方向的缓冲区大小
+const DUPLEX_BUFFER_SIZE: usize = 64 * 1024;
+
+/// # spawn_server_with_duplex() 函数
+///
+/// 用config创建数据库，在tokio::io::duplex()的一端上运行一个Handler，返回另一端上的Connection，
+/// 测试可以直接在返回的Connection上读写帧。每次调用都使用一个新的数据库，
+/// 返回的Connection被drop之后Handler读到EOF结束，数据库也随之关闭
+///
+/// 必须在tokio运行时中调用
+pub fn spawn_server_with_duplex(config: ServerConfig) -> crate::Result<Connection> {
+    let database_wrapper = DatabaseWrapper::new(config)?;
+    let database = database_wrapper.database();
+    let limits = database.config().frame_limits();
+
+    let (client, server) = io::duplex(DUPLEX_BUFFER_SIZE);
+    let connection = Connection::from_stream(server, DEFAULT_READ_BUFFER_SIZE)
+        .with_limits(limits)
+        .with_inline_commands();
+
+    let (shutdown_tx, shutdown_rx) = broadcast::channel(1);
+    let (shutdown_finish_tx, _) = mpsc::channel(1);
+    // 新创建的信号量一定有可用的许可
+    let permit = Arc::new(Semaphore::new(1)).try_acquire_owned().unwrap();
+    let mut handler = Handler::new(
+        database,
+        connection,
+        Shutdown::new(shutdown_rx),
+        shutdown_finish_tx,
+        permit,
+    );
+
+    tokio::spawn(async move {
+        if let Err(err) = handler.run().await {
+            debug!(cause = ?err, "duplex handler finished with error");
+        }
+        // Handler结束之前，关闭信号的发送端和数据库都不能被drop
+        drop(shutdown_tx);
+        drop(database_wrapper);
+    });
+
+    Ok(Connection::from_stream(client, DEFAULT_READ_BUFFER_SIZE))
+}
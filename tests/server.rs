@@ -1,19 +1,50 @@
-use rustis::server;
+use bytes::Bytes;
+use rustis::client::Frame;
+use rustis::server::{
+    self,
+    config::ServerConfig,
+    testing::{spawn_server_with_duplex, Connection},
+};
 
 use std::net::SocketAddr;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::{TcpListener, TcpStream};
 use tokio::time::{self, Duration};
 
+/// # test_config() 函数
+///
+/// 测试使用的服务器配置，不加载RDB文件
+fn test_config() -> ServerConfig {
+    ServerConfig {
+        load_rdb: false,
+        ..Default::default()
+    }
+}
+
 async fn start_server() -> SocketAddr {
     let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
     let addr = listener.local_addr().unwrap();
 
-    tokio::spawn(async move { server::run(listener, tokio::signal::ctrl_c(), false).await });
+    tokio::spawn(
+        async move { server::run(listener, tokio::signal::ctrl_c(), test_config()).await },
+    );
 
     addr
 }
 
+/// # request() 函数
+///
+/// 在内存连接上发送一个由args组成的命令，返回服务器的响应
+async fn request(connection: &mut Connection, args: &[&str]) -> Frame {
+    let frame = Frame::Array(
+        args.iter()
+            .map(|arg| Frame::Bulk(Bytes::from(arg.to_string())))
+            .collect(),
+    );
+    connection.write_frame(&frame).await.unwrap();
+    connection.read_frame().await.unwrap().unwrap()
+}
+
 /// 测试一个简单的GET SET   
 #[tokio::test]
 async fn key_value_get_set() {
@@ -110,6 +141,7 @@ async fn key_value_timeout() {
 }
 
 /// 测试一个简单的PUBLISH SUBSCRIBE
+#[cfg(feature = "cmd-pubsub")]
 #[tokio::test]
 async fn pub_sub() {
     let addr = start_server().await;
@@ -226,6 +258,7 @@ async fn pub_sub() {
 }
 
 /// 测试订阅channels的管理
+#[cfg(feature = "cmd-pubsub")]
 #[tokio::test]
 async fn manage_subscription() {
     let addr = start_server().await;
@@ -317,24 +350,41 @@ async fn manage_subscription() {
 /// 测试发送未知命令
 #[tokio::test]
 async fn send_error_unknown_command() {
-    let addr = start_server().await;
-
-    let mut stream = TcpStream::connect(addr).await.unwrap();
+    let mut connection = spawn_server_with_duplex(test_config()).unwrap();
 
     // 发送一个未知命令
-    stream
-        .write_all(b"*2\r\n$3\r\nFOO\r\n$5\r\nhello\r\n")
-        .await
-        .unwrap();
+    let response = request(&mut connection, &["FOO", "hello"]).await;
+    assert_eq!(
+        response,
+        Frame::Error("ERR unknown command 'foo'".to_string())
+    );
+}
 
-    let mut response = [0; 28];
+/// 测试关闭cmd-pubsub特性编译时，发布订阅命令被当作未知命令，连接仍然可以继续使用
+#[cfg(not(feature = "cmd-pubsub"))]
+#[tokio::test]
+async fn pubsub_commands_unknown_without_feature() {
+    let mut connection = spawn_server_with_duplex(test_config()).unwrap();
 
-    stream.read_exact(&mut response).await.unwrap();
+    let response = request(&mut connection, &["SUBSCRIBE", "hello"]).await;
+    assert_eq!(
+        response,
+        Frame::Error("ERR unknown command 'subscribe'".to_string())
+    );
+
+    // 命令表中也没有这些命令，不会先回复参数个数错误
+    let response = request(&mut connection, &["PUBLISH"]).await;
+    assert_eq!(
+        response,
+        Frame::Error("ERR unknown command 'publish'".to_string())
+    );
 
-    assert_eq!(b"-ERR unknown command \'foo\'\r\n", &response);
+    let response = request(&mut connection, &["PING"]).await;
+    assert_eq!(response, Frame::Simple("PONG".to_string()));
 }
 
 /// 测试在订阅后尝试GET SET，应该返回错误
+#[cfg(feature = "cmd-pubsub")]
 #[tokio::test]
 async fn send_error_get_set_after_subscribe() {
     let addr = start_server().await;
@@ -371,3 +421,956 @@ async fn send_error_get_set_after_subscribe() {
     stream.read_exact(&mut response).await.unwrap();
     assert_eq!(b"-ERR unknown command \'get\'\r\n", &response);
 }
+
+/// # start_server_with_config() 函数
+///
+/// 使用指定配置启动一个服务器实例
+async fn start_server_with_config(config: ServerConfig) -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    tokio::spawn(async move { server::run(listener, tokio::signal::ctrl_c(), config).await });
+
+    addr
+}
+
+/// # ping() 函数
+///
+/// 发送PING并确认收到PONG，用于确认连接已经被服务器处理
+async fn ping(stream: &mut TcpStream) {
+    stream.write_all(b"*1\r\n$4\r\nPING\r\n").await.unwrap();
+    let mut response = [0; 7];
+    stream.read_exact(&mut response).await.unwrap();
+    assert_eq!(b"+PONG\r\n", &response);
+}
+
+/// 测试达到最大连接数时，新连接被拒绝
+#[tokio::test]
+async fn max_connections_reject() {
+    let addr = start_server_with_config(ServerConfig {
+        max_connections: 2,
+        ..test_config()
+    })
+    .await;
+
+    let mut conn1 = TcpStream::connect(addr).await.unwrap();
+    ping(&mut conn1).await;
+    let mut conn2 = TcpStream::connect(addr).await.unwrap();
+    ping(&mut conn2).await;
+
+    // 第三个连接被拒绝
+    let mut conn3 = TcpStream::connect(addr).await.unwrap();
+    let mut response = Vec::new();
+    conn3.read_to_end(&mut response).await.unwrap();
+    assert_eq!(
+        &b"-ERR max number of clients reached\r\n"[..],
+        &response[..]
+    );
+
+    // 已连接的客户端数量
+    conn1
+        .write_all(b"*2\r\n$4\r\nINFO\r\n$7\r\nclients\r\n")
+        .await
+        .unwrap();
+    let mut response = [0; 60];
+    let n = conn1.read(&mut response).await.unwrap();
+    let info = String::from_utf8_lossy(&response[..n]);
+    assert!(info.contains("connected_clients:2\r\n"));
+    assert!(info.contains("maxclients:2\r\n"));
+
+    // 关闭一个连接后，新连接可以被接受
+    drop(conn2);
+    time::sleep(Duration::from_millis(50)).await;
+    let mut conn4 = TcpStream::connect(addr).await.unwrap();
+    ping(&mut conn4).await;
+}
+
+/// 测试达到最大连接数时，新连接排队等待
+#[tokio::test]
+async fn max_connections_queue() {
+    let addr = start_server_with_config(ServerConfig {
+        max_connections: 2,
+        reject_on_max_connections: false,
+        ..test_config()
+    })
+    .await;
+
+    let mut conn1 = TcpStream::connect(addr).await.unwrap();
+    ping(&mut conn1).await;
+    let mut conn2 = TcpStream::connect(addr).await.unwrap();
+    ping(&mut conn2).await;
+
+    // 第三个连接在排队，没有响应
+    let mut conn3 = TcpStream::connect(addr).await.unwrap();
+    conn3.write_all(b"*1\r\n$4\r\nPING\r\n").await.unwrap();
+    let mut response = [0; 7];
+    time::timeout(Duration::from_millis(100), conn3.read_exact(&mut response))
+        .await
+        .unwrap_err();
+
+    // 关闭一个连接后，排队的连接被处理
+    drop(conn1);
+    conn3.read_exact(&mut response).await.unwrap();
+    assert_eq!(b"+PONG\r\n", &response);
+}
+
+/// # info_stat() 函数
+///
+/// 通过INFO stats读取一个统计项的值
+async fn info_stat(stream: &mut TcpStream, name: &str) -> u64 {
+    stream
+        .write_all(b"*2\r\n$4\r\nINFO\r\n$5\r\nstats\r\n")
+        .await
+        .unwrap();
+    let mut response = vec![0; 4096];
+    let n = stream.read(&mut response).await.unwrap();
+    let info = String::from_utf8_lossy(&response[..n]);
+    info.lines()
+        .find_map(|line| line.strip_prefix(name)?.strip_prefix(':'))
+        .unwrap_or_else(|| panic!("{} not found in {}", name, info))
+        .parse()
+        .unwrap()
+}
+
+/// # connection_storm() 函数
+///
+/// 同时打开count个连接，每个连接发送PING后最多等待wait时间就关闭，返回所有连接的任务
+fn connection_storm(addr: SocketAddr, count: usize, wait: Duration) -> tokio::task::JoinSet<()> {
+    let mut storm = tokio::task::JoinSet::new();
+    for _ in 0..count {
+        storm.spawn(async move {
+            let Ok(mut stream) = TcpStream::connect(addr).await else {
+                return;
+            };
+            let _ = stream.write_all(b"*1\r\n$4\r\nPING\r\n").await;
+            let mut response = Vec::new();
+            let _ = time::timeout(wait, stream.read_to_end(&mut response)).await;
+        });
+    }
+    storm
+}
+
+/// 测试连接风暴中被拒绝的连接不会影响已经连接的客户端
+#[tokio::test]
+async fn connection_storm_reject() {
+    let addr = start_server_with_config(ServerConfig {
+        max_connections: 10,
+        ..test_config()
+    })
+    .await;
+
+    let mut conn = TcpStream::connect(addr).await.unwrap();
+    ping(&mut conn).await;
+
+    let mut storm = connection_storm(addr, 300, Duration::from_millis(500));
+    for _ in 0..20 {
+        time::timeout(Duration::from_secs(1), ping(&mut conn))
+            .await
+            .expect("server stopped responding during connection storm");
+    }
+    while storm.join_next().await.is_some() {}
+
+    assert!(info_stat(&mut conn, "rejected_connections").await > 0);
+
+    // 风暴结束后新连接可以被正常处理
+    time::sleep(Duration::from_millis(50)).await;
+    let mut conn = TcpStream::connect(addr).await.unwrap();
+    ping(&mut conn).await;
+}
+
+/// 测试排队模式下连接风暴在TCP层排队，已经连接的客户端仍然可以正常响应
+#[tokio::test]
+async fn connection_storm_queue() {
+    let addr = start_server_with_config(ServerConfig {
+        max_connections: 5,
+        reject_on_max_connections: false,
+        ..test_config()
+    })
+    .await;
+
+    let mut conn = TcpStream::connect(addr).await.unwrap();
+    ping(&mut conn).await;
+
+    let mut storm = connection_storm(addr, 200, Duration::from_millis(20));
+    for _ in 0..20 {
+        time::timeout(Duration::from_secs(1), ping(&mut conn))
+            .await
+            .expect("server stopped responding during connection storm");
+    }
+    while storm.join_next().await.is_some() {}
+
+    // 排队的连接关闭后，许可被归还，新连接可以被处理
+    drop(conn);
+    let mut conn = TcpStream::connect(addr).await.unwrap();
+    time::timeout(Duration::from_secs(10), ping(&mut conn))
+        .await
+        .expect("queued connections were not drained");
+}
+
+/// 测试同时绑定IPv4和IPv6地址，两个地址都可以连接并共享同一个数据库
+#[tokio::test]
+async fn bind_multiple_addresses() {
+    let listener_v4 = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let listener_v6 = TcpListener::bind("[::1]:0").await.unwrap();
+    let addr_v4 = listener_v4.local_addr().unwrap();
+    let addr_v6 = listener_v6.local_addr().unwrap();
+
+    tokio::spawn(async move {
+        server::run_with_listeners(
+            vec![listener_v4, listener_v6],
+            tokio::signal::ctrl_c(),
+            test_config(),
+        )
+        .await
+    });
+
+    // 通过IPv4地址设置一个键
+    let mut stream_v4 = TcpStream::connect(addr_v4).await.unwrap();
+    stream_v4
+        .write_all(b"*3\r\n$3\r\nSET\r\n$5\r\nhello\r\n$5\r\nworld\r\n")
+        .await
+        .unwrap();
+    let mut response = [0; 5];
+    stream_v4.read_exact(&mut response).await.unwrap();
+    assert_eq!(b"+OK\r\n", &response);
+
+    // 通过IPv6地址获取这个键
+    let mut stream_v6 = TcpStream::connect(addr_v6).await.unwrap();
+    stream_v6
+        .write_all(b"*2\r\n$3\r\nGET\r\n$5\r\nhello\r\n")
+        .await
+        .unwrap();
+    let mut response = [0; 11];
+    stream_v6.read_exact(&mut response).await.unwrap();
+    assert_eq!(b"$5\r\nworld\r\n", &response);
+}
+
+/// 测试超长的数组帧在只收到长度时就被拒绝，而不是等待并缓冲所有元素
+#[tokio::test]
+async fn reject_oversized_array() {
+    let addr = start_server().await;
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+
+    // 声明一个超过上限的MSET参数数组，只发送前几个元素
+    stream
+        .write_all(b"*2000001\r\n$4\r\nMSET\r\n$1\r\na\r\n$1\r\nb\r\n")
+        .await
+        .unwrap();
+
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).await.unwrap();
+    assert_eq!(
+        b"-ERR Protocol error: invalid multibulk length\r\n",
+        &response[..]
+    );
+}
+
+/// 测试数组中未知的帧类型字节会得到协议错误的回复，然后连接被关闭
+#[tokio::test]
+async fn reject_unknown_frame_type() {
+    let addr = start_server().await;
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+
+    stream.write_all(b"*1\r\n!3\r\nfoo\r\n").await.unwrap();
+
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).await.unwrap();
+    assert_eq!(
+        b"-ERR Protocol error: invalid frame type byte '!'\r\n",
+        &response[..]
+    );
+}
+
+/// 测试格式错误的请求先得到协议错误的回复，之前请求的响应不会丢失，然后连接被关闭，并计入统计
+#[tokio::test]
+async fn protocol_error_reply_before_close() {
+    let addr = start_server().await;
+    let mut conn = TcpStream::connect(addr).await.unwrap();
+    let before = info_stat(&mut conn, "total_protocol_errors").await;
+
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    stream
+        .write_all(b"*1\r\n$4\r\nPING\r\n*1\r\n$abc\r\nPING\r\n")
+        .await
+        .unwrap();
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).await.unwrap();
+    let response = String::from_utf8(response).unwrap();
+    assert!(
+        response.starts_with("+PONG\r\n-ERR Protocol error: "),
+        "{}",
+        response
+    );
+    assert!(response.ends_with("\r\n"));
+
+    // 订阅模式下的协议错误也会回复
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    stream
+        .write_all(b"*2\r\n$9\r\nSUBSCRIBE\r\n$5\r\nhello\r\n*1\r\n!3\r\nfoo\r\n")
+        .await
+        .unwrap();
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).await.unwrap();
+    let response = String::from_utf8(response).unwrap();
+    assert!(
+        response.ends_with("-ERR Protocol error: invalid frame type byte '!'\r\n"),
+        "{}",
+        response
+    );
+
+    assert_eq!(
+        info_stat(&mut conn, "total_protocol_errors").await,
+        before + 2
+    );
+}
+
+/// 测试HELLO切换协议版本，不支持的版本和没有认证的连接会被拒绝
+#[tokio::test]
+async fn hello_switches_protocol() {
+    let addr = start_server_with_config(ServerConfig {
+        requirepass: Some("secret".to_string()),
+        ..test_config()
+    })
+    .await;
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+
+    stream.write_all(b"HELLO 3\r\n").await.unwrap();
+    let mut response = vec![0; 512];
+    let n = stream.read(&mut response).await.unwrap();
+    assert!(response[..n].starts_with(b"-NOAUTH HELLO must be called"));
+
+    stream
+        .write_all(b"HELLO 4 AUTH default secret\r\n")
+        .await
+        .unwrap();
+    let n = stream.read(&mut response).await.unwrap();
+    assert_eq!(b"-NOPROTO unsupported protocol version\r\n", &response[..n]);
+
+    // AUTH选项同时完成认证，回复按照RESP3编码为map
+    stream
+        .write_all(b"HELLO 3 AUTH default secret\r\n")
+        .await
+        .unwrap();
+    let n = stream.read(&mut response).await.unwrap();
+    assert!(response[..n].starts_with(b"%7\r\n$6\r\nserver\r\n$6\r\nrustis\r\n"));
+    assert!(response[..n].ends_with(b"$7\r\nmodules\r\n*0\r\n"));
+
+    // RESP3下不存在的键回复_
+    stream.write_all(b"GET missing\r\n").await.unwrap();
+    let n = stream.read(&mut response).await.unwrap();
+    assert_eq!(b"_\r\n", &response[..n]);
+
+    // 切换回RESP2
+    stream.write_all(b"HELLO 2\r\n").await.unwrap();
+    let n = stream.read(&mut response).await.unwrap();
+    assert!(response[..n].starts_with(b"*14\r\n"));
+}
+
+/// 测试telnet风格的内联命令，和RESP格式的请求可以混合使用
+#[tokio::test]
+async fn inline_commands() {
+    let addr = start_server().await;
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+
+    stream.write_all(b"PING\r\n").await.unwrap();
+    let mut response = [0; 7];
+    stream.read_exact(&mut response).await.unwrap();
+    assert_eq!(b"+PONG\r\n", &response);
+
+    // 空行被忽略，引号内的空格属于参数
+    stream
+        .write_all(b"\r\nSET a \"b c\"\r\n*2\r\n$3\r\nGET\r\n$1\r\na\r\n")
+        .await
+        .unwrap();
+    let mut response = [0; 14];
+    stream.read_exact(&mut response).await.unwrap();
+    assert_eq!(b"+OK\r\n$3\r\nb c\r\n", &response);
+
+    stream.write_all(b"GET a\n").await.unwrap();
+    let mut response = [0; 9];
+    stream.read_exact(&mut response).await.unwrap();
+    assert_eq!(b"$3\r\nb c\r\n", &response);
+
+    // 只有`*`开头的请求按RESP格式解析，其他类型字节开头的请求也是内联命令
+    stream.write_all(b"+ping\r\n").await.unwrap();
+    let mut response = [0; 30];
+    stream.read_exact(&mut response).await.unwrap();
+    assert_eq!(b"-ERR unknown command '+ping'\r\n", &response);
+}
+
+/// 测试超过64KB还没有换行的内联命令会得到协议错误，而不是一直缓冲
+#[tokio::test]
+async fn reject_oversized_inline_command() {
+    let addr = start_server().await;
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+
+    let line = vec![b'a'; 64 * 1024 + 1];
+    stream.write_all(&line).await.unwrap();
+
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).await.unwrap();
+    assert_eq!(
+        b"-ERR Protocol error: too big inline request\r\n",
+        &response[..]
+    );
+}
+
+/// 测试元素个数过多的数组在只收到长度时就被拒绝
+#[tokio::test]
+async fn reject_oversized_multibulk() {
+    let addr = start_server().await;
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+
+    stream.write_all(b"*1000000000\r\n").await.unwrap();
+
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).await.unwrap();
+    assert_eq!(
+        b"-ERR Protocol error: invalid multibulk length\r\n",
+        &response[..]
+    );
+}
+
+/// 测试配置的请求长度上限：超过proto-max-bulk-len的bulk，以及加起来超过client-query-buffer-limit的请求，
+/// 都在数据到达之前被拒绝
+#[tokio::test]
+async fn configured_request_limits() {
+    let addr = start_server_with_config(ServerConfig {
+        proto_max_bulk_len: 16,
+        client_query_buffer_limit: 64,
+        ..test_config()
+    })
+    .await;
+
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    stream
+        .write_all(b"*3\r\n$3\r\nSET\r\n$1\r\na\r\n$17\r\n")
+        .await
+        .unwrap();
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).await.unwrap();
+    assert_eq!(
+        b"-ERR Protocol error: invalid bulk length\r\n",
+        &response[..]
+    );
+
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    stream
+        .write_all(
+            b"*5\r\n$4\r\nMSET\r\n$16\r\naaaaaaaaaaaaaaaa\r\n$16\r\nbbbbbbbbbbbbbbbb\r\n$16\r\n",
+        )
+        .await
+        .unwrap();
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).await.unwrap();
+    assert_eq!(
+        b"-ERR Protocol error: invalid frame length\r\n",
+        &response[..]
+    );
+
+    // 没有结束的内联命令超过上限时也会关闭连接，而不是一直缓冲到MAX_INLINE_LEN
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    stream.write_all(&[b'+'; 65]).await.unwrap();
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).await.unwrap();
+    assert_eq!(b"-ERR Protocol error: too big request\r\n", &response[..]);
+}
+
+/// 测试请求中超过64KB的一行在收到换行之前就被拒绝，不会一直缓冲到client-query-buffer-limit
+#[tokio::test]
+async fn reject_oversized_line() {
+    let addr = start_server().await;
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+
+    let mut request = b"*1\r\n$".to_vec();
+    request.resize(64 * 1024 + 5, b'1');
+    stream.write_all(&request).await.unwrap();
+
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).await.unwrap();
+    assert_eq!(
+        b"-ERR Protocol error: invalid line length\r\n",
+        &response[..]
+    );
+}
+
+/// 测试超长的bulk帧在只收到长度时就被拒绝
+#[tokio::test]
+async fn reject_oversized_bulk() {
+    let addr = start_server().await;
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+
+    stream
+        .write_all(b"*3\r\n$3\r\nSET\r\n$5\r\nhello\r\n$1073741824\r\nworld")
+        .await
+        .unwrap();
+
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).await.unwrap();
+    assert_eq!(
+        b"-ERR Protocol error: invalid bulk length\r\n",
+        &response[..]
+    );
+}
+
+/// 测试有一个客户端一直不读取响应时，服务器仍然能在宽限期后关闭
+#[tokio::test]
+async fn shutdown_with_stuck_client() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (shutdown_tx, shutdown_rx) = tokio::sync::oneshot::channel::<()>();
+    let config = ServerConfig {
+        shutdown_timeout: 1,
+        dir: std::env::temp_dir(),
+        dbfilename: "rustis-shutdown-test.rdb".to_string(),
+        ..test_config()
+    };
+    let server = tokio::spawn(server::run(listener, shutdown_rx, config));
+
+    // 先写入一个1MB的值
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    let value = vec![b'x'; 1024 * 1024];
+    let mut request = format!("*3\r\n$3\r\nSET\r\n$3\r\nbig\r\n${}\r\n", value.len()).into_bytes();
+    request.extend_from_slice(&value);
+    request.extend_from_slice(b"\r\n");
+    stream.write_all(&request).await.unwrap();
+    let mut response = [0; 5];
+    stream.read_exact(&mut response).await.unwrap();
+    assert_eq!(b"+OK\r\n", &response);
+
+    // 连续发送GET但从不读取响应，服务器写响应时会因为发送缓冲区满而阻塞
+    for _ in 0..64 {
+        stream
+            .write_all(b"*2\r\n$3\r\nGET\r\n$3\r\nbig\r\n")
+            .await
+            .unwrap();
+    }
+    time::sleep(Duration::from_millis(200)).await;
+
+    shutdown_tx.send(()).unwrap();
+    time::timeout(Duration::from_secs(5), server)
+        .await
+        .expect("服务器没有在宽限期后关闭")
+        .unwrap()
+        .unwrap();
+}
+
+/// 测试开启心跳后，订阅者会定期收到pong帧
+#[cfg(feature = "cmd-pubsub")]
+#[tokio::test]
+async fn subscriber_heartbeat() {
+    let addr = start_server_with_config(ServerConfig {
+        subscriber_heartbeat: 1,
+        ..test_config()
+    })
+    .await;
+
+    let mut subscriber = TcpStream::connect(addr).await.unwrap();
+    subscriber
+        .write_all(b"*2\r\n$9\r\nSUBSCRIBE\r\n$5\r\nhello\r\n")
+        .await
+        .unwrap();
+    let mut response = [0; 34];
+    subscriber.read_exact(&mut response).await.unwrap();
+    assert_eq!(
+        &b"*3\r\n$9\r\nsubscribe\r\n$5\r\nhello\r\n:1\r\n"[..],
+        &response[..]
+    );
+
+    // 一个心跳周期后收到pong帧
+    let mut response = [0; 20];
+    time::timeout(Duration::from_secs(3), subscriber.read_exact(&mut response))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(b"*2\r\n$4\r\npong\r\n$0\r\n\r\n", &response);
+}
+
+/// 测试退订所有channel之后不再推送心跳
+#[cfg(feature = "cmd-pubsub")]
+#[tokio::test]
+async fn subscriber_heartbeat_stops_without_subscriptions() {
+    let addr = start_server_with_config(ServerConfig {
+        subscriber_heartbeat: 1,
+        ..test_config()
+    })
+    .await;
+
+    let mut subscriber = TcpStream::connect(addr).await.unwrap();
+    subscriber
+        .write_all(b"*2\r\n$9\r\nSUBSCRIBE\r\n$5\r\nhello\r\n")
+        .await
+        .unwrap();
+    let mut response = [0; 34];
+    subscriber.read_exact(&mut response).await.unwrap();
+
+    subscriber
+        .write_all(b"*1\r\n$11\r\nUNSUBSCRIBE\r\n")
+        .await
+        .unwrap();
+    let mut response = [0; 37];
+    subscriber.read_exact(&mut response).await.unwrap();
+    assert_eq!(
+        &b"*3\r\n$11\r\nunsubscribe\r\n$5\r\nhello\r\n:0\r\n"[..],
+        &response[..]
+    );
+
+    // 超过一个心跳周期也没有收到pong帧
+    let mut response = [0; 1];
+    let result = time::timeout(Duration::from_millis(1500), subscriber.read(&mut response)).await;
+    assert!(result.is_err(), "退订所有channel后仍然收到了数据");
+}
+
+/// 测试开启心跳后，不再读取数据的订阅者连接会在心跳周期内被关闭
+#[cfg(feature = "cmd-pubsub")]
+#[tokio::test]
+async fn dead_subscriber_is_closed() {
+    let addr = start_server_with_config(ServerConfig {
+        subscriber_heartbeat: 1,
+        ..test_config()
+    })
+    .await;
+
+    // 订阅之后再也不读取数据，模拟一个已经失效的连接
+    let mut subscriber = TcpStream::connect(addr).await.unwrap();
+    subscriber
+        .write_all(b"*2\r\n$9\r\nSUBSCRIBE\r\n$5\r\nhello\r\n")
+        .await
+        .unwrap();
+
+    // 发布足够多的大消息，填满socket缓冲区
+    let mut publisher = TcpStream::connect(addr).await.unwrap();
+    let message = vec![b'x'; 1024 * 1024];
+    let mut request = format!(
+        "*3\r\n$7\r\nPUBLISH\r\n$5\r\nhello\r\n${}\r\n",
+        message.len()
+    )
+    .into_bytes();
+    request.extend_from_slice(&message);
+    request.extend_from_slice(b"\r\n");
+    for _ in 0..32 {
+        publisher.write_all(&request).await.unwrap();
+        let mut response = [0; 4];
+        publisher.read_exact(&mut response).await.unwrap();
+    }
+
+    // 订阅者的连接被关闭后，只剩下发布者一个连接
+    let mut closed = false;
+    for _ in 0..50 {
+        publisher
+            .write_all(b"*2\r\n$4\r\nINFO\r\n$7\r\nclients\r\n")
+            .await
+            .unwrap();
+        let mut response = [0; 128];
+        let n = publisher.read(&mut response).await.unwrap();
+        if String::from_utf8_lossy(&response[..n]).contains("connected_clients:1\r\n") {
+            closed = true;
+            break;
+        }
+        time::sleep(Duration::from_millis(100)).await;
+    }
+    assert!(closed, "订阅者连接没有在心跳周期内被关闭");
+}
+
+/// # LogCapture 结构体
+///
+/// 把日志写入共享缓冲区的writer，用于在测试中检查日志内容
+#[derive(Clone, Default)]
+struct LogCapture(std::sync::Arc<std::sync::Mutex<Vec<u8>>>);
+
+impl std::io::Write for LogCapture {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// 测试CLIENT ID返回连接的唯一标识，并且连接产生的日志都带有conn_id和peer字段
+#[tokio::test]
+async fn connection_id_in_logs() {
+    let capture = LogCapture::default();
+    let writer = capture.clone();
+    let subscriber = tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::DEBUG)
+        .with_ansi(false)
+        .with_writer(move || writer.clone())
+        .finish();
+    // 测试使用单线程运行时，服务器的任务都在当前线程上执行
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    let addr = start_server().await;
+
+    let mut first = TcpStream::connect(addr).await.unwrap();
+    let mut second = TcpStream::connect(addr).await.unwrap();
+
+    let mut ids = Vec::new();
+    for stream in [&mut first, &mut second] {
+        stream
+            .write_all(b"*2\r\n$6\r\nCLIENT\r\n$2\r\nID\r\n")
+            .await
+            .unwrap();
+
+        let mut response = [0; 16];
+        let n = stream.read(&mut response).await.unwrap();
+        let response = std::str::from_utf8(&response[..n]).unwrap();
+        assert!(
+            response.starts_with(':') && response.ends_with("\r\n"),
+            "{}",
+            response
+        );
+        ids.push(response[1..n - 2].parse::<u64>().unwrap());
+    }
+    assert_ne!(ids[0], ids[1]);
+
+    let logs = String::from_utf8(capture.0.lock().unwrap().clone()).unwrap();
+    let peer = second.local_addr().unwrap();
+    assert!(
+        logs.lines()
+            .any(|line| line.contains(&format!("conn_id={}", ids[1]))
+                && line.contains(&format!("peer={}", peer))
+                && line.contains("cmd=")),
+        "{}",
+        logs
+    );
+}
+
+/// 测试命令参数错误时回复错误并保持连接，之后的命令正常执行
+#[tokio::test]
+async fn bad_command_keeps_connection() {
+    let mut connection = spawn_server_with_duplex(test_config()).unwrap();
+
+    // GET缺少参数
+    let response = request(&mut connection, &["GET"]).await;
+    assert_eq!(
+        response,
+        Frame::Error("ERR wrong number of arguments for 'get' command".to_string())
+    );
+
+    // SET带有未知的选项
+    let response = request(&mut connection, &["SET", "a", "b", "FOO"]).await;
+    assert_eq!(response, Frame::Error("ERR syntax error".to_string()));
+
+    // 同一个连接上继续执行命令
+    assert_eq!(
+        request(&mut connection, &["PING"]).await,
+        Frame::Simple("PONG".to_string())
+    );
+}
+
+/// 测试非常大的过期时间：换算成毫秒后溢出时回复错误，没有溢出时正常写入并且键不会立即过期
+#[tokio::test]
+async fn enormous_expire_time() {
+    let addr = start_server().await;
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    let mut response = [0; 64];
+
+    let cases: [(&[u8], &[u8]); 7] = [
+        (
+            b"SET a b EX 18446744073709551615\r\n",
+            b"-ERR invalid expire time\r\n",
+        ),
+        (
+            b"SET a b EX 9223372036854776\r\n",
+            b"-ERR invalid expire time\r\n",
+        ),
+        (
+            b"SET a b PX 9223372036854775808\r\n",
+            b"-ERR invalid expire time\r\n",
+        ),
+        (b"SET a b EX 9223372036854775\r\n", b"+OK\r\n"),
+        (
+            b"EXPIRE a 18446744073709551615\r\n",
+            b"-ERR invalid expire time\r\n",
+        ),
+        (b"PEXPIRE a 9223372036854775807\r\n", b":1\r\n"),
+        (b"GET a\r\n", b"$1\r\nb\r\n"),
+    ];
+    for (request, expected) in cases {
+        stream.write_all(request).await.unwrap();
+        let n = stream.read(&mut response).await.unwrap();
+        assert_eq!(
+            std::str::from_utf8(&response[..n]).unwrap(),
+            std::str::from_utf8(expected).unwrap(),
+            "{}",
+            std::str::from_utf8(request).unwrap()
+        );
+    }
+}
+
+/// 测试在普通模式下发送只能在订阅模式下使用的命令时回复错误，连接仍然可以继续使用
+#[cfg(feature = "cmd-pubsub")]
+#[tokio::test]
+async fn subscribe_mode_commands_outside_subscribe_mode() {
+    let addr = start_server().await;
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    let mut response = [0; 128];
+
+    stream
+        .write_all(b"*2\r\n$11\r\nUNSUBSCRIBE\r\n$5\r\nhello\r\n")
+        .await
+        .unwrap();
+    let n = stream.read(&mut response).await.unwrap();
+    assert_eq!(
+        b"-ERR UNSUBSCRIBE can't be used outside subscribe mode\r\n",
+        &response[..n]
+    );
+
+    stream
+        .write_all(b"*1\r\n$13\r\nEXITSUBSCRIBE\r\n")
+        .await
+        .unwrap();
+    let n = stream.read(&mut response).await.unwrap();
+    assert_eq!(
+        b"-ERR EXITSUBSCRIBE can't be used outside subscribe mode\r\n",
+        &response[..n]
+    );
+
+    stream.write_all(b"*1\r\n$4\r\nPING\r\n").await.unwrap();
+    let n = stream.read(&mut response).await.unwrap();
+    assert_eq!(b"+PONG\r\n", &response[..n]);
+}
+
+/// 测试参数个数错误时，无论缺少参数还是多出参数，所有命令都回复同样格式的错误
+#[tokio::test]
+async fn wrong_number_of_arguments() {
+    let addr = start_server().await;
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+
+    let cases: &[(&[u8], &str)] = &[
+        (b"GET\r\n", "get"),
+        (b"GET a b\r\n", "get"),
+        (b"SET a\r\n", "set"),
+        (b"SELECT\r\n", "select"),
+        (b"SELECT 0 1\r\n", "select"),
+        #[cfg(feature = "cmd-pubsub")]
+        (b"PUBLISH channel\r\n", "publish"),
+        (b"PING a b\r\n", "ping"),
+        (b"REPLICAOF no one please\r\n", "replicaof"),
+    ];
+    for (request, name) in cases {
+        stream.write_all(request).await.unwrap();
+        let expected = format!("-ERR wrong number of arguments for '{}' command\r\n", name);
+        let mut response = vec![0; expected.len()];
+        stream.read_exact(&mut response).await.unwrap();
+        assert_eq!(expected.as_bytes(), &response[..]);
+    }
+
+    // 参数个数错误之后连接仍然可用
+    ping(&mut stream).await;
+}
+
+/// 测试COMMAND INFO和COMMAND COUNT使用命令表中的arity、标志和键的位置
+#[tokio::test]
+async fn command_info_from_table() {
+    let addr = start_server().await;
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+
+    stream
+        .write_all(b"COMMAND INFO get nosuchcmd\r\n")
+        .await
+        .unwrap();
+    let expected =
+        b"*2\r\n*6\r\n$3\r\nget\r\n:2\r\n*2\r\n+readonly\r\n+fast\r\n:1\r\n:1\r\n:1\r\n$-1\r\n";
+    let mut response = [0; 60];
+    stream.read_exact(&mut response).await.unwrap();
+    assert_eq!(&expected[..], &response[..]);
+
+    stream.write_all(b"COMMAND COUNT\r\n").await.unwrap();
+    let mut response = [0; 64];
+    let n = stream.read(&mut response).await.unwrap();
+    let count: usize = std::str::from_utf8(&response[1..n - 2])
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert!(count > 30);
+}
+
+/// 测试服务器二进制的--log-level和--log-stderr参数：启动日志输出到标准错误，而不是logs/server.log
+#[test]
+fn server_binary_logs_to_stderr() {
+    use std::io::{BufRead, BufReader};
+    use std::process::{Command, Stdio};
+
+    // 在临时目录中运行，避免加载或者写入当前目录中的RDB和日志文件
+    let dir = std::env::temp_dir().join(format!("rustis-log-stderr-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_server"))
+        .args(["--port", "0", "--log-level", "debug", "--log-stderr"])
+        .current_dir(&dir)
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    // 在另一个线程中读取标准错误，服务器启动失败时不会一直阻塞
+    let stderr = child.stderr.take().unwrap();
+    let (tx, rx) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        for line in BufReader::new(stderr).lines().map_while(Result::ok) {
+            if tx.send(line).is_err() {
+                break;
+            }
+        }
+    });
+
+    let started = std::iter::from_fn(|| rx.recv_timeout(std::time::Duration::from_secs(10)).ok())
+        .any(|line| line.contains("DEBUG") && line.contains("Rustis server has been started"));
+
+    child.kill().unwrap();
+    child.wait().unwrap();
+    let wrote_log_file = dir.join("logs").exists();
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert!(started, "启动日志没有输出到标准错误");
+    assert!(!wrote_log_file);
+}
+
+/// 测试健康检查端口：服务器运行时回复HTTP 200，关闭之后不再接受连接
+#[tokio::test]
+async fn health_port() {
+    // 先占用一个空闲端口再释放，作为健康检查端口
+    let health_port = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let config = ServerConfig {
+        health_port: Some(health_port),
+        dir: std::env::temp_dir(),
+        dbfilename: format!("rustis-health-port-{}.rdb", std::process::id()),
+        ..test_config()
+    };
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let (shutdown_tx, shutdown_rx) = tokio::sync::oneshot::channel::<()>();
+    let server = tokio::spawn(server::run(listener, shutdown_rx, config.clone()));
+
+    // 等待健康检查端口开始监听
+    let health_addr: SocketAddr = ([127, 0, 0, 1], health_port).into();
+    let mut stream = loop {
+        match TcpStream::connect(health_addr).await {
+            Ok(stream) => break stream,
+            Err(_) => time::sleep(Duration::from_millis(10)).await,
+        }
+    };
+
+    stream
+        .write_all(b"GET /healthz HTTP/1.1\r\nHost: localhost\r\n\r\n")
+        .await
+        .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{response}");
+    assert!(response.ends_with("\r\n\r\nOK\n"));
+
+    shutdown_tx.send(()).unwrap();
+    server.await.unwrap().unwrap();
+    std::fs::remove_file(config.rdb_path()).unwrap();
+
+    assert!(TcpStream::connect(health_addr).await.is_err());
+}